@@ -27,26 +27,59 @@ Register-ArgumentCompleter -Native -CommandName 'zydeco' -ScriptBlock {
             [CompletionResult]::new('--version', 'version', [CompletionResultType]::ParameterName, 'Print version')
             [CompletionResult]::new('run', 'run', [CompletionResultType]::ParameterValue, 'Run a zydeco program')
             [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check a zydeco program')
+            [CompletionResult]::new('build', 'build', [CompletionResultType]::ParameterValue, 'Compile a zydeco program to another language')
+            [CompletionResult]::new('debug', 'debug', [CompletionResultType]::ParameterValue, 'Step through a zydeco program interactively')
             [CompletionResult]::new('repl', 'repl', [CompletionResultType]::ParameterValue, 'Start a REPL')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
         }
         'zydeco;run' {
+            [CompletionResult]::new('--max-steps', 'max-steps', [CompletionResultType]::ParameterName, 'Stop the program after this many evaluation steps')
+            [CompletionResult]::new('--seed', 'seed', [CompletionResultType]::ParameterName, 'Start the random numbers from this seed, so that every run gets the same ones')
+            [CompletionResult]::new('--allow-read', 'allow-read', [CompletionResultType]::ParameterName, 'Let the program read files and list directories under these paths, or anywhere if none are given')
+            [CompletionResult]::new('--allow-write', 'allow-write', [CompletionResultType]::ParameterName, 'Let the program write files under these paths, or anywhere if none are given')
+            [CompletionResult]::new('--profile', 'profile', [CompletionResultType]::ParameterName, 'Print a flat profile to stderr and write collapsed stacks for flamegraph tools to PATH')
+            [CompletionResult]::new('--backend', 'backend', [CompletionResultType]::ParameterName, 'How to execute the program')
             [CompletionResult]::new('--dry', 'dry', [CompletionResultType]::ParameterName, 'Dry run (don''t execute)')
+            [CompletionResult]::new('-v', 'v', [CompletionResultType]::ParameterName, 'Level of verbosity')
+            [CompletionResult]::new('--verbose', 'verbose', [CompletionResultType]::ParameterName, 'Level of verbosity')
+            [CompletionResult]::new('--allow-env', 'allow-env', [CompletionResultType]::ParameterName, 'Let the program read environment variables')
+            [CompletionResult]::new('--allow-exec', 'allow-exec', [CompletionResultType]::ParameterName, 'Let the program run other programs')
+            [CompletionResult]::new('--cps', 'cps', [CompletionResultType]::ParameterName, 'Run the program after transforming it into continuation-passing style')
+            [CompletionResult]::new('-O', 'O ', [CompletionResultType]::ParameterName, 'Optimize the program before running it')
+            [CompletionResult]::new('--opt', 'opt', [CompletionResultType]::ParameterName, 'Optimize the program before running it')
+            [CompletionResult]::new('--dump-ir', 'dump-ir', [CompletionResultType]::ParameterName, 'Print the program after each optimization pass')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            break
+        }
+        'zydeco;check' {
             [CompletionResult]::new('-v', 'v', [CompletionResultType]::ParameterName, 'Level of verbosity')
             [CompletionResult]::new('--verbose', 'verbose', [CompletionResultType]::ParameterName, 'Level of verbosity')
             [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help')
             break
         }
-        'zydeco;check' {
+        'zydeco;build' {
+            [CompletionResult]::new('--target', 'target', [CompletionResultType]::ParameterName, 'The language to compile to')
+            [CompletionResult]::new('-o', 'o', [CompletionResultType]::ParameterName, 'Where to write the compiled program [default: FILE with the target''s extension]')
+            [CompletionResult]::new('--output', 'output', [CompletionResultType]::ParameterName, 'Where to write the compiled program [default: FILE with the target''s extension]')
             [CompletionResult]::new('-v', 'v', [CompletionResultType]::ParameterName, 'Level of verbosity')
             [CompletionResult]::new('--verbose', 'verbose', [CompletionResultType]::ParameterName, 'Level of verbosity')
+            [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            break
+        }
+        'zydeco;debug' {
+            [CompletionResult]::new('-b', 'b', [CompletionResultType]::ParameterName, 'Set a breakpoint on a top-level def name, FILE:LINE or LINE')
+            [CompletionResult]::new('--break', 'break', [CompletionResultType]::ParameterName, 'Set a breakpoint on a top-level def name, FILE:LINE or LINE')
+            [CompletionResult]::new('--input', 'input', [CompletionResultType]::ParameterName, 'Feed this file to the program as its input, since debugger commands are read from stdin [default: no input]')
             [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', 'help', [CompletionResultType]::ParameterName, 'Print help')
             break
         }
         'zydeco;repl' {
+            [CompletionResult]::new('--max-steps', 'max-steps', [CompletionResultType]::ParameterName, 'Stop each evaluation after this many steps')
             [CompletionResult]::new('-v', 'v', [CompletionResultType]::ParameterName, 'Level of verbosity')
             [CompletionResult]::new('--verbose', 'verbose', [CompletionResultType]::ParameterName, 'Level of verbosity')
             [CompletionResult]::new('-h', 'h', [CompletionResultType]::ParameterName, 'Print help')
@@ -56,6 +89,8 @@ Register-ArgumentCompleter -Native -CommandName 'zydeco' -ScriptBlock {
         'zydeco;help' {
             [CompletionResult]::new('run', 'run', [CompletionResultType]::ParameterValue, 'Run a zydeco program')
             [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check a zydeco program')
+            [CompletionResult]::new('build', 'build', [CompletionResultType]::ParameterValue, 'Compile a zydeco program to another language')
+            [CompletionResult]::new('debug', 'debug', [CompletionResultType]::ParameterValue, 'Step through a zydeco program interactively')
             [CompletionResult]::new('repl', 'repl', [CompletionResultType]::ParameterValue, 'Start a REPL')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
@@ -66,6 +101,12 @@ Register-ArgumentCompleter -Native -CommandName 'zydeco' -ScriptBlock {
         'zydeco;help;check' {
             break
         }
+        'zydeco;help;build' {
+            break
+        }
+        'zydeco;help;debug' {
+            break
+        }
         'zydeco;help;repl' {
             break
         }
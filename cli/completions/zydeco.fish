@@ -2,16 +2,39 @@ complete -c zydeco -n "__fish_use_subcommand" -s h -l help -d 'Print help'
 complete -c zydeco -n "__fish_use_subcommand" -s V -l version -d 'Print version'
 complete -c zydeco -n "__fish_use_subcommand" -f -a "run" -d 'Run a zydeco program'
 complete -c zydeco -n "__fish_use_subcommand" -f -a "check" -d 'Check a zydeco program'
+complete -c zydeco -n "__fish_use_subcommand" -f -a "build" -d 'Compile a zydeco program to another language'
+complete -c zydeco -n "__fish_use_subcommand" -f -a "debug" -d 'Step through a zydeco program interactively'
 complete -c zydeco -n "__fish_use_subcommand" -f -a "repl" -d 'Start a REPL'
 complete -c zydeco -n "__fish_use_subcommand" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l max-steps -d 'Stop the program after this many evaluation steps' -r
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l seed -d 'Start the random numbers from this seed, so that every run gets the same ones' -r
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l allow-read -d 'Let the program read files and list directories under these paths, or anywhere if none are given' -r -F
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l allow-write -d 'Let the program write files under these paths, or anywhere if none are given' -r -F
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l profile -d 'Print a flat profile to stderr and write collapsed stacks for flamegraph tools to PATH' -r -F
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l backend -d 'How to execute the program' -r -f -a "{eval	'The small-step evaluator',vm	'Compile to bytecode and run it on a virtual machine'}"
 complete -c zydeco -n "__fish_seen_subcommand_from run" -l dry -d 'Dry run (don\'t execute)'
 complete -c zydeco -n "__fish_seen_subcommand_from run" -s v -l verbose -d 'Level of verbosity'
-complete -c zydeco -n "__fish_seen_subcommand_from run" -s h -l help -d 'Print help'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l allow-env -d 'Let the program read environment variables'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l allow-exec -d 'Let the program run other programs'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l cps -d 'Run the program after transforming it into continuation-passing style'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -s O -l opt -d 'Optimize the program before running it'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -l dump-ir -d 'Print the program after each optimization pass'
+complete -c zydeco -n "__fish_seen_subcommand_from run" -s h -l help -d 'Print help (see more with \'--help\')'
 complete -c zydeco -n "__fish_seen_subcommand_from check" -s v -l verbose -d 'Level of verbosity'
 complete -c zydeco -n "__fish_seen_subcommand_from check" -s h -l help -d 'Print help'
+complete -c zydeco -n "__fish_seen_subcommand_from build" -l target -d 'The language to compile to' -r -f -a "{js	'A standalone JavaScript file, runnable with Node.js',c	'A single C file with its runtime included, for any C11 compiler',wasm	'A WebAssembly module for any WASI runtime'}"
+complete -c zydeco -n "__fish_seen_subcommand_from build" -s o -l output -d 'Where to write the compiled program [default: FILE with the target\'s extension]' -r -F
+complete -c zydeco -n "__fish_seen_subcommand_from build" -s v -l verbose -d 'Level of verbosity'
+complete -c zydeco -n "__fish_seen_subcommand_from build" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c zydeco -n "__fish_seen_subcommand_from debug" -s b -l break -d 'Set a breakpoint on a top-level def name, FILE:LINE or LINE' -r
+complete -c zydeco -n "__fish_seen_subcommand_from debug" -l input -d 'Feed this file to the program as its input, since debugger commands are read from stdin [default: no input]' -r -F
+complete -c zydeco -n "__fish_seen_subcommand_from debug" -s h -l help -d 'Print help'
+complete -c zydeco -n "__fish_seen_subcommand_from repl" -l max-steps -d 'Stop each evaluation after this many steps' -r
 complete -c zydeco -n "__fish_seen_subcommand_from repl" -s v -l verbose -d 'Level of verbosity'
 complete -c zydeco -n "__fish_seen_subcommand_from repl" -s h -l help -d 'Print help'
-complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "run" -d 'Run a zydeco program'
-complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "check" -d 'Check a zydeco program'
-complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "repl" -d 'Start a REPL'
-complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from build; and not __fish_seen_subcommand_from debug; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "run" -d 'Run a zydeco program'
+complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from build; and not __fish_seen_subcommand_from debug; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "check" -d 'Check a zydeco program'
+complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from build; and not __fish_seen_subcommand_from debug; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "build" -d 'Compile a zydeco program to another language'
+complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from build; and not __fish_seen_subcommand_from debug; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "debug" -d 'Step through a zydeco program interactively'
+complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from build; and not __fish_seen_subcommand_from debug; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "repl" -d 'Start a REPL'
+complete -c zydeco -n "__fish_seen_subcommand_from help; and not __fish_seen_subcommand_from run; and not __fish_seen_subcommand_from check; and not __fish_seen_subcommand_from build; and not __fish_seen_subcommand_from debug; and not __fish_seen_subcommand_from repl; and not __fish_seen_subcommand_from help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
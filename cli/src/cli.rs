@@ -21,6 +21,43 @@ pub enum Commands {
         /// Level of verbosity
         #[arg(short, long, default_value_t = false)]
         verbose: bool,
+        /// Stop the program after this many evaluation steps
+        #[arg(long, value_name = "N")]
+        max_steps: Option<u64>,
+        /// Start the random numbers from this seed, so that every run gets the same ones
+        #[arg(long, value_name = "N")]
+        seed: Option<u64>,
+        /// Let the program read files and list directories under these paths, or anywhere if
+        /// none are given
+        #[arg(long, value_name = "PATH", num_args = 0.., value_delimiter = ',')]
+        #[arg(require_equals = true)]
+        allow_read: Option<Vec<PathBuf>>,
+        /// Let the program write files under these paths, or anywhere if none are given
+        #[arg(long, value_name = "PATH", num_args = 0.., value_delimiter = ',')]
+        #[arg(require_equals = true)]
+        allow_write: Option<Vec<PathBuf>>,
+        /// Let the program read environment variables
+        #[arg(long, default_value_t = false)]
+        allow_env: bool,
+        /// Let the program run other programs
+        #[arg(long, default_value_t = false)]
+        allow_exec: bool,
+        /// Print a flat profile to stderr and write collapsed stacks for flamegraph tools to PATH
+        #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+        #[arg(default_missing_value = "zydeco.folded")]
+        profile: Option<PathBuf>,
+        /// How to execute the program
+        #[arg(long, value_enum, default_value_t = Backend::Eval)]
+        backend: Backend,
+        /// Run the program after transforming it into continuation-passing style
+        #[arg(long, default_value_t = false)]
+        cps: bool,
+        /// Optimize the program before running it
+        #[arg(short = 'O', long = "opt", default_value_t = false)]
+        opt: bool,
+        /// Print the program after each optimization pass
+        #[arg(long, default_value_t = false, requires = "opt")]
+        dump_ir: bool,
         /// Environmental arguments to pass to the program
         #[arg(last = true)]
         args: Vec<String>,
@@ -34,10 +71,76 @@ pub enum Commands {
         #[arg(short, long, default_value_t = false)]
         verbose: bool,
     },
+    /// Compile a zydeco program to another language
+    ///
+    /// The compiled program is not sandboxed: it may touch files, the environment and other
+    /// programs without any of the --allow-* flags that `run` asks for.
+    Build {
+        /// Path to the file to compile
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// The language to compile to
+        #[arg(long, value_enum, default_value_t = Target::Js)]
+        target: Target,
+        /// Where to write the compiled program [default: FILE with the target's extension]
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Level of verbosity
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// Step through a zydeco program interactively
+    Debug {
+        /// Path to the file to debug
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+        /// Set a breakpoint on a top-level def name, FILE:LINE or LINE
+        #[arg(short, long = "break", value_name = "LOC")]
+        breakpoints: Vec<String>,
+        /// Feed this file to the program as its input, since debugger commands are read from
+        /// stdin [default: no input]
+        #[arg(long, value_name = "PATH")]
+        input: Option<PathBuf>,
+        /// Environmental arguments to pass to the program
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
     /// Start a REPL
     Repl {
         /// Level of verbosity
         #[arg(short, long, default_value_t = false)]
         verbose: bool,
+        /// Stop each evaluation after this many steps
+        #[arg(long, value_name = "N")]
+        max_steps: Option<u64>,
     },
 }
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub enum Backend {
+    /// The small-step evaluator
+    #[default]
+    Eval,
+    /// Compile to bytecode and run it on a virtual machine
+    Vm,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Target {
+    /// A standalone JavaScript file, runnable with Node.js
+    Js,
+    /// A single C file with its runtime included, for any C11 compiler
+    C,
+    /// A WebAssembly module for any WASI runtime
+    Wasm,
+}
+
+impl Target {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Target::Js => "js",
+            Target::C => "c",
+            Target::Wasm => "wasm",
+        }
+    }
+}
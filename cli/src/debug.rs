@@ -0,0 +1,194 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+};
+use zydeco_lang::{
+    dynamics::{
+        debug::{Breakpoint, Debugger, Pause},
+        os::RealOs,
+        syntax as ds,
+    },
+    prelude::*,
+    zydeco::ZydecoFile,
+};
+
+pub struct DebugShell;
+
+const HELP: &str = "\
+Commands:
+  s, step             take a single evaluation step
+  n, next             step over a `do`, pausing where its continuation resumes
+  c, continue         run until a breakpoint is hit or the program finishes
+  b, break LOC        set a breakpoint on a top-level def name, FILE:LINE or LINE
+  d, delete N         remove breakpoint N
+  i, info             list breakpoints
+  e, env              show the local bindings in the environment
+  k, stack            show the continuation stack, innermost frame first
+  t, term             show the computation about to be stepped
+  h, help             show this message
+  q, quit             stop debugging
+An empty line repeats the previous command.";
+
+impl DebugShell {
+    pub fn launch(
+        paths: Vec<PathBuf>, breakpoints: Vec<String>, input: Option<PathBuf>, args: Vec<String>,
+    ) -> Result<i32, String> {
+        let m = ZydecoFile::parse(paths)?;
+        let m = ZydecoFile::elab(m)?;
+        ZydecoFile::tyck(m.clone())?;
+        let p = ZydecoFile::link(m.inner())?;
+
+        // Note: stdin carries the debugger commands, so the program never reads it
+        let mut input: Box<dyn BufRead> = match input {
+            Some(path) => {
+                let file = File::open(&path)
+                    .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+                Box::new(BufReader::new(file))
+            }
+            None => Box::new(std::io::empty()),
+        };
+        let mut output = std::io::stdout();
+        let runtime = ds::Runtime::new(&mut input, &mut output, &args);
+        let mut debugger = match Debugger::new(p, runtime) {
+            Ok(debugger) => debugger,
+            Err(e) => {
+                eprintln!("Runtime Error: {}", e);
+                return Ok(1);
+            }
+        };
+        for loc in breakpoints {
+            Self::add_breakpoint(&mut debugger, &loc)?;
+        }
+        println!("Type `help` for a list of commands.");
+        Self::show_term(&debugger);
+
+        let mut last = String::new();
+        loop {
+            let mut line = String::new();
+            print!("(debug) ");
+            std::io::stdout().flush().unwrap();
+            let n = std::io::stdin().read_line(&mut line).map_err(|e| e.to_string())?;
+            // Ctrl-D to exit
+            if n == 0 {
+                break Ok(0);
+            }
+            if line.trim().is_empty() {
+                line = last.clone();
+            }
+            last = line.clone();
+            let mut words = line.split_whitespace();
+            let pause = match (words.next(), words.next()) {
+                (None, _) => continue,
+                (Some("s" | "step"), None) => debugger.step(),
+                (Some("n" | "next"), None) => debugger.step_over(),
+                (Some("c" | "continue"), None) => debugger.resume(),
+                (Some("b" | "break"), Some(loc)) => {
+                    if let Err(e) = Self::add_breakpoint(&mut debugger, loc) {
+                        println!("{}", e);
+                    }
+                    continue;
+                }
+                (Some("d" | "delete"), Some(n)) => {
+                    match n.parse::<usize>() {
+                        Ok(n) if n < debugger.breakpoints.len() => {
+                            let bp = debugger.breakpoints.remove(n);
+                            println!("Deleted breakpoint {}: {}", n, bp);
+                        }
+                        _ => println!("No breakpoint {}", n),
+                    }
+                    continue;
+                }
+                (Some("i" | "info"), None) => {
+                    for (i, bp) in debugger.breakpoints.iter().enumerate() {
+                        println!("{}: {}", i, bp);
+                    }
+                    continue;
+                }
+                (Some("e" | "env"), None) => {
+                    println!("{}", debugger.locals().fmt());
+                    continue;
+                }
+                (Some("k" | "stack"), None) => {
+                    Self::show_stack(&debugger);
+                    continue;
+                }
+                (Some("t" | "term"), None) => {
+                    match &debugger.current {
+                        Some(comp) => println!("{}", comp.inner_ref().fmt()),
+                        None => println!("The program has finished"),
+                    }
+                    continue;
+                }
+                (Some("h" | "help"), None) => {
+                    println!("{}", HELP);
+                    continue;
+                }
+                (Some("q" | "quit"), None) => break Ok(0),
+                _ => {
+                    println!("Unknown command {}; type `help` for a list of commands", line.trim());
+                    continue;
+                }
+            };
+            match pause {
+                Pause::Stepped => Self::show_term(&debugger),
+                Pause::Breakpoint(i) => {
+                    println!("Hit breakpoint {}: {}", i, debugger.breakpoints[i]);
+                    Self::show_term(&debugger);
+                }
+                Pause::Finished => {
+                    let Some(outcome) = debugger.outcome.take() else { unreachable!() };
+                    break match outcome {
+                        ds::ProgKont::ExitCode(x) => {
+                            println!("Program exited with code {}", x);
+                            Ok(x)
+                        }
+                        ds::ProgKont::Error(e) => {
+                            eprintln!("Runtime Error: {}", e);
+                            Ok(1)
+                        }
+                        ds::ProgKont::OutOfFuel => unreachable!(),
+                        ds::ProgKont::Ret(_) => Err("Program did not exit".to_string()),
+                    };
+                }
+            }
+        }
+    }
+
+    fn add_breakpoint(debugger: &mut Debugger<RealOs>, loc: &str) -> Result<(), String> {
+        let bp = match loc.rsplit_once(':') {
+            Some((file, line)) => {
+                let line = line.parse().map_err(|_| format!("Invalid line number in {}", loc))?;
+                Breakpoint::Line { file: Some(file.to_string()), line }
+            }
+            None => match loc.parse() {
+                Ok(line) => Breakpoint::Line { file: None, line },
+                Err(_) if debugger.has_def(loc) => Breakpoint::Def(loc.to_string()),
+                Err(_) => Err(format!("No top-level def named {}", loc))?,
+            },
+        };
+        println!("Breakpoint {}: {}", debugger.breakpoints.len(), bp);
+        debugger.breakpoints.push(bp);
+        Ok(())
+    }
+
+    fn show_term(debugger: &Debugger<RealOs>) {
+        let Some(comp) = &debugger.current else { return };
+        let term = comp.inner_ref().fmt_truncate(72);
+        if comp.info.is_dummy() {
+            println!("-> (<internal>)\n   {}", term);
+        } else {
+            println!("-> ({})\n   {}", comp.info, term);
+        }
+    }
+
+    fn show_stack(debugger: &Debugger<RealOs>) {
+        let stack = &debugger.runtime.stack;
+        if stack.is_empty() {
+            println!("(empty stack)");
+        }
+        for (i, frame) in stack.iter().rev().enumerate() {
+            println!("#{} {}", i, frame.fmt_inline_debug());
+        }
+    }
+}
@@ -2,9 +2,11 @@
 #![allow(clippy::useless_format)]
 
 pub mod cli;
+pub mod debug;
 pub mod repl;
 
 pub use crate::{
     cli::{Cli, Commands},
+    debug::DebugShell,
     repl::Repl,
 };
@@ -1,15 +1,53 @@
 use clap::Parser;
-use cli::{Cli, Commands, Repl};
+use cli::{
+    cli::{Backend, Target},
+    Cli, Commands, DebugShell, Repl,
+};
 use zydeco_lang::{
+    dynamics::permission::{Access, Permissions},
     prelude::*,
     zydeco::{ProgKont, ZydecoFile},
 };
 
 fn main() -> Result<(), ()> {
     let res = match Cli::parse().command {
-        Commands::Run { files, dry, verbose, args } => run_files(files, dry, verbose, args),
-        Commands::Check { files, verbose } => run_files(files, true, verbose, vec![]),
-        Commands::Repl { .. } => Repl::launch(),
+        Commands::Run {
+            files,
+            dry,
+            verbose,
+            max_steps,
+            seed,
+            allow_read,
+            allow_write,
+            allow_env,
+            allow_exec,
+            profile,
+            backend,
+            cps,
+            opt,
+            dump_ir,
+            args,
+        } => {
+            let permissions = Permissions {
+                read: access(allow_read),
+                write: access(allow_write),
+                env: allow_env,
+                exec: allow_exec,
+            };
+            let options =
+                EvalOptions { max_steps, seed, permissions, profile, backend, cps, opt, dump_ir };
+            run_files(files, dry, verbose, options, args)
+        }
+        Commands::Check { files, verbose } => {
+            run_files(files, true, verbose, EvalOptions::default(), vec![])
+        }
+        Commands::Build { files, target, output, verbose } => {
+            build_files(files, target, output, verbose)
+        }
+        Commands::Debug { files, breakpoints, input, args } => {
+            DebugShell::launch(files, breakpoints, input, args)
+        }
+        Commands::Repl { max_steps, .. } => Repl::launch(max_steps),
     };
     match res {
         Ok(x) => {
@@ -22,9 +60,32 @@ fn main() -> Result<(), ()> {
     }
 }
 
+#[derive(Default)]
+struct EvalOptions {
+    max_steps: Option<u64>,
+    seed: Option<u64>,
+    permissions: Permissions,
+    profile: Option<std::path::PathBuf>,
+    backend: Backend,
+    cps: bool,
+    opt: bool,
+    dump_ir: bool,
+}
+
+/// No paths grant nothing, and a flag given without any grants everything.
+fn access(paths: Option<Vec<std::path::PathBuf>>) -> Access {
+    match paths {
+        None => Access::Nowhere,
+        Some(paths) if paths.is_empty() => Access::Everywhere,
+        Some(paths) => Access::Under(paths),
+    }
+}
+
 fn run_files(
-    paths: Vec<std::path::PathBuf>, dry_run: bool, verbose: bool, args: Vec<String>,
+    paths: Vec<std::path::PathBuf>, dry_run: bool, verbose: bool, options: EvalOptions,
+    args: Vec<String>,
 ) -> Result<i32, String> {
+    let EvalOptions { max_steps, seed, permissions, profile, backend, cps, opt, dump_ir } = options;
     let title =
         &paths.iter().map(|path| format!("{}", path.display())).collect::<Vec<_>>().join(", ");
     // parse
@@ -42,15 +103,83 @@ fn run_files(
     if !dry_run {
         // link
         announce_phase(verbose, title, "link");
-        let sem_m = ZydecoFile::link(m.inner())?;
+        let mut sem_m = ZydecoFile::link(m.inner())?;
+        if opt {
+            announce_phase(verbose, title, "opt");
+            sem_m = ZydecoFile::opt(sem_m, |pass, p| {
+                if dump_ir {
+                    println!("=== [{}] <opt: {}>", title, pass.name());
+                    println!("{}", p.fmt());
+                }
+            });
+        }
+        if cps {
+            announce_phase(verbose, title, "cps");
+            sem_m = ZydecoFile::cps(sem_m);
+        }
         if verbose {
             println!("{}", sem_m.fmt());
         }
         // eval
         announce_phase(verbose, title, "eval");
-        let res = ZydecoFile::eval_os(sem_m, &args);
-        let ProgKont::ExitCode(x) = res.entry else { Err("Program did not exit".to_string())? };
-        return Ok(x);
+        let res = match (profile, backend) {
+            (Some(_), Backend::Vm) => Err("Profiling is only supported by the eval backend")?,
+            (Some(path), Backend::Eval) => {
+                let (res, profile) =
+                    ZydecoFile::eval_os_profiled(sem_m, &args, max_steps, seed, permissions);
+                eprint!("{}", profile);
+                std::fs::write(&path, profile.collapsed()).map_err(|e| e.to_string())?;
+                eprintln!("Collapsed stacks written to {}", path.display());
+                res
+            }
+            (None, Backend::Eval) => {
+                ZydecoFile::eval_os(sem_m, &args, max_steps, seed, permissions)
+            }
+            (None, Backend::Vm) => ZydecoFile::eval_vm(sem_m, &args, max_steps, seed, permissions),
+        };
+        return match res.entry {
+            ProgKont::ExitCode(x) => Ok(x),
+            ProgKont::Error(e) => {
+                // Note: a runtime error ends the program like `exit 1` does
+                eprintln!("Runtime Error: {}", e);
+                Ok(1)
+            }
+            ProgKont::OutOfFuel => {
+                eprintln!("Program ran out of fuel after {} steps", max_steps.unwrap_or_default());
+                Ok(1)
+            }
+            ProgKont::Ret(_) => Err("Program did not exit".to_string()),
+        };
+    }
+    Ok(0)
+}
+
+fn build_files(
+    paths: Vec<std::path::PathBuf>, target: Target, output: Option<std::path::PathBuf>,
+    verbose: bool,
+) -> Result<i32, String> {
+    let output = match output {
+        Some(output) => output,
+        None => paths.first().ok_or("No file to build")?.with_extension(target.extension()),
+    };
+    let title =
+        &paths.iter().map(|path| format!("{}", path.display())).collect::<Vec<_>>().join(", ");
+    announce_phase(verbose, title, "parse");
+    let m = ZydecoFile::parse(paths)?;
+    let m = ZydecoFile::elab(m)?;
+    announce_phase(verbose, title, "tyck");
+    ZydecoFile::tyck(m.clone())?;
+    announce_phase(verbose, title, "link");
+    let sem_m = ZydecoFile::link(m.inner())?;
+    announce_phase(verbose, title, "codegen");
+    let code = match target {
+        Target::Js => ZydecoFile::emit_js(&sem_m)?.into_bytes(),
+        Target::C => ZydecoFile::emit_c(&sem_m)?.into_bytes(),
+        Target::Wasm => ZydecoFile::emit_wasm(&sem_m)?,
+    };
+    std::fs::write(&output, code).map_err(|e| e.to_string())?;
+    if verbose {
+        println!("Wrote {}", output.display());
     }
     Ok(0)
 }
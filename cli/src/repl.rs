@@ -4,9 +4,10 @@ use zydeco_lang::{dynamics::syntax as ds, prelude::*, statics::syntax as ss, zyd
 pub struct Repl;
 
 impl Repl {
-    pub fn launch() -> Result<i32, String> {
+    pub fn launch(max_steps: Option<u64>) -> Result<i32, String> {
         println!("Zydeco v0.2.0");
         let mut zydeco_expr = ZydecoExpr::new();
+        zydeco_expr.max_steps = max_steps;
         loop {
             let mut line = String::new();
             {
@@ -98,17 +99,29 @@ impl Repl {
             let snapshot = zydeco_expr.clone();
             let res = if let Some(()) = ty.clone().elim_os(zydeco_expr.ctx.clone(), &Span::dummy())
             {
-                let c = ZydecoExpr::link_computation(c);
+                let c = term.info.make(ZydecoExpr::link_computation(c));
                 let c = zydeco_expr.eval_os(c, &[]);
-                let ds::ProgKont::ExitCode(i) = c.entry else { unreachable!() };
-                println!("Program exited with code {}", i);
-                Ok(())
+                match c.entry {
+                    ds::ProgKont::ExitCode(i) => {
+                        println!("Program exited with code {}", i);
+                        Ok(())
+                    }
+                    ds::ProgKont::Error(e) => Err(format!("Runtime Error: {}", e)),
+                    ds::ProgKont::OutOfFuel => Err(format!("Program ran out of fuel")),
+                    ds::ProgKont::Ret(_) => unreachable!(),
+                }
             } else if let Some(ty) = ty.clone().elim_ret(zydeco_expr.ctx.clone(), &Span::dummy()) {
-                let c = ZydecoExpr::link_computation(c);
+                let c = term.info.make(ZydecoExpr::link_computation(c));
                 let c = zydeco_expr.eval_ret_computation(c);
-                let ds::ProgKont::Ret(value) = c else { unreachable!() };
-                println!("{} : {}", value.fmt(), ty.fmt());
-                Ok(())
+                match c {
+                    ds::ProgKont::Ret(value) => {
+                        println!("{} : {}", value.fmt(), ty.fmt());
+                        Ok(())
+                    }
+                    ds::ProgKont::Error(e) => Err(format!("Runtime Error: {}", e)),
+                    ds::ProgKont::OutOfFuel => Err(format!("Program ran out of fuel")),
+                    ds::ProgKont::ExitCode(_) => unreachable!(),
+                }
             } else {
                 let mut s = String::new();
                 s += &format!("Can't run computation of type {}", ty.fmt());
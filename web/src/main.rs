@@ -1,8 +1,14 @@
+mod os;
+
+use os::WebOs;
 use std::path::PathBuf;
 use web_sys::HtmlTextAreaElement;
 use yew::prelude::*;
 use zydeco_lang::{dynamics::syntax as ds, prelude::*, zydeco::ZydecoFile};
 
+/// The playground stops programs after this many steps instead of hanging the page.
+const MAX_STEPS: u64 = 10_000_000;
+
 const EXAMPLE: &str = "
 let f = {
   fn (x: Int) -> ret x
@@ -50,10 +56,16 @@ fn run(input: &str) -> Result<String, String> {
     let p = ZydecoFile::elab(p)?;
     ZydecoFile::tyck(p.clone())?;
     let p = ZydecoFile::link(p.inner)?;
-    let p = ZydecoFile::eval_os(p, &[]);
+    // Note: a program typed into the playground may not touch the files, the environment or
+    // the programs of whoever runs it, and the browser has no host clock to read
+    let (p, _) = ZydecoFile::eval_os_with(p, WebOs::new(), Some(MAX_STEPS));
     let s = match p.entry {
         ds::ProgKont::Ret(v) => v.fmt(),
         ds::ProgKont::ExitCode(i) => format!("exit code: {}", i),
+        ds::ProgKont::Error(e) => Err(format!("{}", e))?,
+        ds::ProgKont::OutOfFuel => {
+            Err(format!("Program ran out of fuel after {} steps", MAX_STEPS))?
+        }
     };
     Ok(s)
 }
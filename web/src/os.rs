@@ -0,0 +1,106 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use zydeco_lang::dynamics::{
+    os::{ProcessOutput, SplitMix, VirtualOs},
+    permission::Permissions,
+};
+
+/// The operating system of the playground, which runs in the browser where there is no stdin,
+/// no files and no host clock to reach.
+///
+/// The program gets no input and no arguments, and what it prints is kept in `output`. The
+/// clock builtins stop the program instead of reading the time, and nothing beyond the streams
+/// and the random numbers is permitted.
+pub struct WebOs {
+    pub output: Vec<u8>,
+    pub error: Vec<u8>,
+    input: io::Empty,
+    rng: SplitMix,
+    permissions: Permissions,
+}
+
+impl WebOs {
+    pub fn new() -> Self {
+        WebOs {
+            output: Vec::new(),
+            error: Vec::new(),
+            input: io::empty(),
+            rng: SplitMix(0),
+            permissions: Permissions::none(),
+        }
+    }
+}
+
+impl Default for WebOs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn no_clock() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "the playground has no clock")
+}
+
+fn unsupported() -> io::Error {
+    io::ErrorKind::Unsupported.into()
+}
+
+impl VirtualOs for WebOs {
+    fn input(&mut self) -> &mut dyn io::BufRead {
+        &mut self.input
+    }
+    fn output(&mut self) -> &mut dyn io::Write {
+        &mut self.output
+    }
+    fn error(&mut self) -> &mut dyn io::Write {
+        &mut self.error
+    }
+    fn args(&self) -> &[String] {
+        &[]
+    }
+    fn read_file(&mut self, _path: &str) -> io::Result<Vec<u8>> {
+        Err(unsupported())
+    }
+    fn write_file(&mut self, _path: &str, _contents: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+    fn append_file(&mut self, _path: &str, _contents: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+    fn list_dir(&mut self, _path: &str) -> io::Result<Vec<String>> {
+        Err(unsupported())
+    }
+    fn file_exists(&mut self, _path: &str) -> bool {
+        false
+    }
+    fn resolve_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+    fn env_var(&mut self, _name: &str) -> Option<String> {
+        None
+    }
+    fn now_millis(&mut self) -> io::Result<i64> {
+        Err(no_clock())
+    }
+    fn monotonic_millis(&mut self) -> io::Result<i64> {
+        Err(no_clock())
+    }
+    fn sleep_millis(&mut self, _millis: i64) -> io::Result<()> {
+        Err(no_clock())
+    }
+    fn random(&mut self) -> u64 {
+        self.rng.next()
+    }
+    fn seed_random(&mut self, seed: u64) {
+        self.rng = SplitMix(seed);
+    }
+    fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+    fn spawn(&mut self, _program: &str, _args: &[String]) -> io::Result<ProcessOutput> {
+        Err(unsupported())
+    }
+    fn exit(&mut self, _code: i32) {}
+}
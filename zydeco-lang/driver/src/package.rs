@@ -80,7 +80,7 @@ impl Project {
                     cache: None,
                 },
                 mode: config.mode,
-                parsed: ParsedMap::new(config.name, path.as_ref()),
+                parsed: ParsedMap::new(config.name, path),
                 dep_tracker: DependencyTracker::default(),
                 resolved: None,
                 deps: config
@@ -98,7 +98,7 @@ impl Project {
                     cache: None,
                 },
                 mode: ProjectMode::Root,
-                parsed: ParsedMap::new(project_name, path.as_ref()),
+                parsed: ParsedMap::new(project_name, path),
                 dep_tracker: DependencyTracker::default(),
                 resolved: None,
                 deps: HashMap::default(),
@@ -187,7 +187,7 @@ impl Project {
         for (id, deps_path) in &self.parsed.deps_record {
             for dep_path in deps_path {
                 let dep_id = self.parsed.get_dep_id(dep_path, id)?;
-                self.dep_tracker.update_dep(id.clone(), dep_id)
+                self.dep_tracker.update_dep(*id, dep_id)
             }
         }
         self.resolved = Some(ResolvedMap::new(self.dep_tracker.clone()));
@@ -195,7 +195,6 @@ impl Project {
     }
 
     pub fn resolve(&mut self) -> Result<(), SurfaceError> {
-        if self.mode != ProjectMode::RootNoStd {}
         self.resolved.as_mut().unwrap().resolve_one_by_one(&self.parsed)?;
         Ok(())
     }
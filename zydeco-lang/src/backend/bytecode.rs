@@ -0,0 +1,388 @@
+//! A compact bytecode for linked programs, run by [`super::vm`].
+//!
+//! Every thunk body becomes its own [`Code`] unit. Variables are resolved at compile time to a
+//! local slot of the running code, a captured value of its closure, or a top-level definition.
+
+use crate::{
+    dynamics::syntax as ds,
+    library::syntax::{self as ls, *},
+    prelude::*,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    rc::Rc,
+};
+
+pub type CodeId = usize;
+
+#[derive(Clone, Copy, Debug)]
+pub enum VarRef {
+    Local(usize),
+    Capture(usize),
+    Global(usize),
+}
+
+pub enum Instr {
+    /* ------------------ values, pushed onto the operand stack ----------------- */
+    Var(VarRef),
+    /// a variable that is not in scope; raises an error when reached
+    Unbound(TermV),
+    Literal(Literal),
+    /// a semantic value embedded in a builtin's result
+    Const(usize),
+    Closure {
+        code: CodeId,
+        captures: Box<[VarRef]>,
+    },
+    Ctor {
+        ctor: usize,
+        arity: usize,
+    },
+    /// pops a value and appends it to the top-level definitions
+    Define,
+    /* ------------------------------ computations ------------------------------ */
+    Let(usize),
+    Abs(usize),
+    App,
+    Dtor(usize),
+    Do {
+        slot: usize,
+        resume: usize,
+        var: TermV,
+        body: ls::RcComp,
+    },
+    Ret,
+    Force,
+    /// binds the running closure to a slot
+    Rec(usize),
+    Match(Box<[MatchArm]>),
+    Comatch(Box<[ComatchArm]>),
+    Prim(Prim),
+    /// an effect operation, handler or delimited control, which the VM cannot run; raises an
+    /// error when reached
+    Effect,
+}
+
+pub struct MatchArm {
+    pub ctor: usize,
+    pub slots: Box<[usize]>,
+    pub pc: usize,
+}
+
+pub struct ComatchArm {
+    pub dtor: usize,
+    pub pc: usize,
+}
+
+impl Instr {
+    /// Whether the instruction is a computation step, as opposed to building a value.
+    pub fn is_step(&self) -> bool {
+        !matches!(
+            self,
+            Instr::Var(_)
+                | Instr::Unbound(_)
+                | Instr::Literal(_)
+                | Instr::Const(_)
+                | Instr::Closure { .. }
+                | Instr::Ctor { .. }
+                | Instr::Define
+        )
+    }
+}
+
+pub struct Code {
+    pub id: CodeId,
+    pub instrs: Vec<Instr>,
+    /// the source location of each instruction
+    pub spans: Vec<Span>,
+    pub nlocals: usize,
+    /// the names of the captured variables, in capture order
+    pub captures: Vec<TermV>,
+    /// the computation this code was compiled from
+    pub source: ls::RcComp,
+}
+
+pub struct Interner<T> {
+    names: Vec<T>,
+    ids: HashMap<T, usize>,
+}
+
+impl<T: Clone + Hash + Eq> Interner<T> {
+    pub fn new() -> Self {
+        Interner { names: Vec::new(), ids: HashMap::new() }
+    }
+    pub fn intern(&mut self, name: &T) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        self.names.push(name.clone());
+        self.ids.insert(name.clone(), self.names.len() - 1);
+        self.names.len() - 1
+    }
+    pub fn name(&self, id: usize) -> &T {
+        &self.names[id]
+    }
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+struct Scope {
+    instrs: Vec<Instr>,
+    spans: Vec<Span>,
+    names: Vec<(TermV, usize)>,
+    nlocals: usize,
+    /// captured variables and where the enclosing code finds them
+    captures: Vec<(TermV, Option<VarRef>)>,
+    /// for code compiled from a semantic thunk: the names its environment binds
+    open: Option<HashSet<TermV>>,
+}
+
+impl Scope {
+    fn new(open: Option<HashSet<TermV>>) -> Self {
+        Scope {
+            instrs: Vec::new(),
+            spans: Vec::new(),
+            names: Vec::new(),
+            nlocals: 0,
+            captures: Vec::new(),
+            open,
+        }
+    }
+}
+
+pub struct Compiler {
+    pub codes: Vec<Rc<Code>>,
+    pub consts: Vec<ds::SemVal>,
+    pub ctors: Interner<CtorV>,
+    pub dtors: Interner<DtorV>,
+    globals: HashMap<TermV, usize>,
+    ndefs: usize,
+    by_source: HashMap<*const Sp<SynComp>, CodeId>,
+    scopes: Vec<Scope>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            codes: Vec::new(),
+            consts: Vec::new(),
+            ctors: Interner::new(),
+            dtors: Interner::new(),
+            globals: HashMap::new(),
+            ndefs: 0,
+            by_source: HashMap::new(),
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Compiles the module definitions followed by the entry computation into a single code unit.
+    pub fn compile_program(&mut self, p: &ls::Program) -> CodeId {
+        self.scopes.push(Scope::new(None));
+        for (var, def) in p.module.define.iter() {
+            self.value(def, var.span());
+            self.emit(Instr::Define, var.span());
+            self.globals.insert(var.clone(), self.ndefs);
+            self.ndefs += 1;
+        }
+        let entry = rc!(p.entry.clone());
+        self.comp(&entry);
+        self.finish(entry).0
+    }
+
+    /// Compiles a thunk body whose free variables are bound by a semantic environment,
+    /// reusing the code it was compiled to if it came from this program.
+    pub fn compile_thunk(&mut self, body: &ls::RcComp, env: &Env<TermV, ds::SemVal>) -> CodeId {
+        if let Some(&id) = self.by_source.get(&Rc::as_ptr(body)) {
+            if self.codes[id].captures.iter().all(|x| env.contains_key(x)) {
+                return id;
+            }
+        }
+        let names = env.keys().cloned().collect();
+        self.scopes.push(Scope::new(Some(names)));
+        self.comp(body);
+        self.finish(body.clone()).0
+    }
+
+    fn emit(&mut self, instr: Instr, info: &Span) -> usize {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.instrs.push(instr);
+        scope.spans.push(info.clone());
+        scope.instrs.len() - 1
+    }
+
+    fn pc(&self) -> usize {
+        self.scopes.last().unwrap().instrs.len()
+    }
+
+    fn bind(&mut self, var: &TermV) -> usize {
+        let scope = self.scopes.last_mut().unwrap();
+        let slot = scope.nlocals;
+        scope.nlocals += 1;
+        scope.names.push((var.clone(), slot));
+        slot
+    }
+
+    fn resolve(&mut self, depth: usize, var: &TermV) -> Option<VarRef> {
+        let scope = &self.scopes[depth];
+        if let Some((_, slot)) = scope.names.iter().rev().find(|(x, _)| x == var) {
+            return Some(VarRef::Local(*slot));
+        }
+        if let Some(i) = scope.captures.iter().position(|(x, _)| x == var) {
+            return Some(VarRef::Capture(i));
+        }
+        let from = match &scope.open {
+            Some(names) if names.contains(var) => None,
+            Some(_) => return None,
+            None if depth == 0 => return self.globals.get(var).map(|&g| VarRef::Global(g)),
+            None => match self.resolve(depth - 1, var)? {
+                VarRef::Global(g) => return Some(VarRef::Global(g)),
+                from => Some(from),
+            },
+        };
+        let captures = &mut self.scopes[depth].captures;
+        captures.push((var.clone(), from));
+        Some(VarRef::Capture(captures.len() - 1))
+    }
+
+    /// Ends the innermost code unit, returning it along with where the enclosing code
+    /// finds each captured variable.
+    fn finish(&mut self, source: ls::RcComp) -> (CodeId, Box<[VarRef]>) {
+        let Scope { instrs, spans, nlocals, captures, .. } = self.scopes.pop().unwrap();
+        let id = self.codes.len();
+        let (captures, from): (Vec<_>, Vec<_>) = captures.into_iter().unzip();
+        self.by_source.entry(Rc::as_ptr(&source)).or_insert(id);
+        self.codes.push(Rc::new(Code { id, instrs, spans, nlocals, captures, source }));
+        (id, from.into_iter().flatten().collect())
+    }
+
+    fn closure(&mut self, body: &ls::RcComp, info: &Span) {
+        self.scopes.push(Scope::new(None));
+        self.comp(body);
+        let (code, captures) = self.finish(body.clone());
+        self.emit(Instr::Closure { code, captures }, info);
+    }
+
+    fn value(&mut self, val: &SynVal, info: &Span) {
+        match val {
+            SynVal::Var(var) => {
+                let depth = self.scopes.len() - 1;
+                match self.resolve(depth, var) {
+                    Some(var) => self.emit(Instr::Var(var), info),
+                    None => self.emit(Instr::Unbound(var.clone()), var.span()),
+                };
+            }
+            SynVal::Thunk(Thunk(body)) => self.closure(body, info),
+            SynVal::Ctor(Ctor { ctorv, args }) => {
+                for arg in args {
+                    self.value(arg, info);
+                }
+                let ctor = self.ctors.intern(ctorv);
+                self.emit(Instr::Ctor { ctor, arity: args.len() }, info);
+            }
+            SynVal::Literal(lit) => {
+                self.emit(Instr::Literal(lit.clone()), info);
+            }
+            SynVal::SemValue(sem) => {
+                self.consts.push(sem.clone());
+                self.emit(Instr::Const(self.consts.len() - 1), info);
+            }
+        }
+    }
+
+    fn comp(&mut self, comp: &ls::RcComp) {
+        let info = &comp.info;
+        match &comp.inner {
+            SynComp::Abs(Abs { param, body }) => {
+                let slot = self.bind(param);
+                self.emit(Instr::Abs(slot), info);
+                self.comp(body);
+            }
+            SynComp::App(App { body, arg }) => {
+                self.value(arg, info);
+                self.emit(Instr::App, info);
+                self.comp(body);
+            }
+            SynComp::Ret(Ret(v)) => {
+                self.value(v, info);
+                self.emit(Instr::Ret, info);
+            }
+            SynComp::Force(Force(v)) => {
+                self.value(v, info);
+                self.emit(Instr::Force, info);
+            }
+            SynComp::Let(Let { var, def, body }) => {
+                self.value(def, info);
+                let slot = self.bind(var);
+                self.emit(Instr::Let(slot), info);
+                self.comp(body);
+            }
+            SynComp::Do(Do { var, comp: bound, body }) => {
+                let instr = Instr::Do { slot: 0, resume: 0, var: var.clone(), body: body.clone() };
+                let at = self.emit(instr, info);
+                let mark = self.scopes.last().unwrap().names.len();
+                self.comp(bound);
+                self.scopes.last_mut().unwrap().names.truncate(mark);
+                let pc = self.pc();
+                let new_slot = self.bind(var);
+                if let Instr::Do { slot, resume, .. } =
+                    &mut self.scopes.last_mut().unwrap().instrs[at]
+                {
+                    (*slot, *resume) = (new_slot, pc);
+                }
+                self.comp(body);
+            }
+            SynComp::Rec(Rec { var, body }) => {
+                // Note: forcing the recursive thunk runs the `rec` again, like the evaluator does
+                self.scopes.push(Scope::new(None));
+                let slot = self.bind(var);
+                self.emit(Instr::Rec(slot), info);
+                self.comp(body);
+                let (code, captures) = self.finish(comp.clone());
+                self.emit(Instr::Closure { code, captures }, info);
+                self.emit(Instr::Force, info);
+            }
+            SynComp::Match(Match { scrut, arms }) => {
+                self.value(scrut, info);
+                let at = self.emit(Instr::Match(Box::new([])), info);
+                let mut compiled = Vec::new();
+                for Matcher { ctorv, vars, body } in arms {
+                    let mark = self.scopes.last().unwrap().names.len();
+                    let ctor = self.ctors.intern(ctorv);
+                    let slots = vars.iter().map(|var| self.bind(var)).collect();
+                    compiled.push(MatchArm { ctor, slots, pc: self.pc() });
+                    self.comp(body);
+                    self.scopes.last_mut().unwrap().names.truncate(mark);
+                }
+                self.scopes.last_mut().unwrap().instrs[at] = Instr::Match(compiled.into());
+            }
+            SynComp::Comatch(Comatch { arms }) => {
+                let at = self.emit(Instr::Comatch(Box::new([])), info);
+                let mut compiled = Vec::new();
+                for Comatcher { dtorv, body } in arms {
+                    compiled.push(ComatchArm { dtor: self.dtors.intern(dtorv), pc: self.pc() });
+                    self.comp(body);
+                }
+                self.scopes.last_mut().unwrap().instrs[at] = Instr::Comatch(compiled.into());
+            }
+            SynComp::Dtor(Dtor { body, dtorv }) => {
+                let dtor = self.dtors.intern(dtorv);
+                self.emit(Instr::Dtor(dtor), info);
+                self.comp(body);
+            }
+            SynComp::Prim(prim) => {
+                self.emit(Instr::Prim(prim.clone()), info);
+            }
+            SynComp::Perform(_) | SynComp::Handle(_) | SynComp::Resume(_) | SynComp::Control(_) => {
+                self.emit(Instr::Effect, info);
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Compiles a linked program into a single C file.
+//!
+//! The program is closure-converted by [`super::closure`], and closures are defunctionalized: a
+//! closure is the number of its code unit, and `zy_run` in the generated file dispatches on it.
+//! The runtime in `runtime.c` holds the stack and implements the builtins.
+
+use super::closure::{self as cc, Block, ConvertError, Converter, Stmt, Tail, Val, Var};
+use crate::library::syntax::{self as ls, *};
+use num_bigint::Sign;
+use std::fmt::Write;
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+pub struct CGen<'a> {
+    program: &'a cc::Program,
+    /// file-scope declarations of string literals and closures without captures
+    decls: Vec<String>,
+    /// the unit being emitted, its output and its indentation
+    unit: &'a cc::Unit,
+    out: String,
+    indent: usize,
+    fresh: usize,
+}
+
+impl<'a> CGen<'a> {
+    /// Compiles a program into a C file with a `main` that runs it.
+    pub fn program(p: &ls::Program) -> Result<String, ConvertError> {
+        let program = Converter::program(p)?;
+        let mut gen = CGen {
+            program: &program,
+            decls: Vec::new(),
+            unit: &program.units[0],
+            out: String::new(),
+            indent: 3,
+            fresh: 0,
+        };
+        let mut units = Vec::new();
+        for unit in &program.units {
+            gen.unit = unit;
+            gen.block(&unit.body);
+            units.push(std::mem::take(&mut gen.out));
+        }
+
+        let names = |names: Vec<&str>| {
+            let names: Vec<_> = names.into_iter().map(string).collect();
+            // Note: C does not allow an empty initializer list
+            if names.is_empty() {
+                "0".to_string()
+            } else {
+                names.join(", ")
+            }
+        };
+        let ctors = (0..program.ctors.len()).map(|i| program.ctors.name(i).name()).collect();
+        let dtors = (0..program.dtors.len()).map(|i| program.dtors.name(i).name()).collect();
+        let mut c = String::from(RUNTIME);
+        c +=
+            "\n/* ------------------------------- the program ----------------------------- */\n\n";
+        writeln!(c, "static const char *const zy_ctor_names[] = {{{}}};", names(ctors)).unwrap();
+        writeln!(c, "static const char *const zy_dtor_names[] = {{{}}};", names(dtors)).unwrap();
+        for (i, var) in program.globals.iter().enumerate() {
+            writeln!(c, "static Value {};", global(var, i)).unwrap();
+        }
+        for decl in &gen.decls {
+            writeln!(c, "{}", decl).unwrap();
+        }
+        c += "\nstatic void zy_mark_globals(void) {\n";
+        for (i, var) in program.globals.iter().enumerate() {
+            writeln!(c, "  zy_mark({});", global(var, i)).unwrap();
+        }
+        c += "}\n";
+        c += "\nstatic void zy_run(Clo *k) {\n  for (;;) {\n";
+        c += "    if (zy_allocated >= zy_gc_limit) zy_collect(k);\n    Clo *self = k;\n";
+        c += "    switch (self->code) {\n";
+        for (id, unit) in units.iter().enumerate() {
+            writeln!(c, "    case {}: {{\n{}    }}", id, unit).unwrap();
+        }
+        // Note: the runtime's own continuations have codes past the units
+        c += "    default:\n      k = zy_native(self);\n      continue;\n    }\n  }\n}\n\n";
+        c += "int main(int argc, char **argv) {\n  zy_argc = argc;\n  zy_argv = argv;\n";
+        c += "  zy_run(zy_clo(0, 0, NULL));\n}\n";
+        Ok(c)
+    }
+
+    fn line(&mut self, s: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.out += "  ";
+        }
+        self.out += s.as_ref();
+        self.out += "\n";
+    }
+
+    fn local(&self, slot: usize) -> String {
+        format!("v_{}_{}", ident(self.unit.locals[slot].name()), slot)
+    }
+
+    fn var(&self, var: Var) -> String {
+        match var {
+            Var::Local(slot) => self.local(slot),
+            Var::Env(i) => format!("self->env[{}]", i),
+            Var::Global(g) => global(&self.program.globals[g], g),
+        }
+    }
+
+    fn value(&mut self, val: &Val) -> String {
+        match val {
+            Val::Var(var) => self.var(*var),
+            Val::Unbound(var) => format!("zy_unbound({})", string(var.name())),
+            Val::Literal(Literal::Int(i64::MIN)) => "I(INT64_MIN)".to_string(),
+            Val::Literal(Literal::Int(i)) => format!("I(INT64_C({}))", i),
+            Val::Literal(Literal::Char(c)) => format!("I({})", *c as u32),
+            Val::Literal(Literal::Float(Float(f))) => format!("F({:?})", f),
+            Val::Literal(Literal::BigInt(i)) => {
+                let (sign, limbs) = i.to_u32_digits();
+                let len = limbs.len();
+                let limbs: Vec<_> = limbs.iter().map(|l| format!("{}u", l)).collect();
+                let limbs = if limbs.is_empty() { "0".to_string() } else { limbs.join(", ") };
+                let neg = (sign == Sign::Minus) as u8;
+                format!("P(zy_big_lit({}, {}, (uint32_t[]){{{}}}))", neg, len, limbs)
+            }
+            Val::Literal(Literal::String(s)) => {
+                let name = format!("zy_lit_{}", self.fresh);
+                self.fresh += 1;
+                let chars: Vec<_> = s.iter().map(|c| (*c as u32).to_string()).collect();
+                let chars = if chars.is_empty() { "0".to_string() } else { chars.join(", ") };
+                let len = s.len();
+                self.decls
+                    .push(format!("static Str {} = {{{}, (uint32_t[]){{{}}}}};", name, len, chars));
+                format!("P(&{})", name)
+            }
+            Val::Ctor(tag, args) if args.is_empty() => format!("P(zy_ctor({}, 0, NULL))", tag),
+            Val::Ctor(tag, args) => {
+                let args: Vec<_> = args.iter().map(|arg| self.value(arg)).collect();
+                format!("P(zy_ctor({}, {}, (Value[]){{{}}}))", tag, args.len(), args.join(", "))
+            }
+            Val::Closure(..) => format!("P({})", self.closure(val)),
+            Val::Current => "P(self)".to_string(),
+            Val::Resumed => "zy_R".to_string(),
+        }
+    }
+
+    fn closure(&mut self, val: &Val) -> String {
+        let Val::Closure(id, env) = val else { return format!("{}.p", self.value(val)) };
+        if env.is_empty() {
+            self.decls.push(format!("static Clo zy_code_{} = {{{}}};", id, id));
+            return format!("&zy_code_{}", id);
+        }
+        let env: Vec<_> = env.iter().map(|var| self.var(*var)).collect();
+        format!("zy_clo({}, {}, (Value[]){{{}}})", id, env.len(), env.join(", "))
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Define(g, val) => {
+                    let val = self.value(val);
+                    self.line(format!("{} = {};", global(&self.program.globals[*g], *g), val));
+                }
+                Stmt::Let(slot, val) => {
+                    let val = self.value(val);
+                    self.line(format!("Value {} = {};", self.local(*slot), val));
+                }
+                Stmt::Arg(slot) => self.line(format!("Value {} = zy_arg();", self.local(*slot))),
+                Stmt::PushArg(val) => {
+                    let val = self.value(val);
+                    self.line(format!("zy_push_arg({});", val));
+                }
+                Stmt::PushKont(kont) => {
+                    let kont = self.closure(kont);
+                    self.line(format!("zy_push_kont({});", kont));
+                }
+                Stmt::PushDtor(dtor) => {
+                    let name = self.program.dtors.name(*dtor);
+                    self.line(format!("zy_push_dtor({}); /* .{} */", dtor, name));
+                }
+            }
+        }
+        match &block.tail {
+            Tail::Ret(val) => {
+                let val = self.value(val);
+                self.line(format!("k = zy_ret({});", val));
+                self.line("continue;");
+            }
+            Tail::Force(val) => {
+                let k = self.closure(val);
+                self.line(format!("k = {};", k));
+                self.line("continue;");
+            }
+            Tail::Prim(Prim { name, .. }) => {
+                self.line(format!("k = zy_prim_{}();", name));
+                self.line("continue;");
+            }
+            Tail::Match(scrut, arms) => {
+                let scrut = self.value(scrut);
+                let name = format!("s_{}", self.fresh);
+                self.fresh += 1;
+                self.line(format!("Ctor *{} = {}.p;", name, scrut));
+                self.line(format!("switch ({}->tag) {{", name));
+                for cc::Arm { tag, binds, body } in arms {
+                    self.line(format!("case {}: {{ /* {} */", tag, self.program.ctors.name(*tag)));
+                    self.indent += 1;
+                    for (i, slot) in binds.iter().enumerate() {
+                        self.line(format!("Value {} = {}->args[{}];", self.local(*slot), name, i));
+                    }
+                    self.block(body);
+                    self.indent -= 1;
+                    self.line("}");
+                }
+                self.line("default:");
+                self.line(format!(
+                    "  zy_error(\"No arm matches the constructor %s\", zy_ctor_names[{}->tag]);",
+                    name
+                ));
+                self.line("}");
+            }
+            Tail::Comatch(arms) => {
+                let name = format!("d_{}", self.fresh);
+                self.fresh += 1;
+                self.line(format!("uint32_t {} = zy_dtor();", name));
+                self.line(format!("switch ({}) {{", name));
+                for cc::Coarm { dtor, body } in arms {
+                    self.line(format!(
+                        "case {}: {{ /* .{} */",
+                        dtor,
+                        self.program.dtors.name(*dtor)
+                    ));
+                    self.indent += 1;
+                    self.block(body);
+                    self.indent -= 1;
+                    self.line("}");
+                }
+                self.line("default:");
+                self.line(format!(
+                    "  zy_error(\"No arm matches the destructor .%s\", zy_dtor_names[{}]);",
+                    name
+                ));
+                self.line("}");
+            }
+        }
+    }
+}
+
+fn ident(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn global(var: &TermV, g: usize) -> String {
+    format!("g_{}_{}", ident(var.name()), g)
+}
+
+/// A C string literal.
+fn string(s: &str) -> String {
+    let mut lit = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => lit += "\\\"",
+            b'\\' => lit += "\\\\",
+            b' '..=b'~' => lit.push(b as char),
+            b => write!(lit, "\\{:03o}", b).unwrap(),
+        }
+    }
+    lit.push('"');
+    lit
+}
@@ -0,0 +1,299 @@
+//! Closure conversion of linked programs, shared by the native code generators.
+//!
+//! Thunk bodies, `do` continuations and `rec`s become numbered code units that read their free
+//! variables from the closure's environment, so that a closure is just the number of its code
+//! and the values it captured. A unit is a tree of blocks: straight-line statements followed by
+//! a jump to the next computation or a branch into more blocks.
+
+use super::bytecode::Interner;
+use crate::{
+    dynamics::syntax as ds,
+    library::syntax::{self as ls, *},
+    prelude::*,
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Clone, Debug)]
+pub enum ConvertError {
+    #[error("Cannot compile the closure of a builtin result")]
+    Closure,
+    #[error("Cannot compile the heap cells of a builtin result")]
+    Heap,
+    #[error("Cannot compile the map of a builtin result")]
+    IntMap,
+    #[error("Cannot compile builtin {name} after the CPS transform")]
+    CpsPrim { name: &'static str },
+    #[error("Cannot compile effect handlers or delimited continuations")]
+    Handlers,
+}
+
+/// The constructors the runtimes build, which are numbered first and in this order.
+pub const RUNTIME_CTORS: [&str; 9] =
+    ["True", "False", "None", "Some", "Cons", "Nil", "Unit", "Left", "Right"];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Var {
+    Local(usize),
+    Env(usize),
+    Global(usize),
+}
+
+pub enum Val {
+    Var(Var),
+    Unbound(TermV),
+    Literal(Literal),
+    Ctor(usize, Vec<Val>),
+    /// a closure of a code unit over variables of the enclosing code
+    Closure(usize, Vec<Var>),
+    /// the closure of the running code unit
+    Current,
+    /// the value the running continuation resumes with
+    Resumed,
+}
+
+pub enum Stmt {
+    Define(usize, Val),
+    Let(usize, Val),
+    /// pops an argument into a local
+    Arg(usize),
+    PushArg(Val),
+    PushKont(Val),
+    PushDtor(usize),
+}
+
+pub enum Tail {
+    Ret(Val),
+    Force(Val),
+    Prim(Prim),
+    Match(Val, Vec<Arm>),
+    Comatch(Vec<Coarm>),
+}
+
+pub struct Arm {
+    pub tag: usize,
+    pub binds: Vec<usize>,
+    pub body: Block,
+}
+
+pub struct Coarm {
+    pub dtor: usize,
+    pub body: Block,
+}
+
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub tail: Tail,
+}
+
+pub struct Unit {
+    /// the variable each local was bound to
+    pub locals: Vec<TermV>,
+    pub body: Block,
+}
+
+pub struct Program {
+    /// the code units; the entry is unit 0, which starts by defining the globals
+    pub units: Vec<Unit>,
+    pub globals: Vec<TermV>,
+    pub ctors: Interner<CtorV>,
+    pub dtors: Interner<DtorV>,
+}
+
+struct Scope {
+    locals: Vec<TermV>,
+    names: Vec<(TermV, usize)>,
+    /// captured variables and how the enclosing code refers to them
+    captures: Vec<(TermV, Var)>,
+}
+
+impl Scope {
+    fn new() -> Self {
+        Scope { locals: Vec::new(), names: Vec::new(), captures: Vec::new() }
+    }
+}
+
+pub struct Converter {
+    units: Vec<Option<Unit>>,
+    scopes: Vec<Scope>,
+    globals: HashMap<TermV, usize>,
+    global_names: Vec<TermV>,
+    ctors: Interner<CtorV>,
+    dtors: Interner<DtorV>,
+}
+
+impl Converter {
+    pub fn program(p: &ls::Program) -> Result<Program, ConvertError> {
+        let mut ctors = Interner::new();
+        for name in RUNTIME_CTORS {
+            ctors.intern(&CtorV::new(name.to_string(), Span::dummy()));
+        }
+        let mut conv = Converter {
+            units: vec![None],
+            scopes: vec![Scope::new()],
+            globals: HashMap::new(),
+            global_names: Vec::new(),
+            ctors,
+            dtors: Interner::new(),
+        };
+        let mut stmts = Vec::new();
+        for (var, def) in p.module.define.iter() {
+            let def = conv.value(def)?;
+            let global = conv.global_names.len();
+            stmts.push(Stmt::Define(global, def));
+            conv.global_names.push(var.clone());
+            conv.globals.insert(var.clone(), global);
+        }
+        let tail = conv.comp(&p.entry, &mut stmts)?;
+        let locals = conv.scopes.pop().unwrap().locals;
+        conv.units[0] = Some(Unit { locals, body: Block { stmts, tail } });
+        Ok(Program {
+            units: conv.units.into_iter().map(|unit| unit.unwrap()).collect(),
+            globals: conv.global_names,
+            ctors: conv.ctors,
+            dtors: conv.dtors,
+        })
+    }
+
+    fn bind(&mut self, var: &TermV) -> usize {
+        let scope = self.scopes.last_mut().unwrap();
+        scope.locals.push(var.clone());
+        scope.names.push((var.clone(), scope.locals.len() - 1));
+        scope.locals.len() - 1
+    }
+
+    fn resolve(&mut self, depth: usize, var: &TermV) -> Option<Var> {
+        let scope = &self.scopes[depth];
+        if let Some((_, slot)) = scope.names.iter().rev().find(|(x, _)| x == var) {
+            return Some(Var::Local(*slot));
+        }
+        if let Some(i) = scope.captures.iter().position(|(x, _)| x == var) {
+            return Some(Var::Env(i));
+        }
+        if depth == 0 {
+            return self.globals.get(var).map(|&g| Var::Global(g));
+        }
+        match self.resolve(depth - 1, var)? {
+            Var::Global(g) => Some(Var::Global(g)),
+            from => {
+                let captures = &mut self.scopes[depth].captures;
+                captures.push((var.clone(), from));
+                Some(Var::Env(captures.len() - 1))
+            }
+        }
+    }
+
+    /// Converts a computation into a new code unit, returning its closure.
+    fn closure(
+        &mut self, prologue: Option<(&TermV, Val)>, body: &Sp<SynComp>,
+    ) -> Result<Val, ConvertError> {
+        self.scopes.push(Scope::new());
+        let mut stmts = Vec::new();
+        if let Some((var, init)) = prologue {
+            let slot = self.bind(var);
+            stmts.push(Stmt::Let(slot, init));
+        }
+        let tail = self.comp(body, &mut stmts)?;
+        let Scope { locals, captures, .. } = self.scopes.pop().unwrap();
+        self.units.push(Some(Unit { locals, body: Block { stmts, tail } }));
+        let env = captures.into_iter().map(|(_, from)| from).collect();
+        Ok(Val::Closure(self.units.len() - 1, env))
+    }
+
+    fn value(&mut self, val: &SynVal) -> Result<Val, ConvertError> {
+        Ok(match val {
+            SynVal::Var(var) => match self.resolve(self.scopes.len() - 1, var) {
+                Some(var) => Val::Var(var),
+                None => Val::Unbound(var.clone()),
+            },
+            SynVal::Thunk(Thunk(body)) => self.closure(None, body)?,
+            SynVal::Ctor(Ctor { ctorv, args }) => {
+                let args = args.iter().map(|arg| self.value(arg)).collect::<Result<_, _>>()?;
+                Val::Ctor(self.ctors.intern(ctorv), args)
+            }
+            SynVal::Literal(lit) => Val::Literal(lit.clone()),
+            SynVal::SemValue(sem) => self.sem_value(sem)?,
+        })
+    }
+
+    fn sem_value(&mut self, sem: &ds::SemVal) -> Result<Val, ConvertError> {
+        Ok(match sem {
+            ds::SemVal::Thunk(_) => Err(ConvertError::Closure)?,
+            ds::SemVal::Ref(_) | ds::SemVal::Array(_) => Err(ConvertError::Heap)?,
+            ds::SemVal::IntMap(_) | ds::SemVal::Map(_) => Err(ConvertError::IntMap)?,
+            ds::SemVal::Prompt(_) | ds::SemVal::SubCont(_) => Err(ConvertError::Handlers)?,
+            ds::SemVal::Ctor(Ctor { ctorv, args }) => {
+                let args = args.iter().map(|arg| self.sem_value(arg)).collect::<Result<_, _>>()?;
+                Val::Ctor(self.ctors.intern(ctorv), args)
+            }
+            ds::SemVal::Literal(lit) => Val::Literal(lit.clone()),
+        })
+    }
+
+    fn block(&mut self, comp: &Sp<SynComp>) -> Result<Block, ConvertError> {
+        let mut stmts = Vec::new();
+        let tail = self.comp(comp, &mut stmts)?;
+        Ok(Block { stmts, tail })
+    }
+
+    fn comp(&mut self, comp: &Sp<SynComp>, stmts: &mut Vec<Stmt>) -> Result<Tail, ConvertError> {
+        Ok(match &comp.inner {
+            SynComp::Abs(Abs { param, body }) => {
+                let slot = self.bind(param);
+                stmts.push(Stmt::Arg(slot));
+                self.comp(body, stmts)?
+            }
+            SynComp::App(App { body, arg }) => {
+                stmts.push(Stmt::PushArg(self.value(arg)?));
+                self.comp(body, stmts)?
+            }
+            SynComp::Ret(Ret(v)) => Tail::Ret(self.value(v)?),
+            SynComp::Force(Force(v)) => Tail::Force(self.value(v)?),
+            SynComp::Let(Let { var, def, body }) => {
+                let def = self.value(def)?;
+                let slot = self.bind(var);
+                stmts.push(Stmt::Let(slot, def));
+                self.comp(body, stmts)?
+            }
+            SynComp::Do(Do { var, comp, body }) => {
+                stmts.push(Stmt::PushKont(self.closure(Some((var, Val::Resumed)), body)?));
+                self.comp(comp, stmts)?
+            }
+            SynComp::Rec(Rec { var, body }) => {
+                // Note: forcing the recursive thunk runs the `rec` again, like the evaluator does
+                Tail::Force(self.closure(Some((var, Val::Current)), body)?)
+            }
+            SynComp::Match(Match { scrut, arms }) => {
+                let scrut = self.value(scrut)?;
+                let mut converted = Vec::new();
+                for Matcher { ctorv, vars, body } in arms {
+                    let mark = self.scopes.last().unwrap().names.len();
+                    let tag = self.ctors.intern(ctorv);
+                    let binds = vars.iter().map(|var| self.bind(var)).collect();
+                    converted.push(Arm { tag, binds, body: self.block(body)? });
+                    self.scopes.last_mut().unwrap().names.truncate(mark);
+                }
+                Tail::Match(scrut, converted)
+            }
+            SynComp::Comatch(Comatch { arms }) => {
+                let mut converted = Vec::new();
+                for Comatcher { dtorv, body } in arms {
+                    let mark = self.scopes.last().unwrap().names.len();
+                    let dtor = self.dtors.intern(dtorv);
+                    converted.push(Coarm { dtor, body: self.block(body)? });
+                    self.scopes.last_mut().unwrap().names.truncate(mark);
+                }
+                Tail::Comatch(converted)
+            }
+            SynComp::Dtor(Dtor { body, dtorv }) => {
+                stmts.push(Stmt::PushDtor(self.dtors.intern(dtorv)));
+                self.comp(body, stmts)?
+            }
+            SynComp::Prim(Prim { name, cps: true, .. }) => Err(ConvertError::CpsPrim { name })?,
+            SynComp::Prim(prim) => Tail::Prim(prim.clone()),
+            SynComp::Perform(_) | SynComp::Handle(_) | SynComp::Resume(_) | SynComp::Control(_) => {
+                Err(ConvertError::Handlers)?
+            }
+        })
+    }
+}
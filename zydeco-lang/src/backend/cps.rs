@@ -1,15 +1,25 @@
-use crate::library::syntax::*;
+use crate::{
+    dynamics::{err::Halt, os::VirtualOs, syntax as ds},
+    library::syntax::*,
+};
 use std::{rc::Rc, vec};
-use zydeco_utils::span::Span;
+use zydeco_utils::span::{Sp, Span};
 
 pub trait CpsTransform {
     fn cps_transform(&self) -> Self;
 }
 
+impl<T: CpsTransform> CpsTransform for Sp<T> {
+    fn cps_transform(&self) -> Self {
+        self.map_ref(|t| t.cps_transform())
+    }
+}
+
 impl CpsTransform for SynComp {
     fn cps_transform(&self) -> Self {
         let call = DtorV::new("$call".into(), Span::dummy());
         let cont = TermV::new("$cont".into(), Span::dummy());
+        let dummy = Span::dummy();
         match self {
             SynComp::Abs(Abs { param, body }) => {
                 Abs { param: param.clone(), body: Rc::new(body.cps_transform()) }.into()
@@ -19,11 +29,11 @@ impl CpsTransform for SynComp {
                     .into()
             }
             SynComp::Do(Do { var, comp, body }) => App {
-                body: Rc::new(
+                body: dummy.make_rc(
                     Dtor { body: Rc::new(comp.cps_transform()), dtorv: call.clone() }.into(),
                 ),
                 arg: Rc::new(
-                    Thunk(Rc::new(
+                    Thunk(dummy.make_rc(
                         Abs { param: var.clone(), body: Rc::new(body.cps_transform()) }.into(),
                     ))
                     .into(),
@@ -33,12 +43,12 @@ impl CpsTransform for SynComp {
             SynComp::Ret(Ret(val)) => Comatch {
                 arms: vec![Comatcher {
                     dtorv: call.clone(),
-                    body: Rc::new(
+                    body: dummy.make_rc(
                         Abs {
                             param: cont.clone(),
-                            body: Rc::new(
+                            body: dummy.make_rc(
                                 App {
-                                    body: Rc::new(Force(Rc::new(cont.clone().into())).into()),
+                                    body: dummy.make_rc(Force(Rc::new(cont.clone().into())).into()),
                                     arg: Rc::new(val.cps_transform()),
                                 }
                                 .into(),
@@ -84,17 +94,38 @@ impl CpsTransform for SynComp {
             SynComp::Dtor(Dtor { body, dtorv }) => {
                 Dtor { body: Rc::new(body.cps_transform()), dtorv: dtorv.clone() }.into()
             }
-            SynComp::Prim(Prim { arity, body }) => {
-                Prim { arity: *arity, body: body.clone() }.into()
-            }
+            // Note: builtins answer in direct style, so their results are transformed when they run
+            SynComp::Prim(prim) => Prim { cps: true, ..prim.clone() }.into(),
+            // Note: handlers and prompts find their frames on the stack, which transformed
+            // programs don't use
+            SynComp::Perform(_) => unsupported("perform"),
+            SynComp::Handle(_) => unsupported("handle"),
+            SynComp::Control(control) => unsupported(match control {
+                Control::PushPrompt(..) => "push_prompt",
+                Control::TakeSubcont(..) => "take_subcont",
+                Control::PushSubcont(..) => "push_subcont",
+            }),
+            SynComp::Resume(_) => self.clone(),
         }
     }
 }
 
+/// A builtin named after the construct it replaces, which stops the program when it runs.
+fn unsupported(name: &'static str) -> SynComp {
+    Prim { name, arity: 0, body: no_handlers, cps: false, keeps: false }.into()
+}
+
+fn no_handlers(
+    _: Vec<ds::SemVal>, _: &mut dyn VirtualOs, _: &mut ds::Heap,
+) -> Result<SynComp, Halt> {
+    Err(Halt::NoHandlers("the CPS transform"))
+}
+
 impl CpsTransform for SynVal {
     fn cps_transform(&self) -> Self {
         match self {
-            SynVal::SemValue(_) => unreachable!(),
+            // Note: semantic values only come from builtins of a program that was transformed already
+            SynVal::SemValue(_) => self.clone(),
             SynVal::Ctor(Ctor { ctorv, args }) => Ctor {
                 ctorv: ctorv.clone(),
                 args: args.iter().map(|arg| Rc::new(arg.cps_transform())).collect(),
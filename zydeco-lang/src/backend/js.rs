@@ -0,0 +1,253 @@
+//! Compiles a linked program into standalone JavaScript.
+//!
+//! Every computation becomes a block of statements that ends by returning the next computation
+//! to run, and thunks become closures over the JavaScript variables their body mentions. The
+//! runtime in `runtime.js` drives the program, holds the stack and implements the builtins.
+
+use crate::{
+    dynamics::syntax as ds,
+    library::syntax::{self as ls, *},
+    prelude::*,
+};
+use std::fmt::Write;
+use thiserror::Error;
+
+const RUNTIME: &str = include_str!("runtime.js");
+
+#[derive(Error, Clone, Debug)]
+pub enum JsError {
+    #[error("Cannot compile the closure of a builtin result to JavaScript")]
+    Closure,
+    #[error("Cannot compile the heap cells of a builtin result to JavaScript")]
+    Heap,
+    #[error("Cannot compile the map of a builtin result to JavaScript")]
+    IntMap,
+    #[error("Cannot compile builtin {name} after the CPS transform to JavaScript")]
+    CpsPrim { name: &'static str },
+    #[error("Cannot compile effect handlers or delimited continuations to JavaScript")]
+    Handlers,
+}
+
+pub struct JsGen {
+    out: String,
+    indent: usize,
+    /// the JavaScript name of each variable in scope, innermost last
+    names: Vec<(TermV, String)>,
+    fresh: usize,
+}
+
+impl JsGen {
+    /// Compiles a program into a script that runs it when loaded.
+    pub fn program(p: &ls::Program) -> Result<String, JsError> {
+        let mut gen = JsGen { out: String::new(), indent: 0, names: Vec::new(), fresh: 0 };
+        gen.out += RUNTIME;
+        gen.line("\n// definitions");
+        for (var, def) in p.module.define.iter() {
+            let def = gen.value(def)?;
+            let name = gen.bind(var);
+            gen.line(format!("const {} = {};", name, def));
+        }
+        gen.line("\n$run(() => {");
+        gen.block(&p.entry)?;
+        gen.line("});");
+        Ok(gen.out)
+    }
+
+    fn line(&mut self, s: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.out += "  ";
+        }
+        self.out += s.as_ref();
+        self.out += "\n";
+    }
+
+    fn bind(&mut self, var: &TermV) -> String {
+        let mut name: String = (var.name().chars())
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '$' })
+            .collect();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            name.insert(0, '_');
+        }
+        write!(name, "_{}", self.fresh).unwrap();
+        self.fresh += 1;
+        self.names.push((var.clone(), name.clone()));
+        name
+    }
+
+    /// A variable for the generated code only, which no zydeco variable can refer to.
+    fn temp(&mut self, hint: &str) -> String {
+        self.fresh += 1;
+        format!("${}{}", hint, self.fresh - 1)
+    }
+
+    /// Compiles a computation as the body of a function, restoring the scope afterwards.
+    fn block(&mut self, comp: &Sp<SynComp>) -> Result<(), JsError> {
+        let mark = self.names.len();
+        self.indent += 1;
+        let res = self.comp(comp);
+        self.indent -= 1;
+        self.names.truncate(mark);
+        res
+    }
+
+    fn value(&mut self, val: &SynVal) -> Result<String, JsError> {
+        Ok(match val {
+            SynVal::Var(var) => match self.names.iter().rev().find(|(x, _)| x == var) {
+                Some((_, name)) => name.clone(),
+                None => format!("$unbound({})", string(var.name())),
+            },
+            SynVal::Thunk(Thunk(body)) => {
+                let out = std::mem::take(&mut self.out);
+                self.block(body)?;
+                let body = std::mem::replace(&mut self.out, out);
+                format!("() => {{\n{}{}}}", body, "  ".repeat(self.indent))
+            }
+            SynVal::Ctor(Ctor { ctorv, args }) => {
+                let args = args.iter().map(|arg| self.value(arg)).collect::<Result<Vec<_>, _>>()?;
+                format!("{{ c: {}, a: [{}] }}", string(ctorv.name()), args.join(", "))
+            }
+            SynVal::Literal(lit) => literal(lit),
+            SynVal::SemValue(sem) => sem_value(sem)?,
+        })
+    }
+
+    fn comp(&mut self, comp: &Sp<SynComp>) -> Result<(), JsError> {
+        match &comp.inner {
+            SynComp::Abs(Abs { param, body }) => {
+                let name = self.bind(param);
+                self.line(format!("const {} = $arg();", name));
+                self.comp(body)?;
+            }
+            SynComp::App(App { body, arg }) => {
+                let arg = self.value(arg)?;
+                self.line(format!("$S.push({{ t: 0, v: {} }});", arg));
+                self.comp(body)?;
+            }
+            SynComp::Ret(Ret(v)) => {
+                let v = self.value(v)?;
+                self.line(format!("return $ret({});", v));
+            }
+            SynComp::Force(Force(v)) => {
+                let v = self.value(v)?;
+                self.line(format!("return {};", v));
+            }
+            SynComp::Let(Let { var, def, body }) => {
+                let def = self.value(def)?;
+                let name = self.bind(var);
+                self.line(format!("const {} = {};", name, def));
+                self.comp(body)?;
+            }
+            SynComp::Do(Do { var, comp, body }) => {
+                self.line("$S.push({ t: 1, k: () => {");
+                let mark = self.names.len();
+                let name = self.bind(var);
+                self.indent += 1;
+                self.line(format!("const {} = $R;", name));
+                self.indent -= 1;
+                self.block(body)?;
+                self.names.truncate(mark);
+                self.line("} });");
+                self.comp(comp)?;
+            }
+            SynComp::Rec(Rec { var, body }) => {
+                // Note: forcing the recursive thunk runs the `rec` again, like the evaluator does
+                let mark = self.names.len();
+                let name = self.bind(var);
+                self.line(format!("const {} = () => {{", name));
+                self.block(body)?;
+                self.line("};");
+                self.line(format!("return {};", name));
+                self.names.truncate(mark);
+            }
+            SynComp::Match(Match { scrut, arms }) => {
+                let scrut = self.value(scrut)?;
+                let name = self.temp("scrut");
+                self.line(format!("const {} = {};", name, scrut));
+                self.line(format!("switch ({}.c) {{", name));
+                for Matcher { ctorv, vars, body } in arms {
+                    self.line(format!("case {}: {{", string(ctorv.name())));
+                    let mark = self.names.len();
+                    self.indent += 1;
+                    for (i, var) in vars.iter().enumerate() {
+                        let var = self.bind(var);
+                        self.line(format!("const {} = {}.a[{}];", var, name, i));
+                    }
+                    self.indent -= 1;
+                    self.block(body)?;
+                    self.names.truncate(mark);
+                    self.line("}");
+                }
+                self.line(format!("default: return $noArm({}.c);", name));
+                self.line("}");
+            }
+            SynComp::Comatch(Comatch { arms }) => {
+                let name = self.temp("dtor");
+                self.line(format!("const {} = $dtor();", name));
+                self.line(format!("switch ({}) {{", name));
+                for Comatcher { dtorv, body } in arms {
+                    self.line(format!("case {}: {{", string(dtorv.name())));
+                    self.block(body)?;
+                    self.line("}");
+                }
+                self.line(format!("default: return $noCoarm({});", name));
+                self.line("}");
+            }
+            SynComp::Dtor(Dtor { body, dtorv }) => {
+                self.line(format!("$S.push({{ t: 2, d: {} }});", string(dtorv.name())));
+                self.comp(body)?;
+            }
+            SynComp::Prim(Prim { name, cps: true, .. }) => Err(JsError::CpsPrim { name })?,
+            SynComp::Prim(Prim { name, arity, .. }) => {
+                self.line(format!("return $call({}, {});", string(name), arity));
+            }
+            SynComp::Perform(_) | SynComp::Handle(_) | SynComp::Resume(_) | SynComp::Control(_) => {
+                Err(JsError::Handlers)?
+            }
+        }
+        Ok(())
+    }
+}
+
+fn literal(lit: &Literal) -> String {
+    match lit {
+        Literal::Int(i) => format!("({}n)", i),
+        Literal::BigInt(i) => format!("({}n)", i),
+        Literal::Float(Float(f)) => format!("({:?})", f),
+        Literal::String(s) => string(&s.iter().collect::<String>()),
+        Literal::Char(c) => string(&c.to_string()),
+    }
+}
+
+fn sem_value(sem: &ds::SemVal) -> Result<String, JsError> {
+    Ok(match sem {
+        ds::SemVal::Thunk(_) => Err(JsError::Closure)?,
+        ds::SemVal::Ref(_) | ds::SemVal::Array(_) => Err(JsError::Heap)?,
+        ds::SemVal::IntMap(_) | ds::SemVal::Map(_) => Err(JsError::IntMap)?,
+        ds::SemVal::Prompt(_) | ds::SemVal::SubCont(_) => Err(JsError::Handlers)?,
+        ds::SemVal::Ctor(Ctor { ctorv, args }) => {
+            let args = args.iter().map(|arg| sem_value(arg)).collect::<Result<Vec<_>, _>>()?;
+            format!("{{ c: {}, a: [{}] }}", string(ctorv.name()), args.join(", "))
+        }
+        ds::SemVal::Literal(lit) => literal(lit),
+    })
+}
+
+/// A JavaScript string literal.
+fn string(s: &str) -> String {
+    let mut lit = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => lit += "\\\"",
+            '\\' => lit += "\\\\",
+            '\n' => lit += "\\n",
+            '\r' => lit += "\\r",
+            '\t' => lit += "\\t",
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                write!(lit, "\\u{{{:x}}}", c as u32).unwrap()
+            }
+            c => lit.push(c),
+        }
+    }
+    lit.push('"');
+    lit
+}
@@ -0,0 +1,439 @@
+//! Optimization passes over linked programs.
+//!
+//! Every pass keeps the observable behavior of the program: what it prints, how it exits and
+//! which runtime error stops it. The passes run in the order of [`Pass::ALL`], where each one
+//! exposes work for the next: inlining turns calls to builtins into `! { prim }`, which the
+//! force-of-thunk pass reduces, and `do x <- ret v` becomes a `let` that case-of-known-constructor
+//! can see through.
+
+use crate::{library::syntax::*, prelude::*};
+use std::{collections::HashSet, rc::Rc, slice};
+
+/// Definitions whose values have at most this many nodes are inlined.
+const INLINE_SIZE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// replace variables bound to small top-level definitions by their values
+    Inline,
+    /// `! { b }` to `b`
+    ForceThunk,
+    /// `do x <- ret v; b` to `let x = v in b`
+    DoRet,
+    /// `match C(v, ..) | C(x, ..) -> b` to `let x = v in .. b`
+    KnownCtor,
+    /// drop the definitions that the entry does not use
+    DeadDefs,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 5] =
+        [Pass::Inline, Pass::ForceThunk, Pass::DoRet, Pass::KnownCtor, Pass::DeadDefs];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Inline => "inline",
+            Pass::ForceThunk => "force-thunk",
+            Pass::DoRet => "do-ret",
+            Pass::KnownCtor => "known-ctor",
+            Pass::DeadDefs => "dead-defs",
+        }
+    }
+
+    pub fn run(self, p: &Program) -> Program {
+        match self {
+            Pass::Inline => inline(p),
+            Pass::ForceThunk => rewrite(p, &mut ForceThunk),
+            Pass::DoRet => rewrite(p, &mut DoRet),
+            Pass::KnownCtor => rewrite(p, &mut KnownCtor),
+            Pass::DeadDefs => dead_defs(p),
+        }
+    }
+}
+
+/// Runs every pass in order, handing each result to `after`.
+pub fn optimize(p: Program, mut after: impl FnMut(Pass, &Program)) -> Program {
+    let mut p = p;
+    for pass in Pass::ALL {
+        p = pass.run(&p);
+        after(pass, &p);
+    }
+    p
+}
+
+/* --------------------------------- Rewrite -------------------------------- */
+
+/// The local variables in scope, innermost last, with the values of those bound by `let`.
+#[derive(Default)]
+struct Scope(Vec<(TermV, Option<SynVal>)>);
+
+impl Scope {
+    fn binds(&self, var: &TermV) -> bool {
+        self.0.iter().any(|(x, _)| x == var)
+    }
+    /// The value a variable is bound to by `let`, if none of its free variables were rebound
+    /// since.
+    fn lookup(&self, var: &TermV) -> Option<&SynVal> {
+        let i = self.0.iter().rposition(|(x, _)| x == var)?;
+        let def = self.0[i].1.as_ref()?;
+        let inner = &self.0[i + 1..];
+        let shadowed = free_val(def).iter().any(|fv| inner.iter().any(|(x, _)| x == fv));
+        (!shadowed).then_some(def)
+    }
+}
+
+/// A bottom-up rewrite: each hook sees a term whose children are rewritten already, in the
+/// scope the term itself is in.
+trait Rewrite {
+    fn val(&mut self, _scope: &Scope, val: SynVal) -> SynVal {
+        val
+    }
+    fn comp(&mut self, _scope: &Scope, comp: Sp<SynComp>) -> Sp<SynComp> {
+        comp
+    }
+}
+
+fn rewrite(p: &Program, r: &mut impl Rewrite) -> Program {
+    let mut scope = Scope::default();
+    let define = p.module.define.iter().map(|(x, v)| (x.clone(), walk_val(r, &mut scope, v)));
+    let module = Module { name: p.module.name.clone(), define: define.collect() };
+    Program { module, entry: walk_comp(r, &mut scope, &p.entry) }
+}
+
+fn walk_val(r: &mut impl Rewrite, scope: &mut Scope, val: &SynVal) -> SynVal {
+    let val = match val {
+        SynVal::Thunk(Thunk(body)) => Thunk(Rc::new(walk_comp(r, scope, body))).into(),
+        SynVal::Ctor(Ctor { ctorv, args }) => Ctor {
+            ctorv: ctorv.clone(),
+            args: args.iter().map(|arg| Rc::new(walk_val(r, scope, arg))).collect(),
+        }
+        .into(),
+        SynVal::Var(_) | SynVal::Literal(_) | SynVal::SemValue(_) => val.clone(),
+    };
+    r.val(scope, val)
+}
+
+/// Rewrites a computation under a binder.
+fn walk_under(
+    r: &mut impl Rewrite, scope: &mut Scope, binds: Vec<(TermV, Option<SynVal>)>,
+    comp: &Sp<SynComp>,
+) -> Rc<Sp<SynComp>> {
+    let n = binds.len();
+    scope.0.extend(binds);
+    let comp = walk_comp(r, scope, comp);
+    scope.0.truncate(scope.0.len() - n);
+    Rc::new(comp)
+}
+
+fn walk_comp(r: &mut impl Rewrite, scope: &mut Scope, comp: &Sp<SynComp>) -> Sp<SynComp> {
+    let inner = match &comp.inner {
+        SynComp::Abs(Abs { param, body }) => {
+            let body = walk_under(r, scope, vec![(param.clone(), None)], body);
+            Abs { param: param.clone(), body }.into()
+        }
+        SynComp::App(App { body, arg }) => {
+            let arg = Rc::new(walk_val(r, scope, arg));
+            App { body: Rc::new(walk_comp(r, scope, body)), arg }.into()
+        }
+        SynComp::Ret(Ret(v)) => Ret(Rc::new(walk_val(r, scope, v))).into(),
+        SynComp::Force(Force(v)) => Force(Rc::new(walk_val(r, scope, v))).into(),
+        SynComp::Let(Let { var, def, body }) => {
+            let def = walk_val(r, scope, def);
+            let body = walk_under(r, scope, vec![(var.clone(), Some(def.clone()))], body);
+            Let { var: var.clone(), def: Rc::new(def), body }.into()
+        }
+        SynComp::Do(Do { var, comp, body }) => {
+            let comp = Rc::new(walk_comp(r, scope, comp));
+            let body = walk_under(r, scope, vec![(var.clone(), None)], body);
+            Do { var: var.clone(), comp, body }.into()
+        }
+        SynComp::Rec(Rec { var, body }) => {
+            let body = walk_under(r, scope, vec![(var.clone(), None)], body);
+            Rec { var: var.clone(), body }.into()
+        }
+        SynComp::Match(Match { scrut, arms }) => {
+            let scrut = Rc::new(walk_val(r, scope, scrut));
+            let arms = arms
+                .iter()
+                .map(|Matcher { ctorv, vars, body }| {
+                    let binds = vars.iter().map(|var| (var.clone(), None)).collect();
+                    let body = walk_under(r, scope, binds, body);
+                    Matcher { ctorv: ctorv.clone(), vars: vars.clone(), body }
+                })
+                .collect();
+            Match { scrut, arms }.into()
+        }
+        SynComp::Comatch(Comatch { arms }) => {
+            let arms = arms
+                .iter()
+                .map(|Comatcher { dtorv, body }| Comatcher {
+                    dtorv: dtorv.clone(),
+                    body: Rc::new(walk_comp(r, scope, body)),
+                })
+                .collect();
+            Comatch { arms }.into()
+        }
+        SynComp::Dtor(Dtor { body, dtorv }) => {
+            Dtor { body: Rc::new(walk_comp(r, scope, body)), dtorv: dtorv.clone() }.into()
+        }
+        SynComp::Prim(prim) => prim.clone().into(),
+        SynComp::Perform(Perform { opv, args }) => Perform {
+            opv: opv.clone(),
+            args: args.iter().map(|arg| Rc::new(walk_val(r, scope, arg))).collect(),
+        }
+        .into(),
+        SynComp::Handle(Handle { body, ret, arms }) => {
+            let body = Rc::new(walk_comp(r, scope, body));
+            let ret = ret.as_ref().map(|Abs { param, body }| {
+                let body = walk_under(r, scope, vec![(param.clone(), None)], body);
+                Abs { param: param.clone(), body }
+            });
+            let arms = arms
+                .iter()
+                .map(|Handler { opv, vars, kont, body }| {
+                    let binds = vars.iter().chain([kont]).map(|var| (var.clone(), None)).collect();
+                    let body = walk_under(r, scope, binds, body);
+                    Handler { opv: opv.clone(), vars: vars.clone(), kont: kont.clone(), body }
+                })
+                .collect();
+            Handle { body, ret, arms }.into()
+        }
+        SynComp::Resume(resume) => resume.clone().into(),
+        SynComp::Control(control) => control.clone().into(),
+    };
+    r.comp(scope, comp.info.make(inner))
+}
+
+/* ----------------------------- Free Variables ----------------------------- */
+
+fn free_val(val: &SynVal) -> HashSet<TermV> {
+    let mut fvs = HashSet::new();
+    free_val_into(val, &mut Vec::new(), &mut fvs);
+    fvs
+}
+
+fn free_comp(comp: &Sp<SynComp>) -> HashSet<TermV> {
+    let mut fvs = HashSet::new();
+    free_comp_into(comp, &mut Vec::new(), &mut fvs);
+    fvs
+}
+
+fn free_val_into(val: &SynVal, bound: &mut Vec<TermV>, fvs: &mut HashSet<TermV>) {
+    match val {
+        SynVal::Var(x) if !bound.contains(x) => {
+            fvs.insert(x.clone());
+        }
+        SynVal::Thunk(Thunk(body)) => free_comp_into(body, bound, fvs),
+        SynVal::Ctor(Ctor { args, .. }) => {
+            for arg in args {
+                free_val_into(arg, bound, fvs)
+            }
+        }
+        SynVal::Var(_) | SynVal::Literal(_) | SynVal::SemValue(_) => {}
+    }
+}
+
+fn free_under(
+    vars: &[TermV], comp: &Sp<SynComp>, bound: &mut Vec<TermV>, fvs: &mut HashSet<TermV>,
+) {
+    bound.extend(vars.iter().cloned());
+    free_comp_into(comp, bound, fvs);
+    bound.truncate(bound.len() - vars.len());
+}
+
+fn free_comp_into(comp: &Sp<SynComp>, bound: &mut Vec<TermV>, fvs: &mut HashSet<TermV>) {
+    match &comp.inner {
+        SynComp::Abs(Abs { param, body }) => free_under(slice::from_ref(param), body, bound, fvs),
+        SynComp::App(App { body, arg }) => {
+            free_comp_into(body, bound, fvs);
+            free_val_into(arg, bound, fvs);
+        }
+        SynComp::Ret(Ret(v)) | SynComp::Force(Force(v)) => free_val_into(v, bound, fvs),
+        SynComp::Let(Let { var, def, body }) => {
+            free_val_into(def, bound, fvs);
+            free_under(slice::from_ref(var), body, bound, fvs);
+        }
+        SynComp::Do(Do { var, comp, body }) => {
+            free_comp_into(comp, bound, fvs);
+            free_under(slice::from_ref(var), body, bound, fvs);
+        }
+        SynComp::Rec(Rec { var, body }) => free_under(slice::from_ref(var), body, bound, fvs),
+        SynComp::Match(Match { scrut, arms }) => {
+            free_val_into(scrut, bound, fvs);
+            for Matcher { vars, body, .. } in arms {
+                free_under(vars, body, bound, fvs);
+            }
+        }
+        SynComp::Comatch(Comatch { arms }) => {
+            for Comatcher { body, .. } in arms {
+                free_comp_into(body, bound, fvs);
+            }
+        }
+        SynComp::Dtor(Dtor { body, .. }) => free_comp_into(body, bound, fvs),
+        SynComp::Prim(_) | SynComp::Resume(_) | SynComp::Control(_) => {}
+        SynComp::Perform(Perform { args, .. }) => {
+            for arg in args {
+                free_val_into(arg, bound, fvs)
+            }
+        }
+        SynComp::Handle(Handle { body, ret, arms }) => {
+            free_comp_into(body, bound, fvs);
+            if let Some(Abs { param, body }) = ret {
+                free_under(slice::from_ref(param), body, bound, fvs);
+            }
+            for Handler { vars, kont, body, .. } in arms {
+                let vars: Vec<_> = vars.iter().chain([kont]).cloned().collect();
+                free_under(&vars, body, bound, fvs);
+            }
+        }
+    }
+}
+
+/* --------------------------------- Inline --------------------------------- */
+
+fn size_val(val: &SynVal) -> usize {
+    match val {
+        SynVal::Thunk(Thunk(body)) => 1 + size_comp(body),
+        SynVal::Ctor(Ctor { args, .. }) => 1 + args.iter().map(|arg| size_val(arg)).sum::<usize>(),
+        SynVal::Var(_) | SynVal::Literal(_) | SynVal::SemValue(_) => 1,
+    }
+}
+
+fn size_comp(comp: &Sp<SynComp>) -> usize {
+    1 + match &comp.inner {
+        SynComp::Abs(Abs { body, .. }) | SynComp::Rec(Rec { body, .. }) => size_comp(body),
+        SynComp::App(App { body, arg }) => size_comp(body) + size_val(arg),
+        SynComp::Ret(Ret(v)) | SynComp::Force(Force(v)) => size_val(v),
+        SynComp::Let(Let { def, body, .. }) => size_val(def) + size_comp(body),
+        SynComp::Do(Do { comp, body, .. }) => size_comp(comp) + size_comp(body),
+        SynComp::Match(Match { scrut, arms }) => {
+            size_val(scrut) + arms.iter().map(|arm| size_comp(&arm.body)).sum::<usize>()
+        }
+        SynComp::Comatch(Comatch { arms }) => arms.iter().map(|arm| size_comp(&arm.body)).sum(),
+        SynComp::Dtor(Dtor { body, .. }) => size_comp(body),
+        SynComp::Prim(_) | SynComp::Resume(_) | SynComp::Control(_) => 0,
+        SynComp::Perform(Perform { args, .. }) => args.iter().map(|arg| size_val(arg)).sum(),
+        SynComp::Handle(Handle { body, ret, arms }) => {
+            size_comp(body)
+                + ret.iter().map(|ret| size_comp(&ret.body)).sum::<usize>()
+                + arms.iter().map(|arm| size_comp(&arm.body)).sum::<usize>()
+        }
+    }
+}
+
+/// The small definitions seen so far, with their free variables.
+struct Inline(Vec<(TermV, SynVal, HashSet<TermV>)>);
+
+impl Rewrite for Inline {
+    fn val(&mut self, scope: &Scope, val: SynVal) -> SynVal {
+        let SynVal::Var(x) = &val else { return val };
+        if scope.binds(x) {
+            return val;
+        }
+        match self.0.iter().rev().find(|(y, _, _)| y == x) {
+            // Note: the value would be wrong where a local shadows one of its free variables
+            Some((_, def, fvs)) if !fvs.iter().any(|fv| scope.binds(fv)) => def.clone(),
+            _ => val,
+        }
+    }
+}
+
+fn inline(p: &Program) -> Program {
+    let mut r = Inline(Vec::new());
+    let mut define = im::Vector::new();
+    for (x, v) in p.module.define.iter() {
+        let v = walk_val(&mut r, &mut Scope::default(), v);
+        // Note: later code refers to this `x`, so drop what depends on an earlier one
+        r.0.retain(|(y, _, fvs)| y != x && !fvs.contains(x));
+        let fvs = free_val(&v);
+        if size_val(&v) <= INLINE_SIZE && !fvs.contains(x) {
+            r.0.push((x.clone(), v.clone(), fvs));
+        }
+        define.push_back((x.clone(), v));
+    }
+    let entry = walk_comp(&mut r, &mut Scope::default(), &p.entry);
+    Program { module: Module { name: p.module.name.clone(), define }, entry }
+}
+
+/* ------------------------------ Simplification ----------------------------- */
+
+struct ForceThunk;
+
+impl Rewrite for ForceThunk {
+    fn comp(&mut self, _scope: &Scope, comp: Sp<SynComp>) -> Sp<SynComp> {
+        match &comp.inner {
+            SynComp::Force(Force(v)) => match v.as_ref() {
+                // Note: builtins have no source location, so they keep the one of the force,
+                // which the evaluator blames them on
+                SynVal::Thunk(Thunk(body)) if body.info.is_dummy() => {
+                    comp.info.make(body.inner.clone())
+                }
+                SynVal::Thunk(Thunk(body)) => body.as_ref().clone(),
+                _ => comp,
+            },
+            _ => comp,
+        }
+    }
+}
+
+struct DoRet;
+
+impl Rewrite for DoRet {
+    fn comp(&mut self, _scope: &Scope, comp: Sp<SynComp>) -> Sp<SynComp> {
+        match &comp.inner {
+            SynComp::Do(Do { var, comp: first, body }) => match &first.inner {
+                SynComp::Ret(Ret(v)) => {
+                    let term = Let { var: var.clone(), def: v.clone(), body: body.clone() };
+                    comp.info.make(term.into())
+                }
+                _ => comp,
+            },
+            _ => comp,
+        }
+    }
+}
+
+struct KnownCtor;
+
+impl Rewrite for KnownCtor {
+    fn comp(&mut self, scope: &Scope, comp: Sp<SynComp>) -> Sp<SynComp> {
+        let SynComp::Match(Match { scrut, arms }) = &comp.inner else { return comp };
+        let ctor = match scrut.as_ref() {
+            SynVal::Var(x) => scope.lookup(x),
+            scrut => Some(scrut),
+        };
+        let Some(SynVal::Ctor(Ctor { ctorv, args })) = ctor else { return comp };
+        let Some(Matcher { vars, body, .. }) = arms.iter().find(|arm| &arm.ctorv == ctorv) else {
+            return comp;
+        };
+        // Note: each argument is bound in the scope of the arguments before it
+        let captured = args.iter().enumerate().any(|(i, arg)| {
+            let fvs = free_val(arg);
+            vars[..i.min(vars.len())].iter().any(|var| fvs.contains(var))
+        });
+        if vars.len() != args.len() || captured {
+            return comp;
+        }
+        let mut body = body.as_ref().clone();
+        for (var, arg) in vars.iter().zip(args).rev() {
+            let term = Let { var: var.clone(), def: arg.clone(), body: Rc::new(body) };
+            body = var.span().make(term.into());
+        }
+        body
+    }
+}
+
+/* -------------------------------- Dead Defs ------------------------------- */
+
+fn dead_defs(p: &Program) -> Program {
+    // Note: a definition only refers to the ones before it
+    let mut live = free_comp(&p.entry);
+    let mut define = im::Vector::new();
+    for (x, v) in p.module.define.iter().rev() {
+        if live.remove(x) {
+            live.extend(free_val(v));
+            define.push_front((x.clone(), v.clone()));
+        }
+    }
+    Program { module: Module { name: p.module.name.clone(), define }, entry: p.entry.clone() }
+}
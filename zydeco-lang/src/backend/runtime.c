@@ -0,0 +1,1532 @@
+/* Runtime for programs compiled by the zydeco C backend.
+ *
+ * Every thunk body and `do` continuation is closure-converted into a numbered code unit, and a
+ * closure is the number of its code together with the values it captured. The generated
+ * `zy_run` dispatches on that number in a loop, so running a computation never grows the C
+ * stack. `Int`s, `Float`s and `Char`s are stored unboxed; `String`s are arrays of code points
+ * and `BigInt`s are a sign with an array of 32-bit limbs. A `Ref` points to a single value, an
+ * `Array` is its length followed by the elements, and an `IntMap` is a persistent AVL tree whose
+ * empty tree is `NULL`. A `Map` or a `Set` is a `HashMap`, described with its builtins.
+ *
+ * Memory is reclaimed by a mark-and-sweep collector that runs between code units, where the only
+ * live values are the closure to run next, the stack, `zy_R` and the globals. Values carry no
+ * tags, so every word of a reachable object that points to the start of an allocation keeps it
+ * alive.
+ *
+ * The builtins reach the host's files, environment and programs directly. Nothing here checks
+ * the permissions that the evaluator and the VM enforce, so a compiled program is trusted like
+ * any other executable.
+ */
+
+#include <dirent.h>
+#include <errno.h>
+#include <inttypes.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <time.h>
+
+/* generated code binds variables it may not use, and a program need not use every builtin */
+#pragma GCC diagnostic ignored "-Wunused-variable"
+#pragma GCC diagnostic ignored "-Wunused-function"
+
+typedef union {
+  int64_t i;
+  double f;
+  void *p;
+} Value;
+#define I(x) ((Value){.i = (x)})
+#define F(x) ((Value){.f = (x)})
+#define P(x) ((Value){.p = (x)})
+
+typedef struct {
+  size_t len;
+  uint32_t *chars;
+} Str;
+
+typedef struct {
+  uint32_t tag;
+  uint32_t arity;
+  Value args[];
+} Ctor;
+
+typedef struct {
+  size_t len;
+  Value items[];
+} Arr;
+
+typedef struct Map {
+  int64_t key;
+  Value value;
+  struct Map *l, *r;
+  int height;
+  size_t size;
+} Map;
+
+typedef struct {
+  uint32_t code;
+  Value env[];
+} Clo;
+
+enum { ZY_APP, ZY_KONT, ZY_DTOR };
+typedef struct {
+  int tag;
+  union {
+    Value v;
+    Clo *k;
+    uint32_t d;
+  };
+} Frame;
+
+/* constructors the builtins produce; the compiler numbers them first */
+enum { ZY_TRUE, ZY_FALSE, ZY_NONE, ZY_SOME, ZY_CONS, ZY_NIL, ZY_UNIT, ZY_LEFT, ZY_RIGHT };
+
+static Frame *zy_stack;
+static size_t zy_sp, zy_cap;
+/* the value a continuation resumes with */
+static Value zy_R;
+static int zy_argc;
+static char **zy_argv;
+
+static _Noreturn void zy_error(const char *fmt, ...) {
+  va_list args;
+  fflush(stdout);
+  fprintf(stderr, "Runtime Error: ");
+  va_start(args, fmt);
+  vfprintf(stderr, fmt, args);
+  va_end(args);
+  fprintf(stderr, "\n");
+  exit(1);
+}
+
+static Value zy_unbound(const char *x) { zy_error("Variable %s is not bound", x); }
+
+/* ------------------------------- the heap -------------------------------- */
+
+/* The heap is a list of chunks, each filled with blocks that start with a header: the size of the
+ * block, with `ZY_MARKED` or `ZY_FREE` in the low bits. Allocation moves through a free run, and
+ * a collection sweeps the chunks into new runs, coalescing the free blocks next to each other. */
+
+enum { ZY_MARKED = 1, ZY_FREE = 2, ZY_CHUNK = 1 << 20, ZY_GC_MIN = 8 << 20 };
+
+typedef struct {
+  char *start, *end;
+  /* a bit per 8 bytes, set where an allocated object starts */
+  uint8_t *starts;
+} Chunk;
+
+/* sorted by address */
+static Chunk *zy_chunks;
+static size_t zy_nchunks;
+/* the run being allocated from, and the free runs after it, linked through their first word */
+static char *zy_hp, *zy_hp_end;
+static char *zy_runs;
+/* the objects marked but not yet scanned */
+static size_t **zy_grey;
+static size_t zy_ngrey, zy_grey_cap;
+/* the bytes allocated since the last collection, and how many start the next one */
+static size_t zy_allocated, zy_gc_limit = ZY_GC_MIN;
+
+/* turns the rest of the current run into a free block */
+static void zy_close_run(void) {
+  if (zy_hp < zy_hp_end) *(size_t *)zy_hp = (size_t)(zy_hp_end - zy_hp) | ZY_FREE;
+  zy_hp = zy_hp_end = NULL;
+}
+
+static void zy_new_chunk(size_t size) {
+  size = size > ZY_CHUNK ? size : ZY_CHUNK;
+  Chunk c = {malloc(size), NULL, calloc(size / 64 + 1, 1)};
+  zy_chunks = realloc(zy_chunks, (zy_nchunks + 1) * sizeof(Chunk));
+  if (c.start == NULL || c.starts == NULL || zy_chunks == NULL) zy_error("out of memory");
+  c.end = c.start + size;
+  size_t i = zy_nchunks++;
+  for (; i > 0 && zy_chunks[i - 1].start > c.start; i--) zy_chunks[i] = zy_chunks[i - 1];
+  zy_chunks[i] = c;
+  zy_hp = c.start;
+  zy_hp_end = c.end;
+}
+
+static Chunk *zy_chunk_of(const char *p) {
+  size_t lo = 0, hi = zy_nchunks;
+  while (lo < hi) {
+    size_t mid = lo + (hi - lo) / 2;
+    if (zy_chunks[mid].end <= p) lo = mid + 1;
+    else hi = mid;
+  }
+  return lo < zy_nchunks && zy_chunks[lo].start <= p ? &zy_chunks[lo] : NULL;
+}
+
+static void zy_set_start(Chunk *c, const char *p, int set) {
+  size_t i = (size_t)(p - c->start) / 8;
+  if (set) c->starts[i / 8] |= 1 << (i % 8);
+  else c->starts[i / 8] &= ~(1 << (i % 8));
+}
+
+static void *zy_new(size_t size) {
+  size_t need = 8 + (size < 8 ? 8 : (size + 7) & ~(size_t)7);
+  while ((size_t)(zy_hp_end - zy_hp) < need) {
+    zy_close_run();
+    if (zy_runs != NULL) {
+      zy_hp = zy_runs;
+      zy_hp_end = zy_runs + (*(size_t *)zy_runs & ~(size_t)7);
+      zy_runs = *(char **)(zy_runs + 8);
+    } else {
+      zy_new_chunk(need);
+    }
+  }
+  char *block = zy_hp;
+  zy_hp += need;
+  *(size_t *)block = need;
+  zy_set_start(zy_chunk_of(block), block + 8, 1);
+  zy_allocated += need;
+  return block + 8;
+}
+
+static void zy_mark(Value v) {
+  char *p = v.p;
+  if ((uintptr_t)p % 8) return;
+  Chunk *c = zy_chunk_of(p);
+  if (c == NULL || p < c->start + 8) return;
+  size_t i = (size_t)(p - c->start) / 8;
+  if (!(c->starts[i / 8] & (1 << (i % 8)))) return;
+  size_t *header = (size_t *)(p - 8);
+  if (*header & ZY_MARKED) return;
+  *header |= ZY_MARKED;
+  if (zy_ngrey == zy_grey_cap) {
+    zy_grey_cap = zy_grey_cap ? zy_grey_cap * 2 : 1024;
+    zy_grey = realloc(zy_grey, zy_grey_cap * sizeof(size_t *));
+    if (zy_grey == NULL) zy_error("out of memory");
+  }
+  zy_grey[zy_ngrey++] = header;
+}
+
+/* turns the blocks from `run` to `end` into one free block, adding it to the runs if it fits any
+ * object */
+static void zy_free_run(char *run, char *end) {
+  if (run == NULL) return;
+  *(size_t *)run = (size_t)(end - run) | ZY_FREE;
+  if (end - run < 16) return;
+  *(char **)(run + 8) = zy_runs;
+  zy_runs = run;
+}
+
+/* frees the unmarked blocks of a chunk, returning how many bytes stay allocated */
+static size_t zy_sweep(Chunk *c) {
+  size_t live = 0;
+  char *b = c->start, *run = NULL;
+  while (b < c->end) {
+    size_t *header = (size_t *)b, size = *header & ~(size_t)7;
+    if (*header & ZY_MARKED) {
+      zy_free_run(run, b);
+      run = NULL;
+      *header = size;
+      live += size;
+    } else {
+      if (!(*header & ZY_FREE)) zy_set_start(c, b + 8, 0);
+      if (run == NULL) run = b;
+    }
+    b += size;
+  }
+  zy_free_run(run, c->end);
+  return live;
+}
+
+/* marks the globals of the program, which the compiler defines */
+static void zy_mark_globals(void);
+
+static void zy_collect(Clo *k) {
+  zy_close_run();
+  zy_mark(P(k));
+  zy_mark(zy_R);
+  zy_mark_globals();
+  for (size_t i = 0; i < zy_sp; i++)
+    if (zy_stack[i].tag != ZY_DTOR) zy_mark(zy_stack[i].v);
+  while (zy_ngrey) {
+    size_t *header = zy_grey[--zy_ngrey];
+    Value *fields = (Value *)(header + 1);
+    for (size_t i = 0; i < (*header & ~(size_t)7) / 8 - 1; i++) zy_mark(fields[i]);
+  }
+  size_t live = 0, n = 0;
+  zy_runs = NULL;
+  for (size_t i = 0; i < zy_nchunks; i++) {
+    size_t used = zy_sweep(&zy_chunks[i]);
+    live += used;
+    /* Note: a chunk with nothing left in it goes back to the system, along with its runs */
+    if (used == 0) {
+      Chunk *c = &zy_chunks[i];
+      if (zy_runs >= c->start && zy_runs < c->end) zy_runs = *(char **)(zy_runs + 8);
+      free(c->start);
+      free(c->starts);
+    } else {
+      zy_chunks[n++] = zy_chunks[i];
+    }
+  }
+  zy_nchunks = n;
+  zy_allocated = 0;
+  zy_gc_limit = live > ZY_GC_MIN ? live : ZY_GC_MIN;
+}
+
+/* ------------------------------- the stack ------------------------------- */
+
+static void zy_push(Frame f) {
+  if (zy_sp == zy_cap) {
+    zy_cap = zy_cap ? zy_cap * 2 : 1024;
+    zy_stack = realloc(zy_stack, zy_cap * sizeof(Frame));
+    if (zy_stack == NULL) zy_error("out of memory");
+  }
+  zy_stack[zy_sp++] = f;
+}
+static void zy_push_arg(Value v) { zy_push((Frame){.tag = ZY_APP, .v = v}); }
+static void zy_push_kont(Clo *k) { zy_push((Frame){.tag = ZY_KONT, .k = k}); }
+static void zy_push_dtor(uint32_t d) { zy_push((Frame){.tag = ZY_DTOR, .d = d}); }
+
+static const char *zy_frame(void) {
+  if (zy_sp == 0) return "an empty stack";
+  switch (zy_stack[zy_sp - 1].tag) {
+  case ZY_APP: return "an argument";
+  case ZY_KONT: return "a continuation";
+  default: return "a destructor";
+  }
+}
+static Value zy_arg(void) {
+  if (zy_sp == 0 || zy_stack[zy_sp - 1].tag != ZY_APP) {
+    zy_error("Expected an argument on the stack top, but found %s", zy_frame());
+  }
+  return zy_stack[--zy_sp].v;
+}
+static uint32_t zy_dtor(void) {
+  if (zy_sp == 0 || zy_stack[zy_sp - 1].tag != ZY_DTOR) {
+    zy_error("Expected a destructor on the stack top, but found %s", zy_frame());
+  }
+  return zy_stack[--zy_sp].d;
+}
+static Clo *zy_ret(Value v) {
+  if (zy_sp == 0) {
+    fflush(stdout);
+    fprintf(stderr, "Error: Program did not exit\n");
+    exit(0);
+  }
+  if (zy_stack[zy_sp - 1].tag != ZY_KONT) {
+    zy_error("Expected a continuation on the stack top, but found %s", zy_frame());
+  }
+  zy_R = v;
+  return zy_stack[--zy_sp].k;
+}
+static void zy_prim_args(const char *name, int arity, Value *args) {
+  for (int i = 0; i < arity; i++) {
+    if (zy_sp == 0 || zy_stack[zy_sp - 1].tag != ZY_APP) {
+      zy_error("Builtin %s expects %d arguments on the stack", name, arity);
+    }
+    args[i] = zy_stack[--zy_sp].v;
+  }
+}
+static Clo *zy_apply(Value k, Value v) {
+  zy_push_arg(v);
+  return k.p;
+}
+
+/* --------------------------------- values -------------------------------- */
+
+static Clo *zy_clo(uint32_t code, size_t n, const Value *env) {
+  Clo *c = zy_new(sizeof(Clo) + n * sizeof(Value));
+  c->code = code;
+  if (n) memcpy(c->env, env, n * sizeof(Value));
+  return c;
+}
+static Ctor *zy_ctor(uint32_t tag, uint32_t arity, const Value *args) {
+  Ctor *c = zy_new(sizeof(Ctor) + arity * sizeof(Value));
+  c->tag = tag;
+  c->arity = arity;
+  if (arity) memcpy(c->args, args, arity * sizeof(Value));
+  return c;
+}
+static Value zy_bool(int b) { return P(zy_ctor(b ? ZY_TRUE : ZY_FALSE, 0, NULL)); }
+static Value zy_none(void) { return P(zy_ctor(ZY_NONE, 0, NULL)); }
+static Value zy_some(Value v) { return P(zy_ctor(ZY_SOME, 1, &v)); }
+static Value zy_pair(Value a, Value b) { return P(zy_ctor(ZY_CONS, 2, (Value[]){a, b})); }
+static Value zy_left(Value v) { return P(zy_ctor(ZY_LEFT, 1, &v)); }
+static Value zy_right(Value v) { return P(zy_ctor(ZY_RIGHT, 1, &v)); }
+
+static Str *zy_str(size_t len) {
+  Str *s = zy_new(sizeof(Str));
+  s->len = len;
+  s->chars = zy_new(len ? len * sizeof(uint32_t) : 1);
+  return s;
+}
+static Str *zy_substr(const Str *s, size_t from, size_t to) {
+  Str *r = zy_str(to - from);
+  if (to > from) memcpy(r->chars, s->chars + from, (to - from) * sizeof(uint32_t));
+  return r;
+}
+/* decodes UTF-8, replacing malformed sequences */
+static Str *zy_decode(const char *bytes, size_t n) {
+  Str *s = zy_str(n);
+  size_t len = 0;
+  for (size_t i = 0; i < n;) {
+    unsigned char b = bytes[i];
+    int extra = b < 0x80             ? 0
+                : (b & 0xe0) == 0xc0 ? 1
+                : (b & 0xf0) == 0xe0 ? 2
+                : (b & 0xf8) == 0xf0 ? 3
+                                     : -1;
+    uint32_t c = extra == 0 ? b : extra == 1 ? b & 0x1f : extra == 2 ? b & 0x0f : b & 0x07;
+    int ok = extra >= 0 && i + extra < n;
+    for (int j = 1; ok && j <= extra; j++) {
+      unsigned char cont = bytes[i + j];
+      ok = (cont & 0xc0) == 0x80;
+      c = (c << 6) | (cont & 0x3f);
+    }
+    if (ok) {
+      s->chars[len++] = c;
+      i += extra + 1;
+    } else {
+      s->chars[len++] = 0xfffd;
+      i += 1;
+    }
+  }
+  s->len = len;
+  return s;
+}
+/* encodes as NUL-terminated UTF-8, returning the number of bytes */
+static size_t zy_encode(const Str *s, char **out) {
+  char *bytes = zy_new(s->len * 4 + 1);
+  size_t n = 0;
+  for (size_t i = 0; i < s->len; i++) {
+    uint32_t c = s->chars[i];
+    if (c < 0x80) {
+      bytes[n++] = c;
+    } else if (c < 0x800) {
+      bytes[n++] = 0xc0 | (c >> 6);
+      bytes[n++] = 0x80 | (c & 0x3f);
+    } else if (c < 0x10000) {
+      bytes[n++] = 0xe0 | (c >> 12);
+      bytes[n++] = 0x80 | ((c >> 6) & 0x3f);
+      bytes[n++] = 0x80 | (c & 0x3f);
+    } else {
+      bytes[n++] = 0xf0 | (c >> 18);
+      bytes[n++] = 0x80 | ((c >> 12) & 0x3f);
+      bytes[n++] = 0x80 | ((c >> 6) & 0x3f);
+      bytes[n++] = 0x80 | (c & 0x3f);
+    }
+  }
+  bytes[n] = 0;
+  *out = bytes;
+  return n;
+}
+/* parses like Rust's `i64::from_str` */
+static int zy_parse_int(const Str *s, int64_t *out) {
+  size_t i = 0;
+  int neg = 0;
+  if (s->len > 0 && (s->chars[0] == '+' || s->chars[0] == '-')) {
+    neg = s->chars[0] == '-';
+    i = 1;
+  }
+  if (i == s->len) return 0;
+  uint64_t limit = neg ? (uint64_t)INT64_MAX + 1 : (uint64_t)INT64_MAX, n = 0;
+  for (; i < s->len; i++) {
+    uint32_t c = s->chars[i];
+    if (c < '0' || c > '9') return 0;
+    if (n > (limit - (c - '0')) / 10) return 0;
+    n = n * 10 + (c - '0');
+  }
+  *out = neg ? (int64_t)(0 - n) : (int64_t)n;
+  return 1;
+}
+static Str *zy_read_line(void) {
+  size_t cap = 64, n = 0;
+  char *bytes = zy_new(cap);
+  int c;
+  fflush(stdout);
+  while ((c = getchar()) != EOF) {
+    if (n == cap) {
+      char *more = zy_new(cap * 2);
+      memcpy(more, bytes, n);
+      bytes = more;
+      cap *= 2;
+    }
+    bytes[n++] = c;
+    if (c == '\n') break;
+  }
+  Str *s = zy_decode(bytes, n);
+  /* like the interpreter, drop the last character whatever it is */
+  if (s->len > 0) s->len--;
+  return s;
+}
+/* reads to the end of a file, returning NULL on failure */
+static Str *zy_read_all(FILE *f) {
+  size_t cap = 4096, n = 0, got;
+  char *bytes = zy_new(cap);
+  while ((got = fread(bytes + n, 1, cap - n, f)) > 0) {
+    n += got;
+    if (n == cap) {
+      char *more = zy_new(cap * 2);
+      memcpy(more, bytes, n);
+      bytes = more;
+      cap *= 2;
+    }
+  }
+  return ferror(f) ? NULL : zy_decode(bytes, n);
+}
+
+/* ------------------------------ big integers ----------------------------- */
+
+/* the magnitude is least significant limb first, without leading zero limbs */
+typedef struct {
+  int neg;
+  size_t len;
+  uint32_t limbs[];
+} Big;
+
+static Big *zy_big(size_t len) {
+  Big *b = zy_new(sizeof(Big) + (len ? len : 1) * sizeof(uint32_t));
+  b->neg = 0;
+  b->len = len;
+  if (len) memset(b->limbs, 0, len * sizeof(uint32_t));
+  return b;
+}
+static Big *zy_big_trim(Big *b) {
+  while (b->len > 0 && b->limbs[b->len - 1] == 0) b->len--;
+  if (b->len == 0) b->neg = 0;
+  return b;
+}
+static Big *zy_big_copy(const Big *a) {
+  Big *b = zy_big(a->len);
+  b->neg = a->neg;
+  if (a->len) memcpy(b->limbs, a->limbs, a->len * sizeof(uint32_t));
+  return b;
+}
+static Big *zy_big_lit(int neg, size_t len, const uint32_t *limbs) {
+  Big *b = zy_big(len);
+  b->neg = neg;
+  if (len) memcpy(b->limbs, limbs, len * sizeof(uint32_t));
+  return b;
+}
+static Big *zy_big_of(int64_t i) {
+  uint64_t u = i < 0 ? 0 - (uint64_t)i : (uint64_t)i;
+  Big *b = zy_big(2);
+  b->neg = i < 0;
+  b->limbs[0] = (uint32_t)u;
+  b->limbs[1] = (uint32_t)(u >> 32);
+  return zy_big_trim(b);
+}
+static int zy_big_to_int(const Big *a, int64_t *out) {
+  if (a->len > 2) return 0;
+  uint64_t u = a->len > 0 ? a->limbs[0] : 0;
+  if (a->len > 1) u |= (uint64_t)a->limbs[1] << 32;
+  if (u > (a->neg ? (uint64_t)INT64_MAX + 1 : (uint64_t)INT64_MAX)) return 0;
+  *out = a->neg ? (int64_t)(0 - u) : (int64_t)u;
+  return 1;
+}
+static int zy_mag_cmp(const Big *a, const Big *b) {
+  if (a->len != b->len) return a->len < b->len ? -1 : 1;
+  for (size_t i = a->len; i-- > 0;) {
+    if (a->limbs[i] != b->limbs[i]) return a->limbs[i] < b->limbs[i] ? -1 : 1;
+  }
+  return 0;
+}
+static int zy_big_cmp(const Big *a, const Big *b) {
+  if (a->neg != b->neg) return a->neg ? -1 : 1;
+  return a->neg ? -zy_mag_cmp(a, b) : zy_mag_cmp(a, b);
+}
+static Big *zy_mag_add(const Big *a, const Big *b) {
+  if (a->len < b->len) return zy_mag_add(b, a);
+  Big *r = zy_big(a->len + 1);
+  uint64_t carry = 0;
+  for (size_t i = 0; i < a->len; i++) {
+    carry += (uint64_t)a->limbs[i] + (i < b->len ? b->limbs[i] : 0);
+    r->limbs[i] = (uint32_t)carry;
+    carry >>= 32;
+  }
+  r->limbs[a->len] = (uint32_t)carry;
+  return r;
+}
+/* subtracts the magnitude of `b` from that of `a` in place, which must be at least as large */
+static void zy_mag_sub(Big *a, const Big *b) {
+  uint64_t borrow = 0;
+  for (size_t i = 0; i < a->len; i++) {
+    uint64_t d = (uint64_t)a->limbs[i] - (i < b->len ? b->limbs[i] : 0) - borrow;
+    a->limbs[i] = (uint32_t)d;
+    borrow = d >> 63;
+  }
+}
+/* adds `b`, or subtracts it if `negate` is set */
+static Big *zy_big_add(const Big *a, const Big *b, int negate) {
+  int neg = b->neg ^ negate;
+  Big *r;
+  if (a->neg == neg) {
+    r = zy_mag_add(a, b);
+    r->neg = neg;
+  } else if (zy_mag_cmp(a, b) >= 0) {
+    r = zy_big_copy(a);
+    zy_mag_sub(r, b);
+  } else {
+    r = zy_big_copy(b);
+    zy_mag_sub(r, a);
+    r->neg = neg;
+  }
+  return zy_big_trim(r);
+}
+static Big *zy_big_mul(const Big *a, const Big *b) {
+  Big *r = zy_big(a->len + b->len);
+  for (size_t i = 0; i < a->len; i++) {
+    uint64_t carry = 0;
+    for (size_t j = 0; j < b->len; j++) {
+      carry += (uint64_t)a->limbs[i] * b->limbs[j] + r->limbs[i + j];
+      r->limbs[i + j] = (uint32_t)carry;
+      carry >>= 32;
+    }
+    r->limbs[i + b->len] = (uint32_t)carry;
+  }
+  r->neg = a->neg != b->neg;
+  return zy_big_trim(r);
+}
+/* long division one bit at a time, rounding toward zero like the interpreter */
+static void zy_big_divmod(const Big *a, const Big *b, Big **q, Big **r) {
+  Big *quo = zy_big(a->len), *rem = zy_big(b->len + 1);
+  rem->len = 0;
+  for (size_t i = a->len * 32; i-- > 0;) {
+    uint32_t carry = (a->limbs[i / 32] >> (i % 32)) & 1;
+    for (size_t j = 0; j < rem->len; j++) {
+      uint32_t top = rem->limbs[j] >> 31;
+      rem->limbs[j] = (rem->limbs[j] << 1) | carry;
+      carry = top;
+    }
+    if (carry) rem->limbs[rem->len++] = carry;
+    if (zy_mag_cmp(rem, b) >= 0) {
+      zy_mag_sub(rem, b);
+      zy_big_trim(rem);
+      quo->limbs[i / 32] |= (uint32_t)1 << (i % 32);
+    }
+  }
+  quo->neg = a->neg != b->neg;
+  rem->neg = a->neg;
+  *q = zy_big_trim(quo);
+  *r = zy_big_trim(rem);
+}
+static Str *zy_big_to_str(const Big *a) {
+  Big *t = zy_big_copy(a);
+  /* nine digits at a time, each limb having at most ten */
+  size_t cap = a->len * 10 + 10, n = cap;
+  char *bytes = zy_new(cap);
+  do {
+    uint64_t rem = 0;
+    for (size_t i = t->len; i-- > 0;) {
+      uint64_t cur = (rem << 32) | t->limbs[i];
+      t->limbs[i] = (uint32_t)(cur / 1000000000);
+      rem = cur % 1000000000;
+    }
+    zy_big_trim(t);
+    for (int d = 0; d < 9; d++) {
+      bytes[--n] = '0' + rem % 10;
+      rem /= 10;
+    }
+  } while (t->len > 0);
+  while (n < cap - 1 && bytes[n] == '0') n++;
+  if (a->neg) bytes[--n] = '-';
+  return zy_decode(bytes + n, cap - n);
+}
+/* parses the same strings as `zy_parse_int`, but without bounds */
+static Big *zy_parse_big(const Str *s) {
+  size_t i = 0;
+  int neg = 0;
+  if (s->len > 0 && (s->chars[0] == '+' || s->chars[0] == '-')) {
+    neg = s->chars[0] == '-';
+    i = 1;
+  }
+  if (i == s->len) return NULL;
+  /* a limb holds at least nine digits */
+  Big *b = zy_big(s->len / 9 + 1);
+  for (; i < s->len; i++) {
+    uint32_t c = s->chars[i];
+    if (c < '0' || c > '9') return NULL;
+    uint64_t carry = c - '0';
+    for (size_t j = 0; j < b->len; j++) {
+      carry += (uint64_t)b->limbs[j] * 10;
+      b->limbs[j] = (uint32_t)carry;
+      carry >>= 32;
+    }
+  }
+  b->neg = neg;
+  return zy_big_trim(b);
+}
+
+/* --------------------------------- floats -------------------------------- */
+
+static Big *zy_big_pow2(unsigned n) {
+  Big *b = zy_big(n / 32 + 1);
+  b->limbs[n / 32] = (uint32_t)1 << (n % 32);
+  return b;
+}
+static Big *zy_big_pow10(unsigned n) {
+  Big *b = zy_big_of(1), *ten = zy_big_of(10);
+  while (n-- > 0) b = zy_big_mul(b, ten);
+  return b;
+}
+/* The shortest digits that read back as `v`, which must be positive and finite, and the closest
+ * to it among those, by Burger and Dybvig's free-format algorithm. Returns how many there are and
+ * sets `k` so that `v` is `0.digits` times 10 to the `k`. */
+static int zy_float_digits(double v, char *digits, int *k) {
+  uint64_t bits;
+  memcpy(&bits, &v, sizeof bits);
+  int biased = (int)(bits >> 52);
+  uint64_t f = bits & ((UINT64_C(1) << 52) - 1);
+  int e = -1074;
+  if (biased > 0) {
+    f |= UINT64_C(1) << 52;
+    e = biased - 1075;
+  }
+  /* a float reads back from anything strictly between the midpoints to its neighbours, or also
+   * from the midpoints themselves if it is even; the gap below is halved at a power of two */
+  int even = (f & 1) == 0, lower = f == UINT64_C(1) << 52 && biased > 1;
+  /* the midpoints are (r - mm) / s and (r + mp) / s */
+  Big *r, *s, *mp, *mm, *ten = zy_big_of(10);
+  if (e >= 0) {
+    r = zy_big_mul(zy_big_of((int64_t)f), zy_big_pow2(e + 1 + lower));
+    s = zy_big_of(2 << lower);
+    mp = zy_big_pow2(e + lower);
+    mm = zy_big_pow2(e);
+  } else {
+    r = zy_big_of((int64_t)f << (1 + lower));
+    s = zy_big_pow2(1 - e + lower);
+    mp = zy_big_of(1 << lower);
+    mm = zy_big_of(1);
+  }
+  /* estimate k from the binary exponent, then correct it so the upper midpoint is below 10^k */
+  *k = (int)((e + 64 - __builtin_clzll(f) - 1) * 0.30102999566398114 + 1);
+  if (*k >= 0) {
+    s = zy_big_mul(s, zy_big_pow10(*k));
+  } else {
+    Big *scale = zy_big_pow10(-*k);
+    r = zy_big_mul(r, scale);
+    mp = zy_big_mul(mp, scale);
+    mm = zy_big_mul(mm, scale);
+  }
+  for (;;) {
+    int c = zy_mag_cmp(zy_big_add(r, mp, 0), s);
+    if (c < 0 || (c == 0 && !even)) break;
+    s = zy_big_mul(s, ten);
+    ++*k;
+  }
+  for (;;) {
+    int c = zy_mag_cmp(zy_big_mul(zy_big_add(r, mp, 0), ten), s);
+    if (c > 0 || (c == 0 && even)) break;
+    r = zy_big_mul(r, ten);
+    mp = zy_big_mul(mp, ten);
+    mm = zy_big_mul(mm, ten);
+    --*k;
+  }
+  for (int n = 0;;) {
+    r = zy_big_mul(r, ten);
+    mp = zy_big_mul(mp, ten);
+    mm = zy_big_mul(mm, ten);
+    int d = 0;
+    while (zy_mag_cmp(r, s) >= 0) {
+      zy_mag_sub(r, s);
+      zy_big_trim(r);
+      d++;
+    }
+    int c = zy_mag_cmp(r, mm);
+    int low = c < 0 || (c == 0 && even);
+    c = zy_mag_cmp(zy_big_add(r, mp, 0), s);
+    int high = c > 0 || (c == 0 && even);
+    if (low && high) {
+      /* either digit reads back, so take the closer one, or the even one on a tie */
+      c = zy_mag_cmp(zy_big_add(r, r, 0), s);
+      high = c > 0 || (c == 0 && d % 2);
+    }
+    if (low || high) {
+      digits[n++] = '0' + d + high;
+      return n;
+    }
+    digits[n++] = '0' + d;
+  }
+}
+/* writes a float like JavaScript's `Number.prototype.toString`, as the interpreter does */
+static Str *zy_float_to_str(double v) {
+  if (v != v) return zy_decode("NaN", 3);
+  if (v == 0) return zy_decode("0", 1);
+  if (v == 1 / 0.0) return zy_decode("Infinity", 8);
+  if (v == -1 / 0.0) return zy_decode("-Infinity", 9);
+  char digits[17], out[32];
+  int k, n = zy_float_digits(v < 0 ? -v : v, digits, &k), len = 0;
+  if (v < 0) out[len++] = '-';
+  if (n <= k && k <= 21) {
+    memcpy(out + len, digits, n);
+    memset(out + len + n, '0', k - n);
+    len += k;
+  } else if (0 < k && k <= 21) {
+    memcpy(out + len, digits, k);
+    out[len + k] = '.';
+    memcpy(out + len + k + 1, digits + k, n - k);
+    len += n + 1;
+  } else if (-6 < k && k <= 0) {
+    memcpy(out + len, "0.000000", 2 - k);
+    memcpy(out + len + 2 - k, digits, n);
+    len += 2 - k + n;
+  } else {
+    out[len++] = digits[0];
+    if (n > 1) {
+      out[len++] = '.';
+      memcpy(out + len, digits + 1, n - 1);
+      len += n - 1;
+    }
+    len += snprintf(out + len, sizeof out - len, "e%c%d", k > 0 ? '+' : '-', k > 0 ? k - 1 : 1 - k);
+  }
+  return zy_decode(out, len);
+}
+
+/* -------------------------------- builtins ------------------------------- */
+
+#define ZY_ARGS(name, n)                                                                          \
+  Value a[n];                                                                                     \
+  zy_prim_args(#name, n, a)
+#define ZY_ARITH(name, op)                                                                        \
+  static Clo *zy_prim_##name(void) {                                                             \
+    ZY_ARGS(name, 2);                                                                             \
+    return zy_ret(I((int64_t)((uint64_t)a[0].i op(uint64_t) a[1].i)));                            \
+  }
+#define ZY_COMPARE(name, op)                                                                      \
+  static Clo *zy_prim_##name(void) {                                                             \
+    ZY_ARGS(name, 2);                                                                             \
+    return zy_ret(zy_bool(a[0].i op a[1].i));                                                     \
+  }
+#define ZY_CHECKED(name, builtin)                                                                 \
+  static Clo *zy_prim_##name(void) {                                                             \
+    ZY_ARGS(name, 2);                                                                             \
+    int64_t r;                                                                                    \
+    return zy_ret(builtin(a[0].i, a[1].i, &r) ? zy_none() : zy_some(I(r)));                       \
+  }
+#define ZY_FLOAT_ARITH(name, op)                                                                  \
+  static Clo *zy_prim_##name(void) {                                                             \
+    ZY_ARGS(name, 2);                                                                             \
+    return zy_ret(F(a[0].f op a[1].f));                                                           \
+  }
+#define ZY_FLOAT_COMPARE(name, op)                                                                \
+  static Clo *zy_prim_##name(void) {                                                             \
+    ZY_ARGS(name, 2);                                                                             \
+    return zy_ret(zy_bool(a[0].f op a[1].f));                                                     \
+  }
+#define ZY_BIG_COMPARE(name, op)                                                                  \
+  static Clo *zy_prim_##name(void) {                                                             \
+    ZY_ARGS(name, 2);                                                                             \
+    return zy_ret(zy_bool(zy_big_cmp(a[0].p, a[1].p) op 0));                                      \
+  }
+
+ZY_ARITH(add, +)
+ZY_ARITH(sub, -)
+ZY_ARITH(mul, *)
+ZY_COMPARE(int_eq, ==)
+ZY_COMPARE(int_lt, <)
+ZY_COMPARE(int_gt, >)
+ZY_CHECKED(checked_add, __builtin_add_overflow)
+ZY_CHECKED(checked_sub, __builtin_sub_overflow)
+ZY_CHECKED(checked_mul, __builtin_mul_overflow)
+ZY_BIG_COMPARE(bigint_eq, ==)
+ZY_BIG_COMPARE(bigint_lt, <)
+ZY_BIG_COMPARE(bigint_gt, >)
+ZY_FLOAT_ARITH(float_add, +)
+ZY_FLOAT_ARITH(float_sub, -)
+ZY_FLOAT_ARITH(float_mul, *)
+ZY_FLOAT_ARITH(float_div, /)
+ZY_FLOAT_COMPARE(float_eq, ==)
+ZY_FLOAT_COMPARE(float_lt, <)
+ZY_FLOAT_COMPARE(float_gt, >)
+
+static Clo *zy_prim_div(void) {
+  ZY_ARGS(div, 2);
+  if (a[1].i == 0) zy_error("Builtin div failed: division by zero");
+  return zy_ret(I(a[1].i == -1 ? (int64_t)(0 - (uint64_t)a[0].i) : a[0].i / a[1].i));
+}
+static Clo *zy_prim_mod(void) {
+  ZY_ARGS(mod, 2);
+  if (a[1].i == 0) zy_error("Builtin mod failed: division by zero");
+  return zy_ret(I(a[1].i == -1 ? 0 : a[0].i % a[1].i));
+}
+static Clo *zy_prim_checked_div(void) {
+  ZY_ARGS(checked_div, 2);
+  if (a[1].i == 0 || (a[0].i == INT64_MIN && a[1].i == -1)) return zy_ret(zy_none());
+  return zy_ret(zy_some(I(a[0].i / a[1].i)));
+}
+static Clo *zy_prim_checked_mod(void) {
+  ZY_ARGS(checked_mod, 2);
+  if (a[1].i == 0 || (a[0].i == INT64_MIN && a[1].i == -1)) return zy_ret(zy_none());
+  return zy_ret(zy_some(I(a[0].i % a[1].i)));
+}
+static Clo *zy_prim_int_to_bigint(void) {
+  ZY_ARGS(int_to_bigint, 1);
+  return zy_ret(P(zy_big_of(a[0].i)));
+}
+static Clo *zy_prim_bigint_to_int(void) {
+  ZY_ARGS(bigint_to_int, 1);
+  int64_t i;
+  return zy_ret(zy_big_to_int(a[0].p, &i) ? zy_some(I(i)) : zy_none());
+}
+static Clo *zy_prim_bigint_add(void) {
+  ZY_ARGS(bigint_add, 2);
+  return zy_ret(P(zy_big_add(a[0].p, a[1].p, 0)));
+}
+static Clo *zy_prim_bigint_sub(void) {
+  ZY_ARGS(bigint_sub, 2);
+  return zy_ret(P(zy_big_add(a[0].p, a[1].p, 1)));
+}
+static Clo *zy_prim_bigint_mul(void) {
+  ZY_ARGS(bigint_mul, 2);
+  return zy_ret(P(zy_big_mul(a[0].p, a[1].p)));
+}
+static Clo *zy_prim_bigint_div(void) {
+  ZY_ARGS(bigint_div, 2);
+  if (((Big *)a[1].p)->len == 0) zy_error("Builtin bigint_div failed: division by zero");
+  Big *q, *r;
+  zy_big_divmod(a[0].p, a[1].p, &q, &r);
+  return zy_ret(P(q));
+}
+static Clo *zy_prim_bigint_mod(void) {
+  ZY_ARGS(bigint_mod, 2);
+  if (((Big *)a[1].p)->len == 0) zy_error("Builtin bigint_mod failed: division by zero");
+  Big *q, *r;
+  zy_big_divmod(a[0].p, a[1].p, &q, &r);
+  return zy_ret(P(r));
+}
+static Clo *zy_prim_int_to_float(void) {
+  ZY_ARGS(int_to_float, 1);
+  return zy_ret(F((double)a[0].i));
+}
+static Clo *zy_prim_floor(void) {
+  ZY_ARGS(floor, 1);
+  /* the floor is in range exactly when the float is, and casting rounds toward zero */
+  if (!(a[0].f >= -9223372036854775808.0 && a[0].f < 9223372036854775808.0)) {
+    char *s;
+    zy_encode(zy_float_to_str(a[0].f), &s);
+    zy_error("Builtin floor failed: invalid argument: the floor of %s is not an integer in range",
+             s);
+  }
+  int64_t i = (int64_t)a[0].f;
+  return zy_ret(I((double)i > a[0].f ? i - 1 : i));
+}
+static Clo *zy_prim_str_length(void) {
+  ZY_ARGS(str_length, 1);
+  return zy_ret(I(((Str *)a[0].p)->len));
+}
+static Clo *zy_prim_str_append(void) {
+  ZY_ARGS(str_append, 2);
+  Str *x = a[0].p, *y = a[1].p, *s = zy_str(x->len + y->len);
+  if (x->len) memcpy(s->chars, x->chars, x->len * sizeof(uint32_t));
+  if (y->len) memcpy(s->chars + x->len, y->chars, y->len * sizeof(uint32_t));
+  return zy_ret(P(s));
+}
+static Clo *zy_prim_str_split_once(void) {
+  ZY_ARGS(str_split_once, 2);
+  Str *s = a[0].p;
+  for (size_t i = 0; i < s->len; i++) {
+    if (s->chars[i] == (uint32_t)a[1].i) {
+      return zy_ret(zy_some(zy_pair(P(zy_substr(s, 0, i)), P(zy_substr(s, i + 1, s->len)))));
+    }
+  }
+  return zy_ret(zy_none());
+}
+static Clo *zy_prim_str_split_n(void) {
+  ZY_ARGS(str_split_n, 2);
+  Str *s = a[0].p;
+  if (a[1].i < 0 || (uint64_t)a[1].i > s->len) return zy_ret(zy_none());
+  size_t n = a[1].i;
+  return zy_ret(zy_some(zy_pair(P(zy_substr(s, 0, n)), P(zy_substr(s, n, s->len)))));
+}
+static Clo *zy_prim_str_eq(void) {
+  ZY_ARGS(str_eq, 2);
+  Str *x = a[0].p, *y = a[1].p;
+  int eq = x->len == y->len;
+  if (eq && x->len) eq = !memcmp(x->chars, y->chars, x->len * sizeof(uint32_t));
+  return zy_ret(zy_bool(eq));
+}
+static Clo *zy_prim_str_index(void) {
+  ZY_ARGS(str_index, 2);
+  Str *s = a[0].p;
+  if (a[1].i < 0 || (uint64_t)a[1].i >= s->len) {
+    zy_error("Builtin str_index failed: invalid argument: index %" PRId64 " out of bounds", a[1].i);
+  }
+  return zy_ret(I(s->chars[a[1].i]));
+}
+static Clo *zy_prim_int_to_str(void) {
+  ZY_ARGS(int_to_str, 1);
+  char bytes[24];
+  int n = snprintf(bytes, sizeof bytes, "%" PRId64, a[0].i);
+  return zy_ret(P(zy_decode(bytes, n)));
+}
+static Clo *zy_prim_float_to_str(void) {
+  ZY_ARGS(float_to_str, 1);
+  return zy_ret(P(zy_float_to_str(a[0].f)));
+}
+static Clo *zy_prim_char_to_str(void) {
+  ZY_ARGS(char_to_str, 1);
+  Str *s = zy_str(1);
+  s->chars[0] = a[0].i;
+  return zy_ret(P(s));
+}
+static Clo *zy_prim_char_to_int(void) {
+  ZY_ARGS(char_to_int, 1);
+  return zy_ret(I(a[0].i & 0xff));
+}
+static Clo *zy_prim_str_to_int(void) {
+  ZY_ARGS(str_to_int, 1);
+  int64_t i;
+  if (!zy_parse_int(a[0].p, &i)) {
+    char *s;
+    zy_encode(a[0].p, &s);
+    zy_error("Builtin str_to_int failed: invalid argument: \"%s\" is not an integer", s);
+  }
+  return zy_ret(I(i));
+}
+static Clo *zy_prim_str_hash(void) {
+  ZY_ARGS(str_hash, 1);
+  const Str *s = a[0].p;
+  uint64_t h = 0;
+  for (size_t i = 0; i < s->len; i++) h = h * 31 + s->chars[i];
+  return zy_ret(I((int64_t)h));
+}
+static Clo *zy_prim_bigint_to_str(void) {
+  ZY_ARGS(bigint_to_str, 1);
+  return zy_ret(P(zy_big_to_str(a[0].p)));
+}
+static Clo *zy_prim_str_to_bigint(void) {
+  ZY_ARGS(str_to_bigint, 1);
+  Big *i = zy_parse_big(a[0].p);
+  if (i == NULL) {
+    char *s;
+    zy_encode(a[0].p, &s);
+    zy_error("Builtin str_to_bigint failed: invalid argument: \"%s\" is not an integer", s);
+  }
+  return zy_ret(P(i));
+}
+static Clo *zy_prim_ref_new(void) {
+  ZY_ARGS(ref_new, 2);
+  Value *cell = zy_new(sizeof(Value));
+  *cell = a[0];
+  return zy_apply(a[1], P(cell));
+}
+static Clo *zy_prim_ref_get(void) {
+  ZY_ARGS(ref_get, 1);
+  return zy_ret(*(Value *)a[0].p);
+}
+static Clo *zy_prim_ref_set(void) {
+  ZY_ARGS(ref_set, 2);
+  *(Value *)a[0].p = a[1];
+  return zy_ret(P(zy_ctor(ZY_UNIT, 0, NULL)));
+}
+static Value *zy_index(const char *name, Arr *arr, int64_t i) {
+  if (i < 0 || (uint64_t)i >= arr->len) {
+    zy_error("Builtin %s failed: invalid argument: index %" PRId64
+             " is out of bounds for an array of length %zu",
+             name, i, arr->len);
+  }
+  return &arr->items[i];
+}
+static Arr *zy_arr(size_t len) {
+  if (len > (SIZE_MAX - sizeof(Arr)) / sizeof(Value)) zy_error("out of memory");
+  Arr *arr = zy_new(sizeof(Arr) + len * sizeof(Value));
+  arr->len = len;
+  return arr;
+}
+static Clo *zy_prim_array_make(void) {
+  ZY_ARGS(array_make, 2);
+  if (a[0].i < 0) {
+    zy_error("Builtin array_make failed: invalid argument: an array cannot have length %" PRId64,
+             a[0].i);
+  }
+  Arr *arr = zy_arr(a[0].i);
+  for (size_t i = 0; i < arr->len; i++) arr->items[i] = a[1];
+  return zy_ret(P(arr));
+}
+static Clo *zy_prim_array_length(void) {
+  ZY_ARGS(array_length, 1);
+  return zy_ret(I(((Arr *)a[0].p)->len));
+}
+static Clo *zy_prim_array_get(void) {
+  ZY_ARGS(array_get, 2);
+  return zy_ret(*zy_index("array_get", a[0].p, a[1].i));
+}
+static Clo *zy_prim_array_set(void) {
+  ZY_ARGS(array_set, 3);
+  *zy_index("array_set", a[0].p, a[1].i) = a[2];
+  return zy_ret(P(zy_ctor(ZY_UNIT, 0, NULL)));
+}
+static Clo *zy_prim_array_slice(void) {
+  ZY_ARGS(array_slice, 3);
+  Arr *arr = a[0].p;
+  int64_t from = a[1].i, to = a[2].i;
+  if (from < 0 || from > to || (uint64_t)to > arr->len) {
+    zy_error("Builtin array_slice failed: invalid argument: %" PRId64 "..%" PRId64
+             " is not a slice of an array of length %zu",
+             from, to, arr->len);
+  }
+  Arr *slice = zy_arr(to - from);
+  memcpy(slice->items, arr->items + from, slice->len * sizeof(Value));
+  return zy_ret(P(slice));
+}
+static int zy_map_height(const Map *m) { return m == NULL ? 0 : m->height; }
+static size_t zy_map_size(const Map *m) { return m == NULL ? 0 : m->size; }
+static Map *zy_map_node(int64_t key, Value value, Map *l, Map *r) {
+  Map *m = zy_new(sizeof(Map));
+  int hl = zy_map_height(l), hr = zy_map_height(r);
+  *m = (Map){key, value, l, r, (hl > hr ? hl : hr) + 1, zy_map_size(l) + zy_map_size(r) + 1};
+  return m;
+}
+static Map *zy_map_balance(int64_t key, Value value, Map *l, Map *r) {
+  if (zy_map_height(l) > zy_map_height(r) + 1) {
+    if (zy_map_height(l->l) >= zy_map_height(l->r)) {
+      return zy_map_node(l->key, l->value, l->l, zy_map_node(key, value, l->r, r));
+    }
+    Map *lr = l->r;
+    return zy_map_node(lr->key, lr->value, zy_map_node(l->key, l->value, l->l, lr->l),
+                       zy_map_node(key, value, lr->r, r));
+  }
+  if (zy_map_height(r) > zy_map_height(l) + 1) {
+    if (zy_map_height(r->r) >= zy_map_height(r->l)) {
+      return zy_map_node(r->key, r->value, zy_map_node(key, value, l, r->l), r->r);
+    }
+    Map *rl = r->l;
+    return zy_map_node(rl->key, rl->value, zy_map_node(key, value, l, rl->l),
+                       zy_map_node(r->key, r->value, rl->r, r->r));
+  }
+  return zy_map_node(key, value, l, r);
+}
+static Map *zy_map_insert(int64_t key, Value value, Map *m) {
+  if (m == NULL) return zy_map_node(key, value, NULL, NULL);
+  if (key < m->key) return zy_map_balance(m->key, m->value, zy_map_insert(key, value, m->l), m->r);
+  if (key > m->key) return zy_map_balance(m->key, m->value, m->l, zy_map_insert(key, value, m->r));
+  return zy_map_node(key, value, m->l, m->r);
+}
+static Map *zy_map_remove(int64_t key, Map *m) {
+  if (m == NULL) return NULL;
+  if (key < m->key) return zy_map_balance(m->key, m->value, zy_map_remove(key, m->l), m->r);
+  if (key > m->key) return zy_map_balance(m->key, m->value, m->l, zy_map_remove(key, m->r));
+  if (m->r == NULL) return m->l;
+  Map *min = m->r;
+  while (min->l != NULL) min = min->l;
+  return zy_map_balance(min->key, min->value, m->l, zy_map_remove(min->key, m->r));
+}
+static const Map *zy_map_find(const Map *m, int64_t key) {
+  while (m != NULL && m->key != key) m = key < m->key ? m->l : m->r;
+  return m;
+}
+static Value zy_map_to_list(const Map *m, Value rest) {
+  for (; m != NULL; m = m->l) {
+    rest = zy_pair(zy_pair(I(m->key), m->value), zy_map_to_list(m->r, rest));
+  }
+  return rest;
+}
+static Clo *zy_prim_intmap_empty(void) { return zy_ret(P(NULL)); }
+static Clo *zy_prim_intmap_insert(void) {
+  ZY_ARGS(intmap_insert, 3);
+  return zy_ret(P(zy_map_insert(a[0].i, a[1], a[2].p)));
+}
+static Clo *zy_prim_intmap_lookup(void) {
+  ZY_ARGS(intmap_lookup, 2);
+  const Map *m = zy_map_find(a[1].p, a[0].i);
+  return zy_ret(m == NULL ? zy_none() : zy_some(m->value));
+}
+static Clo *zy_prim_intmap_remove(void) {
+  ZY_ARGS(intmap_remove, 2);
+  return zy_ret(P(zy_map_remove(a[0].i, a[1].p)));
+}
+static Clo *zy_prim_intmap_size(void) {
+  ZY_ARGS(intmap_size, 1);
+  return zy_ret(I(zy_map_size(a[0].p)));
+}
+static Clo *zy_prim_intmap_to_list(void) {
+  ZY_ARGS(intmap_to_list, 1);
+  return zy_ret(zy_map_to_list(a[0].p, P(zy_ctor(ZY_NIL, 0, NULL))));
+}
+/* A map over keys the program hashes and compares holds the program's two functions, an int map
+ * from hashes to buckets, which are lists of pairs with the newest first, and its size. Its
+ * builtins call the program's functions with a continuation whose code is one of the
+ * `ZY_MAP_*ED` below, past the numbers of the code units, and `zy_run` hands such a continuation
+ * to `zy_native`. */
+typedef struct {
+  Value hash, eq;
+  Map *buckets;
+  size_t len;
+} HashMap;
+enum { ZY_MAP_HASHED = 0x80000000u, ZY_MAP_COMPARED, ZY_MAP_FOLDED };
+/* the searches of a key's bucket */
+enum { ZY_INSERT, ZY_LOOKUP, ZY_REMOVE, ZY_MEMBER };
+
+/* calls `f` with the arguments, then `kont` */
+static Clo *zy_call(Value f, int n, const Value *args, Clo *kont) {
+  zy_push_kont(kont);
+  for (int i = n - 1; i >= 0; i--) zy_push_arg(args[i]);
+  return f.p;
+}
+/* the list without its first `n` cells, which are copied in front of `tail` */
+static Value zy_splice(Value list, int64_t n, Value tail) {
+  Value head = tail, *link = &head;
+  for (Ctor *c = list.p; n > 0; n--, c = c->args[1].p) {
+    Ctor *cell = zy_ctor(ZY_CONS, 2, (Value[]){c->args[0], tail});
+    *link = P(cell);
+    link = &cell->args[1];
+  }
+  return head;
+}
+/* Finishes a search, whose state is the search, the key, the value, the map, the hash of the
+ * key and the index and the rest of the bucket from the entry compared last. */
+static Clo *zy_map_found(const Value *s, int found) {
+  HashMap *m = s[3].p;
+  Ctor *rest = s[6].p;
+  int64_t op = s[0].i, h = s[4].i;
+  if (op == ZY_LOOKUP) {
+    return zy_ret(found ? zy_some(((Ctor *)rest->args[0].p)->args[1]) : zy_none());
+  }
+  if (op == ZY_MEMBER) return zy_ret(zy_bool(found));
+  if (op == ZY_REMOVE && !found) return zy_ret(s[3]);
+  const Map *b = zy_map_find(m->buckets, h);
+  Value bucket = b == NULL ? P(zy_ctor(ZY_NIL, 0, NULL)) : b->value;
+  if (found) bucket = zy_splice(bucket, s[5].i, rest->args[1]);
+  HashMap *n = zy_new(sizeof(HashMap));
+  *n = *m;
+  if (op == ZY_INSERT) {
+    bucket = zy_pair(zy_pair(s[1], s[2]), bucket);
+    n->len += !found;
+  } else {
+    n->len--;
+  }
+  if (((Ctor *)bucket.p)->tag == ZY_NIL) {
+    n->buckets = zy_map_remove(h, m->buckets);
+  } else {
+    n->buckets = zy_map_insert(h, bucket, m->buckets);
+  }
+  return zy_ret(P(n));
+}
+/* compares the key with the entry at the rest of its bucket, if any */
+static Clo *zy_map_scan(const Value *s) {
+  Ctor *rest = s[6].p;
+  if (rest->tag == ZY_NIL) return zy_map_found(s, 0);
+  Value args[] = {s[1], ((Ctor *)rest->args[0].p)->args[0]};
+  return zy_call(((HashMap *)s[3].p)->eq, 2, args, zy_clo(ZY_MAP_COMPARED, 7, s));
+}
+static Clo *zy_map_search(int64_t op, Value k, Value v, Value m) {
+  Value s[] = {I(op), k, v, m};
+  return zy_call(((HashMap *)m.p)->hash, 1, &k, zy_clo(ZY_MAP_HASHED, 4, s));
+}
+/* Calls the folding function on the next entry, or returns the result if there are none. The
+ * state is the function, whether it folds a set, the pairs of a hash and a bucket left, and the
+ * entries left in the current bucket. */
+static Clo *zy_map_fold(const Value *s, Value acc) {
+  Value buckets = s[2], entries = s[3];
+  while (((Ctor *)entries.p)->tag == ZY_NIL) {
+    Ctor *b = buckets.p;
+    if (b->tag == ZY_NIL) return zy_ret(acc);
+    entries = ((Ctor *)b->args[0].p)->args[1];
+    buckets = b->args[1];
+  }
+  Ctor *cell = entries.p, *e = cell->args[0].p;
+  Clo *kont = zy_clo(ZY_MAP_FOLDED, 4, (Value[]){s[0], s[1], buckets, cell->args[1]});
+  if (s[1].i) return zy_call(s[0], 2, (Value[]){e->args[0], acc}, kont);
+  return zy_call(s[0], 3, (Value[]){e->args[0], e->args[1], acc}, kont);
+}
+static Clo *zy_native(Clo *self) {
+  Value s[7];
+  switch (self->code) {
+  case ZY_MAP_HASHED: {
+    memcpy(s, self->env, 4 * sizeof(Value));
+    const Map *b = zy_map_find(((HashMap *)s[3].p)->buckets, zy_R.i);
+    s[4] = zy_R;
+    s[5] = I(0);
+    s[6] = b == NULL ? P(zy_ctor(ZY_NIL, 0, NULL)) : b->value;
+    return zy_map_scan(s);
+  }
+  case ZY_MAP_COMPARED:
+    memcpy(s, self->env, 7 * sizeof(Value));
+    if (((Ctor *)zy_R.p)->tag == ZY_TRUE) return zy_map_found(s, 1);
+    s[5] = I(s[5].i + 1);
+    s[6] = ((Ctor *)s[6].p)->args[1];
+    return zy_map_scan(s);
+  case ZY_MAP_FOLDED:
+    return zy_map_fold(self->env, zy_R);
+  default:
+    abort();
+  }
+}
+static Clo *zy_map_fold_from(Value f, int set, Value init, Value m) {
+  Value nil = P(zy_ctor(ZY_NIL, 0, NULL));
+  Value s[] = {f, I(set), zy_map_to_list(((HashMap *)m.p)->buckets, nil), nil};
+  return zy_map_fold(s, init);
+}
+static Clo *zy_map_new(Value hash, Value eq) {
+  HashMap *m = zy_new(sizeof(HashMap));
+  *m = (HashMap){hash, eq, NULL, 0};
+  return zy_ret(P(m));
+}
+static Clo *zy_prim_map_new(void) {
+  ZY_ARGS(map_new, 2);
+  return zy_map_new(a[0], a[1]);
+}
+static Clo *zy_prim_map_insert(void) {
+  ZY_ARGS(map_insert, 3);
+  return zy_map_search(ZY_INSERT, a[0], a[1], a[2]);
+}
+static Clo *zy_prim_map_lookup(void) {
+  ZY_ARGS(map_lookup, 2);
+  return zy_map_search(ZY_LOOKUP, a[0], I(0), a[1]);
+}
+static Clo *zy_prim_map_remove(void) {
+  ZY_ARGS(map_remove, 2);
+  return zy_map_search(ZY_REMOVE, a[0], I(0), a[1]);
+}
+static Clo *zy_prim_map_fold(void) {
+  ZY_ARGS(map_fold, 3);
+  return zy_map_fold_from(a[0], 0, a[1], a[2]);
+}
+static Clo *zy_prim_map_size(void) {
+  ZY_ARGS(map_size, 1);
+  return zy_ret(I(((HashMap *)a[0].p)->len));
+}
+static Clo *zy_prim_set_new(void) {
+  ZY_ARGS(set_new, 2);
+  return zy_map_new(a[0], a[1]);
+}
+static Clo *zy_prim_set_insert(void) {
+  ZY_ARGS(set_insert, 2);
+  return zy_map_search(ZY_INSERT, a[0], P(zy_ctor(ZY_UNIT, 0, NULL)), a[1]);
+}
+static Clo *zy_prim_set_member(void) {
+  ZY_ARGS(set_member, 2);
+  return zy_map_search(ZY_MEMBER, a[0], I(0), a[1]);
+}
+static Clo *zy_prim_set_remove(void) {
+  ZY_ARGS(set_remove, 2);
+  return zy_map_search(ZY_REMOVE, a[0], I(0), a[1]);
+}
+static Clo *zy_prim_set_fold(void) {
+  ZY_ARGS(set_fold, 3);
+  return zy_map_fold_from(a[0], 1, a[1], a[2]);
+}
+static Clo *zy_prim_set_size(void) {
+  ZY_ARGS(set_size, 1);
+  return zy_ret(I(((HashMap *)a[0].p)->len));
+}
+static Clo *zy_prim_write_str(void) {
+  ZY_ARGS(write_str, 2);
+  char *bytes;
+  size_t n = zy_encode(a[0].p, &bytes);
+  fwrite(bytes, 1, n, stdout);
+  return a[1].p;
+}
+static Clo *zy_prim_read_line(void) {
+  ZY_ARGS(read_line, 1);
+  return zy_apply(a[0], P(zy_read_line()));
+}
+static Clo *zy_prim_read_line_as_int(void) {
+  ZY_ARGS(read_line_as_int, 1);
+  int64_t i;
+  return zy_apply(a[0], zy_parse_int(zy_read_line(), &i) ? zy_some(I(i)) : zy_none());
+}
+static Clo *zy_prim_read_till_eof(void) {
+  ZY_ARGS(read_till_eof, 1);
+  fflush(stdout);
+  Str *s = zy_read_all(stdin);
+  return zy_apply(a[0], P(s == NULL ? zy_str(0) : s));
+}
+static Clo *zy_prim_arg_list(void) {
+  ZY_ARGS(arg_list, 1);
+  Value list = P(zy_ctor(ZY_NIL, 0, NULL));
+  for (int i = zy_argc - 1; i >= 1; i--) {
+    Value arg = P(zy_decode(zy_argv[i], strlen(zy_argv[i])));
+    list = P(zy_ctor(ZY_CONS, 2, (Value[]){arg, list}));
+  }
+  return zy_apply(a[0], list);
+}
+/* a failed file operation as a `Left`, described in the same words as by the interpreter */
+static Value zy_file_error(const char *path) {
+  const char *reason;
+  switch (errno) {
+  case ENOENT: reason = "no such file or directory"; break;
+  case EACCES:
+  case EPERM: reason = "permission denied"; break;
+  case EISDIR: reason = "is a directory"; break;
+  case ENOTDIR: reason = "not a directory"; break;
+  default: reason = "input/output error";
+  }
+  size_t n = strlen(path) + strlen(reason) + 3;
+  char *msg = zy_new(n);
+  snprintf(msg, n, "%s: %s", path, reason);
+  return zy_left(P(zy_decode(msg, n - 1)));
+}
+static Clo *zy_prim_read_file(void) {
+  ZY_ARGS(read_file, 2);
+  char *path;
+  zy_encode(a[0].p, &path);
+  FILE *f = fopen(path, "rb");
+  if (f == NULL) return zy_apply(a[1], zy_file_error(path));
+  Str *s = zy_read_all(f);
+  int error = errno;
+  fclose(f);
+  errno = error;
+  return zy_apply(a[1], s == NULL ? zy_file_error(path) : zy_right(P(s)));
+}
+static Value zy_write_file(const Str *file, const Str *s, const char *mode) {
+  char *path, *bytes;
+  zy_encode(file, &path);
+  size_t n = zy_encode(s, &bytes);
+  FILE *f = fopen(path, mode);
+  if (f == NULL) return zy_file_error(path);
+  int ok = fwrite(bytes, 1, n, f) == n;
+  ok = fclose(f) == 0 && ok;
+  return ok ? zy_right(P(zy_ctor(ZY_UNIT, 0, NULL))) : zy_file_error(path);
+}
+static Clo *zy_prim_write_file(void) {
+  ZY_ARGS(write_file, 3);
+  return zy_apply(a[2], zy_write_file(a[0].p, a[1].p, "wb"));
+}
+static Clo *zy_prim_append_file(void) {
+  ZY_ARGS(append_file, 3);
+  return zy_apply(a[2], zy_write_file(a[0].p, a[1].p, "ab"));
+}
+static int zy_cmp_names(const void *a, const void *b) {
+  return strcmp(*(char *const *)a, *(char *const *)b);
+}
+static Clo *zy_prim_list_dir(void) {
+  ZY_ARGS(list_dir, 2);
+  char *path;
+  zy_encode(a[0].p, &path);
+  DIR *dir = opendir(path);
+  if (dir == NULL) return zy_apply(a[1], zy_file_error(path));
+  size_t cap = 16, n = 0;
+  char **names = zy_new(cap * sizeof(char *));
+  struct dirent *entry;
+  while ((entry = readdir(dir)) != NULL) {
+    if (strcmp(entry->d_name, ".") == 0 || strcmp(entry->d_name, "..") == 0) continue;
+    if (n == cap) {
+      char **more = zy_new(cap * 2 * sizeof(char *));
+      memcpy(more, names, n * sizeof(char *));
+      names = more;
+      cap *= 2;
+    }
+    names[n] = zy_new(strlen(entry->d_name) + 1);
+    strcpy(names[n++], entry->d_name);
+  }
+  closedir(dir);
+  /* sorted by code point, as the interpreter does */
+  qsort(names, n, sizeof(char *), zy_cmp_names);
+  Value list = P(zy_ctor(ZY_NIL, 0, NULL));
+  while (n > 0) {
+    n--;
+    list = P(zy_ctor(ZY_CONS, 2, (Value[]){P(zy_decode(names[n], strlen(names[n]))), list}));
+  }
+  return zy_apply(a[1], zy_right(list));
+}
+static Clo *zy_prim_file_exists(void) {
+  ZY_ARGS(file_exists, 2);
+  char *path;
+  struct stat st;
+  zy_encode(a[0].p, &path);
+  return zy_apply(a[1], zy_bool(stat(path, &st) == 0));
+}
+static Clo *zy_prim_get_env(void) {
+  ZY_ARGS(get_env, 2);
+  char *name;
+  zy_encode(a[0].p, &name);
+  const char *value = getenv(name);
+  if (value != NULL) return zy_apply(a[1], zy_right(P(zy_decode(value, strlen(value)))));
+  const char *prefix = "environment variable ", *suffix = " is not set";
+  size_t n = strlen(prefix) + strlen(name) + strlen(suffix) + 1;
+  char *msg = zy_new(n);
+  snprintf(msg, n, "%s%s%s", prefix, name, suffix);
+  return zy_apply(a[1], zy_left(P(zy_decode(msg, n - 1))));
+}
+static Clo *zy_prim_write_err(void) {
+  ZY_ARGS(write_err, 2);
+  char *bytes;
+  size_t n = zy_encode(a[0].p, &bytes);
+  fwrite(bytes, 1, n, stderr);
+  return a[1].p;
+}
+/* Note: a compiled program cannot run other programs, since only the interpreter can be told
+ * to allow it */
+static Clo *zy_prim_spawn_process(void) {
+  zy_error("Builtin spawn_process is not supported by the C backend");
+}
+/* Note: prompts find their frames on the interpreter's stack, which compiled programs don't
+ * have */
+static Clo *zy_prim_new_prompt(void) {
+  zy_error("Builtin new_prompt is not supported by the C backend");
+}
+static Clo *zy_prim_push_prompt(void) {
+  zy_error("Builtin push_prompt is not supported by the C backend");
+}
+static Clo *zy_prim_take_subcont(void) {
+  zy_error("Builtin take_subcont is not supported by the C backend");
+}
+static Clo *zy_prim_push_subcont(void) {
+  zy_error("Builtin push_subcont is not supported by the C backend");
+}
+/* random numbers come from SplitMix64, as in the interpreter, seeded at random unless the
+ * program picks a seed */
+static uint64_t zy_seed;
+static int zy_seeded;
+static uint64_t zy_random(void) {
+  if (!zy_seeded) {
+    FILE *f = fopen("/dev/urandom", "rb");
+    if (f == NULL || fread(&zy_seed, sizeof zy_seed, 1, f) != 1) zy_seed = (uint64_t)time(NULL);
+    if (f != NULL) fclose(f);
+    zy_seeded = 1;
+  }
+  uint64_t z = zy_seed += UINT64_C(0x9e3779b97f4a7c15);
+  z = (z ^ (z >> 30)) * UINT64_C(0xbf58476d1ce4e5b9);
+  z = (z ^ (z >> 27)) * UINT64_C(0x94d049bb133111eb);
+  return z ^ (z >> 31);
+}
+static Clo *zy_prim_random_int(void) {
+  ZY_ARGS(random_int, 1);
+  return zy_apply(a[0], I((int64_t)zy_random()));
+}
+static Clo *zy_prim_random_range(void) {
+  ZY_ARGS(random_range, 3);
+  int64_t lo = a[0].i, hi = a[1].i;
+  if (lo >= hi) {
+    zy_error("Builtin random_range failed: invalid argument: the range %" PRId64 "..%" PRId64
+             " is empty",
+             lo, hi);
+  }
+  uint64_t span = (uint64_t)hi - (uint64_t)lo;
+  return zy_apply(a[2], I((int64_t)((uint64_t)lo + zy_random() % span)));
+}
+static Clo *zy_prim_random_seed(void) {
+  ZY_ARGS(random_seed, 2);
+  zy_seed = (uint64_t)a[0].i;
+  zy_seeded = 1;
+  return a[1].p;
+}
+static int64_t zy_millis(clockid_t clock) {
+  struct timespec t;
+  clock_gettime(clock, &t);
+  return (int64_t)t.tv_sec * 1000 + t.tv_nsec / 1000000;
+}
+static Clo *zy_prim_now_millis(void) {
+  ZY_ARGS(now_millis, 1);
+  return zy_apply(a[0], I(zy_millis(CLOCK_REALTIME)));
+}
+static Clo *zy_prim_monotonic_millis(void) {
+  ZY_ARGS(monotonic_millis, 1);
+  return zy_apply(a[0], I(zy_millis(CLOCK_MONOTONIC)));
+}
+static Clo *zy_prim_sleep_millis(void) {
+  ZY_ARGS(sleep_millis, 2);
+  if (a[0].i > 0) {
+    fflush(stdout);
+    struct timespec t = {a[0].i / 1000, a[0].i % 1000 * 1000000};
+    while (nanosleep(&t, &t) != 0 && errno == EINTR);
+  }
+  return a[1].p;
+}
+static Clo *zy_prim_exit(void) {
+  ZY_ARGS(exit, 1);
+  exit((int32_t)a[0].i);
+}
+static Clo *zy_prim_fatal(void) {
+  ZY_ARGS(fatal, 1);
+  char *msg;
+  zy_encode(a[0].p, &msg);
+  zy_error("Program panicked with message: %s", msg);
+}
@@ -0,0 +1,522 @@
+"use strict";
+// Runtime for programs compiled by the zydeco JavaScript backend.
+//
+// A computation is a function that takes no arguments and returns the next computation to run,
+// so the driver loop in `$run` never grows the JavaScript stack. Thunks are computations,
+// constructors are `{ c: name, a: args }`, `Int`s and `BigInt`s are `BigInt`s, `Float`s are
+// numbers and `String`s and `Char`s are JavaScript strings. Frames on the stack `$S` are `{ t: 0, v }` for an argument,
+// `{ t: 1, k }` for a `do` continuation and `{ t: 2, d }` for a destructor; a continuation reads
+// the value it resumes with from `$R`.
+//
+// The builtins reach the host's files, environment and programs directly. Nothing here checks the
+// permissions that the evaluator and the VM enforce, so a compiled program is trusted like any
+// other script.
+
+const $S = [];
+let $R;
+
+class $Halt {
+  constructor(code) {
+    this.code = code;
+  }
+}
+class $Error {
+  constructor(msg) {
+    this.msg = msg;
+  }
+}
+class $Done {}
+
+const $io = (() => {
+  if (typeof process !== "undefined" && typeof require === "function") {
+    const fs = require("fs");
+    let input = null;
+    let pos = 0;
+    const rest = () => {
+      if (input === null) {
+        try {
+          input = fs.readFileSync(0, "utf8");
+        } catch (e) {
+          input = "";
+        }
+      }
+      return input.slice(pos);
+    };
+    return {
+      write: (s) => fs.writeSync(1, s),
+      error: (s) => fs.writeSync(2, s),
+      readLine: () => {
+        const s = rest();
+        const i = s.indexOf("\n");
+        const line = i < 0 ? s : s.slice(0, i + 1);
+        pos += line.length;
+        return line;
+      },
+      readAll: () => {
+        const s = rest();
+        pos += s.length;
+        return s;
+      },
+      args: () => process.argv.slice(2),
+      readFile: (path) => fs.readFileSync(path, "utf8"),
+      writeFile: (path, s) => fs.writeFileSync(path, s),
+      appendFile: (path, s) => fs.appendFileSync(path, s),
+      // sorted by code point, as the interpreter does
+      listDir: (path) =>
+        fs.readdirSync(path).sort((a, b) => Buffer.compare(Buffer.from(a), Buffer.from(b))),
+      fileExists: (path) => fs.existsSync(path),
+      getEnv: (name) => process.env[name],
+      sleep: (ms) => Atomics.wait(new Int32Array(new SharedArrayBuffer(4)), 0, 0, ms),
+      exit: (code) => {
+        process.exitCode = code;
+      },
+    };
+  }
+  // Note: without Node.js there is no stdin, no filesystem and no environment, and output goes
+  // to the console line by line
+  let line = "";
+  const noFs = () => {
+    throw { code: "ENOSYS" };
+  };
+  return {
+    write: (s) => {
+      const lines = (line + s).split("\n");
+      line = lines.pop();
+      lines.forEach((l) => console.log(l));
+    },
+    error: (s) => console.error(s.trimEnd()),
+    readLine: () => "",
+    readAll: () => "",
+    args: () => [],
+    readFile: noFs,
+    writeFile: noFs,
+    appendFile: noFs,
+    listDir: noFs,
+    fileExists: (_path) => false,
+    getEnv: (_name) => undefined,
+    // Note: the main thread of a browser may not block, so this spins
+    sleep: (ms) => {
+      const end = performance.now() + ms;
+      while (performance.now() < end);
+    },
+    exit: (_code) => {
+      if (line !== "") console.log(line);
+      line = "";
+    },
+  };
+})();
+
+function $frame(f) {
+  if (f === undefined) return "an empty stack";
+  return ["an argument", "a continuation", `the destructor .${f.d}`][f.t];
+}
+function $arg() {
+  const f = $S.pop();
+  if (f === undefined || f.t !== 0) {
+    throw new $Error(`Expected an argument on the stack top, but found ${$frame(f)}`);
+  }
+  return f.v;
+}
+function $dtor() {
+  const f = $S.pop();
+  if (f === undefined || f.t !== 2) {
+    throw new $Error(`Expected a destructor on the stack top, but found ${$frame(f)}`);
+  }
+  return f.d;
+}
+function $ret(v) {
+  const f = $S.pop();
+  if (f === undefined) throw new $Done();
+  if (f.t !== 1) {
+    throw new $Error(`Expected a continuation on the stack top, but found ${$frame(f)}`);
+  }
+  $R = v;
+  return f.k;
+}
+function $unbound(x) {
+  throw new $Error(`Variable ${x} is not bound`);
+}
+function $noArm(c) {
+  throw new $Error(`No arm matches the constructor ${c}`);
+}
+function $noCoarm(d) {
+  throw new $Error(`No arm matches the destructor .${d}`);
+}
+
+const $True = { c: "True", a: [] };
+const $False = { c: "False", a: [] };
+const $None = { c: "None", a: [] };
+const $Unit = { c: "Unit", a: [] };
+const $bool = (b) => (b ? $True : $False);
+const $some = (v) => ({ c: "Some", a: [v] });
+const $pair = (a, b) => ({ c: "Cons", a: [a, b] });
+const $int = (n) => BigInt.asIntN(64, n);
+const $chars = (s) => Array.from(s);
+const $checked = (i) => (i === $int(i) ? $some(i) : $None);
+function $fail(name, error) {
+  throw new $Error(`Builtin ${name} failed: ${error}`);
+}
+function $index(name, arr, i) {
+  if (i < 0n || i >= BigInt(arr.length)) {
+    const msg = `index ${i} is out of bounds for an array of length ${arr.length}`;
+    $fail(name, `invalid argument: ${msg}`);
+  }
+  return Number(i);
+}
+const $left = (e) => ({ c: "Left", a: [e] });
+const $right = (v) => ({ c: "Right", a: [v] });
+// a failed file operation, described in the same words as by the interpreter
+function $fileError(path, e) {
+  const reasons = {
+    ENOENT: "no such file or directory",
+    EACCES: "permission denied",
+    EPERM: "permission denied",
+    EISDIR: "is a directory",
+    ENOTDIR: "not a directory",
+  };
+  return $left(`${path}: ${reasons[e.code] ?? "input/output error"}`);
+}
+function $file(path, k, op) {
+  let v;
+  try {
+    v = $right(op());
+  } catch (e) {
+    v = $fileError(path, e);
+  }
+  return $apply(k, v);
+}
+function $parseBigInt(s) {
+  return /^[+-]?[0-9]+$/.test(s) ? BigInt(s) : null;
+}
+function $parseInt(s) {
+  const i = $parseBigInt(s);
+  return i !== null && i === $int(i) ? i : null;
+}
+function $readLine() {
+  // Note: like the interpreter, drop the last character whatever it is
+  return $chars($io.readLine()).slice(0, -1).join("");
+}
+function $apply(k, v) {
+  $S.push({ t: 0, v });
+  return k;
+}
+// Int maps are persistent AVL trees; the empty map is null
+const $height = (m) => (m === null ? 0 : m.h);
+const $size = (m) => (m === null ? 0 : m.n);
+function $node(k, v, l, r) {
+  return { k, v, l, r, h: Math.max($height(l), $height(r)) + 1, n: $size(l) + $size(r) + 1 };
+}
+function $balance(k, v, l, r) {
+  if ($height(l) > $height(r) + 1) {
+    if ($height(l.l) >= $height(l.r)) return $node(l.k, l.v, l.l, $node(k, v, l.r, r));
+    return $node(l.r.k, l.r.v, $node(l.k, l.v, l.l, l.r.l), $node(k, v, l.r.r, r));
+  }
+  if ($height(r) > $height(l) + 1) {
+    if ($height(r.r) >= $height(r.l)) return $node(r.k, r.v, $node(k, v, l, r.l), r.r);
+    return $node(r.l.k, r.l.v, $node(k, v, l, r.l.l), $node(r.k, r.v, r.l.r, r.r));
+  }
+  return $node(k, v, l, r);
+}
+function $insert(k, v, m) {
+  if (m === null) return $node(k, v, null, null);
+  if (k < m.k) return $balance(m.k, m.v, $insert(k, v, m.l), m.r);
+  if (k > m.k) return $balance(m.k, m.v, m.l, $insert(k, v, m.r));
+  return $node(k, v, m.l, m.r);
+}
+function $remove(k, m) {
+  if (m === null) return null;
+  if (k < m.k) return $balance(m.k, m.v, $remove(k, m.l), m.r);
+  if (k > m.k) return $balance(m.k, m.v, m.l, $remove(k, m.r));
+  if (m.r === null) return m.l;
+  let min = m.r;
+  while (min.l !== null) min = min.l;
+  return $balance(min.k, min.v, m.l, $remove(min.k, m.r));
+}
+function $lookup(k, m) {
+  while (m !== null && k !== m.k) m = k < m.k ? m.l : m.r;
+  return m === null ? $None : $some(m.v);
+}
+function $toList(m, rest) {
+  while (m !== null) {
+    rest = { c: "Cons", a: [$pair(m.k, m.v), $toList(m.r, rest)] };
+    m = m.l;
+  }
+  return rest;
+}
+// Maps over keys the program hashes and compares hold its two functions, an int map from
+// hashes to buckets, which are arrays of pairs with the newest first, and their size. Their
+// builtins call the program's functions with a continuation that picks up from `$R`.
+function $callThen(f, args, k) {
+  $S.push({ t: 1, k });
+  for (let i = args.length - 1; i >= 0; i--) $S.push({ t: 0, v: args[i] });
+  return f;
+}
+function $search(op, k, v, m) {
+  return $callThen(m.hash, [k], () => {
+    const b = $lookup($R, m.buckets);
+    return $scan(op, k, v, m, $R, b === $None ? [] : b.a[0], 0);
+  });
+}
+function $scan(op, k, v, m, h, bucket, i) {
+  if (i === bucket.length) return $found(op, k, v, m, h, bucket, -1);
+  return $callThen(m.eq, [k, bucket[i][0]], () =>
+    $R.c === "True" ? $found(op, k, v, m, h, bucket, i) : $scan(op, k, v, m, h, bucket, i + 1),
+  );
+}
+function $found(op, k, v, m, h, bucket, i) {
+  if (op === "lookup") return $ret(i < 0 ? $None : $some(bucket[i][1]));
+  if (op === "member") return $ret($bool(i >= 0));
+  if (op === "remove" && i < 0) return $ret(m);
+  const rest = bucket.filter((_e, j) => j !== i);
+  if (op === "insert") rest.unshift([k, v]);
+  const n = m.n + (op === "remove" ? -1 : i < 0 ? 1 : 0);
+  const buckets = rest.length === 0 ? $remove(h, m.buckets) : $insert(h, rest, m.buckets);
+  return $ret({ hash: m.hash, eq: m.eq, buckets, n });
+}
+function $entries(m, out) {
+  if (m !== null) {
+    $entries(m.l, out);
+    for (const e of m.v) out.push(e);
+    $entries(m.r, out);
+  }
+  return out;
+}
+function $fold(f, set, entries, i, acc) {
+  if (i === entries.length) return $ret(acc);
+  const [k, v] = entries[i];
+  return $callThen(f, set ? [k, acc] : [k, v, acc], () => $fold(f, set, entries, i + 1, $R));
+}
+// random numbers come from SplitMix64, as in the interpreter, seeded at random unless the
+// program picks a seed
+let $seed = null;
+function $random() {
+  if ($seed === null) {
+    const word = () => BigInt(Math.floor(Math.random() * 0x100000000));
+    $seed = (word() << 32n) | word();
+  }
+  $seed = BigInt.asUintN(64, $seed + 0x9e3779b97f4a7c15n);
+  let z = $seed;
+  z = BigInt.asUintN(64, (z ^ (z >> 30n)) * 0xbf58476d1ce4e5b9n);
+  z = BigInt.asUintN(64, (z ^ (z >> 27n)) * 0x94d049bb133111ebn);
+  return z ^ (z >> 31n);
+}
+
+const $prim = {
+  add: ([a, b]) => $ret($int(a + b)),
+  sub: ([a, b]) => $ret($int(a - b)),
+  mul: ([a, b]) => $ret($int(a * b)),
+  div: ([a, b]) => (b === 0n ? $fail("div", "division by zero") : $ret($int(a / b))),
+  mod: ([a, b]) => (b === 0n ? $fail("mod", "division by zero") : $ret(a % b)),
+  int_eq: ([a, b]) => $ret($bool(a === b)),
+  int_lt: ([a, b]) => $ret($bool(a < b)),
+  int_gt: ([a, b]) => $ret($bool(a > b)),
+  checked_add: ([a, b]) => $ret($checked(a + b)),
+  checked_sub: ([a, b]) => $ret($checked(a - b)),
+  checked_mul: ([a, b]) => $ret($checked(a * b)),
+  checked_div: ([a, b]) => $ret(b === 0n ? $None : $checked(a / b)),
+  checked_mod: ([a, b]) => $ret(b === 0n || a / b !== $int(a / b) ? $None : $some(a % b)),
+  int_to_bigint: ([a]) => $ret(a),
+  bigint_to_int: ([a]) => $ret($checked(a)),
+  bigint_add: ([a, b]) => $ret(a + b),
+  bigint_sub: ([a, b]) => $ret(a - b),
+  bigint_mul: ([a, b]) => $ret(a * b),
+  bigint_div: ([a, b]) => (b === 0n ? $fail("bigint_div", "division by zero") : $ret(a / b)),
+  bigint_mod: ([a, b]) => (b === 0n ? $fail("bigint_mod", "division by zero") : $ret(a % b)),
+  bigint_eq: ([a, b]) => $ret($bool(a === b)),
+  bigint_lt: ([a, b]) => $ret($bool(a < b)),
+  bigint_gt: ([a, b]) => $ret($bool(a > b)),
+  float_add: ([a, b]) => $ret(a + b),
+  float_sub: ([a, b]) => $ret(a - b),
+  float_mul: ([a, b]) => $ret(a * b),
+  float_div: ([a, b]) => $ret(a / b),
+  float_eq: ([a, b]) => $ret($bool(a === b)),
+  float_lt: ([a, b]) => $ret($bool(a < b)),
+  float_gt: ([a, b]) => $ret($bool(a > b)),
+  int_to_float: ([a]) => $ret(Number(a)),
+  floor: ([a]) => {
+    const f = Math.floor(a);
+    if (!(f >= -(2 ** 63) && f < 2 ** 63)) {
+      return $fail("floor", `invalid argument: the floor of ${a} is not an integer in range`);
+    }
+    return $ret(BigInt(f));
+  },
+  str_length: ([s]) => $ret(BigInt($chars(s).length)),
+  str_append: ([a, b]) => $ret(a + b),
+  str_split_once: ([s, c]) => {
+    const i = s.indexOf(c);
+    return $ret(i < 0 ? $None : $some($pair(s.slice(0, i), s.slice(i + c.length))));
+  },
+  str_split_n: ([s, n]) => {
+    const cs = $chars(s);
+    if (n < 0n || n > BigInt(cs.length)) return $ret($None);
+    const i = Number(n);
+    return $ret($some($pair(cs.slice(0, i).join(""), cs.slice(i).join(""))));
+  },
+  str_eq: ([a, b]) => $ret($bool(a === b)),
+  str_index: ([s, i]) => {
+    const cs = $chars(s);
+    if (i < 0n || i >= BigInt(cs.length)) {
+      return $fail("str_index", `invalid argument: index ${i} out of bounds`);
+    }
+    return $ret(cs[Number(i)]);
+  },
+  int_to_str: ([i]) => $ret(i.toString()),
+  float_to_str: ([f]) => $ret(String(f)),
+  char_to_str: ([c]) => $ret(c),
+  char_to_int: ([c]) => $ret(BigInt(c.codePointAt(0) & 0xff)),
+  str_to_int: ([s]) => {
+    const i = $parseInt(s);
+    if (i === null) return $fail("str_to_int", `invalid argument: "${s}" is not an integer`);
+    return $ret(i);
+  },
+  str_hash: ([s]) => {
+    let h = 0n;
+    for (const c of s) h = $int(h * 31n + BigInt(c.codePointAt(0)));
+    return $ret(h);
+  },
+  bigint_to_str: ([i]) => $ret(i.toString()),
+  str_to_bigint: ([s]) => {
+    const i = $parseBigInt(s);
+    if (i === null) return $fail("str_to_bigint", `invalid argument: "${s}" is not an integer`);
+    return $ret(i);
+  },
+  ref_new: ([v, k]) => $apply(k, { v }),
+  ref_get: ([r]) => $ret(r.v),
+  ref_set: ([r, v]) => {
+    r.v = v;
+    return $ret($Unit);
+  },
+  array_make: ([n, v]) => {
+    if (n < 0n) return $fail("array_make", `invalid argument: an array cannot have length ${n}`);
+    return $ret(new Array(Number(n)).fill(v));
+  },
+  array_length: ([arr]) => $ret(BigInt(arr.length)),
+  array_get: ([arr, i]) => $ret(arr[$index("array_get", arr, i)]),
+  array_set: ([arr, i, v]) => {
+    arr[$index("array_set", arr, i)] = v;
+    return $ret($Unit);
+  },
+  array_slice: ([arr, from, to]) => {
+    if (from < 0n || from > to || to > BigInt(arr.length)) {
+      const msg = `${from}..${to} is not a slice of an array of length ${arr.length}`;
+      return $fail("array_slice", `invalid argument: ${msg}`);
+    }
+    return $ret(arr.slice(Number(from), Number(to)));
+  },
+  intmap_empty: () => $ret(null),
+  intmap_insert: ([k, v, m]) => $ret($insert(k, v, m)),
+  intmap_lookup: ([k, m]) => $ret($lookup(k, m)),
+  intmap_remove: ([k, m]) => $ret($remove(k, m)),
+  intmap_size: ([m]) => $ret(BigInt($size(m))),
+  intmap_to_list: ([m]) => $ret($toList(m, { c: "Nil", a: [] })),
+  map_new: ([hash, eq]) => $ret({ hash, eq, buckets: null, n: 0 }),
+  map_insert: ([k, v, m]) => $search("insert", k, v, m),
+  map_lookup: ([k, m]) => $search("lookup", k, null, m),
+  map_remove: ([k, m]) => $search("remove", k, null, m),
+  map_fold: ([f, init, m]) => $fold(f, false, $entries(m.buckets, []), 0, init),
+  map_size: ([m]) => $ret(BigInt(m.n)),
+  set_new: ([hash, eq]) => $ret({ hash, eq, buckets: null, n: 0 }),
+  set_insert: ([k, s]) => $search("insert", k, $Unit, s),
+  set_member: ([k, s]) => $search("member", k, null, s),
+  set_remove: ([k, s]) => $search("remove", k, null, s),
+  set_fold: ([f, init, s]) => $fold(f, true, $entries(s.buckets, []), 0, init),
+  set_size: ([s]) => $ret(BigInt(s.n)),
+  write_str: ([s, k]) => {
+    $io.write(s);
+    return k;
+  },
+  read_line: ([k]) => $apply(k, $readLine()),
+  read_line_as_int: ([k]) => {
+    const i = $parseInt($readLine());
+    return $apply(k, i === null ? $None : $some(i));
+  },
+  read_till_eof: ([k]) => $apply(k, $io.readAll()),
+  read_file: ([path, k]) => $file(path, k, () => $io.readFile(path)),
+  write_file: ([path, s, k]) =>
+    $file(path, k, () => {
+      $io.writeFile(path, s);
+      return $Unit;
+    }),
+  append_file: ([path, s, k]) =>
+    $file(path, k, () => {
+      $io.appendFile(path, s);
+      return $Unit;
+    }),
+  list_dir: ([path, k]) =>
+    $file(path, k, () => {
+      let list = { c: "Nil", a: [] };
+      for (const name of $io.listDir(path).reverse()) list = { c: "Cons", a: [name, list] };
+      return list;
+    }),
+  file_exists: ([path, k]) => $apply(k, $bool($io.fileExists(path))),
+  get_env: ([name, k]) => {
+    const value = $io.getEnv(name);
+    if (value === undefined) return $apply(k, $left(`environment variable ${name} is not set`));
+    return $apply(k, $right(value));
+  },
+  write_err: ([s, k]) => {
+    $io.error(s);
+    return k;
+  },
+  arg_list: ([k]) => {
+    let list = { c: "Nil", a: [] };
+    for (const arg of $io.args().reverse()) list = { c: "Cons", a: [arg, list] };
+    return $apply(k, list);
+  },
+  random_int: ([k]) => $apply(k, BigInt.asIntN(64, $random())),
+  random_range: ([lo, hi, k]) => {
+    if (lo >= hi) return $fail("random_range", `invalid argument: the range ${lo}..${hi} is empty`);
+    return $apply(k, $int(lo + ($random() % BigInt.asUintN(64, hi - lo))));
+  },
+  random_seed: ([seed, k]) => {
+    $seed = BigInt.asUintN(64, seed);
+    return k;
+  },
+  now_millis: ([k]) => $apply(k, BigInt(Date.now())),
+  monotonic_millis: ([k]) => $apply(k, BigInt(Math.floor(performance.now()))),
+  sleep_millis: ([ms, k]) => {
+    if (ms > 0n) $io.sleep(Number(ms));
+    return k;
+  },
+  exit: ([code]) => {
+    throw new $Halt(Number(BigInt.asIntN(32, code)));
+  },
+  fatal: ([msg]) => {
+    throw new $Error(`Program panicked with message: ${msg}`);
+  },
+};
+
+function $call(name, arity) {
+  const f = $prim[name];
+  if (f === undefined) {
+    throw new $Error(`Builtin ${name} is not supported by the JavaScript backend`);
+  }
+  const args = [];
+  for (let i = 0; i < arity; i++) {
+    const frame = $S.pop();
+    if (frame === undefined || frame.t !== 0) {
+      throw new $Error(`Builtin ${name} expects ${arity} arguments on the stack`);
+    }
+    args.push(frame.v);
+  }
+  return f(args);
+}
+
+function $run(main) {
+  let k = main;
+  try {
+    for (;;) k = k();
+  } catch (e) {
+    if (e instanceof $Halt) {
+      $io.exit(e.code);
+    } else if (e instanceof $Error) {
+      $io.error(`Runtime Error: ${e.msg}\n`);
+      $io.exit(1);
+    } else if (e instanceof $Done) {
+      $io.error("Error: Program did not exit\n");
+      $io.exit(0);
+    } else {
+      throw e;
+    }
+  }
+}
@@ -0,0 +1,2883 @@
+  ;; Runtime for programs compiled by the zydeco WebAssembly backend.
+  ;;
+  ;; Like the C backend, every thunk body and `do` continuation is a numbered code unit, and a
+  ;; closure is the number of its code together with the values it captured. Code units are
+  ;; functions in the table `$units` that return the next closure to run, and `$run` calls them
+  ;; in a loop. Every value is an `i64`: `Int`s and `Char`s are stored unboxed, `Float`s as their
+  ;; bits, and the rest are addresses of objects in linear memory.
+  ;;
+  ;;   closure      [code: i32][unused: i32][env: i64]...
+  ;;   constructor  [tag: i32][arity: i32][args: i64]...
+  ;;   string       [len: i32][chars: i32]...
+  ;;   big integer  [neg: i32][len: i32][limbs: i32]...
+  ;;   reference    [value: i64]
+  ;;   array        [len: i32][unused: i32][items: i64]...
+  ;;   int map      [height: i32][size: i32][key: i64][value: i64][left: i32][right: i32]
+  ;;   map          [unused: i32][unused: i32][hash: i64][eq: i64][buckets: i64][size: i64]
+  ;;   stack frame  [kind: i32][unused: i32][payload: i64]
+  ;;
+  ;; Objects on the heap follow a header [size: i32][state: i32] with the size of the whole block;
+  ;; see `$collect` for how they are reclaimed.
+  ;;
+  ;; An int map is a persistent AVL tree, and the empty tree is at address 0. A `Map` or a
+  ;; `Set` holds the program's hash and equality functions, an int map from hashes to buckets,
+  ;; which are lists of pairs with the newest first, and its size. Files are reached
+  ;; through the directories the host preopens, like `/` or `.`. Nothing here checks the
+  ;; permissions that the evaluator and the VM enforce; the WASI host alone decides which files,
+  ;; environment variables and programs a compiled program can reach.
+  ;;
+  ;; Memory starts with scratch space for system calls at 0 and the input buffer at 1024. The
+  ;; generated data follows at 8192, then the stack, then the heap.
+  ;; A `str` form around a string literal stands for the address and length of the string, which
+  ;; the compiler adds to the data.
+
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_read"
+    (func $fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_sizes_get"
+    (func $args_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_get"
+    (func $args_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "random_get"
+    (func $random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_close"
+    (func $fd_close (param i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_prestat_get"
+    (func $fd_prestat_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_prestat_dir_name"
+    (func $fd_prestat_dir_name (param i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_readdir"
+    (func $fd_readdir (param i32 i32 i32 i64 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "path_filestat_get"
+    (func $path_filestat_get (param i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "environ_sizes_get"
+    (func $environ_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "environ_get"
+    (func $environ_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "clock_time_get"
+    (func $clock_time_get (param i32 i64 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "poll_oneoff"
+    (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit"
+    (func $proc_exit (param i32)))
+
+  (type $unit (func (param i32) (result i32)))
+
+  ;; frame kinds
+  ;;   0  an argument
+  ;;   1  a continuation
+  ;;   2  a destructor
+  (global $sp (mut i32) (i32.const 0))
+  (global $hp (mut i32) (i32.const 0))
+  ;; the free run being allocated from, and the ones after it, linked through their second word
+  (global $run_ptr (mut i32) (i32.const 0))
+  (global $run_end (mut i32) (i32.const 0))
+  (global $runs (mut i32) (i32.const 0))
+  ;; the bytes allocated since the last collection, and how many start the next one
+  (global $allocated (mut i32) (i32.const 0))
+  (global $gc_limit (mut i32) (i32.const 0x800000))
+  ;; the start bits of the heap's objects and the objects marked but not yet scanned, both above
+  ;; the heap during a collection
+  (global $bitmap (mut i32) (i32.const 0))
+  (global $grey (mut i32) (i32.const 0))
+  ;; the value a continuation resumes with
+  (global $R (mut i64) (i64.const 0))
+  ;; the byte length of the last string `$encode` or `$itoa` produced
+  (global $len (mut i32) (i32.const 0))
+  ;; the result of the last successful `$parse_int` or `$big_to_int`
+  (global $parsed (mut i64) (i64.const 0))
+  ;; the remainder of the last `$big_divmod`
+  (global $big_rem (mut i32) (i32.const 0))
+  ;; the exponent of the last `$float_digits`
+  (global $float_k (mut i32) (i32.const 0))
+  (global $in_pos (mut i32) (i32.const 0))
+  (global $in_len (mut i32) (i32.const 0))
+  ;; the error of the last failed file operation
+  (global $errno (mut i32) (i32.const 0))
+  ;; the path relative to the directory the last `$resolve` found
+  (global $rel (mut i32) (i32.const 0))
+  ;; the state of the random numbers, drawn from the host unless the program picked a seed
+  (global $seed (mut i64) (i64.const 0))
+  (global $seeded (mut i32) (i32.const 0))
+
+  (func (export "_start")
+    (global.set $sp (global.get $stack_base))
+    (global.set $hp (global.get $heap_base))
+    (call $run (global.get $entry)))
+
+  (func $run (param $k i32)
+    (loop $next
+      (if (i32.ge_u (global.get $allocated) (global.get $gc_limit))
+        (then (call $collect (local.get $k))))
+      (local.set $k
+        (call_indirect $units (type $unit) (local.get $k) (i32.load (local.get $k))))
+      (br $next)))
+
+  ;; ------------------------------- output -------------------------------
+
+  ;; writes all the bytes, returning the error that stopped it if any
+  (func $try_write (param $fd i32) (param $ptr i32) (param $len i32) (result i32)
+    (local $errno i32)
+    (block $done
+      (loop $more
+        (br_if $done (i32.eqz (local.get $len)))
+        (i32.store (i32.const 0) (local.get $ptr))
+        (i32.store (i32.const 4) (local.get $len))
+        (local.set $errno
+          (call $fd_write (local.get $fd) (i32.const 0) (i32.const 1) (i32.const 8)))
+        (br_if $done (local.get $errno))
+        (br_if $done (i32.eqz (i32.load (i32.const 8))))
+        (local.set $ptr (i32.add (local.get $ptr) (i32.load (i32.const 8))))
+        (local.set $len (i32.sub (local.get $len) (i32.load (i32.const 8))))
+        (br $more)))
+    (local.get $errno))
+
+  (func $write (param $fd i32) (param $ptr i32) (param $len i32)
+    (drop (call $try_write (local.get $fd) (local.get $ptr) (local.get $len))))
+
+  ;; writes a string as UTF-8, reusing the memory of the encoding
+  (func $write_str (param $fd i32) (param $s i32)
+    (local $hp i32)
+    (local.set $hp (global.get $hp))
+    (call $write (local.get $fd) (call $encode (local.get $s)) (global.get $len))
+    (global.set $hp (local.get $hp)))
+
+  ;; writes the name at an index of a table of addresses and lengths
+  (func $write_name (param $table i32) (param $i i32)
+    (local $entry i32)
+    (local.set $entry (i32.add (local.get $table) (i32.shl (local.get $i) (i32.const 3))))
+    (call $write (i32.const 2) (i32.load (local.get $entry)) (i32.load offset=4 (local.get $entry))))
+
+  ;; ends the message of a runtime error and exits
+  (func $die
+    (call $write (i32.const 2) (str "\n"))
+    (call $proc_exit (i32.const 1))
+    (unreachable))
+
+  (func $fail (param $msg i32) (param $len i32)
+    (call $write (i32.const 2) (str "Runtime Error: "))
+    (call $write (i32.const 2) (local.get $msg) (local.get $len))
+    (call $die))
+
+  (func $unbound (param $x i32) (param $len i32) (result i64)
+    (call $write (i32.const 2) (str "Runtime Error: Variable "))
+    (call $write (i32.const 2) (local.get $x) (local.get $len))
+    (call $write (i32.const 2) (str " is not bound"))
+    (call $die)
+    (unreachable))
+
+  (func $no_arm (param $tag i32) (result i32)
+    (call $write (i32.const 2) (str "Runtime Error: No arm matches the constructor "))
+    (call $write_name (global.get $ctor_names) (local.get $tag))
+    (call $die)
+    (unreachable))
+
+  (func $no_coarm (param $dtor i32) (result i32)
+    (call $write (i32.const 2) (str "Runtime Error: No arm matches the destructor ."))
+    (call $write_name (global.get $dtor_names) (local.get $dtor))
+    (call $die)
+    (unreachable))
+
+  ;; ------------------------------- memory -------------------------------
+
+  ;; makes sure the memory reaches `end`
+  (func $reserve (param $end i32)
+    (local $have i32)
+    (local.set $have (i32.shl (memory.size) (i32.const 16)))
+    (if (i32.gt_u (local.get $end) (local.get $have))
+      (then
+        (if (i32.eq
+              (memory.grow
+                (i32.shr_u
+                  (i32.add (i32.sub (local.get $end) (local.get $have)) (i32.const 0xffff))
+                  (i32.const 16)))
+              (i32.const -1))
+          (then (call $fail (str "out of memory")))))))
+
+  ;; the size of the block for an object of `size` bytes
+  (func $block_size (param $size i32) (result i32)
+    (i32.add (i32.const 8)
+      (select (i32.const 8) (i32.and (i32.add (local.get $size) (i32.const 7)) (i32.const -8))
+        (i32.lt_u (local.get $size) (i32.const 8)))))
+
+  ;; writes the header of a block and returns the address of its object
+  (func $claim (param $block i32) (param $size i32) (result i32)
+    (i32.store (local.get $block) (local.get $size))
+    (i32.store offset=4 (local.get $block) (i32.const 0))
+    (global.set $allocated (i32.add (global.get $allocated) (local.get $size)))
+    (i32.add (local.get $block) (i32.const 8)))
+
+  ;; allocates on top of the heap, where the object is followed by nothing but free memory
+  (func $bump (param $size i32) (result i32)
+    (local $block i32)
+    (local.set $block (global.get $hp))
+    (local.set $size (call $block_size (local.get $size)))
+    (call $reserve (i32.add (local.get $block) (local.get $size)))
+    (global.set $hp (i32.add (local.get $block) (local.get $size)))
+    (call $claim (local.get $block) (local.get $size)))
+
+  ;; turns the rest of the current run into a free block
+  (func $close_run
+    (if (i32.lt_u (global.get $run_ptr) (global.get $run_end))
+      (then
+        (i32.store (global.get $run_ptr) (i32.sub (global.get $run_end) (global.get $run_ptr)))
+        (i32.store offset=4 (global.get $run_ptr) (i32.const 2))))
+    (global.set $run_ptr (i32.const 0))
+    (global.set $run_end (i32.const 0)))
+
+  (func $alloc (param $size i32) (result i32)
+    (local $block i32)
+    (local.set $size (call $block_size (local.get $size)))
+    (block $found
+      (loop $next
+        (br_if $found
+          (i32.ge_u (i32.sub (global.get $run_end) (global.get $run_ptr)) (local.get $size)))
+        (call $close_run)
+        (if (i32.eqz (global.get $runs))
+          (then (return (call $bump (i32.sub (local.get $size) (i32.const 8))))))
+        (global.set $run_ptr (global.get $runs))
+        (global.set $run_end (i32.add (global.get $runs) (i32.load (global.get $runs))))
+        (global.set $runs (i32.load offset=8 (global.get $runs)))
+        (br $next)))
+    (local.set $block (global.get $run_ptr))
+    (global.set $run_ptr (i32.add (local.get $block) (local.get $size)))
+    (call $claim (local.get $block) (local.get $size)))
+
+  ;; ------------------------------ collection -----------------------------
+  ;;
+  ;; The collector runs between code units, where the only live values are the closure to run
+  ;; next, the stack, `$R` and the globals. Values carry no tags, so every word of a reachable
+  ;; object that is the address of an object on the heap keeps it alive. A block's state is
+  ;;   0  allocated
+  ;;   1  allocated and marked
+  ;;   2  free
+  ;; and sweeping coalesces the free blocks next to each other into the runs allocation goes
+  ;; through.
+
+  (func $bit (param $obj i32) (result i32)
+    (i32.shr_u (i32.sub (local.get $obj) (global.get $heap_base)) (i32.const 3)))
+
+  (func $mark (param $v i64)
+    (local $obj i32)
+    (local $bit i32)
+    (if (i64.ne (i64.shr_u (local.get $v) (i64.const 32)) (i64.const 0)) (then (return)))
+    (local.set $obj (i32.wrap_i64 (local.get $v)))
+    (if (i32.or (i32.or
+          (i32.lt_u (local.get $obj) (i32.add (global.get $heap_base) (i32.const 8)))
+          (i32.ge_u (local.get $obj) (global.get $hp)))
+          (i32.and (local.get $obj) (i32.const 7)))
+      (then (return)))
+    (local.set $bit (call $bit (local.get $obj)))
+    (if (i32.eqz
+          (i32.and
+            (i32.load8_u
+              (i32.add (global.get $bitmap) (i32.shr_u (local.get $bit) (i32.const 3))))
+            (i32.shl (i32.const 1) (i32.and (local.get $bit) (i32.const 7)))))
+      (then (return)))
+    (local.set $obj (i32.sub (local.get $obj) (i32.const 8)))
+    (if (i32.load offset=4 (local.get $obj)) (then (return)))
+    (i32.store offset=4 (local.get $obj) (i32.const 1))
+    (call $reserve (i32.add (global.get $grey) (i32.const 4)))
+    (i32.store (global.get $grey) (local.get $obj))
+    (global.set $grey (i32.add (global.get $grey) (i32.const 4))))
+
+  (func $collect (param $k i32)
+    (local $b i32)
+    (local $bit i32)
+    (local $end i32)
+    (local $run i32)
+    (local $live i32)
+    (call $close_run)
+    ;; the start bits of the allocated objects
+    (global.set $bitmap (global.get $hp))
+    (local.set $end
+      (i32.add (global.get $bitmap)
+        (i32.add (i32.shr_u (i32.sub (global.get $hp) (global.get $heap_base)) (i32.const 6))
+          (i32.const 1))))
+    (call $reserve (local.get $end))
+    (memory.fill (global.get $bitmap) (i32.const 0)
+      (i32.sub (local.get $end) (global.get $bitmap)))
+    (local.set $end (i32.and (i32.add (local.get $end) (i32.const 3)) (i32.const -4)))
+    (global.set $grey (local.get $end))
+    (local.set $b (global.get $heap_base))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $b) (global.get $hp)))
+        (if (i32.eqz (i32.load offset=4 (local.get $b)))
+          (then
+            (local.set $bit (call $bit (i32.add (local.get $b) (i32.const 8))))
+            (i32.store8
+              (i32.add (global.get $bitmap) (i32.shr_u (local.get $bit) (i32.const 3)))
+              (i32.or
+                (i32.load8_u
+                  (i32.add (global.get $bitmap) (i32.shr_u (local.get $bit) (i32.const 3))))
+                (i32.shl (i32.const 1) (i32.and (local.get $bit) (i32.const 7)))))))
+        (local.set $b (i32.add (local.get $b) (i32.load (local.get $b))))
+        (br $next)))
+    ;; the roots
+    (call $mark (i64.extend_i32_u (local.get $k)))
+    (call $mark (global.get $R))
+    (call $mark_globals)
+    (local.set $b (global.get $stack_base))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $b) (global.get $sp)))
+        (if (i32.ne (i32.load (local.get $b)) (i32.const 2))
+          (then (call $mark (i64.load offset=8 (local.get $b)))))
+        (local.set $b (i32.add (local.get $b) (i32.const 16)))
+        (br $next)))
+    ;; everything they reach, scanning each object a word at a time
+    (block $done
+      (loop $next
+        (br_if $done (i32.le_u (global.get $grey) (local.get $end)))
+        (global.set $grey (i32.sub (global.get $grey) (i32.const 4)))
+        (local.set $b (i32.load (global.get $grey)))
+        (local.set $run (i32.add (local.get $b) (i32.load (local.get $b))))
+        (local.set $b (i32.add (local.get $b) (i32.const 8)))
+        (block $scanned
+          (loop $word
+            (br_if $scanned (i32.ge_u (local.get $b) (local.get $run)))
+            (call $mark (i64.load32_u (local.get $b)))
+            (local.set $b (i32.add (local.get $b) (i32.const 4)))
+            (br $word)))
+        (br $next)))
+    ;; the sweep
+    (global.set $runs (i32.const 0))
+    (local.set $run (i32.const 0))
+    (local.set $b (global.get $heap_base))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $b) (global.get $hp)))
+        (if (i32.eq (i32.load offset=4 (local.get $b)) (i32.const 1))
+          (then
+            (call $free_run (local.get $run) (local.get $b))
+            (local.set $run (i32.const 0))
+            (i32.store offset=4 (local.get $b) (i32.const 0))
+            (local.set $live (i32.add (local.get $live) (i32.load (local.get $b)))))
+          (else
+            (if (i32.eqz (local.get $run)) (then (local.set $run (local.get $b))))))
+        (local.set $b (i32.add (local.get $b) (i32.load (local.get $b))))
+        (br $next)))
+    ;; Note: free memory at the top goes back to the heap rather than to a run
+    (if (local.get $run) (then (global.set $hp (local.get $run))))
+    (global.set $allocated (i32.const 0))
+    (global.set $gc_limit
+      (select (local.get $live) (i32.const 0x800000)
+        (i32.gt_u (local.get $live) (i32.const 0x800000)))))
+
+  ;; turns the blocks from `run` to `end` into one free block, adding it to the runs if it fits any
+  ;; object
+  (func $free_run (param $run i32) (param $end i32)
+    (if (i32.eqz (local.get $run)) (then (return)))
+    (i32.store (local.get $run) (i32.sub (local.get $end) (local.get $run)))
+    (i32.store offset=4 (local.get $run) (i32.const 2))
+    (if (i32.lt_u (i32.sub (local.get $end) (local.get $run)) (i32.const 16)) (then (return)))
+    (i32.store offset=8 (local.get $run) (global.get $runs))
+    (global.set $runs (local.get $run)))
+
+  ;; allocates a closure or a constructor with `n` fields
+  (func $new (param $head i32) (param $n i32) (result i32)
+    (local $p i32)
+    (local.set $p (call $alloc (i32.add (i32.const 8) (i32.shl (local.get $n) (i32.const 3)))))
+    (i32.store (local.get $p) (local.get $head))
+    (i32.store offset=4 (local.get $p) (local.get $n))
+    (local.get $p))
+
+  ;; constructors the builtins produce; the compiler numbers them first
+  ;;   0 True, 1 False, 2 None, 3 Some, 4 Cons, 5 Nil, 6 Unit, 7 Left, 8 Right
+  (func $ctor0 (param $tag i32) (result i64)
+    (i64.extend_i32_u (call $new (local.get $tag) (i32.const 0))))
+
+  (func $bool (param $b i32) (result i64)
+    (call $ctor0 (select (i32.const 0) (i32.const 1) (local.get $b))))
+
+  (func $some (param $v i64) (result i64)
+    (local $p i32)
+    (local.set $p (call $new (i32.const 3) (i32.const 1)))
+    (i64.store offset=8 (local.get $p) (local.get $v))
+    (i64.extend_i32_u (local.get $p)))
+
+  (func $left (param $v i64) (result i64)
+    (local $p i32)
+    (local.set $p (call $new (i32.const 7) (i32.const 1)))
+    (i64.store offset=8 (local.get $p) (local.get $v))
+    (i64.extend_i32_u (local.get $p)))
+
+  (func $right (param $v i64) (result i64)
+    (local $p i32)
+    (local.set $p (call $new (i32.const 8) (i32.const 1)))
+    (i64.store offset=8 (local.get $p) (local.get $v))
+    (i64.extend_i32_u (local.get $p)))
+
+  (func $pair (param $a i64) (param $b i64) (result i64)
+    (local $p i32)
+    (local.set $p (call $new (i32.const 4) (i32.const 2)))
+    (i64.store offset=8 (local.get $p) (local.get $a))
+    (i64.store offset=16 (local.get $p) (local.get $b))
+    (i64.extend_i32_u (local.get $p)))
+
+  ;; ------------------------------- strings ------------------------------
+
+  (func $str (param $len i32) (result i32)
+    (local $s i32)
+    (local.set $s (call $alloc (i32.add (i32.const 4) (i32.shl (local.get $len) (i32.const 2)))))
+    (i32.store (local.get $s) (local.get $len))
+    (local.get $s))
+
+  (func $substr (param $s i32) (param $from i32) (param $to i32) (result i64)
+    (local $r i32)
+    (local.set $r (call $str (i32.sub (local.get $to) (local.get $from))))
+    (memory.copy
+      (i32.add (local.get $r) (i32.const 4))
+      (i32.add (i32.add (local.get $s) (i32.const 4)) (i32.shl (local.get $from) (i32.const 2)))
+      (i32.shl (i32.sub (local.get $to) (local.get $from)) (i32.const 2)))
+    (i64.extend_i32_u (local.get $r)))
+
+  (func $concat (param $x i32) (param $y i32) (result i32)
+    (local $s i32)
+    (local.set $s (call $str (i32.add (i32.load (local.get $x)) (i32.load (local.get $y)))))
+    (memory.copy (i32.add (local.get $s) (i32.const 4)) (i32.add (local.get $x) (i32.const 4))
+      (i32.shl (i32.load (local.get $x)) (i32.const 2)))
+    (memory.copy
+      (i32.add (i32.add (local.get $s) (i32.const 4)) (i32.shl (i32.load (local.get $x)) (i32.const 2)))
+      (i32.add (local.get $y) (i32.const 4))
+      (i32.shl (i32.load (local.get $y)) (i32.const 2)))
+    (local.get $s))
+
+  ;; compares by code point, returning whether `x` comes before `y`
+  (func $str_lt (param $x i32) (param $y i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done
+          (i32.or (i32.ge_u (local.get $i) (i32.load (local.get $x)))
+            (i32.ge_u (local.get $i) (i32.load (local.get $y)))))
+        (if (i32.ne (call $char_at (local.get $x) (local.get $i))
+              (call $char_at (local.get $y) (local.get $i)))
+          (then
+            (return (i32.lt_u (call $char_at (local.get $x) (local.get $i))
+              (call $char_at (local.get $y) (local.get $i))))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.lt_u (i32.load (local.get $x)) (i32.load (local.get $y))))
+
+  (func $char_at (param $s i32) (param $i i32) (result i32)
+    (i32.load offset=4 (i32.add (local.get $s) (i32.shl (local.get $i) (i32.const 2)))))
+
+  ;; decodes UTF-8, replacing malformed sequences
+  (func $decode (param $bytes i32) (param $n i32) (result i32)
+    (local $s i32)
+    (local $len i32)
+    (local $i i32)
+    (local $b i32)
+    (local $extra i32)
+    (local $c i32)
+    (local $ok i32)
+    (local $j i32)
+    (local $cont i32)
+    (local.set $s (call $str (local.get $n)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $b (i32.load8_u (i32.add (local.get $bytes) (local.get $i))))
+        (local.set $extra
+          (if (result i32) (i32.lt_u (local.get $b) (i32.const 0x80))
+            (then (i32.const 0))
+            (else
+              (if (result i32) (i32.eq (i32.and (local.get $b) (i32.const 0xe0)) (i32.const 0xc0))
+                (then (i32.const 1))
+                (else
+                  (if (result i32)
+                    (i32.eq (i32.and (local.get $b) (i32.const 0xf0)) (i32.const 0xe0))
+                    (then (i32.const 2))
+                    (else
+                      (select (i32.const 3) (i32.const -1)
+                        (i32.eq (i32.and (local.get $b) (i32.const 0xf8)) (i32.const 0xf0))))))))))
+        (local.set $c
+          (i32.and (local.get $b)
+            (i32.shr_u (i32.const 0x7f) (local.get $extra))))
+        (local.set $ok
+          (i32.and
+            (i32.ge_s (local.get $extra) (i32.const 0))
+            (i32.lt_u (i32.add (local.get $i) (local.get $extra)) (local.get $n))))
+        (local.set $j (i32.const 1))
+        (block $end
+          (loop $more
+            (br_if $end (i32.eqz (local.get $ok)))
+            (br_if $end (i32.gt_s (local.get $j) (local.get $extra)))
+            (local.set $cont
+              (i32.load8_u (i32.add (i32.add (local.get $bytes) (local.get $i)) (local.get $j))))
+            (local.set $ok (i32.eq (i32.and (local.get $cont) (i32.const 0xc0)) (i32.const 0x80)))
+            (local.set $c
+              (i32.or (i32.shl (local.get $c) (i32.const 6))
+                (i32.and (local.get $cont) (i32.const 0x3f))))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $more)))
+        (if (local.get $ok)
+          (then
+            (i32.store offset=4
+              (i32.add (local.get $s) (i32.shl (local.get $len) (i32.const 2)))
+              (local.get $c))
+            (local.set $i (i32.add (local.get $i) (i32.add (local.get $extra) (i32.const 1)))))
+          (else
+            (i32.store offset=4
+              (i32.add (local.get $s) (i32.shl (local.get $len) (i32.const 2)))
+              (i32.const 0xfffd))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (br $next)))
+    (i32.store (local.get $s) (local.get $len))
+    (local.get $s))
+
+  ;; a continuation byte of UTF-8 with the six bits of a code point from `shift` up
+  (func $cont (param $c i32) (param $shift i32) (result i32)
+    (i32.or (i32.const 0x80)
+      (i32.and (i32.shr_u (local.get $c) (local.get $shift)) (i32.const 0x3f))))
+
+  ;; encodes as UTF-8, returning the address and leaving the length in `$len`
+  (func $encode (param $s i32) (result i32)
+    (local $out i32)
+    (local $n i32)
+    (local $i i32)
+    (local $c i32)
+    (local $p i32)
+    (local.set $out (call $alloc (i32.shl (i32.load (local.get $s)) (i32.const 2))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (i32.load (local.get $s))))
+        (local.set $c (call $char_at (local.get $s) (local.get $i)))
+        (local.set $p (i32.add (local.get $out) (local.get $n)))
+        (if (i32.lt_u (local.get $c) (i32.const 0x80))
+          (then
+            (i32.store8 (local.get $p) (local.get $c))
+            (local.set $n (i32.add (local.get $n) (i32.const 1))))
+          (else (if (i32.lt_u (local.get $c) (i32.const 0x800))
+            (then
+              (i32.store8 (local.get $p)
+                (i32.or (i32.const 0xc0) (i32.shr_u (local.get $c) (i32.const 6))))
+              (i32.store8 offset=1 (local.get $p) (call $cont (local.get $c) (i32.const 0)))
+              (local.set $n (i32.add (local.get $n) (i32.const 2))))
+            (else (if (i32.lt_u (local.get $c) (i32.const 0x10000))
+              (then
+                (i32.store8 (local.get $p)
+                  (i32.or (i32.const 0xe0) (i32.shr_u (local.get $c) (i32.const 12))))
+                (i32.store8 offset=1 (local.get $p) (call $cont (local.get $c) (i32.const 6)))
+                (i32.store8 offset=2 (local.get $p) (call $cont (local.get $c) (i32.const 0)))
+                (local.set $n (i32.add (local.get $n) (i32.const 3))))
+              (else
+                (i32.store8 (local.get $p)
+                  (i32.or (i32.const 0xf0) (i32.shr_u (local.get $c) (i32.const 18))))
+                (i32.store8 offset=1 (local.get $p) (call $cont (local.get $c) (i32.const 12)))
+                (i32.store8 offset=2 (local.get $p) (call $cont (local.get $c) (i32.const 6)))
+                (i32.store8 offset=3 (local.get $p) (call $cont (local.get $c) (i32.const 0)))
+                (local.set $n (i32.add (local.get $n) (i32.const 4)))))))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (global.set $len (local.get $n))
+    (local.get $out))
+
+  ;; formats in decimal into the scratch space, leaving the length in `$len`
+  (func $itoa (param $i i64) (result i32)
+    (local $u i64)
+    (local $p i32)
+    (local.set $u (select (i64.sub (i64.const 0) (local.get $i)) (local.get $i)
+      (i64.lt_s (local.get $i) (i64.const 0))))
+    (local.set $p (i32.const 64))
+    (loop $digit
+      (local.set $p (i32.sub (local.get $p) (i32.const 1)))
+      (i32.store8 (local.get $p)
+        (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $u) (i64.const 10)))))
+      (local.set $u (i64.div_u (local.get $u) (i64.const 10)))
+      (br_if $digit (i64.ne (local.get $u) (i64.const 0))))
+    (if (i64.lt_s (local.get $i) (i64.const 0))
+      (then
+        (local.set $p (i32.sub (local.get $p) (i32.const 1)))
+        (i32.store8 (local.get $p) (i32.const 45))))
+    (global.set $len (i32.sub (i32.const 64) (local.get $p)))
+    (local.get $p))
+
+  ;; parses like Rust's `i64::from_str`, leaving the result in `$parsed`
+  (func $parse_int (param $s i32) (result i32)
+    (local $len i32)
+    (local $i i32)
+    (local $neg i32)
+    (local $c i32)
+    (local $d i64)
+    (local $limit i64)
+    (local $n i64)
+    (local.set $len (i32.load (local.get $s)))
+    (if (i32.gt_u (local.get $len) (i32.const 0))
+      (then
+        (local.set $c (call $char_at (local.get $s) (i32.const 0)))
+        (if (i32.or (i32.eq (local.get $c) (i32.const 43)) (i32.eq (local.get $c) (i32.const 45)))
+          (then
+            (local.set $neg (i32.eq (local.get $c) (i32.const 45)))
+            (local.set $i (i32.const 1))))))
+    (if (i32.eq (local.get $i) (local.get $len)) (then (return (i32.const 0))))
+    (local.set $limit
+      (i64.add (i64.const 0x7fffffffffffffff) (i64.extend_i32_u (local.get $neg))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $c (call $char_at (local.get $s) (local.get $i)))
+        (if (i32.gt_u (i32.sub (local.get $c) (i32.const 48)) (i32.const 9))
+          (then (return (i32.const 0))))
+        (local.set $d (i64.extend_i32_u (i32.sub (local.get $c) (i32.const 48))))
+        (if (i64.gt_u (local.get $n)
+              (i64.div_u (i64.sub (local.get $limit) (local.get $d)) (i64.const 10)))
+          (then (return (i32.const 0))))
+        (local.set $n (i64.add (i64.mul (local.get $n) (i64.const 10)) (local.get $d)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (global.set $parsed
+      (select (i64.sub (i64.const 0) (local.get $n)) (local.get $n) (local.get $neg)))
+    (i32.const 1))
+
+  ;; ---------------------------- big integers ----------------------------
+  ;;
+  ;;   big integer  [neg: i32][len: i32][limbs: i32]...
+  ;;
+  ;; The limbs of the magnitude come least significant first, without leading zero limbs.
+
+  (func $big (param $len i32) (result i32)
+    (local $b i32)
+    (local.set $b
+      (call $alloc (i32.add (i32.const 8) (i32.shl (local.get $len) (i32.const 2)))))
+    (i32.store (local.get $b) (i32.const 0))
+    (i32.store offset=4 (local.get $b) (local.get $len))
+    (memory.fill
+      (i32.add (local.get $b) (i32.const 8)) (i32.const 0) (i32.shl (local.get $len) (i32.const 2)))
+    (local.get $b))
+
+  (func $limb_at (param $b i32) (param $i i32) (result i32)
+    (i32.add (i32.add (local.get $b) (i32.const 8)) (i32.shl (local.get $i) (i32.const 2))))
+
+  ;; a limb, or zero past the end
+  (func $limb (param $b i32) (param $i i32) (result i64)
+    (if (result i64) (i32.lt_u (local.get $i) (i32.load offset=4 (local.get $b)))
+      (then (i64.load32_u (call $limb_at (local.get $b) (local.get $i))))
+      (else (i64.const 0))))
+
+  (func $big_trim (param $b i32) (result i32)
+    (local $len i32)
+    (local.set $len (i32.load offset=4 (local.get $b)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $len)))
+        (br_if $done
+          (i32.load (call $limb_at (local.get $b) (i32.sub (local.get $len) (i32.const 1)))))
+        (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+        (br $next)))
+    (i32.store offset=4 (local.get $b) (local.get $len))
+    (if (i32.eqz (local.get $len)) (then (i32.store (local.get $b) (i32.const 0))))
+    (local.get $b))
+
+  (func $big_copy (param $a i32) (result i32)
+    (local $b i32)
+    (local.set $b (call $big (i32.load offset=4 (local.get $a))))
+    (i32.store (local.get $b) (i32.load (local.get $a)))
+    (memory.copy
+      (call $limb_at (local.get $b) (i32.const 0))
+      (call $limb_at (local.get $a) (i32.const 0))
+      (i32.shl (i32.load offset=4 (local.get $a)) (i32.const 2)))
+    (local.get $b))
+
+  (func $big_of (param $i i64) (result i32)
+    (local $b i32)
+    (local.set $b (call $big (i32.const 2)))
+    (i32.store (local.get $b) (i64.lt_s (local.get $i) (i64.const 0)))
+    (i64.store offset=8 (local.get $b)
+      (select (i64.sub (i64.const 0) (local.get $i)) (local.get $i)
+        (i64.lt_s (local.get $i) (i64.const 0))))
+    (call $big_trim (local.get $b)))
+
+  ;; converts to an `Int` if it fits, leaving the result in `$parsed`
+  (func $big_to_int (param $a i32) (result i32)
+    (local $u i64)
+    (if (i32.gt_u (i32.load offset=4 (local.get $a)) (i32.const 2))
+      (then (return (i32.const 0))))
+    (local.set $u
+      (i64.or (call $limb (local.get $a) (i32.const 0))
+        (i64.shl (call $limb (local.get $a) (i32.const 1)) (i64.const 32))))
+    (if (i64.gt_u (local.get $u)
+          (i64.add (i64.const 0x7fffffffffffffff) (i64.extend_i32_u (i32.load (local.get $a)))))
+      (then (return (i32.const 0))))
+    (global.set $parsed
+      (select (i64.sub (i64.const 0) (local.get $u)) (local.get $u) (i32.load (local.get $a))))
+    (i32.const 1))
+
+  ;; compares magnitudes, returning -1, 0 or 1
+  (func $mag_cmp (param $a i32) (param $b i32) (result i32)
+    (local $i i32)
+    (local $x i32)
+    (local $y i32)
+    (local.set $i (i32.load offset=4 (local.get $a)))
+    (if (i32.ne (local.get $i) (i32.load offset=4 (local.get $b)))
+      (then
+        (return
+          (select (i32.const -1) (i32.const 1)
+            (i32.lt_u (local.get $i) (i32.load offset=4 (local.get $b)))))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $i)))
+        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+        (local.set $x (i32.load (call $limb_at (local.get $a) (local.get $i))))
+        (local.set $y (i32.load (call $limb_at (local.get $b) (local.get $i))))
+        (if (i32.ne (local.get $x) (local.get $y))
+          (then
+            (return (select (i32.const -1) (i32.const 1) (i32.lt_u (local.get $x) (local.get $y))))))
+        (br $next)))
+    (i32.const 0))
+
+  (func $big_cmp (param $a i32) (param $b i32) (result i32)
+    (if (i32.ne (i32.load (local.get $a)) (i32.load (local.get $b)))
+      (then (return (select (i32.const -1) (i32.const 1) (i32.load (local.get $a))))))
+    (if (result i32) (i32.load (local.get $a))
+      (then (call $mag_cmp (local.get $b) (local.get $a)))
+      (else (call $mag_cmp (local.get $a) (local.get $b)))))
+
+  (func $mag_add (param $a i32) (param $b i32) (result i32)
+    (local $len i32)
+    (local $r i32)
+    (local $i i32)
+    (local $carry i64)
+    (local.set $len (i32.load offset=4 (local.get $a)))
+    (if (i32.gt_u (i32.load offset=4 (local.get $b)) (local.get $len))
+      (then (local.set $len (i32.load offset=4 (local.get $b)))))
+    (local.set $r (call $big (i32.add (local.get $len) (i32.const 1))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $carry
+          (i64.add (local.get $carry)
+            (i64.add (call $limb (local.get $a) (local.get $i))
+              (call $limb (local.get $b) (local.get $i)))))
+        (i32.store (call $limb_at (local.get $r) (local.get $i)) (i32.wrap_i64 (local.get $carry)))
+        (local.set $carry (i64.shr_u (local.get $carry) (i64.const 32)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.store (call $limb_at (local.get $r) (local.get $len)) (i32.wrap_i64 (local.get $carry)))
+    (local.get $r))
+
+  ;; subtracts the magnitude of `b` from that of `a` in place, which must be at least as large
+  (func $mag_sub (param $a i32) (param $b i32)
+    (local $i i32)
+    (local $d i64)
+    (local $borrow i64)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (i32.load offset=4 (local.get $a))))
+        (local.set $d
+          (i64.sub
+            (i64.sub (call $limb (local.get $a) (local.get $i)) (call $limb (local.get $b) (local.get $i)))
+            (local.get $borrow)))
+        (i32.store (call $limb_at (local.get $a) (local.get $i)) (i32.wrap_i64 (local.get $d)))
+        (local.set $borrow (i64.shr_u (local.get $d) (i64.const 63)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next))))
+
+  ;; adds `b`, or subtracts it if `negate` is set
+  (func $big_add (param $a i32) (param $b i32) (param $negate i32) (result i32)
+    (local $neg i32)
+    (local $r i32)
+    (local.set $neg (i32.xor (i32.load (local.get $b)) (local.get $negate)))
+    (if (i32.eq (i32.load (local.get $a)) (local.get $neg))
+      (then
+        (local.set $r (call $mag_add (local.get $a) (local.get $b)))
+        (i32.store (local.get $r) (local.get $neg)))
+      (else
+        (if (i32.ge_s (call $mag_cmp (local.get $a) (local.get $b)) (i32.const 0))
+          (then
+            (local.set $r (call $big_copy (local.get $a)))
+            (call $mag_sub (local.get $r) (local.get $b)))
+          (else
+            (local.set $r (call $big_copy (local.get $b)))
+            (call $mag_sub (local.get $r) (local.get $a))
+            (i32.store (local.get $r) (local.get $neg))))))
+    (call $big_trim (local.get $r)))
+
+  (func $big_mul (param $a i32) (param $b i32) (result i32)
+    (local $r i32)
+    (local $i i32)
+    (local $j i32)
+    (local $p i32)
+    (local $carry i64)
+    (local.set $r
+      (call $big (i32.add (i32.load offset=4 (local.get $a)) (i32.load offset=4 (local.get $b)))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (i32.load offset=4 (local.get $a))))
+        (local.set $carry (i64.const 0))
+        (local.set $j (i32.const 0))
+        (block $end
+          (loop $more
+            (br_if $end (i32.ge_u (local.get $j) (i32.load offset=4 (local.get $b))))
+            (local.set $p (call $limb_at (local.get $r) (i32.add (local.get $i) (local.get $j))))
+            (local.set $carry
+              (i64.add (local.get $carry)
+                (i64.add
+                  (i64.mul
+                    (call $limb (local.get $a) (local.get $i))
+                    (call $limb (local.get $b) (local.get $j)))
+                  (i64.load32_u (local.get $p)))))
+            (i32.store (local.get $p) (i32.wrap_i64 (local.get $carry)))
+            (local.set $carry (i64.shr_u (local.get $carry) (i64.const 32)))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $more)))
+        (i32.store (call $limb_at (local.get $r) (i32.add (local.get $i) (local.get $j)))
+          (i32.wrap_i64 (local.get $carry)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.store (local.get $r) (i32.xor (i32.load (local.get $a)) (i32.load (local.get $b))))
+    (call $big_trim (local.get $r)))
+
+  ;; long division one bit at a time, rounding toward zero like the interpreter; returns the
+  ;; quotient and leaves the remainder in `$big_rem`
+  (func $big_divmod (param $a i32) (param $b i32) (result i32)
+    (local $q i32)
+    (local $r i32)
+    (local $i i32)
+    (local $j i32)
+    (local $p i32)
+    (local $bit i32)
+    (local $top i32)
+    (local.set $q (call $big (i32.load offset=4 (local.get $a))))
+    (local.set $r (call $big (i32.add (i32.load offset=4 (local.get $b)) (i32.const 1))))
+    (i32.store offset=4 (local.get $r) (i32.const 0))
+    (local.set $i (i32.shl (i32.load offset=4 (local.get $a)) (i32.const 5)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $i)))
+        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+        ;; Note: shift counts are taken modulo 32
+        (local.set $bit
+          (i32.and (i32.const 1)
+            (i32.shr_u
+              (i32.load (call $limb_at (local.get $a) (i32.shr_u (local.get $i) (i32.const 5))))
+              (local.get $i))))
+        (local.set $j (i32.const 0))
+        (block $end
+          (loop $more
+            (br_if $end (i32.ge_u (local.get $j) (i32.load offset=4 (local.get $r))))
+            (local.set $p (call $limb_at (local.get $r) (local.get $j)))
+            (local.set $top (i32.shr_u (i32.load (local.get $p)) (i32.const 31)))
+            (i32.store (local.get $p)
+              (i32.or (i32.shl (i32.load (local.get $p)) (i32.const 1)) (local.get $bit)))
+            (local.set $bit (local.get $top))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $more)))
+        (if (local.get $bit)
+          (then
+            (i32.store (call $limb_at (local.get $r) (local.get $j)) (local.get $bit))
+            (i32.store offset=4 (local.get $r) (i32.add (local.get $j) (i32.const 1)))))
+        (if (i32.ge_s (call $mag_cmp (local.get $r) (local.get $b)) (i32.const 0))
+          (then
+            (call $mag_sub (local.get $r) (local.get $b))
+            (drop (call $big_trim (local.get $r)))
+            (local.set $p (call $limb_at (local.get $q) (i32.shr_u (local.get $i) (i32.const 5))))
+            (i32.store (local.get $p)
+              (i32.or (i32.load (local.get $p)) (i32.shl (i32.const 1) (local.get $i))))))
+        (br $next)))
+    (i32.store (local.get $q) (i32.xor (i32.load (local.get $a)) (i32.load (local.get $b))))
+    (i32.store (local.get $r) (i32.load (local.get $a)))
+    (global.set $big_rem (call $big_trim (local.get $r)))
+    (call $big_trim (local.get $q)))
+
+  ;; formats in decimal as a string
+  (func $big_to_str (param $a i32) (result i32)
+    (local $t i32)
+    (local $cap i32)
+    (local $bytes i32)
+    (local $n i32)
+    (local $i i32)
+    (local $d i32)
+    (local $cur i64)
+    (local $rem i64)
+    (local.set $t (call $big_copy (local.get $a)))
+    ;; nine digits at a time, each limb having at most ten
+    (local.set $cap
+      (i32.add (i32.mul (i32.load offset=4 (local.get $a)) (i32.const 10)) (i32.const 10)))
+    (local.set $bytes (call $alloc (local.get $cap)))
+    (local.set $n (local.get $cap))
+    (loop $chunk
+      (local.set $rem (i64.const 0))
+      (local.set $i (i32.load offset=4 (local.get $t)))
+      (block $divided
+        (loop $limbs
+          (br_if $divided (i32.eqz (local.get $i)))
+          (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+          (local.set $cur
+            (i64.or (i64.shl (local.get $rem) (i64.const 32))
+              (i64.load32_u (call $limb_at (local.get $t) (local.get $i)))))
+          (i32.store (call $limb_at (local.get $t) (local.get $i))
+            (i32.wrap_i64 (i64.div_u (local.get $cur) (i64.const 1000000000))))
+          (local.set $rem (i64.rem_u (local.get $cur) (i64.const 1000000000)))
+          (br $limbs)))
+      (drop (call $big_trim (local.get $t)))
+      (local.set $d (i32.const 0))
+      (loop $digit
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (i32.store8 (i32.add (local.get $bytes) (local.get $n))
+          (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $rem) (i64.const 10)))))
+        (local.set $rem (i64.div_u (local.get $rem) (i64.const 10)))
+        (local.set $d (i32.add (local.get $d) (i32.const 1)))
+        (br_if $digit (i32.lt_u (local.get $d) (i32.const 9))))
+      (br_if $chunk (i32.load offset=4 (local.get $t))))
+    (block $trimmed
+      (loop $zero
+        (br_if $trimmed (i32.ge_u (local.get $n) (i32.sub (local.get $cap) (i32.const 1))))
+        (br_if $trimmed
+          (i32.ne (i32.load8_u (i32.add (local.get $bytes) (local.get $n))) (i32.const 48)))
+        (local.set $n (i32.add (local.get $n) (i32.const 1)))
+        (br $zero)))
+    (if (i32.load (local.get $a))
+      (then
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (i32.store8 (i32.add (local.get $bytes) (local.get $n)) (i32.const 45))))
+    (call $decode
+      (i32.add (local.get $bytes) (local.get $n)) (i32.sub (local.get $cap) (local.get $n))))
+
+  ;; parses the same strings as `$parse_int` but without bounds, returning 0 on failure
+  (func $parse_big (param $s i32) (result i32)
+    (local $len i32)
+    (local $i i32)
+    (local $neg i32)
+    (local $c i32)
+    (local $b i32)
+    (local $j i32)
+    (local $p i32)
+    (local $carry i64)
+    (local.set $len (i32.load (local.get $s)))
+    (if (i32.gt_u (local.get $len) (i32.const 0))
+      (then
+        (local.set $c (call $char_at (local.get $s) (i32.const 0)))
+        (if (i32.or (i32.eq (local.get $c) (i32.const 43)) (i32.eq (local.get $c) (i32.const 45)))
+          (then
+            (local.set $neg (i32.eq (local.get $c) (i32.const 45)))
+            (local.set $i (i32.const 1))))))
+    (if (i32.eq (local.get $i) (local.get $len)) (then (return (i32.const 0))))
+    ;; a limb holds at least nine digits
+    (local.set $b (call $big (i32.add (i32.div_u (local.get $len) (i32.const 9)) (i32.const 1))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $c (call $char_at (local.get $s) (local.get $i)))
+        (if (i32.gt_u (i32.sub (local.get $c) (i32.const 48)) (i32.const 9))
+          (then (return (i32.const 0))))
+        (local.set $carry (i64.extend_i32_u (i32.sub (local.get $c) (i32.const 48))))
+        (local.set $j (i32.const 0))
+        (block $end
+          (loop $more
+            (br_if $end (i32.ge_u (local.get $j) (i32.load offset=4 (local.get $b))))
+            (local.set $p (call $limb_at (local.get $b) (local.get $j)))
+            (local.set $carry
+              (i64.add (local.get $carry) (i64.mul (i64.load32_u (local.get $p)) (i64.const 10))))
+            (i32.store (local.get $p) (i32.wrap_i64 (local.get $carry)))
+            (local.set $carry (i64.shr_u (local.get $carry) (i64.const 32)))
+            (local.set $j (i32.add (local.get $j) (i32.const 1)))
+            (br $more)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.store (local.get $b) (local.get $neg))
+    (call $big_trim (local.get $b)))
+
+  ;; -------------------------------- floats ------------------------------
+
+  (func $big_pow2 (param $n i32) (result i32)
+    (local $b i32)
+    (local.set $b (call $big (i32.add (i32.shr_u (local.get $n) (i32.const 5)) (i32.const 1))))
+    ;; Note: shift counts are taken modulo 32
+    (i32.store (call $limb_at (local.get $b) (i32.shr_u (local.get $n) (i32.const 5)))
+      (i32.shl (i32.const 1) (local.get $n)))
+    (local.get $b))
+
+  (func $big_mul10 (param $b i32) (result i32)
+    (call $big_mul (local.get $b) (call $big_of (i64.const 10))))
+
+  (func $big_pow10 (param $n i32) (result i32)
+    (local $b i32)
+    (local.set $b (call $big_of (i64.const 1)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $n)))
+        (local.set $b (call $big_mul10 (local.get $b)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $next)))
+    (local.get $b))
+
+  ;; The shortest digits that read back as `v`, which must be positive and finite, and the
+  ;; closest to it among those, by Burger and Dybvig's free-format algorithm. Writes them to
+  ;; `out`, returns how many there are and leaves `k` in `$float_k` so that `v` is `0.digits`
+  ;; times 10 to the `k`.
+  (func $float_digits (param $v f64) (param $out i32) (result i32)
+    (local $bits i64)
+    (local $biased i32)
+    (local $f i64)
+    (local $e i32)
+    (local $even i32)
+    (local $lower i32)
+    (local $r i32)
+    (local $s i32)
+    (local $mp i32)
+    (local $mm i32)
+    (local $scale i32)
+    (local $k i32)
+    (local $c i32)
+    (local $n i32)
+    (local $d i32)
+    (local $low i32)
+    (local $high i32)
+    (local.set $bits (i64.reinterpret_f64 (local.get $v)))
+    (local.set $biased (i32.wrap_i64 (i64.shr_u (local.get $bits) (i64.const 52))))
+    (local.set $f (i64.and (local.get $bits) (i64.const 0xfffffffffffff)))
+    (local.set $e (i32.const -1074))
+    (if (local.get $biased)
+      (then
+        (local.set $f (i64.or (local.get $f) (i64.const 0x10000000000000)))
+        (local.set $e (i32.sub (local.get $biased) (i32.const 1075)))))
+    ;; a float reads back from anything strictly between the midpoints to its neighbours, or
+    ;; also from the midpoints themselves if it is even; the gap below is halved at a power of two
+    (local.set $even (i64.eqz (i64.and (local.get $f) (i64.const 1))))
+    (local.set $lower
+      (i32.and
+        (i64.eq (local.get $f) (i64.const 0x10000000000000))
+        (i32.gt_s (local.get $biased) (i32.const 1))))
+    ;; the midpoints are (r - mm) / s and (r + mp) / s
+    (if (i32.ge_s (local.get $e) (i32.const 0))
+      (then
+        (local.set $r
+          (call $big_mul (call $big_of (local.get $f))
+            (call $big_pow2 (i32.add (i32.add (local.get $e) (i32.const 1)) (local.get $lower)))))
+        (local.set $s (call $big_of (i64.extend_i32_u (i32.shl (i32.const 2) (local.get $lower)))))
+        (local.set $mp (call $big_pow2 (i32.add (local.get $e) (local.get $lower))))
+        (local.set $mm (call $big_pow2 (local.get $e))))
+      (else
+        (local.set $r
+          (call $big_of
+            (i64.shl (local.get $f) (i64.extend_i32_u (i32.add (local.get $lower) (i32.const 1))))))
+        (local.set $s (call $big_pow2 (i32.sub (i32.add (local.get $lower) (i32.const 1)) (local.get $e))))
+        (local.set $mp (call $big_of (i64.extend_i32_u (i32.shl (i32.const 1) (local.get $lower)))))
+        (local.set $mm (call $big_of (i64.const 1)))))
+    ;; estimate k from the binary exponent, then correct it so the upper midpoint is below 10^k
+    (local.set $k
+      (i32.trunc_f64_s
+        (f64.add
+          (f64.mul
+            (f64.convert_i32_s
+              (i32.add (local.get $e) (i32.wrap_i64 (i64.sub (i64.const 63) (i64.clz (local.get $f))))))
+            (f64.const 0.30102999566398114))
+          (f64.const 1))))
+    (if (i32.ge_s (local.get $k) (i32.const 0))
+      (then (local.set $s (call $big_mul (local.get $s) (call $big_pow10 (local.get $k)))))
+      (else
+        (local.set $scale (call $big_pow10 (i32.sub (i32.const 0) (local.get $k))))
+        (local.set $r (call $big_mul (local.get $r) (local.get $scale)))
+        (local.set $mp (call $big_mul (local.get $mp) (local.get $scale)))
+        (local.set $mm (call $big_mul (local.get $mm) (local.get $scale)))))
+    (block $high_enough
+      (loop $raise
+        (local.set $c
+          (call $mag_cmp (call $big_add (local.get $r) (local.get $mp) (i32.const 0)) (local.get $s)))
+        (br_if $high_enough
+          (i32.or (i32.lt_s (local.get $c) (i32.const 0))
+            (i32.and (i32.eqz (local.get $c)) (i32.eqz (local.get $even)))))
+        (local.set $s (call $big_mul10 (local.get $s)))
+        (local.set $k (i32.add (local.get $k) (i32.const 1)))
+        (br $raise)))
+    (block $low_enough
+      (loop $lower_k
+        (local.set $c
+          (call $mag_cmp
+            (call $big_mul10 (call $big_add (local.get $r) (local.get $mp) (i32.const 0)))
+            (local.get $s)))
+        (br_if $low_enough
+          (i32.or (i32.gt_s (local.get $c) (i32.const 0))
+            (i32.and (i32.eqz (local.get $c)) (local.get $even))))
+        (local.set $r (call $big_mul10 (local.get $r)))
+        (local.set $mp (call $big_mul10 (local.get $mp)))
+        (local.set $mm (call $big_mul10 (local.get $mm)))
+        (local.set $k (i32.sub (local.get $k) (i32.const 1)))
+        (br $lower_k)))
+    (global.set $float_k (local.get $k))
+    (loop $digit
+      (local.set $r (call $big_mul10 (local.get $r)))
+      (local.set $mp (call $big_mul10 (local.get $mp)))
+      (local.set $mm (call $big_mul10 (local.get $mm)))
+      (local.set $d (i32.const 0))
+      (block $divided
+        (loop $subtract
+          (br_if $divided (i32.lt_s (call $mag_cmp (local.get $r) (local.get $s)) (i32.const 0)))
+          (call $mag_sub (local.get $r) (local.get $s))
+          (drop (call $big_trim (local.get $r)))
+          (local.set $d (i32.add (local.get $d) (i32.const 1)))
+          (br $subtract)))
+      (local.set $c (call $mag_cmp (local.get $r) (local.get $mm)))
+      (local.set $low
+        (i32.or (i32.lt_s (local.get $c) (i32.const 0))
+          (i32.and (i32.eqz (local.get $c)) (local.get $even))))
+      (local.set $c
+        (call $mag_cmp (call $big_add (local.get $r) (local.get $mp) (i32.const 0)) (local.get $s)))
+      (local.set $high
+        (i32.or (i32.gt_s (local.get $c) (i32.const 0))
+          (i32.and (i32.eqz (local.get $c)) (local.get $even))))
+      (if (i32.and (local.get $low) (local.get $high))
+        (then
+          ;; either digit reads back, so take the closer one, or the even one on a tie
+          (local.set $c
+            (call $mag_cmp (call $big_add (local.get $r) (local.get $r) (i32.const 0)) (local.get $s)))
+          (local.set $high
+            (i32.or (i32.gt_s (local.get $c) (i32.const 0))
+              (i32.and (i32.eqz (local.get $c)) (i32.and (local.get $d) (i32.const 1)))))))
+      (if (i32.or (local.get $low) (local.get $high))
+        (then
+          (i32.store8 (i32.add (local.get $out) (local.get $n))
+            (i32.add (i32.add (i32.const 48) (local.get $d)) (local.get $high)))
+          (return (i32.add (local.get $n) (i32.const 1)))))
+      (i32.store8 (i32.add (local.get $out) (local.get $n)) (i32.add (i32.const 48) (local.get $d)))
+      (local.set $n (i32.add (local.get $n) (i32.const 1)))
+      (br $digit))
+    (unreachable))
+
+  ;; writes a float like JavaScript's `Number.prototype.toString`, as the interpreter does
+  (func $float_to_str (param $v f64) (result i32)
+    (local $digits i32)
+    (local $out i32)
+    (local $n i32)
+    (local $k i32)
+    (local $len i32)
+    (local $e i32)
+    (if (f64.ne (local.get $v) (local.get $v)) (then (return (call $decode (str "NaN")))))
+    (if (f64.eq (local.get $v) (f64.const 0)) (then (return (call $decode (str "0")))))
+    (if (f64.eq (local.get $v) (f64.const inf)) (then (return (call $decode (str "Infinity")))))
+    (if (f64.eq (local.get $v) (f64.const -inf)) (then (return (call $decode (str "-Infinity")))))
+    (local.set $digits (call $alloc (i32.const 24)))
+    (local.set $out (call $alloc (i32.const 32)))
+    (local.set $n (call $float_digits (f64.abs (local.get $v)) (local.get $digits)))
+    (local.set $k (global.get $float_k))
+    (if (f64.lt (local.get $v) (f64.const 0))
+      (then
+        (i32.store8 (local.get $out) (i32.const 45))
+        (local.set $len (i32.const 1))))
+    (if (i32.and (i32.le_s (local.get $n) (local.get $k)) (i32.le_s (local.get $k) (i32.const 21)))
+      (then
+        (memory.copy (i32.add (local.get $out) (local.get $len)) (local.get $digits) (local.get $n))
+        (memory.fill (i32.add (i32.add (local.get $out) (local.get $len)) (local.get $n))
+          (i32.const 48) (i32.sub (local.get $k) (local.get $n)))
+        (local.set $len (i32.add (local.get $len) (local.get $k))))
+      (else
+        (if (i32.and (i32.gt_s (local.get $k) (i32.const 0)) (i32.le_s (local.get $k) (i32.const 21)))
+          (then
+            (memory.copy (i32.add (local.get $out) (local.get $len)) (local.get $digits) (local.get $k))
+            (local.set $len (i32.add (local.get $len) (local.get $k)))
+            (i32.store8 (i32.add (local.get $out) (local.get $len)) (i32.const 46))
+            (memory.copy (i32.add (i32.add (local.get $out) (local.get $len)) (i32.const 1))
+              (i32.add (local.get $digits) (local.get $k)) (i32.sub (local.get $n) (local.get $k)))
+            (local.set $len
+              (i32.add (local.get $len) (i32.add (i32.sub (local.get $n) (local.get $k)) (i32.const 1)))))
+          (else
+            (if (i32.and (i32.gt_s (local.get $k) (i32.const -6)) (i32.le_s (local.get $k) (i32.const 0)))
+              (then
+                ;; "0."
+                (i32.store16 (i32.add (local.get $out) (local.get $len)) (i32.const 0x2e30))
+                (local.set $len (i32.add (local.get $len) (i32.const 2)))
+                (memory.fill (i32.add (local.get $out) (local.get $len))
+                  (i32.const 48) (i32.sub (i32.const 0) (local.get $k)))
+                (local.set $len (i32.sub (local.get $len) (local.get $k)))
+                (memory.copy (i32.add (local.get $out) (local.get $len)) (local.get $digits) (local.get $n))
+                (local.set $len (i32.add (local.get $len) (local.get $n))))
+              (else
+                (i32.store8 (i32.add (local.get $out) (local.get $len)) (i32.load8_u (local.get $digits)))
+                (local.set $len (i32.add (local.get $len) (i32.const 1)))
+                (if (i32.gt_s (local.get $n) (i32.const 1))
+                  (then
+                    (i32.store8 (i32.add (local.get $out) (local.get $len)) (i32.const 46))
+                    (memory.copy (i32.add (i32.add (local.get $out) (local.get $len)) (i32.const 1))
+                      (i32.add (local.get $digits) (i32.const 1)) (i32.sub (local.get $n) (i32.const 1)))
+                    (local.set $len (i32.add (local.get $len) (local.get $n)))))
+                (i32.store8 (i32.add (local.get $out) (local.get $len)) (i32.const 101))
+                (i32.store8 (i32.add (i32.add (local.get $out) (local.get $len)) (i32.const 1))
+                  (select (i32.const 43) (i32.const 45) (i32.gt_s (local.get $k) (i32.const 0))))
+                (local.set $len (i32.add (local.get $len) (i32.const 2)))
+                (local.set $e
+                  (select
+                    (i32.sub (local.get $k) (i32.const 1))
+                    (i32.sub (i32.const 1) (local.get $k))
+                    (i32.gt_s (local.get $k) (i32.const 0))))
+                ;; the exponent has at most three digits
+                (if (i32.ge_u (local.get $e) (i32.const 100))
+                  (then
+                    (i32.store8 (i32.add (local.get $out) (local.get $len))
+                      (i32.add (i32.const 48) (i32.div_u (local.get $e) (i32.const 100))))
+                    (local.set $len (i32.add (local.get $len) (i32.const 1)))))
+                (if (i32.ge_u (local.get $e) (i32.const 10))
+                  (then
+                    (i32.store8 (i32.add (local.get $out) (local.get $len))
+                      (i32.add (i32.const 48)
+                        (i32.rem_u (i32.div_u (local.get $e) (i32.const 10)) (i32.const 10))))
+                    (local.set $len (i32.add (local.get $len) (i32.const 1)))))
+                (i32.store8 (i32.add (local.get $out) (local.get $len))
+                  (i32.add (i32.const 48) (i32.rem_u (local.get $e) (i32.const 10))))
+                (local.set $len (i32.add (local.get $len) (i32.const 1)))))))))
+    (call $decode (local.get $out) (local.get $len)))
+
+  ;; -------------------------------- input -------------------------------
+
+  (func $getc (result i32)
+    (if (i32.ge_u (global.get $in_pos) (global.get $in_len))
+      (then
+        (i32.store (i32.const 0) (i32.const 1024))
+        (i32.store (i32.const 4) (i32.const 4096))
+        (if (i32.or
+              (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8))
+              (i32.eqz (i32.load (i32.const 8))))
+          (then (return (i32.const -1))))
+        (global.set $in_pos (i32.const 0))
+        (global.set $in_len (i32.load (i32.const 8)))))
+    (global.set $in_pos (i32.add (global.get $in_pos) (i32.const 1)))
+    (i32.load8_u offset=1023 (global.get $in_pos)))
+
+  ;; reads bytes to the top of the heap up to a newline or the end of the input, which the
+  ;; decoded string then follows
+  (func $read (param $line i32) (result i32)
+    (local $bytes i32)
+    (local $n i32)
+    (local $c i32)
+    ;; Note: the bytes go where `$bump` puts the object once their number is known
+    (local.set $bytes (i32.add (global.get $hp) (i32.const 8)))
+    (block $done
+      (loop $next
+        (local.set $c (call $getc))
+        (br_if $done (i32.lt_s (local.get $c) (i32.const 0)))
+        (call $reserve (i32.add (i32.add (local.get $bytes) (local.get $n)) (i32.const 1)))
+        (i32.store8 (i32.add (local.get $bytes) (local.get $n)) (local.get $c))
+        (local.set $n (i32.add (local.get $n) (i32.const 1)))
+        (br_if $done (i32.and (local.get $line) (i32.eq (local.get $c) (i32.const 10))))
+        (br $next)))
+    (drop (call $bump (local.get $n)))
+    (call $decode (local.get $bytes) (local.get $n)))
+
+  (func $read_line (result i32)
+    (local $s i32)
+    (local.set $s (call $read (i32.const 1)))
+    ;; like the interpreter, drop the last character whatever it is
+    (if (i32.load (local.get $s))
+      (then (i32.store (local.get $s) (i32.sub (i32.load (local.get $s)) (i32.const 1)))))
+    (local.get $s))
+
+  ;; -------------------------------- files -------------------------------
+  ;;
+  ;; A path is resolved against the preopened directory with the longest name that is a prefix
+  ;; of it, where `.` holds the relative paths. The scratch space from 64 holds the results of
+  ;; the system calls.
+
+  ;; the descriptor of the directory holding a UTF-8 path, leaving the path relative to it in
+  ;; `$rel` and `$len`, or -1 if there is none
+  (func $resolve (param $path i32) (param $n i32) (result i32)
+    (local $fd i32)
+    (local $best i32)
+    (local $best_len i32)
+    (local $name i32)
+    (local $m i32)
+    (local $i i32)
+    (local $ok i32)
+    (local.set $best (i32.const -1))
+    (local.set $best_len (i32.const -1))
+    (local.set $fd (i32.const 3))
+    (block $done
+      (loop $next
+        (br_if $done (call $fd_prestat_get (local.get $fd) (i32.const 64)))
+        ;; a directory is [tag: i8 = 0][unused: i24][name length: i32]
+        (if (i32.eqz (i32.load8_u (i32.const 64)))
+          (then
+            (local.set $m (i32.load (i32.const 68)))
+            (local.set $name (call $alloc (local.get $m)))
+            (drop (call $fd_prestat_dir_name (local.get $fd) (local.get $name) (local.get $m)))
+            (block $trimmed
+              (loop $trim
+                (br_if $trimmed (i32.eqz (local.get $m)))
+                (br_if $trimmed
+                  (i32.ne
+                    (i32.load8_u
+                      (i32.add (local.get $name) (i32.sub (local.get $m) (i32.const 1))))
+                    (i32.const 47)))
+                (local.set $m (i32.sub (local.get $m) (i32.const 1)))
+                (br $trim)))
+            (if (i32.and (i32.eq (local.get $m) (i32.const 1))
+                  (i32.eq (i32.load8_u (local.get $name)) (i32.const 46)))
+              (then
+                (local.set $m (i32.const 0))
+                (local.set $ok
+                  (i32.and (i32.ne (local.get $n) (i32.const 0))
+                    (i32.ne (i32.load8_u (local.get $path)) (i32.const 47)))))
+              (else
+                ;; the name must be followed by a slash, unless it is the whole path
+                (local.set $ok
+                  (if (result i32) (i32.lt_u (local.get $m) (local.get $n))
+                    (then
+                      (i32.eq (i32.load8_u (i32.add (local.get $path) (local.get $m)))
+                        (i32.const 47)))
+                    (else
+                      (i32.and (i32.eq (local.get $m) (local.get $n))
+                        (i32.ne (local.get $m) (i32.const 0))))))
+                (local.set $i (i32.const 0))
+                (block $differ
+                  (loop $byte
+                    (br_if $differ
+                      (i32.or (i32.eqz (local.get $ok)) (i32.ge_u (local.get $i) (local.get $m))))
+                    (local.set $ok
+                      (i32.eq (i32.load8_u (i32.add (local.get $name) (local.get $i)))
+                        (i32.load8_u (i32.add (local.get $path) (local.get $i)))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $byte)))))
+            (if (i32.and (local.get $ok) (i32.gt_s (local.get $m) (local.get $best_len)))
+              (then
+                (local.set $best (local.get $fd))
+                (local.set $best_len (local.get $m))))))
+        (local.set $fd (i32.add (local.get $fd) (i32.const 1)))
+        (br $next)))
+    (if (i32.lt_s (local.get $best) (i32.const 0))
+      (then
+        ;; notcapable
+        (global.set $errno (i32.const 76))
+        (return (i32.const -1))))
+    (local.set $i (local.get $best_len))
+    (block $skipped
+      (loop $skip
+        (br_if $skipped (i32.ge_u (local.get $i) (local.get $n)))
+        (br_if $skipped
+          (i32.ne (i32.load8_u (i32.add (local.get $path) (local.get $i))) (i32.const 47)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $skip)))
+    (global.set $rel (i32.add (local.get $path) (local.get $i)))
+    (global.set $len (i32.sub (local.get $n) (local.get $i)))
+    (if (i32.eqz (global.get $len))
+      (then
+        (global.set $rel (call $alloc (i32.const 1)))
+        (i32.store8 (global.get $rel) (i32.const 46))
+        (global.set $len (i32.const 1))))
+    (local.get $best))
+
+  ;; opens a file with the given flags and rights, returning the descriptor or -1 with the
+  ;; error in `$errno`
+  (func $open (param $path i32) (param $oflags i32) (param $rights i64) (param $fdflags i32)
+    (result i32)
+    (local $dir i32)
+    (local.set $dir (call $resolve (call $encode (local.get $path)) (global.get $len)))
+    (if (i32.lt_s (local.get $dir) (i32.const 0)) (then (return (i32.const -1))))
+    (global.set $errno
+      (call $path_open (local.get $dir) (i32.const 1) (global.get $rel) (global.get $len)
+        (local.get $oflags) (local.get $rights) (i64.const 0) (local.get $fdflags) (i32.const 72)))
+    (if (result i32) (global.get $errno)
+      (then (i32.const -1))
+      (else (i32.load (i32.const 72)))))
+
+  ;; a failed file operation as a `Left`, described in the same words as by the interpreter
+  (func $file_error (param $path i32) (result i64)
+    (local $errno i32)
+    (local $reason i32)
+    (local.set $errno (global.get $errno))
+    (local.set $reason
+      (if (result i32) (i32.eq (local.get $errno) (i32.const 44))
+        (then (call $decode (str "no such file or directory")))
+        (else
+          ;; acces, perm and notcapable
+          (if (result i32) (i32.or (i32.eq (local.get $errno) (i32.const 2))
+                (i32.or (i32.eq (local.get $errno) (i32.const 63))
+                  (i32.eq (local.get $errno) (i32.const 76))))
+            (then (call $decode (str "permission denied")))
+            (else
+              (if (result i32) (i32.eq (local.get $errno) (i32.const 31))
+                (then (call $decode (str "is a directory")))
+                (else
+                  (if (result i32) (i32.eq (local.get $errno) (i32.const 54))
+                    (then (call $decode (str "not a directory")))
+                    (else (call $decode (str "input/output error")))))))))))
+    (call $left
+      (i64.extend_i32_u
+        (call $concat (call $concat (local.get $path) (call $decode (str ": ")))
+          (local.get $reason)))))
+
+  ;; reads to the end of a file, returning the decoded string or 0 with the error in `$errno`
+  (func $read_fd (param $fd i32) (result i32)
+    (local $bytes i32)
+    (local $n i32)
+    ;; Note: like `$read`, the bytes go where `$bump` puts the object
+    (local.set $bytes (i32.add (global.get $hp) (i32.const 8)))
+    (block $done
+      (loop $next
+        (call $reserve (i32.add (i32.add (local.get $bytes) (local.get $n)) (i32.const 4096)))
+        (i32.store (i32.const 64) (i32.add (local.get $bytes) (local.get $n)))
+        (i32.store (i32.const 68) (i32.const 4096))
+        (global.set $errno
+          (call $fd_read (local.get $fd) (i32.const 64) (i32.const 1) (i32.const 72)))
+        (br_if $done (global.get $errno))
+        (local.set $n (i32.add (local.get $n) (i32.load (i32.const 72))))
+        (br_if $done (i32.eqz (i32.load (i32.const 72))))
+        (br $next)))
+    (drop (call $bump (local.get $n)))
+    (if (result i32) (global.get $errno)
+      (then (i32.const 0))
+      (else (call $decode (local.get $bytes) (local.get $n)))))
+
+  ;; writes a string to a file opened with the given flags, returning the result as an `Either`
+  (func $write_file (param $path i32) (param $s i32) (param $oflags i32) (param $fdflags i32)
+    (result i64)
+    (local $fd i32)
+    ;; the right fd_write
+    (local.set $fd
+      (call $open (local.get $path) (local.get $oflags) (i64.const 64) (local.get $fdflags)))
+    (if (i32.lt_s (local.get $fd) (i32.const 0))
+      (then (return (call $file_error (local.get $path)))))
+    (global.set $errno
+      (call $try_write (local.get $fd) (call $encode (local.get $s)) (global.get $len)))
+    (drop (call $fd_close (local.get $fd)))
+    (if (result i64) (global.get $errno)
+      (then (call $file_error (local.get $path)))
+      (else (call $right (call $ctor0 (i32.const 6))))))
+
+  ;; inserts a string into a sorted list whose cells no one else holds yet, reusing them
+  (func $insert_sorted (param $s i32) (param $list i64) (result i64)
+    (local $p i32)
+    (local $next i32)
+    (if (i32.or (i32.eq (i32.load (i32.wrap_i64 (local.get $list))) (i32.const 5))
+          (call $str_lt (local.get $s) (i32.load offset=8 (i32.wrap_i64 (local.get $list)))))
+      (then (return (call $pair (i64.extend_i32_u (local.get $s)) (local.get $list)))))
+    (local.set $p (i32.wrap_i64 (local.get $list)))
+    (block $found
+      (loop $walk
+        (local.set $next (i32.wrap_i64 (i64.load offset=16 (local.get $p))))
+        (br_if $found (i32.eq (i32.load (local.get $next)) (i32.const 5)))
+        (br_if $found (call $str_lt (local.get $s) (i32.load offset=8 (local.get $next))))
+        (local.set $p (local.get $next))
+        (br $walk)))
+    (i64.store offset=16 (local.get $p)
+      (call $pair (i64.extend_i32_u (local.get $s)) (i64.load offset=16 (local.get $p))))
+    (local.get $list))
+
+  ;; -------------------------------- stack -------------------------------
+
+  (func $push (param $kind i32) (param $v i64)
+    (if (i32.ge_u (global.get $sp) (global.get $stack_end))
+      (then (call $fail (str "stack overflow"))))
+    (i32.store (global.get $sp) (local.get $kind))
+    (i64.store offset=8 (global.get $sp) (local.get $v))
+    (global.set $sp (i32.add (global.get $sp) (i32.const 16))))
+
+  (func $write_frame (param $kind i32)
+    (if (i32.lt_s (local.get $kind) (i32.const 0))
+      (then (call $write (i32.const 2) (str "an empty stack")))
+      (else
+        (if (i32.eqz (local.get $kind))
+          (then (call $write (i32.const 2) (str "an argument")))
+          (else
+            (if (i32.eq (local.get $kind) (i32.const 1))
+              (then (call $write (i32.const 2) (str "a continuation")))
+              (else (call $write (i32.const 2) (str "a destructor")))))))))
+
+  ;; the kind of the frame on the stack top, or -1 if the stack is empty
+  (func $top (result i32)
+    (if (result i32) (i32.eq (global.get $sp) (global.get $stack_base))
+      (then (i32.const -1))
+      (else (i32.load (i32.sub (global.get $sp) (i32.const 16))))))
+
+  ;; pops a frame of the given kind and returns its payload
+  (func $pop (param $kind i32) (result i64)
+    (if (i32.ne (call $top) (local.get $kind))
+      (then
+        (call $write (i32.const 2) (str "Runtime Error: Expected "))
+        (call $write_frame (local.get $kind))
+        (call $write (i32.const 2) (str " on the stack top, but found "))
+        (call $write_frame (call $top))
+        (call $die)))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 16)))
+    (i64.load offset=8 (global.get $sp)))
+
+  (func $arg (result i64)
+    (call $pop (i32.const 0)))
+
+  (func $dtor (result i32)
+    (i32.wrap_i64 (call $pop (i32.const 2))))
+
+  (func $ret (param $v i64) (result i32)
+    (if (i32.eq (global.get $sp) (global.get $stack_base))
+      (then
+        (call $write (i32.const 2) (str "Error: Program did not exit\n"))
+        (call $proc_exit (i32.const 0))
+        (unreachable)))
+    (global.set $R (local.get $v))
+    (i32.wrap_i64 (call $pop (i32.const 1))))
+
+  (func $prim_arg (param $name i32) (param $len i32) (param $arity i32) (result i64)
+    (if (i32.ne (call $top) (i32.const 0))
+      (then
+        (call $write (i32.const 2) (str "Runtime Error: Builtin "))
+        (call $write (i32.const 2) (local.get $name) (local.get $len))
+        (call $write (i32.const 2) (str " expects "))
+        (call $write (i32.const 2)
+          (call $itoa (i64.extend_i32_u (local.get $arity))) (global.get $len))
+        (call $write (i32.const 2) (str " arguments on the stack"))
+        (call $die)))
+    (call $arg))
+
+  (func $apply (param $k i64) (param $v i64) (result i32)
+    (call $push (i32.const 0) (local.get $v))
+    (i32.wrap_i64 (local.get $k)))
+
+  ;; ------------------------------ builtins ------------------------------
+
+  (func $prim_add (result i32)
+    (local $a i64)
+    (local.set $a (call $prim_arg (str "add") (i32.const 2)))
+    (call $ret (i64.add (local.get $a) (call $prim_arg (str "add") (i32.const 2)))))
+
+  (func $prim_sub (result i32)
+    (local $a i64)
+    (local.set $a (call $prim_arg (str "sub") (i32.const 2)))
+    (call $ret (i64.sub (local.get $a) (call $prim_arg (str "sub") (i32.const 2)))))
+
+  (func $prim_mul (result i32)
+    (local $a i64)
+    (local.set $a (call $prim_arg (str "mul") (i32.const 2)))
+    (call $ret (i64.mul (local.get $a) (call $prim_arg (str "mul") (i32.const 2)))))
+
+  (func $prim_div (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local.set $a (call $prim_arg (str "div") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "div") (i32.const 2)))
+    (if (i64.eqz (local.get $b))
+      (then (call $fail (str "Builtin div failed: division by zero"))))
+    (if (i64.eq (local.get $b) (i64.const -1))
+      (then (return (call $ret (i64.sub (i64.const 0) (local.get $a))))))
+    (call $ret (i64.div_s (local.get $a) (local.get $b))))
+
+  (func $prim_mod (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local.set $a (call $prim_arg (str "mod") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "mod") (i32.const 2)))
+    (if (i64.eqz (local.get $b))
+      (then (call $fail (str "Builtin mod failed: division by zero"))))
+    (call $ret (i64.rem_s (local.get $a) (local.get $b))))
+
+  (func $prim_int_eq (result i32)
+    (local $a i64)
+    (local.set $a (call $prim_arg (str "int_eq") (i32.const 2)))
+    (call $ret (call $bool (i64.eq (local.get $a) (call $prim_arg (str "int_eq") (i32.const 2))))))
+
+  (func $prim_int_lt (result i32)
+    (local $a i64)
+    (local.set $a (call $prim_arg (str "int_lt") (i32.const 2)))
+    (call $ret
+      (call $bool (i64.lt_s (local.get $a) (call $prim_arg (str "int_lt") (i32.const 2))))))
+
+  (func $prim_int_gt (result i32)
+    (local $a i64)
+    (local.set $a (call $prim_arg (str "int_gt") (i32.const 2)))
+    (call $ret
+      (call $bool (i64.gt_s (local.get $a) (call $prim_arg (str "int_gt") (i32.const 2))))))
+
+  ;; returns `+Some(r)` if `ok`, and `+None()` otherwise
+  (func $checked (param $ok i32) (param $r i64) (result i32)
+    (call $ret
+      (if (result i64) (local.get $ok)
+        (then (call $some (local.get $r)))
+        (else (call $ctor0 (i32.const 2))))))
+
+  (func $prim_checked_add (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local $r i64)
+    (local.set $a (call $prim_arg (str "checked_add") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "checked_add") (i32.const 2)))
+    (local.set $r (i64.add (local.get $a) (local.get $b)))
+    ;; overflows if the result has a different sign than both operands
+    (call $checked
+      (i64.ge_s
+        (i64.and
+          (i64.xor (local.get $a) (local.get $r))
+          (i64.xor (local.get $b) (local.get $r)))
+        (i64.const 0))
+      (local.get $r)))
+
+  (func $prim_checked_sub (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local $r i64)
+    (local.set $a (call $prim_arg (str "checked_sub") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "checked_sub") (i32.const 2)))
+    (local.set $r (i64.sub (local.get $a) (local.get $b)))
+    ;; overflows if the operands differ in sign and the result differs from the first
+    (call $checked
+      (i64.ge_s
+        (i64.and
+          (i64.xor (local.get $a) (local.get $b))
+          (i64.xor (local.get $a) (local.get $r)))
+        (i64.const 0))
+      (local.get $r)))
+
+  (func $prim_checked_mul (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local $r i64)
+    (local.set $a (call $prim_arg (str "checked_mul") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "checked_mul") (i32.const 2)))
+    (local.set $r (i64.mul (local.get $a) (local.get $b)))
+    (call $checked
+      (if (result i32) (i64.eqz (local.get $a))
+        (then (i32.const 1))
+        (else
+          (if (result i32) (i64.eq (local.get $a) (i64.const -1))
+            (then (i64.ne (local.get $b) (i64.const 0x8000000000000000)))
+            (else (i64.eq (i64.div_s (local.get $r) (local.get $a)) (local.get $b))))))
+      (local.get $r)))
+
+  ;; whether `div` and `mod` are defined without wrapping
+  (func $divisible (param $a i64) (param $b i64) (result i32)
+    (i32.eqz
+      (i32.or (i64.eqz (local.get $b))
+        (i32.and
+          (i64.eq (local.get $a) (i64.const 0x8000000000000000))
+          (i64.eq (local.get $b) (i64.const -1))))))
+
+  (func $prim_checked_div (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local.set $a (call $prim_arg (str "checked_div") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "checked_div") (i32.const 2)))
+    (if (i32.eqz (call $divisible (local.get $a) (local.get $b)))
+      (then (return (call $checked (i32.const 0) (i64.const 0)))))
+    (call $checked (i32.const 1) (i64.div_s (local.get $a) (local.get $b))))
+
+  (func $prim_checked_mod (result i32)
+    (local $a i64)
+    (local $b i64)
+    (local.set $a (call $prim_arg (str "checked_mod") (i32.const 2)))
+    (local.set $b (call $prim_arg (str "checked_mod") (i32.const 2)))
+    (if (i32.eqz (call $divisible (local.get $a) (local.get $b)))
+      (then (return (call $checked (i32.const 0) (i64.const 0)))))
+    (call $checked (i32.const 1) (i64.rem_s (local.get $a) (local.get $b))))
+
+  (func $prim_int_to_bigint (result i32)
+    (call $ret
+      (i64.extend_i32_u (call $big_of (call $prim_arg (str "int_to_bigint") (i32.const 1))))))
+
+  (func $prim_bigint_to_int (result i32)
+    (call $checked
+      (call $big_to_int (i32.wrap_i64 (call $prim_arg (str "bigint_to_int") (i32.const 1))))
+      (global.get $parsed)))
+
+  (func $prim_bigint_add (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_add") (i32.const 2))))
+    (call $ret
+      (i64.extend_i32_u
+        (call $big_add (local.get $a)
+          (i32.wrap_i64 (call $prim_arg (str "bigint_add") (i32.const 2))) (i32.const 0)))))
+
+  (func $prim_bigint_sub (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_sub") (i32.const 2))))
+    (call $ret
+      (i64.extend_i32_u
+        (call $big_add (local.get $a)
+          (i32.wrap_i64 (call $prim_arg (str "bigint_sub") (i32.const 2))) (i32.const 1)))))
+
+  (func $prim_bigint_mul (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_mul") (i32.const 2))))
+    (call $ret
+      (i64.extend_i32_u
+        (call $big_mul (local.get $a)
+          (i32.wrap_i64 (call $prim_arg (str "bigint_mul") (i32.const 2)))))))
+
+  (func $prim_bigint_div (result i32)
+    (local $a i32)
+    (local $b i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_div") (i32.const 2))))
+    (local.set $b (i32.wrap_i64 (call $prim_arg (str "bigint_div") (i32.const 2))))
+    (if (i32.eqz (i32.load offset=4 (local.get $b)))
+      (then (call $fail (str "Builtin bigint_div failed: division by zero"))))
+    (call $ret (i64.extend_i32_u (call $big_divmod (local.get $a) (local.get $b)))))
+
+  (func $prim_bigint_mod (result i32)
+    (local $a i32)
+    (local $b i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_mod") (i32.const 2))))
+    (local.set $b (i32.wrap_i64 (call $prim_arg (str "bigint_mod") (i32.const 2))))
+    (if (i32.eqz (i32.load offset=4 (local.get $b)))
+      (then (call $fail (str "Builtin bigint_mod failed: division by zero"))))
+    (drop (call $big_divmod (local.get $a) (local.get $b)))
+    (call $ret (i64.extend_i32_u (global.get $big_rem))))
+
+  (func $prim_bigint_eq (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_eq") (i32.const 2))))
+    (call $ret
+      (call $bool
+        (i32.eqz
+          (call $big_cmp (local.get $a)
+            (i32.wrap_i64 (call $prim_arg (str "bigint_eq") (i32.const 2))))))))
+
+  (func $prim_bigint_lt (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_lt") (i32.const 2))))
+    (call $ret
+      (call $bool
+        (i32.lt_s
+          (call $big_cmp (local.get $a)
+            (i32.wrap_i64 (call $prim_arg (str "bigint_lt") (i32.const 2))))
+          (i32.const 0)))))
+
+  (func $prim_bigint_gt (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "bigint_gt") (i32.const 2))))
+    (call $ret
+      (call $bool
+        (i32.gt_s
+          (call $big_cmp (local.get $a)
+            (i32.wrap_i64 (call $prim_arg (str "bigint_gt") (i32.const 2))))
+          (i32.const 0)))))
+
+  (func $prim_float_add (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_add") (i32.const 2))))
+    (call $ret
+      (i64.reinterpret_f64
+        (f64.add (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_add") (i32.const 2)))))))
+
+  (func $prim_float_sub (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_sub") (i32.const 2))))
+    (call $ret
+      (i64.reinterpret_f64
+        (f64.sub (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_sub") (i32.const 2)))))))
+
+  (func $prim_float_mul (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_mul") (i32.const 2))))
+    (call $ret
+      (i64.reinterpret_f64
+        (f64.mul (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_mul") (i32.const 2)))))))
+
+  (func $prim_float_div (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_div") (i32.const 2))))
+    (call $ret
+      (i64.reinterpret_f64
+        (f64.div (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_div") (i32.const 2)))))))
+
+  (func $prim_float_eq (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_eq") (i32.const 2))))
+    (call $ret
+      (call $bool
+        (f64.eq (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_eq") (i32.const 2)))))))
+
+  (func $prim_float_lt (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_lt") (i32.const 2))))
+    (call $ret
+      (call $bool
+        (f64.lt (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_lt") (i32.const 2)))))))
+
+  (func $prim_float_gt (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "float_gt") (i32.const 2))))
+    (call $ret
+      (call $bool
+        (f64.gt (local.get $a)
+          (f64.reinterpret_i64 (call $prim_arg (str "float_gt") (i32.const 2)))))))
+
+  (func $prim_int_to_float (result i32)
+    (call $ret
+      (i64.reinterpret_f64
+        (f64.convert_i64_s (call $prim_arg (str "int_to_float") (i32.const 1))))))
+
+  (func $prim_floor (result i32)
+    (local $a f64)
+    (local.set $a (f64.reinterpret_i64 (call $prim_arg (str "floor") (i32.const 1))))
+    ;; the floor is in range exactly when the float is
+    (if (i32.eqz
+          (i32.and
+            (f64.ge (local.get $a) (f64.const -9223372036854775808))
+            (f64.lt (local.get $a) (f64.const 9223372036854775808))))
+      (then
+        (call $write (i32.const 2)
+          (str "Runtime Error: Builtin floor failed: invalid argument: the floor of "))
+        (call $write_str (i32.const 2) (call $float_to_str (local.get $a)))
+        (call $write (i32.const 2) (str " is not an integer in range"))
+        (call $die)))
+    (call $ret (i64.trunc_f64_s (f64.floor (local.get $a)))))
+
+  (func $prim_str_length (result i32)
+    (call $ret
+      (i64.load32_u (i32.wrap_i64 (call $prim_arg (str "str_length") (i32.const 1))))))
+
+  (func $prim_str_append (result i32)
+    (local $x i32)
+    (local.set $x (i32.wrap_i64 (call $prim_arg (str "str_append") (i32.const 2))))
+    (call $ret
+      (i64.extend_i32_u
+        (call $concat (local.get $x)
+          (i32.wrap_i64 (call $prim_arg (str "str_append") (i32.const 2)))))))
+
+  (func $prim_str_split_once (result i32)
+    (local $s i32)
+    (local $c i64)
+    (local $i i32)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "str_split_once") (i32.const 2))))
+    (local.set $c (call $prim_arg (str "str_split_once") (i32.const 2)))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (i32.load (local.get $s))))
+        (if (i64.eq (i64.extend_i32_u (call $char_at (local.get $s) (local.get $i))) (local.get $c))
+          (then
+            (return
+              (call $ret
+                (call $some
+                  (call $pair
+                    (call $substr (local.get $s) (i32.const 0) (local.get $i))
+                    (call $substr (local.get $s) (i32.add (local.get $i) (i32.const 1))
+                      (i32.load (local.get $s)))))))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $ret (call $ctor0 (i32.const 2))))
+
+  (func $prim_str_split_n (result i32)
+    (local $s i32)
+    (local $n i64)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "str_split_n") (i32.const 2))))
+    (local.set $n (call $prim_arg (str "str_split_n") (i32.const 2)))
+    (if (i64.gt_u (local.get $n) (i64.load32_u (local.get $s)))
+      (then (return (call $ret (call $ctor0 (i32.const 2))))))
+    (call $ret
+      (call $some
+        (call $pair
+          (call $substr (local.get $s) (i32.const 0) (i32.wrap_i64 (local.get $n)))
+          (call $substr (local.get $s) (i32.wrap_i64 (local.get $n)) (i32.load (local.get $s)))))))
+
+  (func $prim_str_eq (result i32)
+    (local $x i32)
+    (local $y i32)
+    (local $i i32)
+    (local.set $x (i32.wrap_i64 (call $prim_arg (str "str_eq") (i32.const 2))))
+    (local.set $y (i32.wrap_i64 (call $prim_arg (str "str_eq") (i32.const 2))))
+    (if (i32.ne (i32.load (local.get $x)) (i32.load (local.get $y)))
+      (then (return (call $ret (call $bool (i32.const 0))))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (i32.load (local.get $x))))
+        (if (i32.ne (call $char_at (local.get $x) (local.get $i))
+              (call $char_at (local.get $y) (local.get $i)))
+          (then (return (call $ret (call $bool (i32.const 0))))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $ret (call $bool (i32.const 1))))
+
+  (func $prim_str_index (result i32)
+    (local $s i32)
+    (local $i i64)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "str_index") (i32.const 2))))
+    (local.set $i (call $prim_arg (str "str_index") (i32.const 2)))
+    (if (i64.ge_u (local.get $i) (i64.load32_u (local.get $s)))
+      (then
+        (call $write (i32.const 2)
+          (str "Runtime Error: Builtin str_index failed: invalid argument: index "))
+        (call $write (i32.const 2) (call $itoa (local.get $i)) (global.get $len))
+        (call $write (i32.const 2) (str " out of bounds"))
+        (call $die)))
+    (call $ret (i64.extend_i32_u (call $char_at (local.get $s) (i32.wrap_i64 (local.get $i))))))
+
+  (func $prim_int_to_str (result i32)
+    (call $ret
+      (i64.extend_i32_u
+        (call $decode
+          (call $itoa (call $prim_arg (str "int_to_str") (i32.const 1)))
+          (global.get $len)))))
+
+  (func $prim_float_to_str (result i32)
+    (call $ret
+      (i64.extend_i32_u
+        (call $float_to_str
+          (f64.reinterpret_i64 (call $prim_arg (str "float_to_str") (i32.const 1)))))))
+
+  (func $prim_char_to_str (result i32)
+    (local $s i32)
+    (local.set $s (call $str (i32.const 1)))
+    (i32.store offset=4 (local.get $s)
+      (i32.wrap_i64 (call $prim_arg (str "char_to_str") (i32.const 1))))
+    (call $ret (i64.extend_i32_u (local.get $s))))
+
+  (func $prim_char_to_int (result i32)
+    (call $ret (i64.and (call $prim_arg (str "char_to_int") (i32.const 1)) (i64.const 0xff))))
+
+  (func $prim_str_to_int (result i32)
+    (local $s i32)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "str_to_int") (i32.const 1))))
+    (if (i32.eqz (call $parse_int (local.get $s)))
+      (then
+        (call $write (i32.const 2)
+          (str "Runtime Error: Builtin str_to_int failed: invalid argument: \""))
+        (call $write_str (i32.const 2) (local.get $s))
+        (call $write (i32.const 2) (str "\" is not an integer"))
+        (call $die)))
+    (call $ret (global.get $parsed)))
+
+  (func $prim_str_hash (result i32)
+    (local $s i32)
+    (local $i i32)
+    (local $h i64)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "str_hash") (i32.const 1))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eq (local.get $i) (i32.load (local.get $s))))
+        (local.set $h
+          (i64.add (i64.mul (local.get $h) (i64.const 31))
+            (i64.extend_i32_u (call $char_at (local.get $s) (local.get $i)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $ret (local.get $h)))
+
+  (func $prim_bigint_to_str (result i32)
+    (call $ret
+      (i64.extend_i32_u
+        (call $big_to_str (i32.wrap_i64 (call $prim_arg (str "bigint_to_str") (i32.const 1)))))))
+
+  (func $prim_str_to_bigint (result i32)
+    (local $s i32)
+    (local $b i32)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "str_to_bigint") (i32.const 1))))
+    (local.set $b (call $parse_big (local.get $s)))
+    (if (i32.eqz (local.get $b))
+      (then
+        (call $write (i32.const 2)
+          (str "Runtime Error: Builtin str_to_bigint failed: invalid argument: \""))
+        (call $write_str (i32.const 2) (local.get $s))
+        (call $write (i32.const 2) (str "\" is not an integer"))
+        (call $die)))
+    (call $ret (i64.extend_i32_u (local.get $b))))
+
+  (func $prim_ref_new (result i32)
+    (local $cell i32)
+    (local.set $cell (call $alloc (i32.const 8)))
+    (i64.store (local.get $cell) (call $prim_arg (str "ref_new") (i32.const 2)))
+    (call $apply (call $prim_arg (str "ref_new") (i32.const 2))
+      (i64.extend_i32_u (local.get $cell))))
+
+  (func $prim_ref_get (result i32)
+    (call $ret (i64.load (i32.wrap_i64 (call $prim_arg (str "ref_get") (i32.const 1))))))
+
+  (func $prim_ref_set (result i32)
+    (local $cell i32)
+    (local.set $cell (i32.wrap_i64 (call $prim_arg (str "ref_set") (i32.const 2))))
+    (i64.store (local.get $cell) (call $prim_arg (str "ref_set") (i32.const 2)))
+    (call $ret (call $ctor0 (i32.const 6))))
+
+  ;; the address of the element `i` of the array `a`, stopping the program if there is none
+  (func $array_index (param $name i32) (param $n i32) (param $a i32) (param $i i64) (result i32)
+    (if (i64.ge_u (local.get $i) (i64.extend_i32_u (i32.load (local.get $a))))
+      (then
+        (call $write (i32.const 2) (str "Runtime Error: Builtin "))
+        (call $write (i32.const 2) (local.get $name) (local.get $n))
+        (call $write (i32.const 2) (str " failed: invalid argument: index "))
+        (call $write (i32.const 2) (call $itoa (local.get $i)) (global.get $len))
+        (call $write (i32.const 2) (str " is out of bounds for an array of length "))
+        (call $write (i32.const 2)
+          (call $itoa (i64.extend_i32_u (i32.load (local.get $a)))) (global.get $len))
+        (call $die)))
+    (i32.add (i32.add (local.get $a) (i32.const 8))
+      (i32.shl (i32.wrap_i64 (local.get $i)) (i32.const 3))))
+
+  (func $array (param $len i32) (result i32)
+    (local $a i32)
+    (if (i32.gt_u (local.get $len) (i32.const 0x0fffffff))
+      (then (call $fail (str "out of memory"))))
+    (local.set $a (call $alloc (i32.add (i32.const 8) (i32.shl (local.get $len) (i32.const 3)))))
+    (i32.store (local.get $a) (local.get $len))
+    (local.get $a))
+
+  (func $prim_array_make (result i32)
+    (local $n i64)
+    (local $v i64)
+    (local $a i32)
+    (local $i i32)
+    (local.set $n (call $prim_arg (str "array_make") (i32.const 2)))
+    (local.set $v (call $prim_arg (str "array_make") (i32.const 2)))
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then
+        (call $write (i32.const 2) (str "Runtime Error: Builtin array_make failed: "))
+        (call $write (i32.const 2) (str "invalid argument: an array cannot have length "))
+        (call $write (i32.const 2) (call $itoa (local.get $n)) (global.get $len))
+        (call $die)))
+    (if (i64.gt_u (local.get $n) (i64.const 0x0fffffff))
+      (then (call $fail (str "out of memory"))))
+    (local.set $a (call $array (i32.wrap_i64 (local.get $n))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eq (local.get $i) (i32.load (local.get $a))))
+        (i64.store offset=8 (i32.add (local.get $a) (i32.shl (local.get $i) (i32.const 3)))
+          (local.get $v))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (call $ret (i64.extend_i32_u (local.get $a))))
+
+  (func $prim_array_length (result i32)
+    (call $ret
+      (i64.load32_u (i32.wrap_i64 (call $prim_arg (str "array_length") (i32.const 1))))))
+
+  (func $prim_array_get (result i32)
+    (local $a i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "array_get") (i32.const 2))))
+    (call $ret (i64.load (call $array_index (str "array_get") (local.get $a)
+      (call $prim_arg (str "array_get") (i32.const 2))))))
+
+  (func $prim_array_set (result i32)
+    (local $a i32)
+    (local $p i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "array_set") (i32.const 3))))
+    (local.set $p (call $array_index (str "array_set") (local.get $a)
+      (call $prim_arg (str "array_set") (i32.const 3))))
+    (i64.store (local.get $p) (call $prim_arg (str "array_set") (i32.const 3)))
+    (call $ret (call $ctor0 (i32.const 6))))
+
+  (func $prim_array_slice (result i32)
+    (local $a i32)
+    (local $from i64)
+    (local $to i64)
+    (local $slice i32)
+    (local.set $a (i32.wrap_i64 (call $prim_arg (str "array_slice") (i32.const 3))))
+    (local.set $from (call $prim_arg (str "array_slice") (i32.const 3)))
+    (local.set $to (call $prim_arg (str "array_slice") (i32.const 3)))
+    ;; unsigned, so that negative indices are out of range too
+    (if (i32.eqz
+          (i32.and
+            (i64.le_u (local.get $to) (i64.extend_i32_u (i32.load (local.get $a))))
+            (i64.le_u (local.get $from) (local.get $to))))
+      (then
+        (call $write (i32.const 2) (str "Runtime Error: Builtin array_slice failed: "))
+        (call $write (i32.const 2) (str "invalid argument: "))
+        (call $write (i32.const 2) (call $itoa (local.get $from)) (global.get $len))
+        (call $write (i32.const 2) (str ".."))
+        (call $write (i32.const 2) (call $itoa (local.get $to)) (global.get $len))
+        (call $write (i32.const 2) (str " is not a slice of an array of length "))
+        (call $write (i32.const 2)
+          (call $itoa (i64.extend_i32_u (i32.load (local.get $a)))) (global.get $len))
+        (call $die)))
+    (local.set $slice (call $array (i32.wrap_i64 (i64.sub (local.get $to) (local.get $from)))))
+    (memory.copy (i32.add (local.get $slice) (i32.const 8))
+      (i32.add (i32.add (local.get $a) (i32.const 8))
+        (i32.shl (i32.wrap_i64 (local.get $from)) (i32.const 3)))
+      (i32.shl (i32.load (local.get $slice)) (i32.const 3)))
+    (call $ret (i64.extend_i32_u (local.get $slice))))
+
+  (func $map_height (param $m i32) (result i32)
+    (if (result i32) (local.get $m)
+      (then (i32.load (local.get $m)))
+      (else (i32.const 0))))
+
+  (func $map_size (param $m i32) (result i32)
+    (if (result i32) (local.get $m)
+      (then (i32.load offset=4 (local.get $m)))
+      (else (i32.const 0))))
+
+  (func $map_node (param $k i64) (param $v i64) (param $l i32) (param $r i32) (result i32)
+    (local $m i32)
+    (local.set $m (call $alloc (i32.const 32)))
+    (i32.store (local.get $m)
+      (i32.add (i32.const 1)
+        (select (call $map_height (local.get $l)) (call $map_height (local.get $r))
+          (i32.gt_u (call $map_height (local.get $l)) (call $map_height (local.get $r))))))
+    (i32.store offset=4 (local.get $m)
+      (i32.add (i32.const 1)
+        (i32.add (call $map_size (local.get $l)) (call $map_size (local.get $r)))))
+    (i64.store offset=8 (local.get $m) (local.get $k))
+    (i64.store offset=16 (local.get $m) (local.get $v))
+    (i32.store offset=24 (local.get $m) (local.get $l))
+    (i32.store offset=28 (local.get $m) (local.get $r))
+    (local.get $m))
+
+  ;; a node for the key and value, rotating once or twice if the heights of the subtrees differ
+  ;; by two
+  (func $map_balance (param $k i64) (param $v i64) (param $l i32) (param $r i32) (result i32)
+    (local $c i32)
+    (if (i32.gt_u (call $map_height (local.get $l))
+          (i32.add (call $map_height (local.get $r)) (i32.const 1)))
+      (then
+        (if (i32.ge_u (call $map_height (i32.load offset=24 (local.get $l)))
+              (call $map_height (i32.load offset=28 (local.get $l))))
+          (then
+            (return
+              (call $map_node (i64.load offset=8 (local.get $l)) (i64.load offset=16 (local.get $l))
+                (i32.load offset=24 (local.get $l))
+                (call $map_node (local.get $k) (local.get $v)
+                  (i32.load offset=28 (local.get $l)) (local.get $r))))))
+        (local.set $c (i32.load offset=28 (local.get $l)))
+        (return
+          (call $map_node (i64.load offset=8 (local.get $c)) (i64.load offset=16 (local.get $c))
+            (call $map_node (i64.load offset=8 (local.get $l)) (i64.load offset=16 (local.get $l))
+              (i32.load offset=24 (local.get $l)) (i32.load offset=24 (local.get $c)))
+            (call $map_node (local.get $k) (local.get $v)
+              (i32.load offset=28 (local.get $c)) (local.get $r))))))
+    (if (i32.gt_u (call $map_height (local.get $r))
+          (i32.add (call $map_height (local.get $l)) (i32.const 1)))
+      (then
+        (if (i32.ge_u (call $map_height (i32.load offset=28 (local.get $r)))
+              (call $map_height (i32.load offset=24 (local.get $r))))
+          (then
+            (return
+              (call $map_node (i64.load offset=8 (local.get $r)) (i64.load offset=16 (local.get $r))
+                (call $map_node (local.get $k) (local.get $v)
+                  (local.get $l) (i32.load offset=24 (local.get $r)))
+                (i32.load offset=28 (local.get $r))))))
+        (local.set $c (i32.load offset=24 (local.get $r)))
+        (return
+          (call $map_node (i64.load offset=8 (local.get $c)) (i64.load offset=16 (local.get $c))
+            (call $map_node (local.get $k) (local.get $v)
+              (local.get $l) (i32.load offset=24 (local.get $c)))
+            (call $map_node (i64.load offset=8 (local.get $r)) (i64.load offset=16 (local.get $r))
+              (i32.load offset=28 (local.get $c)) (i32.load offset=28 (local.get $r)))))))
+    (call $map_node (local.get $k) (local.get $v) (local.get $l) (local.get $r)))
+
+  (func $map_insert (param $k i64) (param $v i64) (param $m i32) (result i32)
+    (local $key i64)
+    (if (i32.eqz (local.get $m))
+      (then (return (call $map_node (local.get $k) (local.get $v) (i32.const 0) (i32.const 0)))))
+    (local.set $key (i64.load offset=8 (local.get $m)))
+    (if (i64.lt_s (local.get $k) (local.get $key))
+      (then
+        (return
+          (call $map_balance (local.get $key) (i64.load offset=16 (local.get $m))
+            (call $map_insert (local.get $k) (local.get $v) (i32.load offset=24 (local.get $m)))
+            (i32.load offset=28 (local.get $m))))))
+    (if (i64.gt_s (local.get $k) (local.get $key))
+      (then
+        (return
+          (call $map_balance (local.get $key) (i64.load offset=16 (local.get $m))
+            (i32.load offset=24 (local.get $m))
+            (call $map_insert (local.get $k) (local.get $v) (i32.load offset=28 (local.get $m)))))))
+    (call $map_node (local.get $k) (local.get $v)
+      (i32.load offset=24 (local.get $m)) (i32.load offset=28 (local.get $m))))
+
+  (func $map_remove (param $k i64) (param $m i32) (result i32)
+    (local $key i64)
+    (local $min i32)
+    (if (i32.eqz (local.get $m)) (then (return (i32.const 0))))
+    (local.set $key (i64.load offset=8 (local.get $m)))
+    (if (i64.lt_s (local.get $k) (local.get $key))
+      (then
+        (return
+          (call $map_balance (local.get $key) (i64.load offset=16 (local.get $m))
+            (call $map_remove (local.get $k) (i32.load offset=24 (local.get $m)))
+            (i32.load offset=28 (local.get $m))))))
+    (if (i64.gt_s (local.get $k) (local.get $key))
+      (then
+        (return
+          (call $map_balance (local.get $key) (i64.load offset=16 (local.get $m))
+            (i32.load offset=24 (local.get $m))
+            (call $map_remove (local.get $k) (i32.load offset=28 (local.get $m)))))))
+    (local.set $min (i32.load offset=28 (local.get $m)))
+    (if (i32.eqz (local.get $min)) (then (return (i32.load offset=24 (local.get $m)))))
+    (block $found
+      (loop $next
+        (br_if $found (i32.eqz (i32.load offset=24 (local.get $min))))
+        (local.set $min (i32.load offset=24 (local.get $min)))
+        (br $next)))
+    (call $map_balance (i64.load offset=8 (local.get $min)) (i64.load offset=16 (local.get $min))
+      (i32.load offset=24 (local.get $m))
+      (call $map_remove (i64.load offset=8 (local.get $min)) (i32.load offset=28 (local.get $m)))))
+
+  ;; the pairs of keys and values in increasing order of the keys, followed by `rest`
+  ;; the node with the key, or 0 if there is none
+  (func $map_find (param $m i32) (param $k i64) (result i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $m)))
+        (br_if $done (i64.eq (local.get $k) (i64.load offset=8 (local.get $m))))
+        (local.set $m
+          (if (result i32) (i64.lt_s (local.get $k) (i64.load offset=8 (local.get $m)))
+            (then (i32.load offset=24 (local.get $m)))
+            (else (i32.load offset=28 (local.get $m)))))
+        (br $next)))
+    (local.get $m))
+
+  (func $map_to_list (param $m i32) (param $rest i64) (result i64)
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $m)))
+        (local.set $rest
+          (call $pair
+            (call $pair (i64.load offset=8 (local.get $m)) (i64.load offset=16 (local.get $m)))
+            (call $map_to_list (i32.load offset=28 (local.get $m)) (local.get $rest))))
+        (local.set $m (i32.load offset=24 (local.get $m)))
+        (br $next)))
+    (local.get $rest))
+
+  (func $prim_intmap_empty (result i32)
+    (call $ret (i64.const 0)))
+
+  (func $prim_intmap_insert (result i32)
+    (local $k i64)
+    (local $v i64)
+    (local.set $k (call $prim_arg (str "intmap_insert") (i32.const 3)))
+    (local.set $v (call $prim_arg (str "intmap_insert") (i32.const 3)))
+    (call $ret
+      (i64.extend_i32_u
+        (call $map_insert (local.get $k) (local.get $v)
+          (i32.wrap_i64 (call $prim_arg (str "intmap_insert") (i32.const 3)))))))
+
+  (func $prim_intmap_lookup (result i32)
+    (local $k i64)
+    (local $m i32)
+    (local.set $k (call $prim_arg (str "intmap_lookup") (i32.const 2)))
+    (local.set $m
+      (call $map_find (i32.wrap_i64 (call $prim_arg (str "intmap_lookup") (i32.const 2)))
+        (local.get $k)))
+    (call $ret
+      (if (result i64) (local.get $m)
+        (then (call $some (i64.load offset=16 (local.get $m))))
+        (else (call $ctor0 (i32.const 2))))))
+
+  (func $prim_intmap_remove (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "intmap_remove") (i32.const 2)))
+    (call $ret
+      (i64.extend_i32_u
+        (call $map_remove (local.get $k)
+          (i32.wrap_i64 (call $prim_arg (str "intmap_remove") (i32.const 2)))))))
+
+  (func $prim_intmap_size (result i32)
+    (call $ret
+      (i64.extend_i32_u
+        (call $map_size (i32.wrap_i64 (call $prim_arg (str "intmap_size") (i32.const 1)))))))
+
+  (func $prim_intmap_to_list (result i32)
+    (call $ret
+      (call $map_to_list (i32.wrap_i64 (call $prim_arg (str "intmap_to_list") (i32.const 1)))
+        (call $ctor0 (i32.const 5)))))
+
+  ;; The builtins of a map over keys the program hashes and compares call the program's functions
+  ;; with a continuation whose code is one of `$map_hashed`, `$map_compared` and `$map_folded`,
+  ;; which follow the code units in `$units` from `$natives` on. A search is continued by a
+  ;; closure over [search][key][value][map] and, once the key is hashed, [hash][index][rest]: the
+  ;; index and the rest of the bucket from the entry compared next. The searches are
+  ;;   0  insert
+  ;;   1  lookup
+  ;;   2  remove
+  ;;   3  member
+
+  ;; pushes the continuation, then returns the function to call, whose arguments the caller
+  ;; pushes after
+  (func $call (param $f i64) (param $k i32) (result i32)
+    (call $push (i32.const 1) (i64.extend_i32_u (local.get $k)))
+    (i32.wrap_i64 (local.get $f)))
+
+  (func $hash_map (param $hash i64) (param $eq i64) (result i32)
+    (local $m i32)
+    (local.set $m (call $new (i32.const 0) (i32.const 4)))
+    (i64.store offset=8 (local.get $m) (local.get $hash))
+    (i64.store offset=16 (local.get $m) (local.get $eq))
+    (i64.store offset=24 (local.get $m) (i64.const 0))
+    (i64.store offset=32 (local.get $m) (i64.const 0))
+    (call $ret (i64.extend_i32_u (local.get $m))))
+
+  ;; the bucket under the hash, which is empty if there is none
+  (func $bucket (param $m i32) (param $h i64) (result i64)
+    (local $b i32)
+    (local.set $b
+      (call $map_find (i32.wrap_i64 (i64.load offset=24 (local.get $m))) (local.get $h)))
+    (if (result i64) (local.get $b)
+      (then (i64.load offset=16 (local.get $b)))
+      (else (call $ctor0 (i32.const 5)))))
+
+  ;; the list without its first `n` cells, which are copied in front of `tail`
+  (func $splice (param $list i64) (param $n i64) (param $tail i64) (result i64)
+    (local $head i64)
+    (local $link i32)
+    (local $c i32)
+    (local $cell i32)
+    (local.set $head (local.get $tail))
+    (local.set $c (i32.wrap_i64 (local.get $list)))
+    (block $done
+      (loop $next
+        (br_if $done (i64.eqz (local.get $n)))
+        (local.set $cell (call $new (i32.const 4) (i32.const 2)))
+        (i64.store offset=8 (local.get $cell) (i64.load offset=8 (local.get $c)))
+        (i64.store offset=16 (local.get $cell) (local.get $tail))
+        (if (local.get $link)
+          (then (i64.store offset=16 (local.get $link) (i64.extend_i32_u (local.get $cell))))
+          (else (local.set $head (i64.extend_i32_u (local.get $cell)))))
+        (local.set $link (local.get $cell))
+        (local.set $c (i32.wrap_i64 (i64.load offset=16 (local.get $c))))
+        (local.set $n (i64.sub (local.get $n) (i64.const 1)))
+        (br $next)))
+    (local.get $head))
+
+  (func $map_search (param $op i64) (param $k i64) (param $v i64) (param $m i64) (result i32)
+    (local $s i32)
+    (local $f i32)
+    (local.set $s (call $new (global.get $natives) (i32.const 4)))
+    (i64.store offset=8 (local.get $s) (local.get $op))
+    (i64.store offset=16 (local.get $s) (local.get $k))
+    (i64.store offset=24 (local.get $s) (local.get $v))
+    (i64.store offset=32 (local.get $s) (local.get $m))
+    (local.set $f
+      (call $call (i64.load offset=8 (i32.wrap_i64 (local.get $m))) (local.get $s)))
+    (call $push (i32.const 0) (local.get $k))
+    (local.get $f))
+
+  ;; compares the key with the entry at the rest of its bucket, if any
+  (func $map_scan (param $s i32) (result i32)
+    (local $rest i32)
+    (local $f i32)
+    (local.set $rest (i32.wrap_i64 (i64.load offset=56 (local.get $s))))
+    (if (i32.eq (i32.load (local.get $rest)) (i32.const 5))
+      (then (return (call $map_found (local.get $s) (i32.const 0)))))
+    (local.set $f
+      (call $call (i64.load offset=16 (i32.wrap_i64 (i64.load offset=32 (local.get $s))))
+        (local.get $s)))
+    (call $push (i32.const 0)
+      (i64.load offset=8 (i32.wrap_i64 (i64.load offset=8 (local.get $rest)))))
+    (call $push (i32.const 0) (i64.load offset=16 (local.get $s)))
+    (local.get $f))
+
+  ;; finishes a search, which found the key at the rest of its bucket if `found`
+  (func $map_found (param $s i32) (param $found i32) (result i32)
+    (local $op i64)
+    (local $m i32)
+    (local $rest i32)
+    (local $h i64)
+    (local $bucket i64)
+    (local $n i32)
+    (local.set $op (i64.load offset=8 (local.get $s)))
+    (local.set $m (i32.wrap_i64 (i64.load offset=32 (local.get $s))))
+    (local.set $rest (i32.wrap_i64 (i64.load offset=56 (local.get $s))))
+    (local.set $h (i64.load offset=40 (local.get $s)))
+    (if (i64.eq (local.get $op) (i64.const 1))
+      (then
+        (return
+          (call $ret
+            (if (result i64) (local.get $found)
+              (then
+                (call $some
+                  (i64.load offset=16 (i32.wrap_i64 (i64.load offset=8 (local.get $rest))))))
+              (else (call $ctor0 (i32.const 2))))))))
+    (if (i64.eq (local.get $op) (i64.const 3))
+      (then (return (call $ret (call $bool (local.get $found))))))
+    (if (i32.and (i64.eq (local.get $op) (i64.const 2)) (i32.eqz (local.get $found)))
+      (then (return (call $ret (i64.extend_i32_u (local.get $m))))))
+    (local.set $bucket (call $bucket (local.get $m) (local.get $h)))
+    (if (local.get $found)
+      (then
+        (local.set $bucket
+          (call $splice (local.get $bucket) (i64.load offset=48 (local.get $s))
+            (i64.load offset=16 (local.get $rest))))))
+    (local.set $n (call $new (i32.const 0) (i32.const 4)))
+    (i64.store offset=8 (local.get $n) (i64.load offset=8 (local.get $m)))
+    (i64.store offset=16 (local.get $n) (i64.load offset=16 (local.get $m)))
+    (if (i64.eqz (local.get $op))
+      (then
+        (local.set $bucket
+          (call $pair
+            (call $pair (i64.load offset=16 (local.get $s)) (i64.load offset=24 (local.get $s)))
+            (local.get $bucket)))
+        (i64.store offset=32 (local.get $n)
+          (i64.add (i64.load offset=32 (local.get $m))
+            (i64.extend_i32_u (i32.eqz (local.get $found))))))
+      (else
+        (i64.store offset=32 (local.get $n)
+          (i64.sub (i64.load offset=32 (local.get $m)) (i64.const 1)))))
+    (i64.store offset=24 (local.get $n)
+      (i64.extend_i32_u
+        (if (result i32) (i32.eq (i32.load (i32.wrap_i64 (local.get $bucket))) (i32.const 5))
+          (then
+            (call $map_remove (local.get $h) (i32.wrap_i64 (i64.load offset=24 (local.get $m)))))
+          (else
+            (call $map_insert (local.get $h) (local.get $bucket)
+              (i32.wrap_i64 (i64.load offset=24 (local.get $m))))))))
+    (call $ret (i64.extend_i32_u (local.get $n))))
+
+  (func $map_hashed (type $unit) (param $self i32) (result i32)
+    (local $s i32)
+    (local.set $s (call $new (i32.add (global.get $natives) (i32.const 1)) (i32.const 7)))
+    (memory.copy (i32.add (local.get $s) (i32.const 8)) (i32.add (local.get $self) (i32.const 8))
+      (i32.const 32))
+    (i64.store offset=40 (local.get $s) (global.get $R))
+    (i64.store offset=48 (local.get $s) (i64.const 0))
+    (i64.store offset=56 (local.get $s)
+      (call $bucket (i32.wrap_i64 (i64.load offset=32 (local.get $s))) (global.get $R)))
+    (call $map_scan (local.get $s)))
+
+  (func $map_compared (type $unit) (param $self i32) (result i32)
+    (local $s i32)
+    (if (i32.eqz (i32.load (i32.wrap_i64 (global.get $R))))
+      (then (return (call $map_found (local.get $self) (i32.const 1)))))
+    (local.set $s (call $new (i32.add (global.get $natives) (i32.const 1)) (i32.const 7)))
+    (memory.copy (i32.add (local.get $s) (i32.const 8)) (i32.add (local.get $self) (i32.const 8))
+      (i32.const 40))
+    (i64.store offset=48 (local.get $s)
+      (i64.add (i64.load offset=48 (local.get $self)) (i64.const 1)))
+    (i64.store offset=56 (local.get $s)
+      (i64.load offset=16 (i32.wrap_i64 (i64.load offset=56 (local.get $self)))))
+    (call $map_scan (local.get $s)))
+
+  ;; Calls the folding function on the next entry, or returns the result if there are none. The
+  ;; function folds a set if `set`, `buckets` are the pairs of a hash and a bucket left, and
+  ;; `entries` are the entries left in the current bucket.
+  (func $map_fold (param $f i64) (param $set i64) (param $buckets i64) (param $entries i64)
+    (param $acc i64) (result i32)
+    (local $cell i32)
+    (local $e i32)
+    (local $k i32)
+    (block $found
+      (loop $next
+        (br_if $found (i32.ne (i32.load (i32.wrap_i64 (local.get $entries))) (i32.const 5)))
+        (local.set $cell (i32.wrap_i64 (local.get $buckets)))
+        (if (i32.eq (i32.load (local.get $cell)) (i32.const 5))
+          (then (return (call $ret (local.get $acc)))))
+        (local.set $entries
+          (i64.load offset=16 (i32.wrap_i64 (i64.load offset=8 (local.get $cell)))))
+        (local.set $buckets (i64.load offset=16 (local.get $cell)))
+        (br $next)))
+    (local.set $cell (i32.wrap_i64 (local.get $entries)))
+    (local.set $e (i32.wrap_i64 (i64.load offset=8 (local.get $cell))))
+    (local.set $k (call $new (i32.add (global.get $natives) (i32.const 2)) (i32.const 4)))
+    (i64.store offset=8 (local.get $k) (local.get $f))
+    (i64.store offset=16 (local.get $k) (local.get $set))
+    (i64.store offset=24 (local.get $k) (local.get $buckets))
+    (i64.store offset=32 (local.get $k) (i64.load offset=16 (local.get $cell)))
+    (drop (call $call (local.get $f) (local.get $k)))
+    (call $push (i32.const 0) (local.get $acc))
+    (if (i64.eqz (local.get $set))
+      (then (call $push (i32.const 0) (i64.load offset=16 (local.get $e)))))
+    (call $push (i32.const 0) (i64.load offset=8 (local.get $e)))
+    (i32.wrap_i64 (local.get $f)))
+
+  (func $map_folded (type $unit) (param $self i32) (result i32)
+    (call $map_fold (i64.load offset=8 (local.get $self)) (i64.load offset=16 (local.get $self))
+      (i64.load offset=24 (local.get $self)) (i64.load offset=32 (local.get $self))
+      (global.get $R)))
+
+  (func $map_fold_from (param $f i64) (param $set i64) (param $init i64) (param $m i64)
+    (result i32)
+    (call $map_fold (local.get $f) (local.get $set)
+      (call $map_to_list (i32.wrap_i64 (i64.load offset=24 (i32.wrap_i64 (local.get $m))))
+        (call $ctor0 (i32.const 5)))
+      (call $ctor0 (i32.const 5)) (local.get $init)))
+
+  (func $prim_map_new (result i32)
+    (local $hash i64)
+    (local.set $hash (call $prim_arg (str "map_new") (i32.const 2)))
+    (call $hash_map (local.get $hash) (call $prim_arg (str "map_new") (i32.const 2))))
+
+  (func $prim_map_insert (result i32)
+    (local $k i64)
+    (local $v i64)
+    (local.set $k (call $prim_arg (str "map_insert") (i32.const 3)))
+    (local.set $v (call $prim_arg (str "map_insert") (i32.const 3)))
+    (call $map_search (i64.const 0) (local.get $k) (local.get $v)
+      (call $prim_arg (str "map_insert") (i32.const 3))))
+
+  (func $prim_map_lookup (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "map_lookup") (i32.const 2)))
+    (call $map_search (i64.const 1) (local.get $k) (i64.const 0)
+      (call $prim_arg (str "map_lookup") (i32.const 2))))
+
+  (func $prim_map_remove (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "map_remove") (i32.const 2)))
+    (call $map_search (i64.const 2) (local.get $k) (i64.const 0)
+      (call $prim_arg (str "map_remove") (i32.const 2))))
+
+  (func $prim_map_fold (result i32)
+    (local $f i64)
+    (local $init i64)
+    (local.set $f (call $prim_arg (str "map_fold") (i32.const 3)))
+    (local.set $init (call $prim_arg (str "map_fold") (i32.const 3)))
+    (call $map_fold_from (local.get $f) (i64.const 0) (local.get $init)
+      (call $prim_arg (str "map_fold") (i32.const 3))))
+
+  (func $prim_map_size (result i32)
+    (call $ret
+      (i64.load offset=32 (i32.wrap_i64 (call $prim_arg (str "map_size") (i32.const 1))))))
+
+  (func $prim_set_new (result i32)
+    (local $hash i64)
+    (local.set $hash (call $prim_arg (str "set_new") (i32.const 2)))
+    (call $hash_map (local.get $hash) (call $prim_arg (str "set_new") (i32.const 2))))
+
+  (func $prim_set_insert (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "set_insert") (i32.const 2)))
+    (call $map_search (i64.const 0) (local.get $k) (call $ctor0 (i32.const 6))
+      (call $prim_arg (str "set_insert") (i32.const 2))))
+
+  (func $prim_set_member (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "set_member") (i32.const 2)))
+    (call $map_search (i64.const 3) (local.get $k) (i64.const 0)
+      (call $prim_arg (str "set_member") (i32.const 2))))
+
+  (func $prim_set_remove (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "set_remove") (i32.const 2)))
+    (call $map_search (i64.const 2) (local.get $k) (i64.const 0)
+      (call $prim_arg (str "set_remove") (i32.const 2))))
+
+  (func $prim_set_fold (result i32)
+    (local $f i64)
+    (local $init i64)
+    (local.set $f (call $prim_arg (str "set_fold") (i32.const 3)))
+    (local.set $init (call $prim_arg (str "set_fold") (i32.const 3)))
+    (call $map_fold_from (local.get $f) (i64.const 1) (local.get $init)
+      (call $prim_arg (str "set_fold") (i32.const 3))))
+
+  (func $prim_set_size (result i32)
+    (call $ret
+      (i64.load offset=32 (i32.wrap_i64 (call $prim_arg (str "set_size") (i32.const 1))))))
+
+  (func $prim_write_str (result i32)
+    (local $s i32)
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "write_str") (i32.const 2))))
+    (call $write_str (i32.const 1) (local.get $s))
+    (i32.wrap_i64 (call $prim_arg (str "write_str") (i32.const 2))))
+
+  (func $prim_read_line (result i32)
+    (call $apply (call $prim_arg (str "read_line") (i32.const 1))
+      (i64.extend_i32_u (call $read_line))))
+
+  (func $prim_read_line_as_int (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "read_line_as_int") (i32.const 1)))
+    (call $apply (local.get $k)
+      (if (result i64) (call $parse_int (call $read_line))
+        (then (call $some (global.get $parsed)))
+        (else (call $ctor0 (i32.const 2))))))
+
+  (func $prim_read_till_eof (result i32)
+    (call $apply (call $prim_arg (str "read_till_eof") (i32.const 1))
+      (i64.extend_i32_u (call $read (i32.const 0)))))
+
+  (func $prim_arg_list (result i32)
+    (local $k i64)
+    (local $argc i32)
+    (local $argv i32)
+    (local $list i64)
+    (local $arg i32)
+    (local $n i32)
+    (local $p i32)
+    (local.set $k (call $prim_arg (str "arg_list") (i32.const 1)))
+    (drop (call $args_sizes_get (i32.const 16) (i32.const 20)))
+    (local.set $argc (i32.load (i32.const 16)))
+    (local.set $argv (call $alloc (i32.shl (local.get $argc) (i32.const 2))))
+    (drop (call $args_get (local.get $argv) (call $alloc (i32.load (i32.const 20)))))
+    (local.set $list (call $ctor0 (i32.const 5)))
+    ;; skip the program name, like the interpreter
+    (block $done
+      (loop $next
+        (br_if $done (i32.le_s (local.get $argc) (i32.const 1)))
+        (local.set $argc (i32.sub (local.get $argc) (i32.const 1)))
+        (local.set $arg
+          (i32.load (i32.add (local.get $argv) (i32.shl (local.get $argc) (i32.const 2)))))
+        (local.set $n (i32.const 0))
+        (block $end
+          (loop $byte
+            (br_if $end (i32.eqz (i32.load8_u (i32.add (local.get $arg) (local.get $n)))))
+            (local.set $n (i32.add (local.get $n) (i32.const 1)))
+            (br $byte)))
+        (local.set $p (call $new (i32.const 4) (i32.const 2)))
+        (i64.store offset=8 (local.get $p)
+          (i64.extend_i32_u (call $decode (local.get $arg) (local.get $n))))
+        (i64.store offset=16 (local.get $p) (local.get $list))
+        (local.set $list (i64.extend_i32_u (local.get $p)))
+        (br $next)))
+    (call $apply (local.get $k) (local.get $list)))
+
+  (func $prim_read_file (result i32)
+    (local $path i32)
+    (local $fd i32)
+    (local $s i32)
+    (local.set $path (i32.wrap_i64 (call $prim_arg (str "read_file") (i32.const 2))))
+    ;; the right fd_read
+    (local.set $fd (call $open (local.get $path) (i32.const 0) (i64.const 2) (i32.const 0)))
+    (if (i32.ge_s (local.get $fd) (i32.const 0))
+      (then
+        (local.set $s (call $read_fd (local.get $fd)))
+        ;; a directory opens without the right fd_read, so reading it is notcapable
+        (if (i32.eq (global.get $errno) (i32.const 76))
+          (then (global.set $errno (i32.const 31))))
+        (drop (call $fd_close (local.get $fd)))))
+    (call $apply (call $prim_arg (str "read_file") (i32.const 2))
+      (if (result i64) (local.get $s)
+        (then (call $right (i64.extend_i32_u (local.get $s))))
+        (else (call $file_error (local.get $path))))))
+
+  (func $prim_write_file (result i32)
+    (local $path i32)
+    (local $s i32)
+    (local.set $path (i32.wrap_i64 (call $prim_arg (str "write_file") (i32.const 3))))
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "write_file") (i32.const 3))))
+    ;; creat and trunc
+    (call $apply (call $prim_arg (str "write_file") (i32.const 3))
+      (call $write_file (local.get $path) (local.get $s) (i32.const 9) (i32.const 0))))
+
+  (func $prim_append_file (result i32)
+    (local $path i32)
+    (local $s i32)
+    (local.set $path (i32.wrap_i64 (call $prim_arg (str "append_file") (i32.const 3))))
+    (local.set $s (i32.wrap_i64 (call $prim_arg (str "append_file") (i32.const 3))))
+    ;; creat, and append to the file
+    (call $apply (call $prim_arg (str "append_file") (i32.const 3))
+      (call $write_file (local.get $path) (local.get $s) (i32.const 1) (i32.const 1))))
+
+  (func $prim_list_dir (result i32)
+    (local $path i32)
+    (local $fd i32)
+    (local $buf i32)
+    (local $used i32)
+    (local $cookie i64)
+    (local $p i32)
+    (local $n i32)
+    (local $name i32)
+    (local $list i64)
+    (local.set $path (i32.wrap_i64 (call $prim_arg (str "list_dir") (i32.const 2))))
+    ;; directory, with the right fd_readdir
+    (local.set $fd (call $open (local.get $path) (i32.const 2) (i64.const 0x4000) (i32.const 0)))
+    (if (i32.lt_s (local.get $fd) (i32.const 0))
+      (then
+        (return
+          (call $apply (call $prim_arg (str "list_dir") (i32.const 2))
+            (call $file_error (local.get $path))))))
+    (local.set $buf (call $alloc (i32.const 4096)))
+    (local.set $list (call $ctor0 (i32.const 5)))
+    (block $done
+      (loop $read
+        (global.set $errno
+          (call $fd_readdir (local.get $fd) (local.get $buf) (i32.const 4096) (local.get $cookie)
+            (i32.const 72)))
+        (br_if $done (global.get $errno))
+        (local.set $used (i32.load (i32.const 72)))
+        ;; an entry is [next: i64][inode: i64][name length: i32][type: i8][unused: i24][name]
+        (local.set $p (local.get $buf))
+        (block $entries
+          (loop $entry
+            (br_if $entries
+              (i32.gt_u (i32.add (local.get $p) (i32.const 24))
+                (i32.add (local.get $buf) (local.get $used))))
+            (local.set $n (i32.load offset=16 (local.get $p)))
+            (br_if $entries
+              (i32.gt_u (i32.add (i32.add (local.get $p) (i32.const 24)) (local.get $n))
+                (i32.add (local.get $buf) (local.get $used))))
+            (local.set $cookie (i64.load (local.get $p)))
+            (local.set $name (call $decode (i32.add (local.get $p) (i32.const 24)) (local.get $n)))
+            ;; skip `.` and `..`
+            (if (i32.eqz
+                  (i32.and (i32.le_u (local.get $n) (i32.const 2))
+                    (i32.and (i32.eq (i32.load8_u offset=24 (local.get $p)) (i32.const 46))
+                      (i32.or (i32.eq (local.get $n) (i32.const 1))
+                        (i32.eq (i32.load8_u offset=25 (local.get $p)) (i32.const 46))))))
+              (then (local.set $list (call $insert_sorted (local.get $name) (local.get $list)))))
+            (local.set $p (i32.add (i32.add (local.get $p) (i32.const 24)) (local.get $n)))
+            (br $entry)))
+        ;; a full buffer may have cut off the entries after the last one read
+        (br_if $read (i32.eq (local.get $used) (i32.const 4096)))))
+    (drop (call $fd_close (local.get $fd)))
+    (call $apply (call $prim_arg (str "list_dir") (i32.const 2))
+      (if (result i64) (global.get $errno)
+        (then (call $file_error (local.get $path)))
+        (else (call $right (local.get $list))))))
+
+  (func $prim_file_exists (result i32)
+    (local $dir i32)
+    (local.set $dir
+      (call $resolve
+        (call $encode (i32.wrap_i64 (call $prim_arg (str "file_exists") (i32.const 2))))
+        (global.get $len)))
+    (call $apply (call $prim_arg (str "file_exists") (i32.const 2))
+      (call $bool
+        (i32.and (i32.ge_s (local.get $dir) (i32.const 0))
+          ;; following symbolic links
+          (i32.eqz
+            (call $path_filestat_get (local.get $dir) (i32.const 1)
+              (global.get $rel) (global.get $len) (i32.const 80)))))))
+
+  (func $prim_get_env (result i32)
+    (local $name i32)
+    (local $bytes i32)
+    (local $n i32)
+    (local $environ i32)
+    (local $count i32)
+    (local $entry i32)
+    (local $i i32)
+    (local $ok i32)
+    (local $end i32)
+    (local.set $name (i32.wrap_i64 (call $prim_arg (str "get_env") (i32.const 2))))
+    (local.set $bytes (call $encode (local.get $name)))
+    (local.set $n (global.get $len))
+    (drop (call $environ_sizes_get (i32.const 64) (i32.const 68)))
+    (local.set $count (i32.load (i32.const 64)))
+    (local.set $environ (call $alloc (i32.shl (local.get $count) (i32.const 2))))
+    (drop (call $environ_get (local.get $environ) (call $alloc (i32.load (i32.const 68)))))
+    ;; each entry is `NAME=value` ending with a NUL
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $count)))
+        (local.set $count (i32.sub (local.get $count) (i32.const 1)))
+        (local.set $entry
+          (i32.load (i32.add (local.get $environ) (i32.shl (local.get $count) (i32.const 2)))))
+        (local.set $ok
+          (i32.eq (i32.load8_u (i32.add (local.get $entry) (local.get $n))) (i32.const 61)))
+        (local.set $i (i32.const 0))
+        (block $differ
+          (loop $byte
+            (br_if $differ
+              (i32.or (i32.eqz (local.get $ok)) (i32.ge_u (local.get $i) (local.get $n))))
+            (local.set $ok
+              (i32.eq (i32.load8_u (i32.add (local.get $entry) (local.get $i)))
+                (i32.load8_u (i32.add (local.get $bytes) (local.get $i)))))
+            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+            (br $byte)))
+        (if (local.get $ok)
+          (then
+            (local.set $entry (i32.add (i32.add (local.get $entry) (local.get $n)) (i32.const 1)))
+            (local.set $end (local.get $entry))
+            (block $found
+              (loop $value
+                (br_if $found (i32.eqz (i32.load8_u (local.get $end))))
+                (local.set $end (i32.add (local.get $end) (i32.const 1)))
+                (br $value)))
+            (return
+              (call $apply (call $prim_arg (str "get_env") (i32.const 2))
+                (call $right
+                  (i64.extend_i32_u
+                    (call $decode (local.get $entry)
+                      (i32.sub (local.get $end) (local.get $entry)))))))))
+        (br $next)))
+    (call $apply (call $prim_arg (str "get_env") (i32.const 2))
+      (call $left
+        (i64.extend_i32_u
+          (call $concat
+            (call $concat (call $decode (str "environment variable ")) (local.get $name))
+            (call $decode (str " is not set")))))))
+
+  (func $prim_write_err (result i32)
+    (call $write_str (i32.const 2)
+      (i32.wrap_i64 (call $prim_arg (str "write_err") (i32.const 2))))
+    (i32.wrap_i64 (call $prim_arg (str "write_err") (i32.const 2))))
+
+  ;; WASI cannot run other programs
+  (func $prim_spawn_process (result i32)
+    (call $fail (str "Builtin spawn_process is not supported by the WebAssembly backend"))
+    (unreachable))
+
+  ;; prompts find their frames on the interpreter's stack, which compiled programs don't have
+  (func $prim_new_prompt (result i32)
+    (call $fail (str "Builtin new_prompt is not supported by the WebAssembly backend"))
+    (unreachable))
+  (func $prim_push_prompt (result i32)
+    (call $fail (str "Builtin push_prompt is not supported by the WebAssembly backend"))
+    (unreachable))
+  (func $prim_take_subcont (result i32)
+    (call $fail (str "Builtin take_subcont is not supported by the WebAssembly backend"))
+    (unreachable))
+  (func $prim_push_subcont (result i32)
+    (call $fail (str "Builtin push_subcont is not supported by the WebAssembly backend"))
+    (unreachable))
+
+  ;; random numbers come from SplitMix64, as in the interpreter
+  (func $random (result i64)
+    (local $z i64)
+    (if (i32.eqz (global.get $seeded))
+      (then
+        (drop (call $random_get (i32.const 24) (i32.const 8)))
+        (global.set $seed (i64.load (i32.const 24)))
+        (global.set $seeded (i32.const 1))))
+    (global.set $seed (i64.add (global.get $seed) (i64.const 0x9e3779b97f4a7c15)))
+    (local.set $z (global.get $seed))
+    (local.set $z
+      (i64.mul
+        (i64.xor (local.get $z) (i64.shr_u (local.get $z) (i64.const 30)))
+        (i64.const 0xbf58476d1ce4e5b9)))
+    (local.set $z
+      (i64.mul
+        (i64.xor (local.get $z) (i64.shr_u (local.get $z) (i64.const 27)))
+        (i64.const 0x94d049bb133111eb)))
+    (i64.xor (local.get $z) (i64.shr_u (local.get $z) (i64.const 31))))
+
+  (func $prim_random_int (result i32)
+    (local $k i64)
+    (local.set $k (call $prim_arg (str "random_int") (i32.const 1)))
+    (call $apply (local.get $k) (call $random)))
+
+  (func $prim_random_range (result i32)
+    (local $lo i64)
+    (local $hi i64)
+    (local.set $lo (call $prim_arg (str "random_range") (i32.const 3)))
+    (local.set $hi (call $prim_arg (str "random_range") (i32.const 3)))
+    (if (i64.ge_s (local.get $lo) (local.get $hi))
+      (then
+        (call $write (i32.const 2)
+          (str "Runtime Error: Builtin random_range failed: invalid argument: the range "))
+        (call $write (i32.const 2) (call $itoa (local.get $lo)) (global.get $len))
+        (call $write (i32.const 2) (str ".."))
+        (call $write (i32.const 2) (call $itoa (local.get $hi)) (global.get $len))
+        (call $write (i32.const 2) (str " is empty"))
+        (call $die)))
+    (call $apply
+      (call $prim_arg (str "random_range") (i32.const 3))
+      (i64.add
+        (local.get $lo)
+        (i64.rem_u (call $random) (i64.sub (local.get $hi) (local.get $lo))))))
+
+  (func $prim_random_seed (result i32)
+    (global.set $seed (call $prim_arg (str "random_seed") (i32.const 2)))
+    (global.set $seeded (i32.const 1))
+    (i32.wrap_i64 (call $prim_arg (str "random_seed") (i32.const 2))))
+
+  ;; the time of a WASI clock in milliseconds
+  (func $millis (param $clock i32) (result i64)
+    (drop (call $clock_time_get (local.get $clock) (i64.const 1000000) (i32.const 24)))
+    (i64.div_u (i64.load (i32.const 24)) (i64.const 1000000)))
+
+  (func $prim_now_millis (result i32)
+    (call $apply (call $prim_arg (str "now_millis") (i32.const 1)) (call $millis (i32.const 0))))
+
+  (func $prim_monotonic_millis (result i32)
+    (call $apply
+      (call $prim_arg (str "monotonic_millis") (i32.const 1))
+      (call $millis (i32.const 1))))
+
+  ;; waits on a single subscription to the monotonic clock, which is at 128 and is followed by
+  ;; the event at 176 and the number of events at 208
+  (func $prim_sleep_millis (result i32)
+    (local $ms i64)
+    (local.set $ms (call $prim_arg (str "sleep_millis") (i32.const 2)))
+    (if (i64.gt_s (local.get $ms) (i64.const 0))
+      (then
+        (memory.fill (i32.const 128) (i32.const 0) (i32.const 48))
+        (i32.store (i32.const 144) (i32.const 1))
+        (i64.store (i32.const 152) (i64.mul (local.get $ms) (i64.const 1000000)))
+        (drop (call $poll_oneoff (i32.const 128) (i32.const 176) (i32.const 1) (i32.const 208)))))
+    (i32.wrap_i64 (call $prim_arg (str "sleep_millis") (i32.const 2))))
+
+  (func $prim_exit (result i32)
+    (call $proc_exit (i32.wrap_i64 (call $prim_arg (str "exit") (i32.const 1))))
+    (unreachable))
+
+  (func $prim_fatal (result i32)
+    (local $msg i32)
+    (local.set $msg (i32.wrap_i64 (call $prim_arg (str "fatal") (i32.const 1))))
+    (call $write (i32.const 2) (str "Runtime Error: Program panicked with message: "))
+    (call $write_str (i32.const 2) (local.get $msg))
+    (call $die)
+    (unreachable))
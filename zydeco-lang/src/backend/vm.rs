@@ -0,0 +1,508 @@
+//! A stack machine for the bytecode in [`super::bytecode`].
+//!
+//! Builtins are the same `Prim` functions the evaluator calls, so their arguments and results
+//! are converted to and from the evaluator's semantic values at the call boundary.
+
+use super::bytecode::*;
+use crate::{
+    dynamics::{
+        err::{Halt, RuntimeError, RuntimeErrorItem},
+        os::{RealOs, VirtualOs},
+        syntax as ds,
+    },
+    library::syntax::{self as ls, *},
+    prelude::*,
+};
+use im::Vector;
+use std::{
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+#[derive(Clone)]
+pub enum Value {
+    Thunk(Rc<Closure>),
+    Ctor(usize, Rc<[Value]>),
+    Literal(Literal),
+    Ref(ds::Loc),
+    Array(ds::Loc),
+    /// entries are converted as for builtins that keep them
+    IntMap(ds::IntMap),
+    /// entries and functions are converted as for builtins that keep them
+    Map(ds::Map),
+    Prompt(ds::Prompt),
+    /// only the evaluator captures one, so the VM merely carries it
+    SubCont(ds::SubCont),
+}
+
+pub struct Closure {
+    pub code: Rc<Code>,
+    pub env: Box<[Value]>,
+}
+
+enum Frame {
+    /// the activation to resume, suspended at the `Do` instruction `at`
+    Kont {
+        closure: Rc<Closure>,
+        locals: Locals,
+        at: usize,
+    },
+    App(Value),
+    Dtor(usize),
+}
+
+/// Local slots, shared between an activation and the `Kont` frames suspending it; a write
+/// copies them only while a frame still holds them.
+type Locals = Rc<[Option<Value>]>;
+
+/// The running code unit along with its local slots.
+struct Activation {
+    closure: Rc<Closure>,
+    pc: usize,
+    locals: Locals,
+}
+
+impl Activation {
+    fn new(closure: Rc<Closure>) -> Self {
+        let locals = vec![None; closure.code.nlocals].into();
+        Activation { closure, pc: 0, locals }
+    }
+    fn set(&mut self, slot: usize, v: Value) {
+        Rc::make_mut(&mut self.locals)[slot] = Some(v);
+    }
+}
+
+pub struct Vm<O: VirtualOs> {
+    /// the streams, files and everything else the builtins reach
+    pub os: O,
+    /// remaining computation steps; `None` means unbounded
+    pub fuel: Option<u64>,
+    /// maximum number of frames on the stack; `None` means unbounded
+    pub max_stack: Option<usize>,
+    compiler: Compiler,
+    globals: Vec<Value>,
+    consts: Vec<Value>,
+    operands: Vec<Value>,
+    frames: Vec<Frame>,
+    /// where the most recent `Force` happened, which builtins are blamed on
+    call_site: (CodeId, usize),
+    /// closures handed to the builtin being called
+    lent: Vec<Rc<Closure>>,
+    heap: ds::Heap,
+    lent_var: TermV,
+}
+
+type Exec = Result<Option<ds::ProgKont>, RuntimeError>;
+
+impl<'rt> Vm<RealOs<'rt>> {
+    pub fn new(
+        input: &'rt mut dyn BufRead, output: &'rt mut dyn Write, args: &'rt [String],
+    ) -> Self {
+        Vm::with_os(RealOs::new(input, output, args))
+    }
+}
+
+impl<O: VirtualOs> Vm<O> {
+    pub fn with_os(os: O) -> Self {
+        Vm {
+            os,
+            fuel: None,
+            max_stack: Some(ds::DEFAULT_MAX_STACK),
+            compiler: Compiler::new(),
+            globals: Vec::new(),
+            consts: Vec::new(),
+            operands: Vec::new(),
+            frames: Vec::new(),
+            call_site: (0, 0),
+            lent: Vec::new(),
+            heap: ds::Heap::default(),
+            lent_var: TermV::new("$lent".to_string(), Span::dummy()),
+        }
+    }
+
+    pub fn run(&mut self, p: ls::Program) -> ds::Program {
+        let code = self.compiler.compile_program(&p);
+        self.sync_consts();
+        let code = self.compiler.codes[code].clone();
+        let mut act = Activation::new(Rc::new(Closure { code, env: Box::new([]) }));
+        let entry = loop {
+            match self.exec(&mut act) {
+                Ok(None) => {}
+                Ok(Some(kont)) => break kont,
+                Err(err) => break ds::ProgKont::Error(err),
+            }
+        };
+        ds::Program { module: ds::Module { name: p.module.name }, entry }
+    }
+
+    fn exec(&mut self, act: &mut Activation) -> Exec {
+        let closure = act.closure.clone();
+        let instr = &closure.code.instrs[act.pc];
+        act.pc += 1;
+        if instr.is_step() {
+            match &mut self.fuel {
+                Some(0) => return Ok(Some(ds::ProgKont::OutOfFuel)),
+                Some(fuel) => *fuel -= 1,
+                None => {}
+            }
+        }
+        match instr {
+            Instr::Var(var) => {
+                let v = match *var {
+                    VarRef::Local(slot) => act.locals[slot].clone().expect("slot is bound"),
+                    VarRef::Capture(i) => closure.env[i].clone(),
+                    VarRef::Global(g) => self.globals[g].clone(),
+                };
+                self.operands.push(v);
+            }
+            Instr::Unbound(var) => {
+                let item = RuntimeErrorItem::UnboundVar { var: var.clone() };
+                Err(self.err_at(item, var.span()))?
+            }
+            Instr::Literal(lit) => self.operands.push(Value::Literal(lit.clone())),
+            Instr::Const(i) => self.operands.push(self.consts[*i].clone()),
+            Instr::Closure { code, captures } => {
+                let env = (captures.iter())
+                    .map(|var| match *var {
+                        VarRef::Local(slot) => act.locals[slot].clone().expect("slot is bound"),
+                        VarRef::Capture(i) => closure.env[i].clone(),
+                        VarRef::Global(g) => self.globals[g].clone(),
+                    })
+                    .collect();
+                let code = self.compiler.codes[*code].clone();
+                self.operands.push(Value::Thunk(Rc::new(Closure { code, env })));
+            }
+            Instr::Ctor { ctor, arity } => {
+                let args = self.operands.split_off(self.operands.len() - arity);
+                self.operands.push(Value::Ctor(*ctor, args.into()));
+            }
+            Instr::Define => {
+                let v = self.pop();
+                self.globals.push(v);
+            }
+            Instr::Let(slot) => {
+                let v = self.pop();
+                act.set(*slot, v);
+            }
+            Instr::Abs(slot) => match self.frames.last() {
+                Some(Frame::App(_)) => {
+                    let Some(Frame::App(arg)) = self.frames.pop() else { unreachable!() };
+                    act.set(*slot, arg);
+                }
+                frame => {
+                    let found = self.found(frame);
+                    Err(self.err(act, RuntimeErrorItem::AppNotAtStacktop { found }))?
+                }
+            },
+            Instr::App => {
+                let arg = self.pop();
+                self.push_frame(act, Frame::App(arg))?;
+            }
+            Instr::Dtor(dtor) => self.push_frame(act, Frame::Dtor(*dtor))?,
+            Instr::Do { .. } => {
+                let (closure, locals) = (act.closure.clone(), act.locals.clone());
+                self.push_frame(act, Frame::Kont { closure, locals, at: act.pc - 1 })?;
+            }
+            Instr::Ret => {
+                let v = self.pop();
+                return self.ret(act, v);
+            }
+            Instr::Force => {
+                let v = self.pop();
+                return self.force(act, v);
+            }
+            Instr::Rec(slot) => {
+                act.set(*slot, Value::Thunk(closure.clone()));
+            }
+            Instr::Match(arms) => {
+                let (ctor, args) = match self.pop() {
+                    Value::Ctor(ctor, args) => (ctor, args),
+                    v => {
+                        let found = self.to_sem(&v).fmt_inline_debug();
+                        Err(self.err(act, RuntimeErrorItem::MatchOnNonCtor { found }))?
+                    }
+                };
+                let Some(arm) = arms.iter().find(|arm| arm.ctor == ctor) else {
+                    let ctorv = self.compiler.ctors.name(ctor).clone();
+                    Err(self.err(act, RuntimeErrorItem::NoMatchingArm { ctorv }))?
+                };
+                for (slot, arg) in arm.slots.iter().zip(args.iter()) {
+                    act.set(*slot, arg.clone());
+                }
+                act.pc = arm.pc;
+            }
+            Instr::Comatch(arms) => {
+                let dtor = match self.frames.last() {
+                    Some(Frame::Dtor(dtor)) => *dtor,
+                    frame => {
+                        let found = self.found(frame);
+                        Err(self.err(act, RuntimeErrorItem::DtorNotAtStacktop { found }))?
+                    }
+                };
+                let Some(arm) = arms.iter().find(|arm| arm.dtor == dtor) else {
+                    let dtorv = self.compiler.dtors.name(dtor).clone();
+                    Err(self.err(act, RuntimeErrorItem::NoMatchingCoarm { dtorv }))?
+                };
+                self.frames.pop();
+                act.pc = arm.pc;
+            }
+            Instr::Effect => {
+                Err(self.err(act, RuntimeErrorItem::NoHandlers { backend: "the bytecode VM" }))?
+            }
+            Instr::Prim(prim) => {
+                let Prim { name, arity, keeps, .. } = *prim;
+                self.lent.clear();
+                let mut args = Vec::new();
+                for _ in 0..arity {
+                    let Some(Frame::App(arg)) = self.frames.last() else {
+                        Err(self.err(act, RuntimeErrorItem::PrimArity { name, arity }))?
+                    };
+                    let arg = arg.clone();
+                    // Note: lent closures are only valid until the next builtin call
+                    args.push(if keeps { self.to_sem(&arg) } else { self.lend(&arg) });
+                    self.frames.pop();
+                }
+                return match prim.call(args, &mut self.os, &mut self.heap) {
+                    Ok(comp) => self.enter(act, comp),
+                    Err(Halt::Exit(exit_code)) => {
+                        self.os.exit(exit_code);
+                        Ok(Some(ds::ProgKont::ExitCode(exit_code)))
+                    }
+                    Err(Halt::Panic(msg)) => Err(self.err(act, RuntimeErrorItem::Panic { msg })),
+                    Err(Halt::Error(error)) => {
+                        Err(self.err(act, RuntimeErrorItem::Builtin { name, error }))
+                    }
+                    Err(Halt::Denied(denied)) => {
+                        Err(self.err(act, RuntimeErrorItem::PermissionDenied { name, denied }))
+                    }
+                    Err(Halt::NoHandlers(backend)) => {
+                        Err(self.err(act, RuntimeErrorItem::NoHandlers { backend }))
+                    }
+                };
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pushes a frame, failing instead if the stack is already at its limit.
+    fn push_frame(&mut self, act: &Activation, frame: Frame) -> Result<(), RuntimeError> {
+        if let Some(max) = self.max_stack {
+            if self.frames.len() >= max {
+                Err(self.err(act, RuntimeErrorItem::StackOverflow { depth: max }))?
+            }
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.operands.pop().expect("operand stack underflow")
+    }
+
+    fn ret(&mut self, act: &mut Activation, v: Value) -> Exec {
+        match self.frames.last() {
+            Some(Frame::Kont { .. }) => {
+                let Some(Frame::Kont { closure, locals, at }) = self.frames.pop() else {
+                    unreachable!()
+                };
+                let Instr::Do { slot, resume, .. } = &closure.code.instrs[at] else {
+                    unreachable!()
+                };
+                let (slot, resume) = (*slot, *resume);
+                *act = Activation { closure, pc: resume, locals };
+                act.set(slot, v);
+                Ok(None)
+            }
+            None => Ok(Some(ds::ProgKont::Ret(self.to_sem(&v)))),
+            frame => {
+                let found = self.found(frame);
+                Err(self.err(act, RuntimeErrorItem::KontNotAtStacktop { found }))
+            }
+        }
+    }
+
+    fn force(&mut self, act: &mut Activation, v: Value) -> Exec {
+        let Value::Thunk(closure) = v else {
+            let found = self.to_sem(&v).fmt_inline_debug();
+            Err(self.err(act, RuntimeErrorItem::ForceOnNonThunk { found }))?
+        };
+        if !act.closure.code.spans[act.pc - 1].is_dummy() {
+            self.call_site = (act.closure.code.id, act.pc - 1);
+        }
+        *act = Activation::new(closure);
+        Ok(None)
+    }
+
+    /// Continues with the computation a builtin returned. The shapes builtins produce are
+    /// handled directly; anything else is compiled first.
+    fn enter(&mut self, act: &mut Activation, comp: SynComp) -> Exec {
+        match &comp {
+            SynComp::Ret(Ret(v)) => {
+                if let Some(v) = self.plain(v) {
+                    return self.ret(act, v);
+                }
+            }
+            SynComp::Force(Force(v)) => {
+                if let Some(v) = self.plain(v) {
+                    return self.force(act, v);
+                }
+            }
+            SynComp::App(App { body, arg }) => {
+                if let Some(arg) = self.plain(arg) {
+                    self.push_frame(act, Frame::App(arg))?;
+                    return self.enter(act, body.inner.clone());
+                }
+            }
+            SynComp::Dtor(Dtor { body, dtorv }) => {
+                let dtor = self.compiler.dtors.intern(dtorv);
+                self.push_frame(act, Frame::Dtor(dtor))?;
+                return self.enter(act, body.inner.clone());
+            }
+            _ => {}
+        }
+        let body = rc!(Span::dummy().make(comp));
+        let code = self.compiler.compile_thunk(&body, &Env::new());
+        self.sync_consts();
+        let code = self.compiler.codes[code].clone();
+        *act = Activation::new(Rc::new(Closure { code, env: Box::new([]) }));
+        Ok(None)
+    }
+
+    /// Converts a value that needs no variables or code, as builtins return them.
+    fn plain(&mut self, v: &SynVal) -> Option<Value> {
+        match v {
+            SynVal::SemValue(sem) => Some(self.from_sem(sem)),
+            SynVal::Literal(lit) => Some(Value::Literal(lit.clone())),
+            SynVal::Ctor(Ctor { ctorv, args }) => {
+                let args = args.iter().map(|arg| self.plain(arg)).collect::<Option<Vec<_>>>()?;
+                Some(Value::Ctor(self.compiler.ctors.intern(ctorv), args.into()))
+            }
+            // Note: builtin results are closed, and the thunks they make are shared so that their
+            // code is compiled only once
+            SynVal::Thunk(Thunk(body)) => {
+                let code = self.compiler.compile_thunk(body, &Env::new());
+                self.sync_consts();
+                let code = self.compiler.codes[code].clone();
+                Some(Value::Thunk(Rc::new(Closure { code, env: Box::new([]) })))
+            }
+            SynVal::Var(_) => None,
+        }
+    }
+
+    fn sync_consts(&mut self) {
+        while self.consts.len() < self.compiler.consts.len() {
+            let sem = self.compiler.consts[self.consts.len()].clone();
+            let v = self.from_sem(&sem);
+            self.consts.push(v);
+        }
+    }
+
+    fn from_sem(&mut self, sem: &ds::SemVal) -> Value {
+        match sem {
+            ds::SemVal::Thunk(ds::Thunk { body, env }) => {
+                if let Some(ds::SemVal::Literal(Literal::Int(i))) = env.lookup(&self.lent_var) {
+                    return Value::Thunk(self.lent[*i as usize].clone());
+                }
+                let code = self.compiler.compile_thunk(body, env);
+                let code = self.compiler.codes[code].clone();
+                let env = (code.captures.iter())
+                    .map(|var| self.from_sem(env.lookup(var).expect("captured variable is bound")))
+                    .collect();
+                self.sync_consts();
+                Value::Thunk(Rc::new(Closure { code, env }))
+            }
+            ds::SemVal::Ctor(Ctor { ctorv, args }) => {
+                let args: Vec<_> = args.iter().map(|arg| self.from_sem(arg)).collect();
+                Value::Ctor(self.compiler.ctors.intern(ctorv), args.into())
+            }
+            ds::SemVal::Literal(lit) => Value::Literal(lit.clone()),
+            ds::SemVal::Ref(loc) => Value::Ref(*loc),
+            ds::SemVal::Array(loc) => Value::Array(*loc),
+            ds::SemVal::IntMap(m) => Value::IntMap(m.clone()),
+            ds::SemVal::Map(m) => Value::Map(m.clone()),
+            ds::SemVal::Prompt(p) => Value::Prompt(*p),
+            ds::SemVal::SubCont(sk) => Value::SubCont(sk.clone()),
+        }
+    }
+
+    /// Converts a value for a builtin. Closures are only tagged so that they come back
+    /// unchanged when the builtin returns them.
+    fn lend(&mut self, v: &Value) -> ds::SemVal {
+        match v {
+            Value::Thunk(closure) => {
+                self.lent.push(closure.clone());
+                let handle = Literal::Int(self.lent.len() as i64 - 1);
+                let env = Env::new().update(self.lent_var.clone(), handle.into());
+                ds::Thunk { body: closure.code.source.clone(), env }.into()
+            }
+            Value::Ctor(ctor, args) => {
+                let args = args.iter().map(|arg| rc!(self.lend(arg))).collect();
+                Ctor { ctorv: self.compiler.ctors.name(*ctor).clone(), args }.into()
+            }
+            Value::Literal(lit) => lit.clone().into(),
+            Value::Ref(loc) => ds::SemVal::Ref(*loc),
+            Value::Array(loc) => ds::SemVal::Array(*loc),
+            Value::IntMap(m) => m.clone().into(),
+            Value::Map(m) => m.clone().into(),
+            Value::Prompt(p) => ds::SemVal::Prompt(*p),
+            Value::SubCont(sk) => ds::SemVal::SubCont(sk.clone()),
+        }
+    }
+
+    /// Converts a value for reporting it, or for a builtin that keeps it.
+    fn to_sem(&self, v: &Value) -> ds::SemVal {
+        match v {
+            Value::Thunk(closure) => {
+                let names = closure.code.captures.iter().cloned();
+                let env = names.zip(closure.env.iter().map(|v| self.to_sem(v))).collect();
+                ds::Thunk { body: closure.code.source.clone(), env }.into()
+            }
+            Value::Ctor(ctor, args) => {
+                let args = args.iter().map(|arg| rc!(self.to_sem(arg))).collect();
+                Ctor { ctorv: self.compiler.ctors.name(*ctor).clone(), args }.into()
+            }
+            Value::Literal(lit) => lit.clone().into(),
+            Value::Ref(loc) => ds::SemVal::Ref(*loc),
+            Value::Array(loc) => ds::SemVal::Array(*loc),
+            Value::IntMap(m) => m.clone().into(),
+            Value::Map(m) => m.clone().into(),
+            Value::Prompt(p) => ds::SemVal::Prompt(*p),
+            Value::SubCont(sk) => ds::SemVal::SubCont(sk.clone()),
+        }
+    }
+
+    fn to_sem_frame(&self, frame: &Frame) -> ds::SemComp {
+        match frame {
+            Frame::Kont { closure, at, .. } => {
+                let Instr::Do { var, body, .. } = &closure.code.instrs[*at] else { unreachable!() };
+                ds::SemComp::Kont(body.clone(), Env::new(), var.clone())
+            }
+            Frame::App(v) => ds::SemComp::App(rc!(self.to_sem(v))),
+            Frame::Dtor(dtor) => ds::SemComp::Dtor(self.compiler.dtors.name(*dtor).clone()),
+        }
+    }
+
+    fn found(&self, frame: Option<&Frame>) -> String {
+        match frame {
+            Some(frame) => self.to_sem_frame(frame).fmt_inline_debug(),
+            None => format!("an empty stack"),
+        }
+    }
+
+    fn err(&self, act: &Activation, item: RuntimeErrorItem) -> RuntimeError {
+        let info = &act.closure.code.spans[act.pc - 1];
+        self.err_at(item, info)
+    }
+
+    fn err_at(&self, item: RuntimeErrorItem, info: &Span) -> RuntimeError {
+        // Note: builtins have no source location, so they are blamed on the call site
+        let info = if info.is_dummy() {
+            let (code, pc) = self.call_site;
+            self.compiler.codes.get(code).map_or(info, |code| &code.spans[pc])
+        } else {
+            info
+        };
+        let stack: Vector<_> = self.frames.iter().map(|frame| self.to_sem_frame(frame)).collect();
+        RuntimeError::new(item, info.clone(), stack)
+    }
+}
@@ -0,0 +1,376 @@
+//! Compiles a linked program into a WebAssembly module that runs under WASI.
+//!
+//! The program is closure-converted by [`super::closure`], and every code unit becomes a function
+//! in a table that `runtime.wat` calls indirectly in a loop. The module is generated as text and
+//! assembled by [`wat`]; the builtins that talk to the OS call `wasi_snapshot_preview1`.
+
+use super::closure::{self as cc, Block, ConvertError, Converter, Stmt, Tail, Val, Var};
+use crate::library::syntax::{self as ls, *};
+use num_bigint::Sign;
+use std::{collections::HashMap, fmt::Write};
+use thiserror::Error;
+
+const RUNTIME: &str = include_str!("runtime.wat");
+
+/// Where the generated data starts; the memory below holds the runtime's buffers.
+const DATA_BASE: u32 = 8192;
+const STACK_SIZE: u32 = 16 << 20;
+const PAGE_SIZE: u32 = 1 << 16;
+/// The continuations `runtime.wat` defines, in the order of their codes from `$natives`.
+const NATIVES: [&str; 3] = ["$map_hashed", "$map_compared", "$map_folded"];
+
+#[derive(Error, Debug)]
+pub enum WasmError {
+    #[error(transparent)]
+    Convert(#[from] ConvertError),
+    #[error("Cannot assemble the generated WebAssembly: {0}")]
+    Assemble(#[from] wat::Error),
+}
+
+pub struct WasmGen<'a> {
+    program: &'a cc::Program,
+    /// the generated data, placed at `DATA_BASE`
+    data: Vec<u8>,
+    /// the objects of constructors without arguments, by tag
+    nullary: HashMap<usize, u32>,
+    /// the unit being emitted, its output and the number of its `i32` temporaries
+    out: String,
+    indent: usize,
+    temps: usize,
+}
+
+impl<'a> WasmGen<'a> {
+    /// Compiles a program into a binary module that runs it from `_start`.
+    pub fn program(p: &ls::Program) -> Result<Vec<u8>, WasmError> {
+        let text = Self::text(p)?;
+        Ok(wat::parse_str(&text)?)
+    }
+
+    /// Compiles a program into a module in the WebAssembly text format.
+    pub fn text(p: &ls::Program) -> Result<String, ConvertError> {
+        let program = Converter::program(p)?;
+        let mut gen = WasmGen {
+            program: &program,
+            data: Vec::new(),
+            nullary: HashMap::new(),
+            out: String::new(),
+            indent: 0,
+            temps: 0,
+        };
+        let mut wat = String::from("(module\n");
+        wat += &gen.runtime();
+        wat +=
+            "\n  ;; ------------------------------- the program ------------------------------\n\n";
+
+        let names = |gen: &mut WasmGen, names: Vec<&str>| {
+            let names: Vec<_> =
+                names.into_iter().map(|name| (gen.bytes(name.as_bytes(), 1), name.len())).collect();
+            let mut table = Vec::new();
+            for (addr, len) in names {
+                table.extend(addr.to_le_bytes());
+                table.extend((len as u32).to_le_bytes());
+            }
+            gen.bytes(&table, 4)
+        };
+        let ctors = (0..program.ctors.len()).map(|i| program.ctors.name(i).name()).collect();
+        let ctors = names(&mut gen, ctors);
+        let dtors = (0..program.dtors.len()).map(|i| program.dtors.name(i).name()).collect();
+        let dtors = names(&mut gen, dtors);
+        writeln!(wat, "  (global $ctor_names i32 (i32.const {}))", ctors).unwrap();
+        writeln!(wat, "  (global $dtor_names i32 (i32.const {}))", dtors).unwrap();
+        let entry = gen.static_closure(0);
+        writeln!(wat, "  (global $entry i32 (i32.const {}))", entry).unwrap();
+        for (g, var) in program.globals.iter().enumerate() {
+            writeln!(wat, "  (global $g{} (mut i64) (i64.const 0)) ;; {}", g, var.name()).unwrap();
+        }
+        wat += "\n  (func $mark_globals\n";
+        for g in 0..program.globals.len() {
+            writeln!(wat, "    (call $mark (global.get $g{}))", g).unwrap();
+        }
+        wat += "  )\n";
+
+        // Note: the runtime's own continuations follow the code units
+        let units = program.units.len();
+        writeln!(wat, "\n  (global $natives i32 (i32.const {}))", units).unwrap();
+        writeln!(wat, "  (table $units {} funcref)", units + NATIVES.len()).unwrap();
+        wat += "  (elem (table $units) (i32.const 0) func";
+        for id in 0..units {
+            write!(wat, " $u{}", id).unwrap();
+        }
+        for native in NATIVES {
+            write!(wat, " {}", native).unwrap();
+        }
+        wat += ")\n";
+        for (id, unit) in program.units.iter().enumerate() {
+            gen.indent = 2;
+            gen.temps = 0;
+            gen.block(&unit.body);
+            let body = std::mem::take(&mut gen.out);
+            writeln!(wat, "\n  (func $u{} (type $unit) (param $self i32) (result i32)", id)
+                .unwrap();
+            for (slot, var) in unit.locals.iter().enumerate() {
+                writeln!(wat, "    (local $l{} i64) ;; {}", slot, var.name()).unwrap();
+            }
+            for temp in 0..gen.temps {
+                writeln!(wat, "    (local $t{} i32)", temp).unwrap();
+            }
+            wat += &body;
+            wat += "  )\n";
+        }
+
+        // Note: the stack and the heap follow the data, and the heap grows on demand
+        let stack_base = align(DATA_BASE + gen.data.len() as u32, 16);
+        let heap_base = stack_base + STACK_SIZE;
+        writeln!(wat, "\n  (global $stack_base i32 (i32.const {}))", stack_base).unwrap();
+        writeln!(wat, "  (global $stack_end i32 (i32.const {}))", heap_base).unwrap();
+        writeln!(wat, "  (global $heap_base i32 (i32.const {}))", heap_base).unwrap();
+        let pages = align(heap_base, PAGE_SIZE) / PAGE_SIZE + 1;
+        writeln!(wat, "  (memory (export \"memory\") {})", pages).unwrap();
+        write!(wat, "  (data (i32.const {}) \"", DATA_BASE).unwrap();
+        for b in &gen.data {
+            write!(wat, "\\{:02x}", b).unwrap();
+        }
+        wat += "\")\n)\n";
+        Ok(wat)
+    }
+
+    /// The runtime, with each `(str "...")` replaced by the address and length of the string.
+    fn runtime(&mut self) -> String {
+        const MARKER: &str = "(str \"";
+        let mut out = String::new();
+        let mut rest = RUNTIME;
+        while let Some(start) = rest.find(MARKER) {
+            out += &rest[..start];
+            rest = &rest[start + MARKER.len()..];
+            let mut s = Vec::new();
+            let mut chars = rest.char_indices();
+            let end = loop {
+                match chars.next().expect("unterminated string in the runtime") {
+                    (i, '"') => break i,
+                    (_, '\\') => match chars.next().unwrap().1 {
+                        'n' => s.push(b'\n'),
+                        c => s.push(c as u8),
+                    },
+                    (_, c) => s.extend(c.to_string().bytes()),
+                }
+            };
+            rest = rest[end + 1..].strip_prefix(')').expect("unterminated string in the runtime");
+            write!(out, "(i32.const {}) (i32.const {})", self.bytes(&s, 1), s.len()).unwrap();
+        }
+        out + rest
+    }
+
+    /// Adds bytes to the data, returning their address.
+    fn bytes(&mut self, bytes: &[u8], alignment: u32) -> u32 {
+        while !(DATA_BASE + self.data.len() as u32).is_multiple_of(alignment) {
+            self.data.push(0);
+        }
+        let addr = DATA_BASE + self.data.len() as u32;
+        self.data.extend_from_slice(bytes);
+        addr
+    }
+
+    /// Adds an object with a header and no fields to the data, returning its address.
+    fn object(&mut self, head: u32) -> u32 {
+        let mut bytes = head.to_le_bytes().to_vec();
+        bytes.extend(0u32.to_le_bytes());
+        self.bytes(&bytes, 8)
+    }
+
+    fn static_closure(&mut self, id: usize) -> u32 {
+        self.object(id as u32)
+    }
+
+    fn line(&mut self, s: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.out += "  ";
+        }
+        self.out += s.as_ref();
+        self.out += "\n";
+    }
+
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("$t{}", self.temps - 1)
+    }
+
+    fn var(&self, var: Var) -> String {
+        match var {
+            Var::Local(slot) => format!("(local.get $l{})", slot),
+            Var::Env(i) => format!("(i64.load offset={} (local.get $self))", 8 + 8 * i),
+            Var::Global(g) => format!("(global.get $g{})", g),
+        }
+    }
+
+    /// An expression for a value as an `i64`.
+    fn value(&mut self, val: &Val) -> String {
+        match val {
+            Val::Var(var) => self.var(*var),
+            Val::Unbound(var) => {
+                let name = var.name();
+                let addr = self.bytes(name.as_bytes(), 1);
+                format!("(call $unbound (i32.const {}) (i32.const {}))", addr, name.len())
+            }
+            Val::Literal(Literal::Int(i)) => format!("(i64.const {})", i),
+            Val::Literal(Literal::Char(c)) => format!("(i64.const {})", *c as u32),
+            Val::Literal(Literal::Float(Float(f))) => format!("(i64.const {})", f.to_bits() as i64),
+            Val::Literal(Literal::String(s)) => {
+                let mut bytes = (s.len() as u32).to_le_bytes().to_vec();
+                for c in s {
+                    bytes.extend((*c as u32).to_le_bytes());
+                }
+                format!("(i64.const {})", self.bytes(&bytes, 8))
+            }
+            Val::Literal(Literal::BigInt(i)) => {
+                let (sign, limbs) = i.to_u32_digits();
+                let mut bytes = ((sign == Sign::Minus) as u32).to_le_bytes().to_vec();
+                bytes.extend((limbs.len() as u32).to_le_bytes());
+                for limb in limbs {
+                    bytes.extend(limb.to_le_bytes());
+                }
+                format!("(i64.const {})", self.bytes(&bytes, 8))
+            }
+            Val::Ctor(tag, args) if args.is_empty() => {
+                let addr = match self.nullary.get(tag) {
+                    Some(addr) => *addr,
+                    None => {
+                        let addr = self.object(*tag as u32);
+                        self.nullary.insert(*tag, addr);
+                        addr
+                    }
+                };
+                format!("(i64.const {})", addr)
+            }
+            Val::Ctor(tag, args) => {
+                let args: Vec<_> = args.iter().map(|arg| self.value(arg)).collect();
+                self.new(*tag, args)
+            }
+            Val::Closure(id, env) if env.is_empty() => {
+                format!("(i64.const {})", self.static_closure(*id))
+            }
+            Val::Closure(id, env) => {
+                let env: Vec<_> = env.iter().map(|var| self.var(*var)).collect();
+                self.new(*id, env)
+            }
+            Val::Current => "(i64.extend_i32_u (local.get $self))".to_string(),
+            Val::Resumed => "(global.get $R)".to_string(),
+        }
+    }
+
+    /// An expression that allocates a closure or a constructor with the given fields.
+    fn new(&mut self, head: usize, fields: Vec<String>) -> String {
+        let t = self.temp();
+        let mut e = format!(
+            "(block (result i64) (local.set {} (call $new (i32.const {}) (i32.const {})))",
+            t,
+            head,
+            fields.len()
+        );
+        for (i, field) in fields.iter().enumerate() {
+            write!(e, " (i64.store offset={} (local.get {}) {})", 8 + 8 * i, t, field).unwrap();
+        }
+        write!(e, " (i64.extend_i32_u (local.get {})))", t).unwrap();
+        e
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Define(g, val) => {
+                    let val = self.value(val);
+                    self.line(format!("(global.set $g{} {})", g, val));
+                }
+                Stmt::Let(slot, val) => {
+                    let val = self.value(val);
+                    self.line(format!("(local.set $l{} {})", slot, val));
+                }
+                Stmt::Arg(slot) => self.line(format!("(local.set $l{} (call $arg))", slot)),
+                Stmt::PushArg(val) => {
+                    let val = self.value(val);
+                    self.line(format!("(call $push (i32.const 0) {})", val));
+                }
+                Stmt::PushKont(kont) => {
+                    let kont = self.value(kont);
+                    self.line(format!("(call $push (i32.const 1) {})", kont));
+                }
+                Stmt::PushDtor(dtor) => {
+                    let name = self.program.dtors.name(*dtor);
+                    self.line(format!(
+                        "(call $push (i32.const 2) (i64.const {})) ;; .{}",
+                        dtor, name
+                    ));
+                }
+            }
+        }
+        match &block.tail {
+            Tail::Ret(val) => {
+                let val = self.value(val);
+                self.line(format!("(return (call $ret {}))", val));
+            }
+            Tail::Force(val) => {
+                let val = self.value(val);
+                self.line(format!("(return (i32.wrap_i64 {}))", val));
+            }
+            Tail::Prim(Prim { name, .. }) => self.line(format!("(return (call $prim_{}))", name)),
+            Tail::Match(scrut, arms) => {
+                let scrut = self.value(scrut);
+                let t = self.temp();
+                self.line(format!("(local.set {} (i32.wrap_i64 {}))", t, scrut));
+                let tags: Vec<_> = arms.iter().map(|arm| arm.tag).collect();
+                self.branch(&tags, &format!("(i32.load (local.get {}))", t), |gen, i| {
+                    let cc::Arm { tag, binds, body } = &arms[i];
+                    gen.line(format!(";; {}", gen.program.ctors.name(*tag)));
+                    for (i, slot) in binds.iter().enumerate() {
+                        gen.line(format!(
+                            "(local.set $l{} (i64.load offset={} (local.get {})))",
+                            slot,
+                            8 + 8 * i,
+                            t
+                        ));
+                    }
+                    gen.block(body);
+                });
+                self.line(format!("(return (call $no_arm (i32.load (local.get {}))))", t));
+            }
+            Tail::Comatch(arms) => {
+                let t = self.temp();
+                self.line(format!("(local.set {} (call $dtor))", t));
+                let dtors: Vec<_> = arms.iter().map(|arm| arm.dtor).collect();
+                self.branch(&dtors, &format!("(local.get {})", t), |gen, i| {
+                    let cc::Coarm { dtor, body } = &arms[i];
+                    gen.line(format!(";; .{}", gen.program.dtors.name(*dtor)));
+                    gen.block(body);
+                });
+                self.line(format!("(return (call $no_coarm (local.get {})))", t));
+            }
+        }
+    }
+
+    /// Branches on an index to the first arm with it, falling through when no arm has it.
+    fn branch(&mut self, keys: &[usize], index: &str, mut arm: impl FnMut(&mut Self, usize)) {
+        // Note: arm `i` follows the end of the block at depth `i` from the `br_table`
+        let mut targets = vec![keys.len(); keys.iter().max().map_or(0, |max| max + 1)];
+        for (i, key) in keys.iter().enumerate().rev() {
+            targets[*key] = i;
+        }
+        self.line("(block");
+        self.indent += 1;
+        for _ in keys {
+            self.line("(block");
+            self.indent += 1;
+        }
+        let targets: Vec<_> = targets.iter().map(|target| target.to_string()).collect();
+        self.line(format!("(br_table {} {} {})", targets.join(" "), keys.len(), index));
+        for i in 0..keys.len() {
+            self.indent -= 1;
+            self.line(")");
+            arm(self, i);
+        }
+        self.indent -= 1;
+        self.line(")");
+    }
+}
+
+fn align(n: u32, alignment: u32) -> u32 {
+    n.div_ceil(alignment) * alignment
+}
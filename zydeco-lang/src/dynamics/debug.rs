@@ -0,0 +1,163 @@
+use super::{
+    err::RuntimeError,
+    eval::{Eval, Step},
+    os::VirtualOs,
+    syntax::*,
+};
+use crate::{prelude::*, syntax::TermV};
+use std::{collections::HashSet, fmt};
+
+/// Where the debugger should pause.
+#[derive(Clone, Debug)]
+pub enum Breakpoint {
+    /// entering the body of a top-level `def`
+    Def(String),
+    /// reaching a term that starts on `line`, optionally only in files whose path ends with `file`
+    Line { file: Option<String>, line: usize },
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Def(name) => write!(f, "def {}", name),
+            Breakpoint::Line { file: Some(file), line } => write!(f, "{}:{}", file, line),
+            Breakpoint::Line { file: None, line } => write!(f, "line {}", line),
+        }
+    }
+}
+
+/// Why the debugger handed control back.
+pub enum Pause {
+    /// a single step was taken
+    Stepped,
+    /// the breakpoint with this index was hit
+    Breakpoint(usize),
+    /// the program finished; the outcome stays available in `Debugger::outcome`
+    Finished,
+}
+
+/// Drives the small-step evaluator one computation at a time.
+pub struct Debugger<O: VirtualOs> {
+    pub runtime: Runtime<O>,
+    /// the computation about to be stepped; `None` once the program has finished
+    pub current: Option<Sp<ls::SynComp>>,
+    pub outcome: Option<ProgKont>,
+    pub breakpoints: Vec<Breakpoint>,
+    defs: Vec<(TermV, Span)>,
+    globals: HashSet<TermV>,
+    last: Span,
+}
+
+impl<O: VirtualOs> Debugger<O> {
+    /// Evaluates the module definitions and stops right before the entry computation.
+    pub fn new(p: ls::Program, mut runtime: Runtime<O>) -> Result<Self, RuntimeError> {
+        let defs = p
+            .module
+            .define
+            .iter()
+            .filter_map(|(x, v)| match v {
+                ls::SynVal::Thunk(ls::Thunk(body)) if !body.info.is_dummy() => {
+                    Some((x.clone(), body.info.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let globals = p.module.define.iter().map(|(x, _)| x.clone()).collect();
+        p.module.eval(&mut runtime)?;
+        Ok(Debugger {
+            runtime,
+            current: Some(p.entry),
+            outcome: None,
+            breakpoints: Vec::new(),
+            defs,
+            globals,
+            last: Span::dummy(),
+        })
+    }
+
+    /// Takes exactly one evaluation step.
+    pub fn step(&mut self) -> Pause {
+        let Some(comp) = self.current.take() else { return Pause::Finished };
+        self.last = comp.info.clone();
+        match comp.step(&mut self.runtime) {
+            Step::Step(next) => {
+                self.current = Some(next);
+                Pause::Stepped
+            }
+            Step::Done(kont) => {
+                self.outcome = Some(kont);
+                Pause::Finished
+            }
+            Step::Err(err) => {
+                self.outcome = Some(ProgKont::Error(err));
+                Pause::Finished
+            }
+        }
+    }
+
+    /// Steps over a `do`: runs its bound computation to completion and pauses where the
+    /// continuation resumes. Any other computation is stepped once.
+    pub fn step_over(&mut self) -> Pause {
+        let depth = self.runtime.stack.len();
+        let is_do = matches!(self.current.as_ref().map(|c| &c.inner), Some(ls::SynComp::Do(_)));
+        let pause = self.step();
+        if !is_do {
+            return pause;
+        }
+        self.run_while(|runtime| runtime.stack.len() > depth)
+    }
+
+    /// Runs until a breakpoint is hit or the program finishes.
+    pub fn resume(&mut self) -> Pause {
+        self.run_while(|_| true)
+    }
+
+    fn run_while(&mut self, cond: impl Fn(&Runtime<O>) -> bool) -> Pause {
+        while cond(&self.runtime) {
+            if let Pause::Finished = self.step() {
+                return Pause::Finished;
+            }
+            if let Some(i) = self.hit() {
+                return Pause::Breakpoint(i);
+            }
+        }
+        Pause::Stepped
+    }
+
+    /// The breakpoint matching the current computation, if any. A breakpoint fires only when
+    /// the computation moves onto its span or line, not on every step that stays there.
+    pub fn hit(&self) -> Option<usize> {
+        let comp = self.current.as_ref()?;
+        let info = &comp.info;
+        if info.is_dummy() || info == &self.last {
+            return None;
+        }
+        self.breakpoints.iter().position(|bp| match bp {
+            Breakpoint::Def(name) => {
+                self.defs.iter().any(|(x, span)| x.name() == name && span == info)
+            }
+            Breakpoint::Line { file, line } => {
+                let start = |span: &Span| span.get_span2().map(|(start, _)| start.line);
+                let in_file = match (file, info.get_path()) {
+                    (Some(file), Some(path)) => path.ends_with(file),
+                    (Some(_), None) => false,
+                    (None, _) => true,
+                };
+                in_file && start(info) == Some(*line) && start(&self.last) != Some(*line)
+            }
+        })
+    }
+
+    /// Whether `name` is a top-level `def` whose body the debugger can break on.
+    pub fn has_def(&self, name: &str) -> bool {
+        self.defs.iter().any(|(x, _)| x.name() == name)
+    }
+
+    /// The bindings in the current environment that are not top-level definitions.
+    pub fn locals(&self) -> Env<TermV, SemVal> {
+        (self.runtime.env.iter())
+            .filter(|(x, _)| !self.globals.contains(*x))
+            .map(|(x, v)| (x.clone(), v.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,157 @@
+use super::{permission::Denied, syntax::*};
+use crate::{
+    prelude::*,
+    syntax::{CtorV, DtorV, OpV, TermV},
+};
+use im::Vector;
+use std::fmt;
+use thiserror::Error;
+
+/// A runtime failure, boxed so that the evaluator's results stay small.
+#[derive(Clone, Debug)]
+pub struct RuntimeError(Box<RuntimeErrorBody>);
+
+#[derive(Clone, Debug)]
+pub struct RuntimeErrorBody {
+    pub item: RuntimeErrorItem,
+    pub info: Span,
+    /// snapshot of the continuation stack when the error is raised
+    pub stack: Vector<SemComp>,
+}
+
+impl RuntimeError {
+    pub fn new(item: RuntimeErrorItem, info: Span, stack: Vector<SemComp>) -> Self {
+        RuntimeError(Box::new(RuntimeErrorBody { item, info, stack }))
+    }
+}
+
+impl std::ops::Deref for RuntimeError {
+    type Target = RuntimeErrorBody;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.item)?;
+        if !self.info.is_dummy() {
+            writeln!(f, "\t({})", self.info)?;
+        }
+        if self.stack.iter().any(|frame| matches!(frame, SemComp::Kont(..))) {
+            writeln!(f)?;
+            writeln!(f, "Backtrace:")?;
+            write!(f, "{}", Backtrace(&self.stack))?;
+        }
+        Ok(())
+    }
+}
+
+/// The pending `do` continuations on a stack, innermost first.
+pub struct Backtrace<'a>(pub &'a Vector<SemComp>);
+
+impl Backtrace<'_> {
+    /// How many continuations are printed before the rest are elided.
+    const LIMIT: usize = 32;
+}
+
+impl fmt::Display for Backtrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut total = 0;
+        for frame in self.0.iter().rev() {
+            if let SemComp::Kont(_, _, var) = frame {
+                total += 1;
+                if total > Self::LIMIT {
+                    continue;
+                }
+                let info = var.span();
+                if info.is_dummy() {
+                    writeln!(f, "- do {} <- ... (<internal>)", var)?;
+                } else {
+                    writeln!(f, "- do {} <- ... ({})", var, info)?;
+                }
+            }
+        }
+        if total > Self::LIMIT {
+            writeln!(f, "- ... and {} more", total - Self::LIMIT)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Clone, Debug)]
+pub enum RuntimeErrorItem {
+    #[error("Variable {var} is not bound")]
+    UnboundVar { var: TermV },
+    #[error("Expected an argument on the stack top, but found {found}")]
+    AppNotAtStacktop { found: String },
+    #[error("Expected a continuation on the stack top, but found {found}")]
+    KontNotAtStacktop { found: String },
+    #[error("Expected a destructor on the stack top, but found {found}")]
+    DtorNotAtStacktop { found: String },
+    #[error("Forcing {found}, which is not a thunk")]
+    ForceOnNonThunk { found: String },
+    #[error("Matching on {found}, which is not a constructor")]
+    MatchOnNonCtor { found: String },
+    #[error("No arm matches the constructor {ctorv}")]
+    NoMatchingArm { ctorv: CtorV },
+    #[error("No arm matches the destructor .{dtorv}")]
+    NoMatchingCoarm { dtorv: DtorV },
+    #[error("Builtin {name} expects {arity} arguments on the stack")]
+    PrimArity { name: &'static str, arity: u64 },
+    #[error("No handler for the operation {opv}")]
+    UnhandledOperation { opv: OpV },
+    #[error("Effect handlers and delimited continuations are not supported by {backend}")]
+    NoHandlers { backend: &'static str },
+    #[error("The prompt <prompt {prompt}> is not on the stack")]
+    MissingPrompt { prompt: usize },
+    #[error("Stack overflow: more than {depth} frames on the stack")]
+    StackOverflow { depth: usize },
+    #[error("Program panicked with message: {msg}")]
+    Panic { msg: String },
+    #[error("Builtin {name} failed: {error}")]
+    Builtin { name: &'static str, error: BuiltinError },
+    #[error("Builtin {name} was denied: {denied}")]
+    PermissionDenied { name: &'static str, denied: Denied },
+}
+
+#[derive(Error, Clone, Debug)]
+pub enum BuiltinError {
+    #[error("ill-typed arguments")]
+    IllTyped,
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("io error: {0}")]
+    Io(String),
+}
+
+/// The ways a builtin can stop the evaluator instead of returning a computation.
+#[derive(Clone, Debug)]
+pub enum Halt {
+    Exit(i32),
+    Panic(String),
+    Error(BuiltinError),
+    Denied(Denied),
+    /// the backend named here cannot run effect handlers or delimited continuations
+    NoHandlers(&'static str),
+}
+
+impl From<BuiltinError> for Halt {
+    fn from(e: BuiltinError) -> Self {
+        Halt::Error(e)
+    }
+}
+
+impl From<Denied> for Halt {
+    fn from(denied: Denied) -> Self {
+        Halt::Denied(denied)
+    }
+}
+
+impl From<std::io::Error> for Halt {
+    fn from(e: std::io::Error) -> Self {
+        Halt::Error(BuiltinError::Io(e.to_string()))
+    }
+}
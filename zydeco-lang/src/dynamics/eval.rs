@@ -1,49 +1,115 @@
-use super::syntax::{Thunk as SemThunk, *};
+use super::{
+    err::{Halt, RuntimeError, RuntimeErrorItem},
+    os::{RealOs, VirtualOs},
+    syntax::{Thunk as SemThunk, *},
+};
 use crate::prelude::*;
 use im::Vector;
 use std::io::{BufRead, Write};
 
-pub trait Eval<'rt>: Sized + FmtArgs {
+pub trait Eval: Sized + FmtArgs {
     type Out;
-    fn step<'e>(self, runtime: &'e mut Runtime<'rt>) -> Step<Self, Self::Out>;
-    fn eval<'e>(self, runtime: &'e mut Runtime<'rt>) -> Self::Out {
+    fn step<O: VirtualOs>(self, runtime: &mut Runtime<O>) -> Step<Self, Self::Out>;
+    fn eval<O: VirtualOs>(self, runtime: &mut Runtime<O>) -> Result<Self::Out, RuntimeError> {
         let mut res = self;
         loop {
             match res.step(runtime) {
-                Step::Done(out) => break out,
+                Step::Done(out) => break Ok(out),
                 Step::Step(next) => res = next,
+                Step::Err(err) => break Err(err),
             }
         }
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum Step<T, Out> {
     Done(Out),
     Step(T),
+    Err(RuntimeError),
 }
 
-impl<'rt> Runtime<'rt> {
+impl<T, Out> From<Result<Step<T, Out>, RuntimeError>> for Step<T, Out> {
+    fn from(res: Result<Step<T, Out>, RuntimeError>) -> Self {
+        res.unwrap_or_else(Step::Err)
+    }
+}
+
+impl<'rt> Runtime<RealOs<'rt>> {
     pub fn new(
         input: &'rt mut dyn BufRead, output: &'rt mut dyn Write, args: &'rt [String],
     ) -> Self {
-        Runtime { input, output, args, stack: Vector::new(), env: Env::new() }
+        Runtime::with_os(RealOs::new(input, output, args))
+    }
+}
+
+impl<O: VirtualOs> Runtime<O> {
+    pub fn with_os(os: O) -> Self {
+        Runtime {
+            os,
+            stack: Vector::new(),
+            env: Env::new(),
+            heap: Heap::default(),
+            fuel: None,
+            max_stack: Some(DEFAULT_MAX_STACK),
+            profile: None,
+        }
+    }
+    pub fn err(&self, item: RuntimeErrorItem, info: &Span) -> RuntimeError {
+        RuntimeError::new(item, info.clone(), self.stack.clone())
+    }
+    /// Pushes a frame, failing instead if the stack is already at its limit.
+    fn push(&mut self, frame: SemComp, info: &Span) -> Result<(), RuntimeError> {
+        if let Some(max) = self.max_stack {
+            if self.stack.len() >= max {
+                Err(self.err(RuntimeErrorItem::StackOverflow { depth: max }, info))?
+            }
+        }
+        self.stack.push_back(frame);
+        Ok(())
+    }
+    fn append(&mut self, frames: Vector<SemComp>, info: &Span) -> Result<(), RuntimeError> {
+        if let Some(max) = self.max_stack {
+            if self.stack.len() + frames.len() > max {
+                Err(self.err(RuntimeErrorItem::StackOverflow { depth: max }, info))?
+            }
+        }
+        self.stack.append(frames);
+        Ok(())
     }
 }
 
-impl<'rt> Eval<'rt> for ls::SynVal {
+fn found(frame: Option<&SemComp>) -> String {
+    match frame {
+        Some(frame) => frame.fmt_inline_debug(),
+        None => format!("an empty stack"),
+    }
+}
+
+impl Eval for ls::SynVal {
     type Out = SemVal;
 
-    fn step<'e>(self, runtime: &'e mut Runtime<'rt>) -> Step<Self, Self::Out> {
+    fn step<O: VirtualOs>(self, runtime: &mut Runtime<O>) -> Step<Self, Self::Out> {
         match self {
-            ls::SynVal::Var(var) => {
-                Step::Done(runtime.env.lookup(&var).expect("variable does not exist").clone())
-            }
+            ls::SynVal::Var(var) => match runtime.env.lookup(&var) {
+                Some(v) => Step::Done(v.clone()),
+                None => {
+                    let info = var.span().clone();
+                    Step::Err(runtime.err(RuntimeErrorItem::UnboundVar { var }, &info))
+                }
+            },
             ls::SynVal::Thunk(ls::Thunk(body)) => {
                 Step::Done(super::syntax::Thunk { body, env: runtime.env.clone() }.into())
             }
             ls::SynVal::Ctor(ls::Ctor { ctorv: ctor, args }) => {
-                let args = args.iter().map(|arg| rc!(arg.as_ref().clone().eval(runtime))).collect();
-                Step::Done(ls::Ctor { ctorv: ctor, args }.into())
+                let mut sem_args = Vec::new();
+                for arg in args.iter() {
+                    match arg.as_ref().clone().eval(runtime) {
+                        Ok(arg) => sem_args.push(rc!(arg)),
+                        Err(err) => return Step::Err(err),
+                    }
+                }
+                Step::Done(ls::Ctor { ctorv: ctor, args: sem_args }.into())
             }
             ls::SynVal::Literal(lit) => Step::Done(lit.into()),
             ls::SynVal::SemValue(sem) => Step::Done(sem),
@@ -51,109 +117,260 @@ impl<'rt> Eval<'rt> for ls::SynVal {
     }
 }
 
-impl<'rt> Eval<'rt> for ls::SynComp {
+impl Eval for Sp<ls::SynComp> {
     type Out = ProgKont;
 
-    fn step<'e>(self, runtime: &'e mut Runtime<'rt>) -> Step<Self, Self::Out> {
-        match self {
-            ls::SynComp::Abs(ls::Abs { param, body }) => match runtime.stack.pop_back() {
-                Some(SemComp::App(arg)) => {
+    fn step<O: VirtualOs>(self, runtime: &mut Runtime<O>) -> Step<Self, Self::Out> {
+        match &mut runtime.fuel {
+            Some(0) => return Step::Done(ProgKont::OutOfFuel),
+            Some(fuel) => *fuel -= 1,
+            None => {}
+        }
+        if let Some(profile) = &mut runtime.profile {
+            profile.record(&self, &runtime.stack);
+        }
+        try_step(self, runtime).into()
+    }
+}
+
+fn try_step<O: VirtualOs>(
+    comp: Sp<ls::SynComp>, runtime: &mut Runtime<O>,
+) -> Result<Step<Sp<ls::SynComp>, ProgKont>, RuntimeError> {
+    let Sp { inner, info } = comp;
+    {
+        match inner {
+            ls::SynComp::Abs(ls::Abs { param, body }) => match runtime.stack.back() {
+                Some(SemComp::App(_)) => {
+                    let Some(SemComp::App(arg)) = runtime.stack.pop_back() else { unreachable!() };
                     let arg = arg.as_ref().clone();
                     let env = runtime.env.update(param, arg);
                     runtime.env = env;
-                    Step::Step(body.as_ref().clone())
+                    Ok(Step::Step(body.as_ref().clone()))
+                }
+                frame => {
+                    let found = found(frame);
+                    Err(runtime.err(RuntimeErrorItem::AppNotAtStacktop { found }, &info))
                 }
-                _ => panic!("App not at stacktop"),
             },
             ls::SynComp::App(ls::App { body, arg }) => {
-                let arg = rc!(arg.as_ref().clone().eval(runtime));
-                runtime.stack.push_back(SemComp::App(arg));
-                Step::Step(body.as_ref().clone())
+                let arg = rc!(arg.as_ref().clone().eval(runtime)?);
+                runtime.push(SemComp::App(arg), &info)?;
+                Ok(Step::Step(body.as_ref().clone()))
             }
             ls::SynComp::Ret(ls::Ret(v)) => {
-                let v = v.as_ref().clone().eval(runtime);
-                match runtime.stack.pop_back() {
-                    Some(SemComp::Kont(comp, env, var)) => {
+                let v = v.as_ref().clone().eval(runtime)?;
+                match runtime.stack.back() {
+                    Some(SemComp::Kont(..)) => {
+                        let Some(SemComp::Kont(comp, env, var)) = runtime.stack.pop_back() else {
+                            unreachable!()
+                        };
                         let env = env.update(var, v);
                         runtime.env = env;
-                        Step::Step(comp.as_ref().clone())
+                        Ok(Step::Step(comp.as_ref().clone()))
+                    }
+                    Some(SemComp::Handle(..)) => {
+                        let Some(SemComp::Handle(handle, env)) = runtime.stack.pop_back() else {
+                            unreachable!()
+                        };
+                        match &handle.ret {
+                            Some(ls::Abs { param, body }) => {
+                                runtime.env = env.update(param.clone(), v);
+                                Ok(Step::Step(body.as_ref().clone()))
+                            }
+                            // Note: without a `ret` arm the value goes on to the handler's caller
+                            None => Ok(Step::Step(info.make(ls::Ret(rc!(v.into())).into()))),
+                        }
+                    }
+                    Some(SemComp::Prompt(_)) => {
+                        runtime.stack.pop_back();
+                        Ok(Step::Step(info.make(ls::Ret(rc!(v.into())).into())))
+                    }
+                    None => Ok(Step::Done(ProgKont::Ret(v))),
+                    frame => {
+                        let found = found(frame);
+                        Err(runtime.err(RuntimeErrorItem::KontNotAtStacktop { found }, &info))
                     }
-                    None => Step::Done(ProgKont::Ret(v)),
-                    _ => panic!("Kont not at stacktop"),
                 }
             }
             ls::SynComp::Force(ls::Force(v)) => {
-                let v = v.as_ref().clone().eval(runtime);
-                let SemVal::Thunk(thunk) = v else { panic!("Force on non-thunk") };
+                let v = v.as_ref().clone().eval(runtime)?;
+                let SemVal::Thunk(thunk) = v else {
+                    let found = v.fmt_inline_debug();
+                    Err(runtime.err(RuntimeErrorItem::ForceOnNonThunk { found }, &info))?
+                };
                 runtime.env = thunk.env;
-                Step::Step(thunk.body.as_ref().clone())
+                let body = thunk.body.as_ref().clone();
+                // Note: builtins have no source location, so they are blamed on the call site
+                if body.info.is_dummy() {
+                    return Ok(Step::Step(info.make(body.inner)));
+                }
+                Ok(Step::Step(body))
             }
             ls::SynComp::Let(ls::Let { var, def, body }) => {
-                let def = def.as_ref().clone().eval(runtime);
+                let def = def.as_ref().clone().eval(runtime)?;
                 let env = runtime.env.update(var, def);
                 runtime.env = env;
-                Step::Step(body.as_ref().clone())
+                Ok(Step::Step(body.as_ref().clone()))
             }
             ls::SynComp::Do(ls::Do { var, comp, body }) => {
-                runtime.stack.push_back(SemComp::Kont(body, runtime.env.clone(), var));
-                Step::Step(comp.as_ref().clone())
+                runtime.push(SemComp::Kont(body, runtime.env.clone(), var), &info)?;
+                Ok(Step::Step(comp.as_ref().clone()))
             }
             ls::SynComp::Rec(e) => {
                 let ls::Rec { var, body } = e.clone();
+                let body_rec = rc!(info.make(e.into()));
                 let env = runtime
                     .env
-                    .update(var, SemThunk { body: rc!(e.into()), env: runtime.env.clone() }.into());
+                    .update(var, SemThunk { body: body_rec, env: runtime.env.clone() }.into());
                 runtime.env = env;
-                Step::Step(body.as_ref().clone())
+                Ok(Step::Step(body.as_ref().clone()))
             }
             ls::SynComp::Match(ls::Match { scrut, arms }) => {
-                let scrut = scrut.as_ref().clone().eval(runtime);
+                let scrut = scrut.as_ref().clone().eval(runtime)?;
                 let SemVal::Ctor(ls::Ctor { ctorv: ctor, args }) = scrut else {
-                    panic!("Match on non-ctor")
+                    let found = scrut.fmt_inline_debug();
+                    Err(runtime.err(RuntimeErrorItem::MatchOnNonCtor { found }, &info))?
                 };
-                let ls::Matcher { ctorv: _, vars, body } =
-                    arms.into_iter().find(|arm| arm.ctorv == ctor).expect("no matching arm");
-                for (var, arg) in vars.into_iter().zip(args.into_iter()) {
+                let Some(ls::Matcher { ctorv: _, vars, body }) =
+                    arms.into_iter().find(|arm| arm.ctorv == ctor)
+                else {
+                    Err(runtime.err(RuntimeErrorItem::NoMatchingArm { ctorv: ctor }, &info))?
+                };
+                for (var, arg) in vars.into_iter().zip(args) {
                     let env = runtime.env.update(var, arg.as_ref().clone());
                     runtime.env = env;
                 }
-                Step::Step(body.as_ref().clone())
+                Ok(Step::Step(body.as_ref().clone()))
             }
             ls::SynComp::Comatch(ls::Comatch { arms }) => {
-                let Some(SemComp::Dtor(dtor)) = runtime.stack.pop_back() else {
-                    panic!("Comatch on non-Dtor")
+                let dtor = match runtime.stack.back() {
+                    Some(SemComp::Dtor(dtor)) => dtor.clone(),
+                    frame => {
+                        let found = found(frame);
+                        let item = RuntimeErrorItem::DtorNotAtStacktop { found };
+                        Err(runtime.err(item, &info))?
+                    }
                 };
-                let ls::Comatcher { dtorv: _, body } =
-                    arms.into_iter().find(|arm| arm.dtorv == dtor).expect("no matching arm");
-                Step::Step(body.as_ref().clone())
+                let Some(ls::Comatcher { dtorv: _, body }) =
+                    arms.into_iter().find(|arm| arm.dtorv == dtor)
+                else {
+                    Err(runtime.err(RuntimeErrorItem::NoMatchingCoarm { dtorv: dtor }, &info))?
+                };
+                runtime.stack.pop_back();
+                Ok(Step::Step(body.as_ref().clone()))
             }
             ls::SynComp::Dtor(ls::Dtor { body, dtorv: dtor }) => {
-                runtime.stack.push_back(SemComp::Dtor(dtor));
-                Step::Step(body.as_ref().clone())
+                runtime.push(SemComp::Dtor(dtor), &info)?;
+                Ok(Step::Step(body.as_ref().clone()))
+            }
+            ls::SynComp::Handle(handle) => {
+                let body = handle.body.as_ref().clone();
+                runtime.push(SemComp::Handle(rc!(handle), runtime.env.clone()), &info)?;
+                Ok(Step::Step(body))
             }
-            ls::SynComp::Prim(ls::Prim { arity, body }) => {
+            ls::SynComp::Perform(ls::Perform { opv, args }) => {
+                let mut sem_args = Vec::new();
+                for arg in args.iter() {
+                    sem_args.push(arg.as_ref().clone().eval(runtime)?);
+                }
+                let handles = |frame: &SemComp| match frame {
+                    SemComp::Handle(handle, _) => handle.arms.iter().any(|arm| arm.opv == opv),
+                    _ => false,
+                };
+                let Some(depth) = runtime.stack.iter().rposition(handles) else {
+                    Err(runtime.err(RuntimeErrorItem::UnhandledOperation { opv }, &info))?
+                };
+                // Note: the handler is captured too, so that resuming reinstalls it
+                let frames = runtime.stack.split_off(depth);
+                let Some(SemComp::Handle(handle, env)) = frames.front() else { unreachable!() };
+                let arm = handle.arms.iter().find(|arm| arm.opv == opv).unwrap();
+                let mut env = env.clone();
+                for (var, arg) in arm.vars.iter().zip(sem_args) {
+                    env = env.update(var.clone(), arg);
+                }
+                let body = arm.body.as_ref().clone();
+                let resume = rc!(Span::dummy().make(ls::Resume(frames.clone()).into()));
+                let kont = SemThunk { body: resume, env: Env::new() };
+                runtime.env = env.update(arm.kont.clone(), kont.into());
+                Ok(Step::Step(body))
+            }
+            ls::SynComp::Resume(ls::Resume(frames)) => {
+                let Some(SemComp::App(_)) = runtime.stack.back() else {
+                    let found = found(runtime.stack.back());
+                    Err(runtime.err(RuntimeErrorItem::AppNotAtStacktop { found }, &info))?
+                };
+                let Some(SemComp::App(arg)) = runtime.stack.pop_back() else { unreachable!() };
+                runtime.append(frames, &info)?;
+                let arg = arg.as_ref().clone();
+                Ok(Step::Step(info.make(ls::Ret(rc!(arg.into())).into())))
+            }
+            ls::SynComp::Control(ls::Control::PushPrompt(prompt, thunk)) => {
+                runtime.push(SemComp::Prompt(prompt), &info)?;
+                Ok(Step::Step(info.make(ls::Force(rc!(thunk.into())).into())))
+            }
+            ls::SynComp::Control(ls::Control::TakeSubcont(prompt, thunk)) => {
+                let delimits =
+                    |frame: &SemComp| matches!(frame, SemComp::Prompt(p) if *p == prompt);
+                let Some(depth) = runtime.stack.iter().rposition(delimits) else {
+                    let item = RuntimeErrorItem::MissingPrompt { prompt: prompt.0 };
+                    Err(runtime.err(item, &info))?
+                };
+                // Note: the prompt itself is dropped, so the thunk runs outside of it
+                let frames = runtime.stack.split_off(depth + 1);
+                runtime.stack.pop_back();
+                runtime.push(SemComp::App(rc!(SubCont(frames).into())), &info)?;
+                Ok(Step::Step(info.make(ls::Force(rc!(thunk.into())).into())))
+            }
+            ls::SynComp::Control(ls::Control::PushSubcont(SubCont(frames), thunk)) => {
+                runtime.append(frames, &info)?;
+                Ok(Step::Step(info.make(ls::Force(rc!(thunk.into())).into())))
+            }
+            ls::SynComp::Prim(prim) => {
+                let ls::Prim { name, arity, .. } = prim;
                 let mut args = Vec::new();
                 for _ in 0..arity {
-                    let Some(SemComp::App(arg)) = runtime.stack.pop_back() else {
-                        panic!("Prim on non-Dtor")
+                    let Some(SemComp::App(arg)) = runtime.stack.back() else {
+                        let item = RuntimeErrorItem::PrimArity { name, arity };
+                        Err(runtime.err(item, &info))?
                     };
                     args.push(arg.as_ref().clone());
+                    runtime.stack.pop_back();
                 }
-                match body(args, runtime.input, runtime.output, runtime.args) {
-                    Ok(e) => Step::Step(e),
-                    Err(exit_code) => Step::Done(ProgKont::ExitCode(exit_code)),
+                match prim.call(args, &mut runtime.os, &mut runtime.heap) {
+                    Ok(e) => Ok(Step::Step(info.make(e))),
+                    Err(Halt::Exit(exit_code)) => {
+                        runtime.os.exit(exit_code);
+                        Ok(Step::Done(ProgKont::ExitCode(exit_code)))
+                    }
+                    Err(Halt::Panic(msg)) => {
+                        Err(runtime.err(RuntimeErrorItem::Panic { msg }, &info))
+                    }
+                    Err(Halt::Error(error)) => {
+                        let item = RuntimeErrorItem::Builtin { name, error };
+                        Err(runtime.err(item, &info))
+                    }
+                    Err(Halt::Denied(denied)) => {
+                        let item = RuntimeErrorItem::PermissionDenied { name, denied };
+                        Err(runtime.err(item, &info))
+                    }
+                    Err(Halt::NoHandlers(backend)) => {
+                        Err(runtime.err(RuntimeErrorItem::NoHandlers { backend }, &info))
+                    }
                 }
             }
         }
     }
 }
 
-impl<'rt> Eval<'rt> for ls::Module {
+impl Eval for ls::Module {
     type Out = Module;
 
-    fn step<'e>(self, runtime: &'e mut Runtime<'rt>) -> Step<Self, Self::Out> {
+    fn step<O: VirtualOs>(self, runtime: &mut Runtime<O>) -> Step<Self, Self::Out> {
         for (x, v) in self.define {
-            let v = v.clone().eval(runtime);
+            let v = match v.clone().eval(runtime) {
+                Ok(v) => v,
+                Err(err) => return Step::Err(err),
+            };
             let env = runtime.env.update(x, v);
             runtime.env = env;
         }
@@ -161,12 +378,15 @@ impl<'rt> Eval<'rt> for ls::Module {
     }
 }
 
-impl<'rt> Eval<'rt> for ls::Program {
+impl Eval for ls::Program {
     type Out = Program;
 
-    fn step<'e>(self, runtime: &'e mut Runtime<'rt>) -> Step<Self, Self::Out> {
-        let module = self.module.eval(runtime);
-        let prog_kont = self.entry.eval(runtime);
+    fn step<O: VirtualOs>(self, runtime: &mut Runtime<O>) -> Step<Self, Self::Out> {
+        let module = match self.module.eval(runtime) {
+            Ok(module) => module,
+            Err(err) => return Step::Err(err),
+        };
+        let prog_kont = self.entry.eval(runtime).unwrap_or_else(ProgKont::Error);
         Step::Done(Program { module, entry: prog_kont })
     }
 }
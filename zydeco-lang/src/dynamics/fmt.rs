@@ -22,11 +22,62 @@ impl FmtArgs for ds::Thunk {
     }
 }
 
+impl FmtArgs for Loc {
+    fn fmt_args(&self, _fargs: Args) -> String {
+        format!("<heap {}>", self.0)
+    }
+}
+
+impl FmtArgs for Prompt {
+    fn fmt_args(&self, _fargs: Args) -> String {
+        format!("<prompt {}>", self.0)
+    }
+}
+
+impl FmtArgs for SubCont {
+    fn fmt_args(&self, _fargs: Args) -> String {
+        format!("<subcont of {} frames>", self.0.len())
+    }
+}
+
+impl FmtArgs for IntMap {
+    fn fmt_args(&self, fargs: Args) -> String {
+        let entries: Vec<_> =
+            self.0.iter().map(|(k, v)| format!("{}: {}", k, v.fmt_args(fargs))).collect();
+        format!("{{{}}}", entries.join(", "))
+    }
+}
+
+impl FmtArgs for Map {
+    fn fmt_args(&self, _fargs: Args) -> String {
+        format!("<map of {} entries>", self.len)
+    }
+}
+
 impl FmtArgs for ProgKont {
     fn fmt_args(&self, fargs: Args) -> String {
         match self {
             ProgKont::Ret(v) => v.fmt_args(fargs),
             ProgKont::ExitCode(c) => format!("exit({})", c),
+            ProgKont::Error(e) => format!("error({})", e.item),
+            ProgKont::OutOfFuel => format!("out_of_fuel"),
+        }
+    }
+}
+
+impl FmtArgs for SemComp {
+    fn fmt_args(&self, fargs: Args) -> String {
+        match self {
+            SemComp::Kont(comp, _, var) => {
+                format!("do {} <- []; {}", var.fmt_args(fargs), comp.fmt_args(fargs))
+            }
+            SemComp::App(val) => format!("[] {}", val.fmt_args(fargs)),
+            SemComp::Dtor(dtor) => format!("[] .{}", dtor.fmt_args(fargs)),
+            SemComp::Handle(handle, _) => {
+                let ops: Vec<_> = handle.arms.iter().map(|arm| arm.opv.fmt_args(fargs)).collect();
+                format!("handle [] with {}", ops.join(", "))
+            }
+            SemComp::Prompt(prompt) => format!("prompt {} []", prompt.fmt_args(fargs)),
         }
     }
 }
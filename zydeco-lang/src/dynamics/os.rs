@@ -0,0 +1,457 @@
+//! The operating system a program runs on, as far as the builtins can tell.
+//!
+//! [`RealOs`] reaches the host, and [`MemOs`] keeps everything in memory so that a test can set
+//! up the files, the environment and the clock, and look at what the program left behind.
+
+use super::permission::Permissions;
+use std::{
+    cell::OnceCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs, io,
+    io::{BufRead, Write},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Everything outside of the program that the builtins touch.
+pub trait VirtualOs {
+    fn input(&mut self) -> &mut dyn BufRead;
+    fn output(&mut self) -> &mut dyn Write;
+    /// where `write_err` writes to
+    fn error(&mut self) -> &mut dyn Write;
+    fn args(&self) -> &[String];
+    fn read_file(&mut self, path: &str) -> io::Result<Vec<u8>>;
+    /// creates the file if it does not exist, and replaces its contents otherwise
+    fn write_file(&mut self, path: &str, contents: &[u8]) -> io::Result<()>;
+    /// creates the file if it does not exist
+    fn append_file(&mut self, path: &str, contents: &[u8]) -> io::Result<()>;
+    /// the names of the entries of a directory, in any order
+    fn list_dir(&mut self, path: &str) -> io::Result<Vec<String>>;
+    fn file_exists(&mut self, path: &str) -> bool;
+    /// the path the permissions are checked against, which is absolute wherever that means
+    /// something, has no `.` or `..`, and goes through no links
+    fn resolve_path(&self, path: &Path) -> Option<PathBuf>;
+    fn env_var(&mut self, name: &str) -> Option<String>;
+    /// milliseconds since the Unix epoch, or an error where there is no clock
+    fn now_millis(&mut self) -> io::Result<i64>;
+    /// milliseconds since some fixed point, which never go back even if the time of day does
+    fn monotonic_millis(&mut self) -> io::Result<i64>;
+    /// does nothing for zero or fewer milliseconds
+    fn sleep_millis(&mut self, millis: i64) -> io::Result<()>;
+    /// a uniformly distributed number
+    fn random(&mut self) -> u64;
+    /// restarts the random numbers, so that the same seed gives the same ones again
+    fn seed_random(&mut self, seed: u64);
+    /// what the builtins check before they touch files, the environment or other programs
+    fn permissions(&self) -> &Permissions;
+    /// runs another program to the end with no input, capturing what it writes
+    fn spawn(&mut self, program: &str, args: &[String]) -> io::Result<ProcessOutput>;
+    /// called when the program exits with `code`, before control returns to the embedder
+    fn exit(&mut self, code: i32);
+}
+
+/// What another program left behind once it finished.
+#[derive(Clone, Debug)]
+pub struct ProcessOutput {
+    /// the exit code, or -1 if the program was killed by a signal
+    pub code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// The host's files, environment, clock and randomness, with the given streams. Nothing beyond
+/// the streams, the clock and the randomness is permitted unless it is granted with
+/// `with_permissions`.
+pub struct RealOs<'a> {
+    pub input: &'a mut dyn BufRead,
+    pub output: &'a mut dyn Write,
+    pub args: &'a [String],
+    /// the host's stderr unless it is replaced with `with_error`
+    pub error: Option<&'a mut dyn Write>,
+    stderr: io::Stderr,
+    rng: SplitMix,
+    /// when the monotonic clock was first read, which is its fixed point
+    start: OnceCell<Instant>,
+    permissions: Permissions,
+}
+
+impl<'a> RealOs<'a> {
+    /// The random numbers start from a seed drawn from the host.
+    pub fn new(input: &'a mut dyn BufRead, output: &'a mut dyn Write, args: &'a [String]) -> Self {
+        let rng = SplitMix(rand::random());
+        let (stderr, start, permissions) = (io::stderr(), OnceCell::new(), Permissions::none());
+        RealOs { input, output, args, error: None, stderr, rng, start, permissions }
+    }
+    pub fn with_error(self, error: &'a mut dyn Write) -> Self {
+        RealOs { error: Some(error), ..self }
+    }
+    pub fn with_seed(self, seed: u64) -> Self {
+        RealOs { rng: SplitMix(seed), ..self }
+    }
+    pub fn with_permissions(self, permissions: Permissions) -> Self {
+        RealOs { permissions, ..self }
+    }
+}
+
+impl VirtualOs for RealOs<'_> {
+    fn input(&mut self) -> &mut dyn BufRead {
+        self.input
+    }
+    fn output(&mut self) -> &mut dyn Write {
+        self.output
+    }
+    fn error(&mut self) -> &mut dyn Write {
+        match &mut self.error {
+            Some(error) => *error,
+            None => &mut self.stderr,
+        }
+    }
+    fn args(&self) -> &[String] {
+        self.args
+    }
+    fn read_file(&mut self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+    fn write_file(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+    fn append_file(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        fs::OpenOptions::new().append(true).create(true).open(path)?.write_all(contents)
+    }
+    fn list_dir(&mut self, path: &str) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(path)? {
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        Ok(names)
+    }
+    fn file_exists(&mut self, path: &str) -> bool {
+        fs::metadata(path).is_ok()
+    }
+    fn resolve_path(&self, path: &Path) -> Option<PathBuf> {
+        resolve(path)
+    }
+    fn env_var(&mut self, name: &str) -> Option<String> {
+        std::env::var_os(name).map(|value| value.to_string_lossy().into_owned())
+    }
+    fn now_millis(&mut self) -> io::Result<i64> {
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64))
+    }
+    fn monotonic_millis(&mut self) -> io::Result<i64> {
+        Ok(self.start.get_or_init(Instant::now).elapsed().as_millis() as i64)
+    }
+    fn sleep_millis(&mut self, millis: i64) -> io::Result<()> {
+        if millis > 0 {
+            let _ = self.output.flush();
+            std::thread::sleep(Duration::from_millis(millis as u64));
+        }
+        Ok(())
+    }
+    fn random(&mut self) -> u64 {
+        self.rng.next()
+    }
+    fn seed_random(&mut self, seed: u64) {
+        self.rng = SplitMix(seed);
+    }
+    fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+    fn spawn(&mut self, program: &str, args: &[String]) -> io::Result<ProcessOutput> {
+        let out = Command::new(program).args(args).stdin(Stdio::null()).output()?;
+        let code = out.status.code().unwrap_or(-1);
+        Ok(ProcessOutput { code, stdout: out.stdout, stderr: out.stderr })
+    }
+    fn exit(&mut self, _code: i32) {
+        let _ = self.output.flush();
+    }
+}
+
+/// Makes a path absolute and follows its symbolic links as far as the path exists, dropping its
+/// `.` and `..` along the way, so that neither `root/../elsewhere` nor a link under `root` that
+/// points elsewhere is taken to be under `root`. A link that cannot be followed because it is
+/// dangling or loops resolves to nothing, since writing through it could create a file anywhere.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let path = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir().unwrap_or_default().join(path),
+    };
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => {
+                resolved.push(component);
+                match fs::canonicalize(&resolved) {
+                    Ok(real) => resolved = real,
+                    Err(_) if fs::symlink_metadata(&resolved).is_ok() => None?,
+                    // Note: a missing path has no links to follow, so it stays as written
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+    Some(resolved)
+}
+
+/// An operating system that lives in memory, for running programs hermetically.
+///
+/// Paths are plain strings: `a/b` is the entry `b` of the directory `a`, and both the root `/`
+/// and the working directory, the empty path, always exist. There are no links, and `..` goes
+/// up a directory except at the root and the working directory, which have nothing above them. The clock only moves when the program
+/// sleeps, and the random numbers are the same for the same seed. The only programs that can be
+/// run are the ones in `programs`, which always give back the same output.
+pub struct MemOs {
+    pub input: io::Cursor<Vec<u8>>,
+    pub output: Vec<u8>,
+    pub error: Vec<u8>,
+    pub args: Vec<String>,
+    pub files: BTreeMap<String, Vec<u8>>,
+    pub dirs: BTreeSet<String>,
+    pub env: HashMap<String, String>,
+    pub programs: HashMap<String, ProcessOutput>,
+    /// everything, unless a test restricts it
+    pub permissions: Permissions,
+    /// milliseconds since the Unix epoch
+    pub clock: i64,
+    /// milliseconds the program has slept
+    pub slept: i64,
+    /// the code the program exited with, once it has
+    pub exit_code: Option<i32>,
+    rng: SplitMix,
+}
+
+impl MemOs {
+    pub fn new() -> Self {
+        MemOs {
+            input: io::Cursor::new(Vec::new()),
+            output: Vec::new(),
+            error: Vec::new(),
+            args: Vec::new(),
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+            env: HashMap::new(),
+            programs: HashMap::new(),
+            permissions: Permissions::all(),
+            clock: 0,
+            slept: 0,
+            exit_code: None,
+            rng: SplitMix(0),
+        }
+    }
+    pub fn with_input(self, input: &str) -> Self {
+        MemOs { input: io::Cursor::new(input.as_bytes().to_vec()), ..self }
+    }
+    pub fn with_args(self, args: &[&str]) -> Self {
+        MemOs { args: args.iter().map(|arg| arg.to_string()).collect(), ..self }
+    }
+    pub fn with_seed(self, seed: u64) -> Self {
+        MemOs { rng: SplitMix(seed), ..self }
+    }
+    /// Sets the time of day, in milliseconds since the Unix epoch.
+    pub fn with_clock(self, clock: i64) -> Self {
+        MemOs { clock, ..self }
+    }
+    /// Creates a directory along with the ones it is in.
+    pub fn with_dir(mut self, path: &str) -> Self {
+        let path = normalize(path);
+        let mut path = path.as_str();
+        while !is_root(path) {
+            self.dirs.insert(path.to_string());
+            path = parent(path);
+        }
+        self
+    }
+    /// Creates a file along with the directories it is in.
+    pub fn with_file(self, path: &str, contents: &str) -> Self {
+        let path = normalize(path);
+        let mut os = self.with_dir(parent(&path));
+        os.files.insert(path, contents.as_bytes().to_vec());
+        os
+    }
+    pub fn with_env(mut self, name: &str, value: &str) -> Self {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+    /// Makes `program` runnable, whatever its arguments.
+    pub fn with_program(mut self, program: &str, code: i32, stdout: &str, stderr: &str) -> Self {
+        let (stdout, stderr) = (stdout.as_bytes().to_vec(), stderr.as_bytes().to_vec());
+        self.programs.insert(program.to_string(), ProcessOutput { code, stdout, stderr });
+        self
+    }
+    pub fn with_permissions(self, permissions: Permissions) -> Self {
+        MemOs { permissions, ..self }
+    }
+    /// The output so far, with malformed UTF-8 replaced.
+    pub fn output_str(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+    fn is_dir(&self, path: &str) -> bool {
+        is_root(path) || self.dirs.contains(path)
+    }
+    /// Checks that a file can be created at `path`, returning its key.
+    fn creatable(&self, path: &str) -> io::Result<String> {
+        let path = normalize(path);
+        if self.is_dir(&path) {
+            Err(io::ErrorKind::IsADirectory)?
+        }
+        self.lookup_dir(parent(&path))?;
+        Ok(path)
+    }
+    fn lookup_dir(&self, path: &str) -> io::Result<()> {
+        if self.is_dir(path) {
+            Ok(())
+        } else if self.files.contains_key(path) {
+            Err(io::ErrorKind::NotADirectory)?
+        } else {
+            Err(io::ErrorKind::NotFound)?
+        }
+    }
+}
+
+impl Default for MemOs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drops `.`, `..` and empty components along with the ones they undo, so that every entry has
+/// a single key.
+fn normalize(path: &str) -> String {
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    match path.starts_with('/') {
+        true => format!("/{}", components.join("/")),
+        false => components.join("/"),
+    }
+}
+fn is_root(path: &str) -> bool {
+    path.is_empty() || path == "/"
+}
+fn parent(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) => "/",
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+impl VirtualOs for MemOs {
+    fn input(&mut self) -> &mut dyn BufRead {
+        &mut self.input
+    }
+    fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+    fn error(&mut self) -> &mut dyn Write {
+        &mut self.error
+    }
+    fn args(&self) -> &[String] {
+        &self.args
+    }
+    fn read_file(&mut self, path: &str) -> io::Result<Vec<u8>> {
+        let path = normalize(path);
+        match self.files.get(&path) {
+            Some(contents) => Ok(contents.clone()),
+            None if self.is_dir(&path) => Err(io::ErrorKind::IsADirectory)?,
+            None => {
+                self.lookup_dir(parent(&path))?;
+                Err(io::ErrorKind::NotFound)?
+            }
+        }
+    }
+    fn write_file(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        let path = self.creatable(path)?;
+        self.files.insert(path, contents.to_vec());
+        Ok(())
+    }
+    fn append_file(&mut self, path: &str, contents: &[u8]) -> io::Result<()> {
+        let path = self.creatable(path)?;
+        self.files.entry(path).or_default().extend_from_slice(contents);
+        Ok(())
+    }
+    fn list_dir(&mut self, path: &str) -> io::Result<Vec<String>> {
+        let path = normalize(path);
+        self.lookup_dir(&path)?;
+        let entries = self.files.keys().chain(self.dirs.iter());
+        let entries = entries.filter(|entry| parent(entry) == path);
+        Ok(entries
+            .map(|entry| entry[entry.rfind('/').map_or(0, |i| i + 1)..].to_string())
+            .collect())
+    }
+    fn file_exists(&mut self, path: &str) -> bool {
+        let path = normalize(path);
+        self.is_dir(&path) || self.files.contains_key(&path)
+    }
+    fn resolve_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(normalize(&path.to_string_lossy()).into())
+    }
+    fn env_var(&mut self, name: &str) -> Option<String> {
+        self.env.get(name).cloned()
+    }
+    fn now_millis(&mut self) -> io::Result<i64> {
+        Ok(self.clock)
+    }
+    fn monotonic_millis(&mut self) -> io::Result<i64> {
+        Ok(self.slept)
+    }
+    fn sleep_millis(&mut self, millis: i64) -> io::Result<()> {
+        let millis = millis.max(0);
+        self.clock = self.clock.saturating_add(millis);
+        self.slept = self.slept.saturating_add(millis);
+        Ok(())
+    }
+    fn random(&mut self) -> u64 {
+        self.rng.next()
+    }
+    fn seed_random(&mut self, seed: u64) {
+        self.rng = SplitMix(seed);
+    }
+    fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+    fn spawn(&mut self, program: &str, _args: &[String]) -> io::Result<ProcessOutput> {
+        self.programs.get(program).cloned().ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+    fn exit(&mut self, code: i32) {
+        self.exit_code = Some(code);
+    }
+}
+
+/// The generator behind the random builtins, which is SplitMix64. The compiled backends
+/// implement the same one, so that a seed picks the same numbers on all of them.
+pub struct SplitMix(pub u64);
+
+impl SplitMix {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let z = self.0;
+        let z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        let z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Describes a failed file operation in the same words on every backend, rather than with the
+/// message of the host.
+pub fn describe(path: &str, error: &io::Error) -> String {
+    let reason = match error.kind() {
+        io::ErrorKind::NotFound => "no such file or directory",
+        io::ErrorKind::PermissionDenied => "permission denied",
+        io::ErrorKind::IsADirectory => "is a directory",
+        io::ErrorKind::NotADirectory => "not a directory",
+        _ => "input/output error",
+    };
+    format!("{}: {}", path, reason)
+}
@@ -0,0 +1,91 @@
+//! What a program may do to the world outside of it.
+//!
+//! Its own streams, its arguments, the clock and the random numbers are always there; files, the
+//! environment and other programs have to be granted. Each builtin that reaches one of them asks
+//! the [`Permissions`] of its [`VirtualOs`](super::os::VirtualOs) first, and a refusal stops the
+//! program with a [`Denied`] error. Paths are checked as the operating system resolves them, so
+//! that on [`MemOs`](super::os::MemOs) they are looked up in its own tree rather than the host's.
+//!
+//! Only the evaluator and the bytecode VM ask. Programs compiled to C, JavaScript or WebAssembly
+//! reach whatever their host lets them, and are not sandboxed.
+
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The paths a program may touch in one way.
+#[derive(Clone, Debug, Default)]
+pub enum Access {
+    #[default]
+    Nowhere,
+    /// the given paths and everything below them
+    Under(Vec<PathBuf>),
+    Everywhere,
+}
+
+impl Access {
+    /// Checks `path` and the roots once `resolve` has made them absolute and followed their
+    /// links; a path that resolves to nothing is never allowed.
+    pub fn allows(&self, path: &str, resolve: impl Fn(&Path) -> Option<PathBuf>) -> bool {
+        match self {
+            Access::Nowhere => false,
+            Access::Under(roots) => {
+                let Some(path) = resolve(Path::new(path)) else { return false };
+                roots.iter().filter_map(|root| resolve(root)).any(|root| path.starts_with(root))
+            }
+            Access::Everywhere => true,
+        }
+    }
+}
+
+/// Everything a program has been granted; the default grants nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Permissions {
+    /// reading files, listing directories and checking whether a path exists
+    pub read: Access,
+    /// creating, writing and appending to files
+    pub write: Access,
+    pub env: bool,
+    pub exec: bool,
+}
+
+impl Permissions {
+    pub fn none() -> Self {
+        Self::default()
+    }
+    pub fn all() -> Self {
+        Permissions { read: Access::Everywhere, write: Access::Everywhere, env: true, exec: true }
+    }
+    /// `resolve` is the [`resolve_path`](super::os::VirtualOs::resolve_path) of the operating
+    /// system the path is read on.
+    pub fn check_read(
+        &self, path: &str, resolve: impl Fn(&Path) -> Option<PathBuf>,
+    ) -> Result<(), Denied> {
+        let allowed = self.read.allows(path, resolve);
+        allowed.then_some(()).ok_or_else(|| Denied::Read(path.to_string()))
+    }
+    pub fn check_write(
+        &self, path: &str, resolve: impl Fn(&Path) -> Option<PathBuf>,
+    ) -> Result<(), Denied> {
+        let allowed = self.write.allows(path, resolve);
+        allowed.then_some(()).ok_or_else(|| Denied::Write(path.to_string()))
+    }
+    pub fn check_env(&self, name: &str) -> Result<(), Denied> {
+        self.env.then_some(()).ok_or_else(|| Denied::Env(name.to_string()))
+    }
+    pub fn check_exec(&self, program: &str) -> Result<(), Denied> {
+        self.exec.then_some(()).ok_or_else(|| Denied::Exec(program.to_string()))
+    }
+}
+
+/// Something a program tried to do without the permission for it.
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum Denied {
+    #[error("reading {0} needs --allow-read")]
+    Read(String),
+    #[error("writing {0} needs --allow-write")]
+    Write(String),
+    #[error("reading the environment variable {0} needs --allow-env")]
+    Env(String),
+    #[error("running {0} needs --allow-exec")]
+    Exec(String),
+}
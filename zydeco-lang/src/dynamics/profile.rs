@@ -0,0 +1,162 @@
+use super::syntax::*;
+use crate::prelude::*;
+use im::Vector;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
+
+/// Counts evaluation steps per top-level definition and per builtin.
+///
+/// Steps are recorded against a call tree: forcing the body of a top-level `def` enters a child
+/// of the frame that owns the innermost pending `do`, and popping that `do` returns to its owner.
+/// A builtin gets one step each time its primitive runs.
+pub struct Profile {
+    defs: HashMap<Span, Rc<str>>,
+    nodes: Vec<Node>,
+    children: HashMap<(usize, Rc<str>), usize>,
+    calls: HashMap<Rc<str>, u64>,
+    builtins: HashSet<Rc<str>>,
+    current: usize,
+    /// the stack index of each pending `do` continuation and the node that pushed it
+    saved: Vec<(usize, usize)>,
+    after_force: bool,
+}
+
+struct Node {
+    parent: usize,
+    name: Rc<str>,
+    steps: u64,
+}
+
+const ROOT: usize = 0;
+
+impl Profile {
+    pub fn new(module: &ls::Module) -> Self {
+        let defs = (module.define.iter())
+            .filter_map(|(x, v)| match v {
+                ls::SynVal::Thunk(ls::Thunk(body)) if !body.info.is_dummy() => {
+                    Some((body.info.clone(), Rc::from(x.name())))
+                }
+                _ => None,
+            })
+            .collect();
+        let root = Node { parent: ROOT, name: Rc::from("main"), steps: 0 };
+        Profile {
+            defs,
+            nodes: vec![root],
+            children: HashMap::new(),
+            calls: HashMap::new(),
+            builtins: HashSet::new(),
+            current: ROOT,
+            saved: Vec::new(),
+            after_force: false,
+        }
+    }
+
+    /// Records that `comp` is about to be stepped with `stack` as the continuation stack.
+    pub fn record(&mut self, comp: &Sp<ls::SynComp>, stack: &Vector<SemComp>) {
+        // return to the owner of each `do` continuation that has been popped since the last step
+        while let Some(&(index, node)) = self.saved.last() {
+            if index < stack.len() {
+                break;
+            }
+            self.current = node;
+            self.saved.pop();
+        }
+        if self.after_force {
+            if let Some(name) = self.defs.get(&comp.info) {
+                let name = name.clone();
+                // Note: a tail call replaces the caller instead of nesting under it
+                let base = self.saved.last().map_or(ROOT, |&(_, node)| node);
+                self.current = self.child(base, name.clone());
+                *self.calls.entry(name).or_default() += 1;
+            }
+        }
+        match &comp.inner {
+            ls::SynComp::Prim(ls::Prim { name, .. }) => {
+                let name: Rc<str> = Rc::from(*name);
+                self.builtins.insert(name.clone());
+                *self.calls.entry(name.clone()).or_default() += 1;
+                let node = self.child(self.current, name);
+                self.nodes[node].steps += 1;
+            }
+            _ => self.nodes[self.current].steps += 1,
+        }
+        if let ls::SynComp::Do(_) = &comp.inner {
+            self.saved.push((stack.len(), self.current));
+        }
+        self.after_force = matches!(comp.inner, ls::SynComp::Force(_));
+    }
+
+    fn child(&mut self, parent: usize, name: Rc<str>) -> usize {
+        let nodes = &mut self.nodes;
+        *self.children.entry((parent, name.clone())).or_insert_with(|| {
+            nodes.push(Node { parent, name, steps: 0 });
+            nodes.len() - 1
+        })
+    }
+
+    /// The total number of steps recorded.
+    pub fn total(&self) -> u64 {
+        self.nodes.iter().map(|node| node.steps).sum()
+    }
+
+    /// Self steps and entry counts per definition and builtin, most expensive first.
+    pub fn flat(&self) -> Vec<FlatEntry> {
+        let mut entries: HashMap<Rc<str>, FlatEntry> = HashMap::new();
+        for node in &self.nodes {
+            let entry = entries.entry(node.name.clone()).or_insert_with(|| FlatEntry {
+                name: node.name.clone(),
+                builtin: self.builtins.contains(&node.name),
+                steps: 0,
+                calls: self.calls.get(&node.name).copied().unwrap_or_default(),
+            });
+            entry.steps += node.steps;
+        }
+        let mut entries: Vec<_> = entries.into_values().collect();
+        entries.sort_by(|a, b| b.steps.cmp(&a.steps).then_with(|| a.name.cmp(&b.name)));
+        entries
+    }
+
+    /// The call tree in the collapsed-stack format read by flamegraph tools,
+    /// one `main;caller;callee steps` line per call path.
+    pub fn collapsed(&self) -> String {
+        let mut s = String::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.steps == 0 {
+                continue;
+            }
+            let mut path = vec![node.name.as_ref()];
+            let mut parent = i;
+            while parent != ROOT {
+                parent = self.nodes[parent].parent;
+                path.push(self.nodes[parent].name.as_ref());
+            }
+            path.reverse();
+            s += &format!("{} {}\n", path.join(";"), node.steps);
+        }
+        s
+    }
+}
+
+pub struct FlatEntry {
+    pub name: Rc<str>,
+    pub builtin: bool,
+    pub steps: u64,
+    pub calls: u64,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total().max(1) as f64;
+        writeln!(f, "{:>12} {:>7} {:>10}  name", "steps", "%", "calls")?;
+        for FlatEntry { name, builtin, steps, calls } in self.flat() {
+            let percent = steps as f64 * 100.0 / total;
+            let kind = if builtin { " (builtin)" } else { "" };
+            writeln!(f, "{:>12} {:>6.2}% {:>10}  {}{}", steps, percent, calls, name, kind)?;
+        }
+        Ok(())
+    }
+}
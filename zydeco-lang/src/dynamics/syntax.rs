@@ -1,16 +1,14 @@
+use super::{err::RuntimeError, os::VirtualOs, profile::Profile};
 use crate::syntax::*;
 pub use crate::{library::syntax as ls, syntax::Env, utils::fmt::FmtArgs};
 use derive_more::From;
 use im::Vector;
-use std::{
-    io::{BufRead, Write},
-    rc::Rc,
-};
+use std::rc::Rc;
 use zydeco_derive::FmtArgs;
 
 #[derive(Clone)]
 pub struct Thunk {
-    pub body: Rc<ls::SynComp>,
+    pub body: ls::RcComp,
     pub env: Env<TermV, SemVal>,
 }
 
@@ -19,14 +17,52 @@ pub enum SemVal {
     Thunk(Thunk),
     Ctor(Ctor<CtorV, TV>),
     Literal(Literal),
+    #[from(ignore)]
+    Ref(Loc),
+    #[from(ignore)]
+    Array(Loc),
+    IntMap(IntMap),
+    Map(Map),
+    Prompt(Prompt),
+    SubCont(SubCont),
 }
 type TV = Rc<SemVal>;
 impl ValueT for SemVal {}
 
+/// A persistent map from `Int`s, ordered by key.
+#[derive(Clone, Default)]
+pub struct IntMap(pub im::OrdMap<i64, SemVal>);
+
+/// A persistent map from keys the program hashes and compares with the functions it made the
+/// map with. Each bucket holds the entries whose keys share a hash, newest first.
+#[derive(Clone)]
+pub struct Map {
+    pub hash: Rc<SemVal>,
+    pub eq: Rc<SemVal>,
+    pub buckets: im::OrdMap<i64, Vector<(SemVal, SemVal)>>,
+    pub len: usize,
+}
+
+/// A delimiter for `take_subcont`; the [`Heap`] numbers them as they are made, so no two are
+/// equal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Prompt(pub usize);
+
+/// The frames between `take_subcont` and its prompt, which `push_subcont` pushes back.
 #[derive(Clone)]
+pub struct SubCont(pub Vector<SemComp>);
+
+/// The address of a block of cells in the [`Heap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Loc(pub usize);
+
+#[derive(From, Clone)]
 pub enum ProgKont {
     Ret(SemVal),
     ExitCode(i32),
+    Error(RuntimeError),
+    /// the step budget ran out before the program finished
+    OutOfFuel,
 }
 impl ComputationT for ProgKont {}
 
@@ -34,9 +70,13 @@ impl ComputationT for ProgKont {}
 
 #[derive(Clone)]
 pub enum SemComp {
-    Kont(Rc<ls::SynComp>, Env<TermV, SemVal>, TermV),
+    Kont(ls::RcComp, Env<TermV, SemVal>, TermV),
     App(Rc<SemVal>),
     Dtor(DtorV),
+    /// a handler installed by `handle`, whose arms run in the environment it was installed in
+    Handle(Rc<ls::Handle<OpV, TermV, ls::RcComp>>, Env<TermV, SemVal>),
+    /// a prompt installed by `push_prompt`, which values return through
+    Prompt(Prompt),
 }
 
 use std::fmt;
@@ -48,16 +88,62 @@ impl fmt::Debug for SemComp {
             }
             SemComp::App(val) => write!(f, "App({})", val.as_ref().fmt()),
             SemComp::Dtor(dtor) => write!(f, "Dtor(.{})", dtor,),
+            SemComp::Handle(handle, _) => {
+                let ops: Vec<_> = handle.arms.iter().map(|arm| arm.opv.name()).collect();
+                write!(f, "Handle({})", ops.join(", "))
+            }
+            SemComp::Prompt(Prompt(n)) => write!(f, "Prompt({})", n),
         }
     }
 }
 
-pub struct Runtime<'rt> {
-    pub input: &'rt mut (dyn BufRead),
-    pub output: &'rt mut (dyn Write),
-    pub args: &'rt [String],
+/// The default bound on the continuation stack; deeper programs stop with a stack overflow.
+pub const DEFAULT_MAX_STACK: usize = 1 << 20;
+
+/// The mutable cells behind `Ref`s and `Array`s, allocated in blocks: a `Ref` is a block of
+/// one cell, and an `Array` has a cell per element. Blocks are never freed while the program
+/// runs. The heap also counts the prompts made so far.
+#[derive(Clone, Default)]
+pub struct Heap {
+    blocks: Vec<Vec<SemVal>>,
+    prompts: usize,
+}
+
+impl Heap {
+    pub fn new_prompt(&mut self) -> Prompt {
+        self.prompts += 1;
+        Prompt(self.prompts - 1)
+    }
+    pub fn alloc(&mut self, cells: Vec<SemVal>) -> Loc {
+        self.blocks.push(cells);
+        Loc(self.blocks.len() - 1)
+    }
+    pub fn len(&self, loc: Loc) -> usize {
+        self.blocks[loc.0].len()
+    }
+    pub fn block(&self, loc: Loc) -> &[SemVal] {
+        &self.blocks[loc.0]
+    }
+    pub fn get(&self, loc: Loc, i: usize) -> &SemVal {
+        &self.blocks[loc.0][i]
+    }
+    pub fn set(&mut self, loc: Loc, i: usize, v: SemVal) {
+        self.blocks[loc.0][i] = v;
+    }
+}
+
+pub struct Runtime<O: VirtualOs> {
+    /// the streams, files and everything else the builtins reach
+    pub os: O,
     pub stack: Vector<SemComp>,
     pub env: Env<TermV, SemVal>,
+    pub heap: Heap,
+    /// remaining computation steps; `None` means unbounded
+    pub fuel: Option<u64>,
+    /// maximum number of frames on `stack`; `None` means unbounded
+    pub max_stack: Option<usize>,
+    /// step counts per definition, collected only when profiling
+    pub profile: Option<Profile>,
 }
 
 /* --------------------------------- Module --------------------------------- */
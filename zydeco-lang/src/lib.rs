@@ -25,6 +25,8 @@ pub mod surface {
 }
 use surface::*;
 
+// Note: elaboration fails with a bare TyckErrorItem, which holds whole types
+#[allow(clippy::result_large_err)]
 pub mod statics {
     pub mod syntax;
     pub mod err;
@@ -47,7 +49,12 @@ pub mod library {
 
 pub mod dynamics {
     pub mod syntax;
+    pub mod err;
     pub mod eval;
+    pub mod debug;
+    pub mod profile;
+    pub mod os;
+    pub mod permission;
     mod fmt;
 
     pub use eval::Eval;
@@ -55,6 +62,13 @@ pub mod dynamics {
 
 pub mod backend {
     pub mod cps;
+    pub mod opt;
+    pub mod closure;
+    pub mod bytecode;
+    pub mod vm;
+    pub mod js;
+    pub mod c;
+    pub mod wasm;
 }
 
 // pub mod core {
@@ -6,17 +6,23 @@ pub(super) struct Builtin {
     name: &'static str,
     arity: u64,
     behavior: Box<PrimComp>,
+    keeps: bool,
 }
 
 impl Builtin {
     fn new(name: &'static str, arity: u64, behavior: PrimComp) -> Self {
-        Builtin { name, arity, behavior: Box::new(behavior) }
+        Builtin { name, arity, behavior: Box::new(behavior), keeps: false }
+    }
+    /// Marks a builtin that stores its arguments, like `ref_set`.
+    fn keeping(self) -> Self {
+        Builtin { keeps: true, ..self }
     }
     fn gen(self) -> (TermV, SynVal) {
-        let Builtin { name, arity, behavior } = self;
+        let Builtin { name, arity, behavior, keeps } = self;
+        let prim = Prim { name, arity, body: *behavior, cps: false, keeps };
         (
             TermV::new(name.to_string(), Span::dummy()),
-            Thunk(rc!(Prim { arity, body: *behavior }.into())).into(),
+            Thunk(rc!(Span::dummy().make(prim.into()))).into(),
         )
     }
     // To add new builtin functions, provide impl and add declaration to std.zydeco
@@ -31,6 +37,30 @@ impl Builtin {
             Builtin::new("int_eq", 2, int_eq),
             Builtin::new("int_lt", 2, int_lt),
             Builtin::new("int_gt", 2, int_gt),
+            Builtin::new("checked_add", 2, checked_add),
+            Builtin::new("checked_sub", 2, checked_sub),
+            Builtin::new("checked_mul", 2, checked_mul),
+            Builtin::new("checked_div", 2, checked_div),
+            Builtin::new("checked_mod", 2, checked_mod),
+            Builtin::new("int_to_bigint", 1, int_to_bigint),
+            Builtin::new("bigint_to_int", 1, bigint_to_int),
+            Builtin::new("bigint_add", 2, bigint_add),
+            Builtin::new("bigint_sub", 2, bigint_sub),
+            Builtin::new("bigint_mul", 2, bigint_mul),
+            Builtin::new("bigint_div", 2, bigint_div),
+            Builtin::new("bigint_mod", 2, bigint_mod),
+            Builtin::new("bigint_eq", 2, bigint_eq),
+            Builtin::new("bigint_lt", 2, bigint_lt),
+            Builtin::new("bigint_gt", 2, bigint_gt),
+            Builtin::new("float_add", 2, float_add),
+            Builtin::new("float_sub", 2, float_sub),
+            Builtin::new("float_mul", 2, float_mul),
+            Builtin::new("float_div", 2, float_div),
+            Builtin::new("float_eq", 2, float_eq),
+            Builtin::new("float_lt", 2, float_lt),
+            Builtin::new("float_gt", 2, float_gt),
+            Builtin::new("int_to_float", 1, int_to_float),
+            Builtin::new("floor", 1, floor),
             Builtin::new("str_length", 1, str_length),
             Builtin::new("str_append", 2, str_append),
             Builtin::new("str_split_once", 2, str_split_once),
@@ -41,13 +71,61 @@ impl Builtin {
             Builtin::new("char_to_str", 1, char_to_str),
             Builtin::new("char_to_int", 1, char_to_int),
             Builtin::new("str_to_int", 1, str_to_int),
+            Builtin::new("str_hash", 1, str_hash),
+            Builtin::new("bigint_to_str", 1, bigint_to_str),
+            Builtin::new("float_to_str", 1, float_to_str),
+            Builtin::new("str_to_bigint", 1, str_to_bigint),
+            Builtin::new("ref_new", 2, ref_new).keeping(),
+            Builtin::new("ref_get", 1, ref_get),
+            Builtin::new("ref_set", 2, ref_set).keeping(),
+            Builtin::new("array_make", 2, array_make).keeping(),
+            Builtin::new("array_length", 1, array_length),
+            Builtin::new("array_get", 2, array_get),
+            Builtin::new("array_set", 3, array_set).keeping(),
+            Builtin::new("array_slice", 3, array_slice),
+            Builtin::new("intmap_empty", 0, intmap_empty),
+            Builtin::new("intmap_insert", 3, intmap_insert).keeping(),
+            Builtin::new("intmap_lookup", 2, intmap_lookup),
+            Builtin::new("intmap_remove", 2, intmap_remove),
+            Builtin::new("intmap_size", 1, intmap_size),
+            Builtin::new("intmap_to_list", 1, intmap_to_list),
+            Builtin::new("map_new", 2, map_new).keeping(),
+            Builtin::new("map_insert", 3, map_insert).keeping(),
+            Builtin::new("map_lookup", 2, map_lookup).keeping(),
+            Builtin::new("map_remove", 2, map_remove).keeping(),
+            Builtin::new("map_fold", 3, map_fold).keeping(),
+            Builtin::new("map_size", 1, map_size),
+            Builtin::new("set_new", 2, map_new).keeping(),
+            Builtin::new("set_insert", 2, set_insert).keeping(),
+            Builtin::new("set_member", 2, set_member).keeping(),
+            Builtin::new("set_remove", 2, map_remove).keeping(),
+            Builtin::new("set_fold", 3, set_fold).keeping(),
+            Builtin::new("set_size", 1, map_size),
+            Builtin::new("new_prompt", 0, new_prompt),
+            Builtin::new("push_prompt", 2, push_prompt).keeping(),
+            Builtin::new("take_subcont", 2, take_subcont).keeping(),
+            Builtin::new("push_subcont", 2, push_subcont).keeping(),
             Builtin::new("write_str", 2, write_str),
             Builtin::new("read_line", 1, read_line),
             Builtin::new("read_line_as_int", 1, read_line_as_int),
             Builtin::new("read_till_eof", 1, read_till_eof),
             Builtin::new("arg_list", 1, arg_list),
             Builtin::new("random_int", 1, random_int),
+            Builtin::new("random_range", 3, random_range),
+            Builtin::new("random_seed", 2, random_seed),
+            Builtin::new("now_millis", 1, now_millis),
+            Builtin::new("monotonic_millis", 1, monotonic_millis),
+            Builtin::new("sleep_millis", 2, sleep_millis),
+            Builtin::new("read_file", 2, read_file),
+            Builtin::new("write_file", 3, write_file),
+            Builtin::new("append_file", 3, append_file),
+            Builtin::new("list_dir", 2, list_dir),
+            Builtin::new("file_exists", 2, file_exists),
+            Builtin::new("get_env", 2, get_env),
+            Builtin::new("write_err", 2, write_err),
+            Builtin::new("spawn_process", 3, spawn_process),
             Builtin::new("exit", 1, exit),
+            Builtin::new("fatal", 1, fatal),
         ]
         .into_iter()
         .map(Builtin::gen)
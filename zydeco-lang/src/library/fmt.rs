@@ -3,7 +3,29 @@ use crate::utils::fmt::{Args, FmtArgs};
 
 impl FmtArgs for ls::Prim {
     fn fmt_args(&self, _fargs: Args) -> String {
-        format!("prim(..{}..)", self.arity)
+        format!("prim({}/{})", self.name, self.arity)
+    }
+}
+
+impl FmtArgs for ls::Resume {
+    fn fmt_args(&self, _fargs: Args) -> String {
+        format!("resume({} frames)", self.0.len())
+    }
+}
+
+impl FmtArgs for ls::Control {
+    fn fmt_args(&self, fargs: Args) -> String {
+        match self {
+            ls::Control::PushPrompt(prompt, thunk) => {
+                format!("push_prompt({}, {})", prompt.fmt_args(fargs), thunk.fmt_args(fargs))
+            }
+            ls::Control::TakeSubcont(prompt, thunk) => {
+                format!("take_subcont({}, {})", prompt.fmt_args(fargs), thunk.fmt_args(fargs))
+            }
+            ls::Control::PushSubcont(sk, thunk) => {
+                format!("push_subcont({}, {})", sk.fmt_args(fargs), thunk.fmt_args(fargs))
+            }
+        }
     }
 }
 
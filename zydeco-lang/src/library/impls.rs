@@ -1,9 +1,14 @@
 use super::syntax::*;
-use crate::{dynamics::syntax as ds, prelude::*};
-use std::{
-    io::{BufRead, Write},
-    rc::Rc,
+use crate::{
+    dynamics::{
+        err::{BuiltinError, Halt},
+        os::{describe, ProcessOutput, VirtualOs},
+        syntax::{self as ds, Heap},
+    },
+    prelude::*,
 };
+use num_bigint::{BigInt, Sign};
+use std::{rc::Rc, thread::LocalKey};
 
 type ZValue = ds::SemVal;
 type ZCompute = SynComp;
@@ -12,15 +17,33 @@ type ZCompute = SynComp;
 fn ret<E>(value: ZValue) -> Result<ZCompute, E> {
     Ok(Ret(rc!(value.into())).into())
 }
-fn app(body: Rc<ZCompute>, arg: ZValue) -> ZCompute {
-    App { body, arg: rc!(arg.into()) }.into()
+fn app(body: ZCompute, arg: ZValue) -> ZCompute {
+    App { body: rc!(Span::dummy().make(body)), arg: rc!(arg.into()) }.into()
 }
 fn ctor(ctor: &str, args: Vec<Rc<ZValue>>) -> ZValue {
     Ctor { ctorv: CtorV::new(format!("{}", ctor), Span::dummy()), args }.into()
 }
 #[allow(unused)]
-fn dtor(body: Rc<ZCompute>, dtor: &str) -> ZCompute {
-    Dtor { body, dtorv: DtorV::new(format!("{}", dtor), Span::dummy()) }.into()
+fn dtor(body: ZCompute, dtor: &str) -> ZCompute {
+    Dtor {
+        body: rc!(Span::dummy().make(body)),
+        dtorv: DtorV::new(format!("{}", dtor), Span::dummy()),
+    }
+    .into()
+}
+
+fn option(v: Option<ZValue>) -> ZValue {
+    match v {
+        Some(v) => ctor("Some", vec![rc!(v)]),
+        None => ctor("None", vec![]),
+    }
+}
+
+fn either(v: Result<ZValue, String>) -> ZValue {
+    match v {
+        Ok(v) => ctor("Right", vec![rc!(v)]),
+        Err(e) => ctor("Left", vec![rc!(Literal::String(e.chars().collect()).into())]),
+    }
 }
 
 // /* Bool */
@@ -33,39 +56,78 @@ fn bool(b: bool) -> ZValue {
 }
 
 // /* Arithmetic */
+// Note: `Int` arithmetic wraps around on overflow; only division by zero is an error
 macro_rules! arith {
-    ( $name:ident, $op:tt ) => {
+    ( $name:ident, $op:ident ) => {
         pub fn $name(
-            args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-        ) -> Result<ZCompute, i32> {
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
             match args.as_slice() {
-                [
-                    ZValue::Literal(Literal::Int(a)),
-                    ZValue::Literal(Literal::Int(b))
-                ] => ret(Literal::Int(a $op b).into()),
-                _ => unreachable!(""),
+                [ZValue::Literal(Literal::Int(a)), ZValue::Literal(Literal::Int(b))] => {
+                    ret(Literal::Int(a.$op(*b)).into())
+                }
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+macro_rules! arith_nonzero {
+    ( $name:ident, $op:ident ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [ZValue::Literal(Literal::Int(_)), ZValue::Literal(Literal::Int(0))] => {
+                    Err(BuiltinError::DivisionByZero)?
+                }
+                [ZValue::Literal(Literal::Int(a)), ZValue::Literal(Literal::Int(b))] => {
+                    ret(Literal::Int(a.$op(*b)).into())
+                }
+                _ => Err(BuiltinError::IllTyped)?,
             }
         }
     };
 }
 
-arith!(add, +);
-arith!(sub, -);
-arith!(mul, *);
-arith!(div, /);
-arith!(modulo, %);
+arith!(add, wrapping_add);
+arith!(sub, wrapping_sub);
+arith!(mul, wrapping_mul);
+arith_nonzero!(div, wrapping_div);
+arith_nonzero!(modulo, wrapping_rem);
+
+macro_rules! arith_checked {
+    ( $name:ident, $op:ident ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [ZValue::Literal(Literal::Int(a)), ZValue::Literal(Literal::Int(b))] => {
+                    ret(option(a.$op(*b).map(|i| Literal::Int(i).into())))
+                }
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+arith_checked!(checked_add, checked_add);
+arith_checked!(checked_sub, checked_sub);
+arith_checked!(checked_mul, checked_mul);
+arith_checked!(checked_div, checked_div);
+arith_checked!(checked_mod, checked_rem);
 
 macro_rules! intcomp {
     ( $name:ident, $op:tt ) => {
         pub fn $name(
-            args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _:&[String],
-        ) -> Result<ZCompute, i32> {
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
             match args.as_slice() {
                 [
                     ZValue::Literal(Literal::Int(a)),
                     ZValue::Literal(Literal::Int(b))
                 ] => ret(bool(a $op b)),
-                _ => unreachable!(""),
+                _ => Err(BuiltinError::IllTyped)?,
             }
         }
     };
@@ -75,32 +137,226 @@ intcomp!(int_eq, ==);
 intcomp!(int_lt, <);
 intcomp!(int_gt, >);
 
+// /* Big integers */
+macro_rules! bigarith {
+    ( $name:ident, $op:tt ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [
+                    ZValue::Literal(Literal::BigInt(a)),
+                    ZValue::Literal(Literal::BigInt(b))
+                ] => ret(Literal::BigInt(a $op b).into()),
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+macro_rules! bigarith_nonzero {
+    ( $name:ident, $op:tt ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [
+                    ZValue::Literal(Literal::BigInt(_)),
+                    ZValue::Literal(Literal::BigInt(b))
+                ] if b.sign() == Sign::NoSign => Err(BuiltinError::DivisionByZero)?,
+                [
+                    ZValue::Literal(Literal::BigInt(a)),
+                    ZValue::Literal(Literal::BigInt(b))
+                ] => ret(Literal::BigInt(a $op b).into()),
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+bigarith!(bigint_add, +);
+bigarith!(bigint_sub, -);
+bigarith!(bigint_mul, *);
+bigarith_nonzero!(bigint_div, /);
+bigarith_nonzero!(bigint_mod, %);
+
+macro_rules! bigcomp {
+    ( $name:ident, $op:tt ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [
+                    ZValue::Literal(Literal::BigInt(a)),
+                    ZValue::Literal(Literal::BigInt(b))
+                ] => ret(bool(a $op b)),
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+bigcomp!(bigint_eq, ==);
+bigcomp!(bigint_lt, <);
+bigcomp!(bigint_gt, >);
+
+pub fn int_to_bigint(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(a))] => ret(Literal::BigInt(BigInt::from(*a)).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn bigint_to_int(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::BigInt(a))] => {
+            ret(option(i64::try_from(a).ok().map(|i| Literal::Int(i).into())))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* Floats */
+macro_rules! floatarith {
+    ( $name:ident, $op:tt ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [
+                    ZValue::Literal(Literal::Float(Float(a))),
+                    ZValue::Literal(Literal::Float(Float(b)))
+                ] => ret(Literal::Float(Float(a $op b)).into()),
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+floatarith!(float_add, +);
+floatarith!(float_sub, -);
+floatarith!(float_mul, *);
+floatarith!(float_div, /);
+
+macro_rules! floatcomp {
+    ( $name:ident, $op:tt ) => {
+        pub fn $name(
+            args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+        ) -> Result<ZCompute, Halt> {
+            match args.as_slice() {
+                [
+                    ZValue::Literal(Literal::Float(Float(a))),
+                    ZValue::Literal(Literal::Float(Float(b)))
+                ] => ret(bool(a $op b)),
+                _ => Err(BuiltinError::IllTyped)?,
+            }
+        }
+    };
+}
+
+floatcomp!(float_eq, ==);
+floatcomp!(float_lt, <);
+floatcomp!(float_gt, >);
+
+pub fn int_to_float(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(a))] => ret(Literal::Float(Float(*a as f64)).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn floor(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Float(Float(a)))] => {
+            let f = a.floor();
+            // Note: false for NaN, and 2^63 is the least float above the range of `Int`
+            if !(-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+                Err(BuiltinError::InvalidArgument(format!(
+                    "the floor of {} is not an integer in range",
+                    float_to_string(*a)
+                )))?
+            }
+            ret(Literal::Int(f as i64).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// Formats a float like JavaScript's `Number.prototype.toString`, which the compiled
+/// backends follow as well: the shortest digits that read back as the same float, written out
+/// in full unless the exponent is below -6 or above 20.
+pub fn float_to_string(f: f64) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if f == 0.0 {
+        return "0".to_string();
+    }
+    // `{:e}` gives the shortest digits as `d.ddd` and an exponent, but rounds a tie between two
+    // of them up where JavaScript rounds to even, as rounding to that many digits does
+    let shortest = format!("{:e}", f.abs());
+    let precision = shortest.split_once('e').unwrap().0.len().saturating_sub(2);
+    let even = format!("{:.*e}", precision, f.abs());
+    let sci = if even.parse() == Ok(f.abs()) { even } else { shortest };
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let n = digits.len() as i32;
+    // the value is `0.digits` times 10 to the `k`
+    let k = exp.parse::<i32>().unwrap() + 1;
+    let s = if n <= k && k <= 21 {
+        format!("{}{}", digits, "0".repeat((k - n) as usize))
+    } else if 0 < k && k <= 21 {
+        let (int, frac) = digits.split_at(k as usize);
+        format!("{}.{}", int, frac)
+    } else if -6 < k && k <= 0 {
+        format!("0.{}{}", "0".repeat(-k as usize), digits)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let dot = if rest.is_empty() { "" } else { "." };
+        format!("{}{}{}e{}{}", first, dot, rest, if k > 0 { '+' } else { '-' }, (k - 1).abs())
+    };
+    if f < 0.0 {
+        format!("-{}", s)
+    } else {
+        s
+    }
+}
+
 // /* Strings */
 pub fn str_length(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(a))] => ret(Literal::Int(a.len() as i64).into()),
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn str_append(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(a)), ZValue::Literal(Literal::String(b))] => {
             let mut a = a.to_owned();
             a.extend(b);
             ret(Literal::String(a).into())
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn str_split_once(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(s)), ZValue::Literal(Literal::Char(p))] => {
             match s.into_iter().collect::<String>().split_once(p.to_owned()) {
@@ -117,16 +373,16 @@ pub fn str_split_once(
                 None => ret(ctor("None", vec![])),
             }
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn str_split_n(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(s)), ZValue::Literal(Literal::Int(n))] => {
-            if n.is_negative() {
+            if n.is_negative() || *n as usize > s.len() {
                 return ret(ctor("None", vec![]));
             }
             let (a, b) = s.split_at(*n as usize);
@@ -141,182 +397,936 @@ pub fn str_split_n(
                 ))],
             ))
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
-pub fn str_eq(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+pub fn str_eq(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(a)), ZValue::Literal(Literal::String(b))] => {
             ret(bool(a == b))
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
-pub fn str_index(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+pub fn str_index(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(a)), ZValue::Literal(Literal::Int(b))] => {
-            ret(Literal::Char(*a.into_iter().nth(*b as usize).unwrap()).into())
+            let c = usize::try_from(*b).ok().and_then(|b| a.get(b)).ok_or_else(|| {
+                BuiltinError::InvalidArgument(format!("index {} out of bounds", b))
+            })?;
+            ret(Literal::Char(*c).into())
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn int_to_str(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::Int(a))] => {
             ret(Literal::String(a.to_string().chars().collect()).into())
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn bigint_to_str(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::BigInt(a))] => {
+            ret(Literal::String(a.to_string().chars().collect()).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn float_to_str(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Float(Float(a)))] => {
+            ret(Literal::String(float_to_string(*a).chars().collect()).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn char_to_str(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::Char(a))] => {
             ret(Literal::String(a.to_string().chars().collect()).into())
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn char_to_int(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::Char(a))] => ret(Literal::Int((*a as u8) as i64).into()),
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn str_to_int(
-    args: Vec<ZValue>, _: &mut dyn BufRead, _: &mut dyn Write, _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(s))] => {
-            ret(Literal::Int(s.into_iter().collect::<String>().parse().unwrap()).into())
+            let s = s.iter().collect::<String>();
+            let i = s.parse().map_err(|_| {
+                BuiltinError::InvalidArgument(format!("\"{}\" is not an integer", s))
+            })?;
+            ret(Literal::Int(i).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// The polynomial hash `c0 * 31^(n-1) + .. + c(n-1)` of the code points, wrapping around.
+pub fn str_hash(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(s))] => {
+            let h = s.iter().fold(0i64, |h, c| h.wrapping_mul(31).wrapping_add(*c as i64));
+            ret(Literal::Int(h).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn str_to_bigint(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(s))] => {
+            let s = s.iter().collect::<String>();
+            // Note: accepts the same strings as `str_to_int`; `BigInt::from_str` also allows `_`
+            let digits = s.strip_prefix(['+', '-']).unwrap_or(&s);
+            let i = (digits.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| BigInt::parse_bytes(digits.as_bytes(), 10))
+                .flatten()
+                .ok_or_else(|| {
+                    BuiltinError::InvalidArgument(format!("\"{}\" is not an integer", s))
+                })?;
+            ret(Literal::BigInt(if s.starts_with('-') { -i } else { i }).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* References */
+pub fn ref_new(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [v, k] => {
+            let loc = heap.alloc(vec![v.clone()]);
+            Ok(app(Force(rc!(k.clone().into())).into(), ZValue::Ref(loc)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn ref_get(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Ref(loc)] => ret(heap.get(*loc, 0).clone()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn ref_set(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Ref(loc), v] => {
+            heap.set(*loc, 0, v.clone());
+            ret(ctor("Unit", vec![]))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* Arrays */
+fn index(i: i64, len: usize) -> Result<usize, BuiltinError> {
+    match usize::try_from(i) {
+        Ok(i) if i < len => Ok(i),
+        _ => Err(BuiltinError::InvalidArgument(format!(
+            "index {} is out of bounds for an array of length {}",
+            i, len
+        ))),
+    }
+}
+
+pub fn array_make(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(n)), v] => {
+            let invalid =
+                || BuiltinError::InvalidArgument(format!("an array cannot have length {}", n));
+            let len = usize::try_from(*n).map_err(|_| invalid())?;
+            // Note: a length the host cannot allocate stops the program rather than the host
+            let mut cells = Vec::new();
+            cells.try_reserve_exact(len).map_err(|_| invalid())?;
+            cells.resize(len, v.clone());
+            ret(ZValue::Array(heap.alloc(cells)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn array_length(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Array(loc)] => ret(Literal::Int(heap.len(*loc) as i64).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn array_get(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Array(loc), ZValue::Literal(Literal::Int(i))] => {
+            let i = index(*i, heap.len(*loc))?;
+            ret(heap.get(*loc, i).clone())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn array_set(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Array(loc), ZValue::Literal(Literal::Int(i)), v] => {
+            let i = index(*i, heap.len(*loc))?;
+            heap.set(*loc, i, v.clone());
+            ret(ctor("Unit", vec![]))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// Copies the elements from index `from` up to but excluding `to` into a new array.
+pub fn array_slice(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Array(loc), ZValue::Literal(Literal::Int(from)), ZValue::Literal(Literal::Int(to))] =>
+        {
+            let len = heap.len(*loc);
+            let cells = match (usize::try_from(*from), usize::try_from(*to)) {
+                (Ok(i), Ok(j)) if i <= j && j <= len => heap.block(*loc)[i..j].to_vec(),
+                _ => Err(BuiltinError::InvalidArgument(format!(
+                    "{}..{} is not a slice of an array of length {}",
+                    from, to, len
+                )))?,
+            };
+            ret(ZValue::Array(heap.alloc(cells)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* Int maps */
+pub fn intmap_empty(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [] => ret(ds::IntMap::default().into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn intmap_insert(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(k)), v, ZValue::IntMap(ds::IntMap(m))] => {
+            ret(ds::IntMap(m.update(*k, v.clone())).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn intmap_lookup(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(k)), ZValue::IntMap(ds::IntMap(m))] => {
+            ret(option(m.get(k).cloned()))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn intmap_remove(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(k)), ZValue::IntMap(ds::IntMap(m))] => {
+            ret(ds::IntMap(m.without(k)).into())
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn intmap_size(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::IntMap(ds::IntMap(m))] => ret(Literal::Int(m.len() as i64).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// The entries as pairs, by increasing key.
+pub fn intmap_to_list(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::IntMap(ds::IntMap(m))] => {
+            let mut list = ctor("Nil", vec![]);
+            for (k, v) in m.iter().rev() {
+                let pair = ctor("Cons", vec![rc!(Literal::Int(*k).into()), rc!(v.clone())]);
+                list = ctor("Cons", vec![rc!(pair), rc!(list)]);
+            }
+            ret(list)
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* Delimited continuations */
+pub fn new_prompt(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, heap: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [] => ret(heap.new_prompt().into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn push_prompt(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Prompt(p), m] => Ok(Control::PushPrompt(*p, m.clone()).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn take_subcont(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Prompt(p), f] => Ok(Control::TakeSubcont(*p, f.clone()).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn push_subcont(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::SubCont(sk), m] => Ok(Control::PushSubcont(sk.clone(), m.clone()).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* Maps */
+// Note: a builtin can't run the program's functions itself, so a map builtin answers with a call
+// to the hash, equality or folding function followed by one of the `map_*ed` builtins below,
+// which picks up from the result with the state of the operation as its other argument
+
+/// `fn f x_1 .. x_n s -> do r <- ! f x_1 .. x_n; ! then r s`, built once per thread so that
+/// the bytecode VM compiles it only once.
+fn call_then(n: usize, name: &'static str, then: PrimComp) -> RcComp {
+    let var = |x: String| TermV::new(x, Span::dummy());
+    let (f, r, s) = (var("f".into()), var("r".into()), var("s".into()));
+    let xs: Vec<_> = (0..n).map(|i| var(format!("x{}", i))).collect();
+    let apply = |body: ZCompute, arg: &TermV| -> ZCompute {
+        App { body: rc!(Span::dummy().make(body)), arg: rc!(arg.clone().into()) }.into()
+    };
+    let call = xs.iter().fold(Force(rc!(f.clone().into())).into(), apply);
+    let then = Prim { name, arity: 2, body: then, cps: false, keeps: true }.into();
+    let then = apply(apply(then, &r), &s);
+    let comp = rc!(Span::dummy().make(call));
+    let mut body = Do { var: r, comp, body: rc!(Span::dummy().make(then)) }.into();
+    for param in std::iter::once(f).chain(xs).chain([s]).rev() {
+        body = Abs { param, body: rc!(Span::dummy().make(body)) }.into();
+    }
+    rc!(Span::dummy().make(body))
+}
+
+thread_local! {
+    static HASH_THEN: RcComp = call_then(1, "map_hashed", map_hashed);
+    static EQ_THEN: RcComp = call_then(2, "map_compared", map_compared);
+    static FOLD_THEN: RcComp = call_then(3, "map_folded", map_folded);
+    static SET_FOLD_THEN: RcComp = call_then(2, "map_folded", map_folded);
+}
+
+/// Calls `f` with the arguments, then the builtin that `then` continues into with the state.
+fn call(
+    then: &'static LocalKey<RcComp>, f: &ZValue, args: Vec<ZValue>, state: Vec<ZValue>,
+) -> ZCompute {
+    let comp = Force(rc!(Thunk(then.with(Rc::clone)).into())).into();
+    let comp = std::iter::once(f.clone()).chain(args).fold(comp, app);
+    app(comp, ctor("$state", state.into_iter().map(Rc::new).collect()))
+}
+
+// the searches of a key's bucket
+const INSERT: i64 = 0;
+const LOOKUP: i64 = 1;
+const REMOVE: i64 = 2;
+const MEMBER: i64 = 3;
+
+/// Hashes the key, then searches its bucket.
+fn map_search(op: i64, k: &ZValue, v: ZValue, m: &ds::Map) -> ZCompute {
+    let state = vec![Literal::Int(op).into(), k.clone(), v, m.clone().into()];
+    call(&HASH_THEN, &m.hash, vec![k.clone()], state)
+}
+
+/// Compares the key with entry `i` of the bucket under the hash `h`, or finishes the search if
+/// the bucket has no more entries.
+fn map_scan(op: i64, k: ZValue, v: ZValue, m: ds::Map, h: i64, i: usize) -> ZCompute {
+    let Some((k2, _)) = m.buckets.get(&h).and_then(|bucket| bucket.get(i)) else {
+        return map_found(op, k, v, m, h, None);
+    };
+    let (eq, k2) = (m.eq.as_ref().clone(), k2.clone());
+    let (h, i) = (Literal::Int(h).into(), Literal::Int(i as i64).into());
+    let state = vec![Literal::Int(op).into(), k.clone(), v, m.into(), h, i];
+    call(&EQ_THEN, &eq, vec![k, k2], state)
+}
+
+/// Finishes a search, which found the key at index `at` of its bucket if at all.
+fn map_found(op: i64, k: ZValue, v: ZValue, mut m: ds::Map, h: i64, at: Option<usize>) -> ZCompute {
+    let mut bucket = m.buckets.get(&h).cloned().unwrap_or_default();
+    let result = match (op, at) {
+        (LOOKUP, _) => option(at.map(|i| bucket[i].1.clone())),
+        (MEMBER, _) => bool(at.is_some()),
+        (REMOVE, None) => m.into(),
+        (INSERT, _) | (REMOVE, _) => {
+            match at {
+                Some(i) => drop(bucket.remove(i)),
+                None => m.len += 1,
+            }
+            if op == INSERT {
+                bucket.push_front((k, v));
+            } else {
+                m.len -= 1;
+            }
+            if bucket.is_empty() {
+                m.buckets.remove(&h);
+            } else {
+                m.buckets.insert(h, bucket);
+            }
+            m.into()
+        }
+        _ => unreachable!("unknown map search {}", op),
+    };
+    Ret(rc!(result.into())).into()
+}
+
+/// Calls the folding function on the first entry of the list, or returns the result if the
+/// list is empty.
+fn map_fold_step(f: &ZValue, set: bool, entries: &ZValue, acc: ZValue) -> Result<ZCompute, Halt> {
+    let ZValue::Ctor(Ctor { args, .. }) = entries else { Err(BuiltinError::IllTyped)? };
+    let [entry, rest] = args.as_slice() else { return ret(acc) };
+    let ZValue::Ctor(Ctor { args: entry, .. }) = entry.as_ref() else {
+        Err(BuiltinError::IllTyped)?
+    };
+    let [k, v] = entry.as_slice() else { Err(BuiltinError::IllTyped)? };
+    let (k, v) = (k.as_ref().clone(), v.as_ref().clone());
+    let state = vec![f.clone(), bool(set), rest.as_ref().clone()];
+    Ok(match set {
+        true => call(&SET_FOLD_THEN, f, vec![k, acc], state),
+        false => call(&FOLD_THEN, f, vec![k, v, acc], state),
+    })
+}
+
+/// The arguments of the state a `map_*ed` builtin continues with.
+fn state(v: &ZValue) -> Result<Vec<ZValue>, Halt> {
+    match v {
+        ZValue::Ctor(Ctor { args, .. }) => {
+            Ok(args.iter().map(|arg| arg.as_ref().clone()).collect())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// Continues a search with the hash of its key.
+pub fn map_hashed(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(h)), s] => match state(s)?.as_slice() {
+            [ZValue::Literal(Literal::Int(op)), k, v, ZValue::Map(m)] => {
+                Ok(map_scan(*op, k.clone(), v.clone(), m.clone(), *h, 0))
+            }
+            _ => Err(BuiltinError::IllTyped)?,
+        },
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// Continues a search with whether its key equals the entry it was compared with.
+pub fn map_compared(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Ctor(Ctor { ctorv, .. }), s] => match state(s)?.as_slice() {
+            [ZValue::Literal(Literal::Int(op)), k, v, ZValue::Map(m), h, i] => {
+                let [ZValue::Literal(Literal::Int(h)), ZValue::Literal(Literal::Int(i))] = [h, i]
+                else {
+                    Err(BuiltinError::IllTyped)?
+                };
+                let (op, k, v, m, h, i) = (*op, k.clone(), v.clone(), m.clone(), *h, *i as usize);
+                Ok(match ctorv.name() {
+                    "True" => map_found(op, k, v, m, h, Some(i)),
+                    _ => map_scan(op, k, v, m, h, i + 1),
+                })
+            }
+            _ => Err(BuiltinError::IllTyped)?,
+        },
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// Continues a fold with the result of the folding function so far.
+pub fn map_folded(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [acc, s] => match state(s)?.as_slice() {
+            [f, ZValue::Ctor(Ctor { ctorv, .. }), rest] => {
+                map_fold_step(f, ctorv.name() == "True", rest, acc.clone())
+            }
+            _ => Err(BuiltinError::IllTyped)?,
+        },
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn map_new(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [hash, eq] => {
+            let (hash, eq) = (rc!(hash.clone()), rc!(eq.clone()));
+            ret(ds::Map { hash, eq, buckets: Default::default(), len: 0 }.into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn map_insert(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k, v, ZValue::Map(m)] => Ok(map_search(INSERT, k, v.clone(), m)),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn map_lookup(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k, ZValue::Map(m)] => Ok(map_search(LOOKUP, k, ctor("Unit", vec![]), m)),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn map_remove(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k, ZValue::Map(m)] => Ok(map_search(REMOVE, k, ctor("Unit", vec![]), m)),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// The entries as pairs, by increasing hash and then newest first.
+fn map_entries(m: &ds::Map) -> ZValue {
+    let entries: Vec<_> = m.buckets.values().flat_map(|bucket| bucket.iter()).collect();
+    let mut list = ctor("Nil", vec![]);
+    for (k, v) in entries.into_iter().rev() {
+        let pair = ctor("Cons", vec![rc!(k.clone()), rc!(v.clone())]);
+        list = ctor("Cons", vec![rc!(pair), rc!(list)]);
+    }
+    list
+}
+
+pub fn map_fold(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [f, init, ZValue::Map(m)] => map_fold_step(f, false, &map_entries(m), init.clone()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn map_size(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Map(m)] => ret(Literal::Int(m.len as i64).into()),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn set_insert(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k, ZValue::Map(m)] => Ok(map_search(INSERT, k, ctor("Unit", vec![]), m)),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn set_member(
+    args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k, ZValue::Map(m)] => Ok(map_search(MEMBER, k, ctor("Unit", vec![]), m)),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn set_fold(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [f, init, ZValue::Map(m)] => map_fold_step(f, true, &map_entries(m), init.clone()),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 // /* IO */
 pub fn write_str(
-    args: Vec<ZValue>, _r: &mut (dyn BufRead), w: &mut (dyn Write), _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [ZValue::Literal(Literal::String(s)), e @ ZValue::Thunk(..)] => {
-            write!(w, "{}", s.into_iter().collect::<String>()).unwrap();
-            w.flush().unwrap();
+            write!(os.output(), "{}", s.iter().collect::<String>())?;
+            os.output().flush()?;
             Ok(Force(rc!(e.clone().into())).into())
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn read_line(
-    args: Vec<ZValue>, r: &mut (dyn BufRead), _w: &mut (dyn Write), _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [e @ ZValue::Thunk(_)] => {
             let mut line = String::new();
-            r.read_line(&mut line).unwrap();
+            os.input().read_line(&mut line)?;
             line.pop();
             Ok(app(
-                rc!(Force(rc!(e.clone().into())).into()),
+                Force(rc!(e.clone().into())).into(),
                 Literal::String(line.chars().collect()).into(),
             ))
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn read_line_as_int(
-    args: Vec<ZValue>, r: &mut (dyn BufRead), _w: &mut (dyn Write), _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [e @ ZValue::Thunk(_)] => {
             let mut line = String::new();
-            r.read_line(&mut line).unwrap();
+            os.input().read_line(&mut line)?;
             line.pop();
             let i: Option<i64> = line.parse().ok();
             match i {
                 Some(i) => Ok(app(
-                    rc!(Force(rc!(e.clone().into())).into()),
-                    ctor("Some", vec!(rc!(Literal::Int(i).into()))),
-                )),
-                None => Ok(app(
-                    rc!(Force(rc!(e.clone().into())).into()),
-                    ctor("None", vec![]),
+                    Force(rc!(e.clone().into())).into(),
+                    ctor("Some", vec![rc!(Literal::Int(i).into())]),
                 )),
+                None => Ok(app(Force(rc!(e.clone().into())).into(), ctor("None", vec![]))),
             }
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn read_till_eof(
-    args: Vec<ZValue>, r: &mut (dyn BufRead), _w: &mut (dyn Write), _: &[String],
-) -> Result<ZCompute, i32> {
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [e @ ZValue::Thunk(_)] => {
             let mut line = String::new();
-            r.read_to_string(&mut line).unwrap();
+            os.input().read_to_string(&mut line)?;
             Ok(app(
-                rc!(Force(rc!(e.clone().into())).into()),
+                Force(rc!(e.clone().into())).into(),
                 Literal::String(line.chars().collect()).into(),
             ))
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
-pub fn arg_list(
-    args: Vec<ZValue>, _r: &mut (dyn BufRead), _w: &mut (dyn Write), argv: &[String],
-) -> Result<ZCompute, i32> {
+pub fn arg_list(args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [k] => {
             let mut z_arg_list = ctor("Nil", vec![]);
-            for arg in argv.iter().rev() {
+            for arg in os.args().iter().rev() {
                 z_arg_list = ctor(
                     "Cons",
                     vec![rc!(Literal::String(arg.chars().collect()).into()), rc!(z_arg_list)],
                 );
             }
-            Ok(app(rc!(Force(rc!(k.clone().into())).into()), z_arg_list))
+            Ok(app(Force(rc!(k.clone().into())).into(), z_arg_list))
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
 pub fn random_int(
-    args: Vec<ZValue>, _: &mut (dyn BufRead), _: &mut (dyn Write), _: &[String],
-) -> Result<ZCompute, i32> {
-    use rand::Rng;
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
         [k] => {
-            let mut rng = rand::thread_rng();
-            let i = Literal::Int(rng.gen_range(i64::MIN..=i64::MAX));
-            Ok(app(rc!(Force(rc!(k.clone().into())).into()), i.into()))
+            let i = Literal::Int(os.random() as i64);
+            Ok(app(Force(rc!(k.clone().into())).into(), i.into()))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// A number in `lo..hi`, where `hi` is excluded.
+pub fn random_range(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(lo)), ZValue::Literal(Literal::Int(hi)), k] => {
+            if lo >= hi {
+                Err(BuiltinError::InvalidArgument(format!("the range {}..{} is empty", lo, hi)))?
+            }
+            // Note: the span may not fit in an `Int`, but always does in a `u64`
+            let span = hi.wrapping_sub(*lo) as u64;
+            let i = Literal::Int(lo.wrapping_add((os.random() % span) as i64));
+            Ok(app(Force(rc!(k.clone().into())).into(), i.into()))
         }
-        _ => unreachable!(""),
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
 
-pub fn exit(
-    args: Vec<ZValue>, _r: &mut (dyn BufRead), _w: &mut (dyn Write), _: &[String],
-) -> Result<ZCompute, i32> {
+pub fn random_seed(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
     match args.as_slice() {
-        [ZValue::Literal(Literal::Int(a))] => Err(*a as i32),
-        _ => unreachable!(""),
+        [ZValue::Literal(Literal::Int(seed)), e @ ZValue::Thunk(..)] => {
+            os.seed_random(*seed as u64);
+            Ok(Force(rc!(e.clone().into())).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn now_millis(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k] => Ok(app(Force(rc!(k.clone().into())).into(), Literal::Int(os.now_millis()?).into())),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn monotonic_millis(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [k] => {
+            let t = Literal::Int(os.monotonic_millis()?);
+            Ok(app(Force(rc!(k.clone().into())).into(), t.into()))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn sleep_millis(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(millis)), e @ ZValue::Thunk(..)] => {
+            os.sleep_millis(*millis)?;
+            Ok(Force(rc!(e.clone().into())).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn exit(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::Int(a))] => Err(Halt::Exit(*a as i32)),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn fatal(args: Vec<ZValue>, _: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(msg))] => Err(Halt::Panic(msg.iter().collect())),
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+// /* Files and the environment */
+// Note: failures are returned to the program as `Left` with a message, not raised as errors,
+// but touching anything the program has no permission for stops it
+pub fn read_file(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(path)), k] => {
+            let path: String = path.iter().collect();
+            os.permissions().check_read(&path, |path| os.resolve_path(path))?;
+            // Note: malformed UTF-8 is replaced, as the compiled backends do
+            let contents = os.read_file(&path).map_err(|e| describe(&path, &e)).map(|bytes| {
+                Literal::String(String::from_utf8_lossy(&bytes).chars().collect()).into()
+            });
+            Ok(app(Force(rc!(k.clone().into())).into(), either(contents)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn write_file(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(path)), ZValue::Literal(Literal::String(s)), k] => {
+            let path: String = path.iter().collect();
+            os.permissions().check_write(&path, |path| os.resolve_path(path))?;
+            let res = os.write_file(&path, s.iter().collect::<String>().as_bytes());
+            let res = res.map_err(|e| describe(&path, &e)).map(|()| ctor("Unit", vec![]));
+            Ok(app(Force(rc!(k.clone().into())).into(), either(res)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn append_file(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(path)), ZValue::Literal(Literal::String(s)), k] => {
+            let path: String = path.iter().collect();
+            os.permissions().check_write(&path, |path| os.resolve_path(path))?;
+            let res = os.append_file(&path, s.iter().collect::<String>().as_bytes());
+            let res = res.map_err(|e| describe(&path, &e)).map(|()| ctor("Unit", vec![]));
+            Ok(app(Force(rc!(k.clone().into())).into(), either(res)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// The names of the entries of a directory, sorted.
+pub fn list_dir(args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(path)), k] => {
+            let path: String = path.iter().collect();
+            os.permissions().check_read(&path, |path| os.resolve_path(path))?;
+            let names = os.list_dir(&path).map_err(|e| describe(&path, &e)).map(|mut names| {
+                names.sort();
+                let mut list = ctor("Nil", vec![]);
+                for name in names.iter().rev() {
+                    let name = Literal::String(name.chars().collect()).into();
+                    list = ctor("Cons", vec![rc!(name), rc!(list)]);
+                }
+                list
+            });
+            Ok(app(Force(rc!(k.clone().into())).into(), either(names)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn file_exists(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(path)), k] => {
+            let path: String = path.iter().collect();
+            os.permissions().check_read(&path, |path| os.resolve_path(path))?;
+            let exists = os.file_exists(&path);
+            Ok(app(Force(rc!(k.clone().into())).into(), bool(exists)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn get_env(args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(name)), k] => {
+            let name: String = name.iter().collect();
+            os.permissions().check_env(&name)?;
+            let value = match os.env_var(&name) {
+                Some(value) => Ok(Literal::String(value.chars().collect()).into()),
+                None => Err(format!("environment variable {} is not set", name)),
+            };
+            Ok(app(Force(rc!(k.clone().into())).into(), either(value)))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+/// Runs a program to the end; one that cannot be started exits with 127, as in a shell.
+pub fn spawn_process(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(program)), list, k] => {
+            let program: String = program.iter().collect();
+            let mut args = Vec::new();
+            let mut list = list;
+            while let ZValue::Ctor(Ctor { ctorv, args: cell }) = list {
+                match (ctorv.name(), cell.as_slice()) {
+                    ("Cons", [arg, tail]) => match arg.as_ref() {
+                        ZValue::Literal(Literal::String(arg)) => {
+                            args.push(arg.iter().collect());
+                            list = tail;
+                        }
+                        _ => Err(BuiltinError::IllTyped)?,
+                    },
+                    ("Nil", []) => break,
+                    _ => Err(BuiltinError::IllTyped)?,
+                }
+            }
+            os.permissions().check_exec(&program)?;
+            let out = os.spawn(&program, &args).unwrap_or_else(|e| ProcessOutput {
+                code: 127,
+                stdout: Vec::new(),
+                stderr: format!("{}\n", describe(&program, &e)).into_bytes(),
+            });
+            let string = |bytes: &[u8]| -> Rc<ZValue> {
+                rc!(Literal::String(String::from_utf8_lossy(bytes).chars().collect()).into())
+            };
+            let code = rc!(Literal::Int(out.code as i64).into());
+            let res = ctor("ProcessResult", vec![code, string(&out.stdout), string(&out.stderr)]);
+            Ok(app(Force(rc!(k.clone().into())).into(), res))
+        }
+        _ => Err(BuiltinError::IllTyped)?,
+    }
+}
+
+pub fn write_err(
+    args: Vec<ZValue>, os: &mut dyn VirtualOs, _: &mut Heap,
+) -> Result<ZCompute, Halt> {
+    match args.as_slice() {
+        [ZValue::Literal(Literal::String(s)), e @ ZValue::Thunk(..)] => {
+            write!(os.error(), "{}", s.iter().collect::<String>())?;
+            os.error().flush()?;
+            Ok(Force(rc!(e.clone().into())).into())
+        }
+        _ => Err(BuiltinError::IllTyped)?,
     }
 }
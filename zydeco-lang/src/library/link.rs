@@ -1,12 +1,12 @@
 use super::{builtins::Builtin, syntax::*};
-use crate::{prelude::rc, statics::syntax as ss};
+use crate::{prelude::*, statics::syntax as ss};
 use im::Vector;
 
 impl From<ss::Program> for Program {
     fn from(p: ss::Program) -> Self {
         let ss::Program { module, entry } = p;
         let module = module.inner().into();
-        let entry = entry.inner_ref().into();
+        let entry = entry.map_ref(|e| e.into());
         Self { module, entry }
     }
 }
@@ -40,7 +40,7 @@ impl From<&ss::TermValue> for SynVal {
         match v {
             ss::TermValue::Annotation(Annotation { term: body, ty: _ }) => body.inner_ref().into(),
             ss::TermValue::Var(x) => x.clone().into(),
-            ss::TermValue::Thunk(Thunk(e)) => Thunk(rc!(e.inner_ref().into())).into(),
+            ss::TermValue::Thunk(Thunk(e)) => Thunk(e.map_rc(|e| e.into())).into(),
             ss::TermValue::Ctor(Ctor { ctorv: ctor, args }) => {
                 let args = args.iter().map(|v| rc!(v.inner_ref().into())).collect();
                 Ctor { ctorv: ctor.clone(), args }.into()
@@ -58,34 +58,37 @@ impl From<&ss::TermComputation> for SynComp {
                 body.inner_ref().into()
             }
             ss::TermComputation::Abs(Abs { param, body }) => {
-                let body = rc!(body.inner_ref().into());
+                let body = body.map_rc(|e| e.into());
                 Abs { param: param.clone(), body }.into()
             }
             ss::TermComputation::App(App { body, arg }) => {
-                let body = rc!(body.inner_ref().into());
+                let body = body.map_rc(|e| e.into());
                 let arg = rc!(arg.inner_ref().into());
                 App { body, arg }.into()
             }
             ss::TermComputation::Ret(Ret(v)) => Ret(rc!(v.inner_ref().into())).into(),
             ss::TermComputation::Force(Force(v)) => Force(rc!(v.inner_ref().into())).into(),
             ss::TermComputation::TailGroup(ss::TailGroup { group, body }) => {
-                let mut body: SynComp = body.inner_ref().into();
+                // Note: each binding in the group is located at its variable
+                let mut body: Sp<SynComp> = body.map_ref(|e| e.into());
                 for item in group.into_iter().rev() {
                     match item {
                         ss::TailTerm::Let(Let { var, def, body: () }) => {
                             let def = rc!(def.inner_ref().into());
-                            body = Let { var: var.clone(), def, body: rc!(body) }.into()
+                            let term = Let { var: var.clone(), def, body: rc!(body) }.into();
+                            body = var.span().make(term)
                         }
                         ss::TailTerm::Do(Do { var, comp, body: () }) => {
-                            let comp = rc!(comp.inner_ref().into());
-                            body = Do { var: var.clone(), comp, body: rc!(body) }.into()
+                            let comp = comp.map_rc(|e| e.into());
+                            let term = Do { var: var.clone(), comp, body: rc!(body) }.into();
+                            body = var.span().make(term)
                         }
                     }
                 }
-                body
+                body.inner
             }
             ss::TermComputation::Rec(Rec { var, body }) => {
-                let body = rc!(body.inner_ref().into());
+                let body = body.map_rc(|e| e.into());
                 Rec { var: var.clone(), body }.into()
             }
             ss::TermComputation::Match(Match { scrut, arms }) => {
@@ -93,7 +96,7 @@ impl From<&ss::TermComputation> for SynComp {
                 let arms = arms
                     .iter()
                     .map(|Matcher { ctorv: ctor, vars, body }| {
-                        let body = rc!(body.inner_ref().into());
+                        let body = body.map_rc(|e| e.into());
                         Matcher { ctorv: ctor.clone(), vars: vars.clone(), body }
                     })
                     .collect();
@@ -103,23 +106,42 @@ impl From<&ss::TermComputation> for SynComp {
                 let arms = arms
                     .iter()
                     .map(|Comatcher { dtorv: dtor, body }| {
-                        let body = rc!(body.inner_ref().into());
+                        let body = body.map_rc(|e| e.into());
                         Comatcher { dtorv: dtor.clone(), body }
                     })
                     .collect();
                 Comatch { arms }.into()
             }
             ss::TermComputation::Dtor(Dtor { body, dtorv: dtor }) => {
-                let body = rc!(body.inner_ref().into());
+                let body = body.map_rc(|e| e.into());
                 Dtor { body, dtorv: dtor.clone() }.into()
             }
             ss::TermComputation::TyAbsTerm(Abs { param: _, body }) => body.inner_ref().into(),
             ss::TermComputation::TyAppTerm(App { body, arg: _ }) => body.inner_ref().into(),
             ss::TermComputation::MatchPack(MatchPack { scrut, tvar: _, var, body }) => {
                 let scrut = rc!(scrut.inner_ref().into());
-                let body = rc!(body.inner_ref().into());
+                let body = body.map_rc(|e| e.into());
                 Let { var: var.clone(), def: scrut, body }.into()
             }
+            ss::TermComputation::Perform(Perform { opv, args }) => {
+                let args = args.iter().map(|v| rc!(v.inner_ref().into())).collect();
+                Perform { opv: opv.clone(), args }.into()
+            }
+            ss::TermComputation::Handle(Handle { body, ret, arms }) => {
+                let body = body.map_rc(|e| e.into());
+                let ret = ret.as_ref().map(|Abs { param, body }| {
+                    let body = body.map_rc(|e| e.into());
+                    Abs { param: param.clone(), body }
+                });
+                let arms = arms
+                    .iter()
+                    .map(|Handler { opv, vars, kont, body }| {
+                        let body = body.map_rc(|e| e.into());
+                        Handler { opv: opv.clone(), vars: vars.clone(), kont: kont.clone(), body }
+                    })
+                    .collect();
+                Handle { body, ret, arms }.into()
+            }
         }
     }
 }
@@ -1,11 +1,12 @@
-use crate::dynamics::syntax as ds;
 pub use crate::syntax::*;
+use crate::{
+    backend::cps::CpsTransform,
+    dynamics::{err::Halt, os::VirtualOs, syntax as ds},
+    prelude::*,
+};
 use derive_more::From;
 use im::Vector;
-use std::{
-    io::{BufRead, Write},
-    rc::Rc,
-};
+use std::rc::Rc;
 use zydeco_derive::FmtArgs;
 
 /* ---------------------------------- Term ---------------------------------- */
@@ -21,13 +22,44 @@ pub enum SynVal {
 type RcValue = Rc<SynVal>;
 impl ValueT for SynVal {}
 
-pub type PrimComp =
-    fn(Vec<ds::SemVal>, &mut (dyn BufRead), &mut (dyn Write), &[String]) -> Result<SynComp, i32>;
+pub type PrimComp = fn(Vec<ds::SemVal>, &mut dyn VirtualOs, &mut ds::Heap) -> Result<SynComp, Halt>;
 
 #[derive(Clone)]
 pub struct Prim {
+    pub name: &'static str,
     pub arity: u64,
     pub body: PrimComp,
+    /// set by the CPS transform, which then also applies to the result of the builtin
+    pub cps: bool,
+    /// whether the builtin keeps its arguments in the heap or in its result, so that they
+    /// must outlive the call
+    pub keeps: bool,
+}
+
+impl Prim {
+    pub fn call(
+        &self, args: Vec<ds::SemVal>, os: &mut dyn VirtualOs, heap: &mut ds::Heap,
+    ) -> Result<SynComp, Halt> {
+        let comp = (self.body)(args, os, heap)?;
+        Ok(if self.cps { comp.cps_transform() } else { comp })
+    }
+}
+
+/// The frames an operation captured up to and including its handler, which are pushed back
+/// when the continuation is applied; only the evaluator builds one.
+#[derive(Clone)]
+pub struct Resume(pub Vector<ds::SemComp>);
+
+/// The delimited control the prompt builtins ask for, which the evaluator carries out since
+/// only it sees the stack.
+#[derive(Clone)]
+pub enum Control {
+    /// install the prompt, then force the thunk under it
+    PushPrompt(ds::Prompt, ds::SemVal),
+    /// remove the frames up to and including the prompt, then apply the thunk to them
+    TakeSubcont(ds::Prompt, ds::SemVal),
+    /// push the frames back, then force the thunk under them
+    PushSubcont(ds::SubCont, ds::SemVal),
 }
 
 #[derive(From, FmtArgs, Clone)]
@@ -43,8 +75,12 @@ pub enum SynComp {
     Comatch(Comatch<DtorV, RcComp>),
     Dtor(Dtor<RcComp, DtorV>),
     Prim(Prim),
+    Perform(Perform<OpV, RcValue>),
+    Handle(Handle<OpV, TermV, RcComp>),
+    Resume(Resume),
+    Control(Control),
 }
-type RcComp = Rc<SynComp>;
+pub type RcComp = Rc<Sp<SynComp>>;
 impl ComputationT for SynComp {}
 
 #[derive(From, FmtArgs, Clone)]
@@ -64,5 +100,5 @@ pub struct Module {
 #[derive(Clone)]
 pub struct Program {
     pub module: Module,
-    pub entry: SynComp,
+    pub entry: Sp<SynComp>,
 }
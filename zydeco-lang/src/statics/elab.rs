@@ -409,6 +409,32 @@ impl Elaboration<ps::TermComputation> for TermComputation {
                 let body = body.try_map_rc(Elaboration::elab)?;
                 MatchPack { scrut, tvar, var, body }.into()
             }
+            ps::TermComputation::Perform(ps::Perform { opv, args }) => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| arg.try_map_rc(Elaboration::elab))
+                    .collect::<Result<_, TyckErrorItem>>()?;
+                Perform { opv, args }.into()
+            }
+            ps::TermComputation::Handle(ps::Handle { body, ret, arms }) => {
+                let body = body.try_map_rc(Elaboration::elab)?;
+                let ret = match ret {
+                    Some(Abs { param, body }) => {
+                        Some(Abs { param: param.into(), body: body.try_map_rc(Elaboration::elab)? })
+                    }
+                    None => None,
+                };
+                let arms = arms
+                    .into_iter()
+                    .map(|arm| {
+                        let ps::Handler { opv, vars, kont, body } = arm;
+                        let vars = vars.into_iter().map(Into::into).collect();
+                        let body = body.try_map_rc(Elaboration::elab)?;
+                        Ok(Handler { opv, vars, kont: kont.into(), body })
+                    })
+                    .collect::<Result<_, TyckErrorItem>>()?;
+                Handle { body, ret, arms }.into()
+            }
         })
     }
 }
@@ -519,6 +545,25 @@ impl Elaboration<ps::Alias<NameDef, Option<Sp<ps::Kind>>, ps::BoxType>> for prel
     }
 }
 
+impl Elaboration<ps::Effect<NameDef, OpV, Sp<ps::Type>>> for prelude::Effect {
+    type Error = TyckErrorItem;
+    fn elab(
+        Effect { name, ops }: ps::Effect<NameDef, OpV, Sp<ps::Type>>,
+    ) -> Result<Self, TyckErrorItem> {
+        Ok(Self { name: name.into(), ops: Elaboration::elab(ops)? })
+    }
+}
+
+impl Elaboration<ps::EffectBr<OpV, Sp<ps::Type>>> for EffectBr<OpV, RcType> {
+    type Error = TyckErrorItem;
+    fn elab(
+        EffectBr { opv, tys, ty }: ps::EffectBr<OpV, Sp<ps::Type>>,
+    ) -> Result<Self, TyckErrorItem> {
+        let tys = Vec::<_>::elab(tys)?.into_iter().map(|ty| rc!(ty)).collect();
+        Ok(Self { opv, tys, ty: ty.try_map_rc(Elaboration::elab)? })
+    }
+}
+
 impl Elaboration<ps::Module> for Module {
     type Error = TyckErrorItem;
     fn elab(ps::Module { name, declarations }: ps::Module) -> Result<Self, TyckErrorItem> {
@@ -534,6 +579,7 @@ impl Elaboration<ps::TopLevel> for Module {
         let mut data = Vec::new();
         let mut codata = Vec::new();
         let mut alias = Vec::new();
+        let mut effect = Vec::new();
         let mut define = Vec::new();
         let mut define_ext = Vec::new();
         for declaration in declarations {
@@ -545,12 +591,14 @@ impl Elaboration<ps::TopLevel> for Module {
                         data: ds,
                         codata: cs,
                         alias: aliases,
+                        effect: effs,
                         define: defs,
                         define_ext: defexts,
                     } = Elaboration::elab(m)?;
                     data.extend(ds);
                     codata.extend(cs);
                     alias.extend(aliases);
+                    effect.extend(effs);
                     define.extend(defs);
                     define_ext.extend(defexts);
                 }
@@ -564,6 +612,9 @@ impl Elaboration<ps::TopLevel> for Module {
                 ps::Declaration::Alias(d) => {
                     alias.push(DeclSymbol { public, external, inner: Elaboration::elab(d)? })
                 }
+                ps::Declaration::Effect(d) => {
+                    effect.push(DeclSymbol { public, external, inner: Elaboration::elab(d)? })
+                }
                 ps::Declaration::Define(d) => {
                     let ps::Define(ps::GenLet { rec, fun, name, params, def }) = d;
                     let (name, ty, te) = desugar_gen_let(rec, fun, name, params, def)?;
@@ -587,7 +638,7 @@ impl Elaboration<ps::TopLevel> for Module {
                 }
             }
         }
-        Ok(Self { name: None, data, codata, alias, define, define_ext })
+        Ok(Self { name: None, data, codata, alias, effect, define, define_ext })
     }
 }
 
@@ -5,7 +5,9 @@ use thiserror::Error;
 
 #[derive(Clone, Debug)]
 pub struct TyckError {
-    pub item: Sp<TyckErrorItem>,
+    /// boxed so that the checker's results, which are on every frame of its recursion, stay
+    /// small
+    pub item: Box<Sp<TyckErrorItem>>,
     pub trace: Trace,
 }
 
@@ -40,6 +42,12 @@ pub enum TyckErrorItem {
     InconsistentMatchers { unexpected: IndentVec<CtorV>, missing: IndentVec<CtorV> },
     #[error("Inconsistent comatchers.\nUnexpected:\n{unexpected}\nMissing:\n{missing}")]
     InconsistentComatchers { unexpected: IndentVec<DtorV>, missing: IndentVec<DtorV> },
+    #[error("Operation {opv} is handled more than once")]
+    DuplicateHandler { opv: OpV },
+    #[error("The handler of effect {effect} misses operations:\n{missing}")]
+    MissingHandlers { effect: TypeV, missing: IndentVec<OpV> },
+    #[error("Effect {effect} is performed where no handler handles it")]
+    UnhandledEffect { effect: TypeV },
     #[error("Inconsistent branches. Expected: {tys}")]
     InconsistentBranches { tys: IndentVec<Type> },
     #[error(transparent)]
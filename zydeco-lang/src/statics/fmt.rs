@@ -50,7 +50,7 @@ impl FmtArgs for App<RcComp, RcType> {
 impl FmtArgs for Module {
     fn fmt_args(&self, args: Args) -> String {
         let mut s = String::new();
-        let Module { name, data, codata, alias, define, define_ext } = self;
+        let Module { name, data, codata, alias, effect, define, define_ext } = self;
         if let Some(name) = name {
             s += &format!("module {} where", name);
             s += &args.br_indent();
@@ -67,6 +67,10 @@ impl FmtArgs for Module {
             s += &d.fmt_args(args);
             s += &args.br_indent();
         }
+        for d in effect {
+            s += &d.fmt_args(args);
+            s += &args.br_indent();
+        }
         for DeclSymbol { public, external: _, inner: Define { name: (var, ty), def: () } } in
             define_ext
         {
@@ -109,6 +109,8 @@ pub enum TermComputation {
     TyAbsTerm(Abs<(TypeV, Option<Sp<Kind>>), RcComp>),
     TyAppTerm(App<RcComp, RcType>),
     MatchPack(MatchPack<RcValue, TypeV, TermV, RcComp>),
+    Perform(Perform<OpV, RcValue>),
+    Handle(Handle<OpV, TermV, RcComp>),
 }
 pub type RcComp = Rc<Sp<TermComputation>>;
 impl ComputationT for TermComputation {}
@@ -127,6 +129,7 @@ pub struct Module {
     pub data: Vec<DeclSymbol<prelude::Data>>,
     pub codata: Vec<DeclSymbol<prelude::Codata>>,
     pub alias: Vec<DeclSymbol<prelude::Alias>>,
+    pub effect: Vec<DeclSymbol<prelude::Effect>>,
     pub define: Vec<DeclSymbol<Define<TermV, RcValue>>>,
     pub define_ext: Vec<DeclSymbol<Define<(TermV, RcType), ()>>>,
 }
@@ -142,4 +145,5 @@ pub mod prelude {
     pub type Data = super::Data<TypeV, Sp<Kind>, CtorV, RcType>;
     pub type Codata = super::Codata<TypeV, Sp<Kind>, DtorV, RcType>;
     pub type Alias = super::Alias<TypeV, Sp<Kind>, RcType>;
+    pub type Effect = super::Effect<TypeV, OpV, RcType>;
 }
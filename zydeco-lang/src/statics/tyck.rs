@@ -17,7 +17,7 @@ pub trait CtxT {
 }
 impl CtxT for () {
     fn err(&self, span: &Span, item: TyckErrorItem) -> TyckError {
-        TyckError { item: span.make(item), trace: Default::default() }
+        TyckError { item: Box::new(span.make(item)), trace: Default::default() }
     }
 }
 
@@ -37,6 +37,12 @@ pub struct Ctx {
     pub codata_env: im::HashMap<TypeV, prelude::Codata>,
     /// the type aliases
     pub alias_env: im::HashMap<TypeV, prelude::Alias>,
+    /// the effect declarations
+    pub effect_env: im::HashMap<TypeV, prelude::Effect>,
+    /// the declaration of each effect operation, along with the effect it belongs to
+    pub op_env: im::HashMap<OpV, (TypeV, EffectBr<OpV, RcType>)>,
+    /// the effects that an enclosing handler handles, which the computation may perform
+    pub effects: im::HashSet<TypeV>,
     pub trace: Trace,
 }
 
@@ -50,7 +56,7 @@ mod ctx {
     }
     impl CtxT for Ctx {
         fn err(&self, span: &Span, item: TyckErrorItem) -> TyckError {
-            TyckError { item: span.make(item), trace: self.trace.clone() }
+            TyckError { item: Box::new(span.make(item)), trace: self.trace.clone() }
         }
     }
 }
@@ -198,7 +198,7 @@ impl TypeCheck for Sp<TermComputation> {
                     ))?
                 };
                 arg.ana(kd.inner_clone(), ctx.clone())?;
-                let diff = Env::init(&[(param, kd)], &[arg.clone()], || {
+                let diff = Env::init(&[(param, kd)], std::slice::from_ref(arg), || {
                     ctx.err(
                         span,
                         ArityMismatch {
@@ -231,8 +231,47 @@ impl TypeCheck for Sp<TermComputation> {
                 span.make(ty_body.clone()).ana(KindBase::CType.into(), ctx)?;
                 Step::Done(ty_body)
             }
+            TermComputation::Perform(Perform { opv, args }) => {
+                let (effect, EffectBr { opv: _, tys, ty }) = ctx.resolve_op(opv, span)?;
+                bool_test(args.len() == tys.len(), || {
+                    ctx.err(
+                        span,
+                        ArityMismatch {
+                            context: format!("operation `{}`", opv),
+                            expected: tys.len(),
+                            found: args.len(),
+                        },
+                    )
+                })?;
+                for (arg, ty) in args.iter().zip(tys) {
+                    arg.ana(ty.inner_clone(), ctx.clone())?;
+                }
+                let ty = span.make_rc(Type::make_ret(ty));
+                Step::Done(TypeApp { tvar: effect.into(), args: vec![ty] }.into())
+            }
+            TermComputation::Handle(Handle { body, ret, arms }) => {
+                let (ty_body, ty_val) = syn_handled(body, arms, &ctx, span)?;
+                let typ = match ret {
+                    Some(Abs { param, body }) => {
+                        let mut ctx = ctx.clone();
+                        ctx.term_ctx.insert(param.to_owned(), ty_val);
+                        let ty = body.syn(ctx.clone())?;
+                        body.span().make(ty.clone()).ana(KindBase::CType.into(), ctx)?;
+                        ty
+                    }
+                    None => ty_body,
+                };
+                ana_handlers(arms, &typ, &ctx)?;
+                Step::Done(typ)
+            }
         })
     }
+    /// Synthesizes the type of a computation that runs here, which may only perform the effects
+    /// that an enclosing handler handles.
+    fn syn(&self, ctx: Self::Ctx) -> Result<Self::Out, TyckError> {
+        let typ = Self::tyck(self.syn_step(ctx.clone())?)?;
+        ctx.handled(typ, self.span())
+    }
     fn ana_step(
         &self, typ: Self::Out, mut ctx: Self::Ctx,
     ) -> Result<Step<(Self::Ctx, &Self), Self::Out>, TyckError> {
@@ -243,6 +282,14 @@ impl TypeCheck for Sp<TermComputation> {
             term: format!("{}", self.fmt_inline_debug()),
             info: self.span().clone(),
         });
+        let (effects, ty) = ctx.peel_effects(typ.clone(), span)?;
+        if !effects.is_empty() {
+            // the computation may perform the effects its type names
+            span.make(typ.clone()).ana(KindBase::CType.into(), ctx.clone())?;
+            ctx.effects.extend(effects);
+            self.ana(ty, ctx)?;
+            return Ok(Step::Done(typ));
+        }
         let typ = ctx.resolve_alias(typ, span)?;
         let typ_syn = typ.resolve()?;
         if let SynType::Hole(_) = typ_syn {
@@ -251,7 +298,9 @@ impl TypeCheck for Sp<TermComputation> {
         span.make(typ.clone()).ana(KindBase::CType.into(), ctx.clone())?;
         Ok(match self.inner_ref() {
             TermComputation::Annotation(Annotation { term, ty }) => {
-                let ty_lub = Type::lub(typ, ty.inner_clone(), ctx.clone(), span)?;
+                ty.ana(KindBase::CType.into(), ctx.clone())?;
+                let ty = ctx.handled(ty.inner_clone(), span)?;
+                let ty_lub = Type::lub(typ, ty, ctx.clone(), span)?;
                 Step::AnaMode((ctx, term), ty_lub)
             }
             TermComputation::Abs(Abs { param, body }) => {
@@ -302,7 +351,14 @@ impl TypeCheck for Sp<TermComputation> {
                 Step::Done(typ_lub)
             }
             TermComputation::Force(Force(v)) => {
-                v.ana(Type::make_thunk(span.make_rc(typ.clone())), ctx)?;
+                let TermValue::Thunk(_) = v.inner_ref() else {
+                    // subsumption, since the thunk may perform fewer effects than are handled
+                    let typ_syn = self.syn(ctx.clone())?;
+                    let typ_lub = Type::lub(typ, typ_syn, ctx.clone(), span)?;
+                    return Ok(Step::Done(typ_lub));
+                };
+                // Note: a thunk forced right away may perform whatever is handled here
+                v.ana(Type::make_thunk(span.make_rc(ctx.with_effects(typ.clone(), span))), ctx)?;
                 Step::Done(typ)
             }
             TermComputation::TailGroup(TailGroup { group, body }) => {
@@ -334,7 +390,8 @@ impl TypeCheck for Sp<TermComputation> {
                 Step::AnaMode((ctx, body), typ)
             }
             TermComputation::Rec(Rec { var, body }) => {
-                ctx.term_ctx.insert(var.to_owned(), Type::make_thunk(span.make_rc(typ.clone())));
+                let ty = span.make_rc(ctx.with_effects(typ.clone(), span));
+                ctx.term_ctx.insert(var.to_owned(), Type::make_thunk(ty));
                 Step::AnaMode((ctx, body), typ)
             }
             TermComputation::Match(Match { scrut, arms }) => {
@@ -436,9 +493,23 @@ impl TypeCheck for Sp<TermComputation> {
                 body.ana(ty.inner_clone(), ctx)?;
                 Step::Done(typ)
             }
+            TermComputation::Handle(Handle { body, ret, arms }) => {
+                let (ty_body, ty_val) = syn_handled(body, arms, &ctx, span)?;
+                let typ = match ret {
+                    Some(Abs { param, body }) => {
+                        let mut ctx = ctx.clone();
+                        ctx.term_ctx.insert(param.to_owned(), ty_val);
+                        body.ana(typ, ctx)?
+                    }
+                    None => Type::lub(typ, ty_body, ctx.clone(), span)?,
+                };
+                ana_handlers(arms, &typ, &ctx)?;
+                Step::Done(typ)
+            }
             TermComputation::Dtor(_)
             | TermComputation::TyAppTerm(_)
-            | TermComputation::MatchPack(_) => {
+            | TermComputation::MatchPack(_)
+            | TermComputation::Perform(_) => {
                 // subsumption
                 let typ_syn = self.syn(ctx.clone())?;
                 let typ_lub = Type::lub(typ, typ_syn, ctx.clone(), span)?;
@@ -447,3 +518,73 @@ impl TypeCheck for Sp<TermComputation> {
         })
     }
 }
+
+/// Synthesizes the type `Ret A` of a handled computation, along with its `A`. The computation
+/// may perform the effects whose every operation has an arm.
+fn syn_handled(
+    body: &RcComp, arms: &[Handler<OpV, TermV, RcComp>], ctx: &Ctx, span: &Span,
+) -> Result<(Type, Type), TyckError> {
+    let mut effects: HashMap<TypeV, HashSet<OpV>> = HashMap::new();
+    for Handler { opv, .. } in arms {
+        let (effect, _) = ctx.resolve_op(opv, opv.span())?;
+        effects.entry(effect).or_default().insert(opv.to_owned());
+    }
+    let mut ctx_body = ctx.clone();
+    for (effect, opvs) in effects {
+        let Effect { name: _, ops } = &ctx.effect_env[&effect];
+        let missing: Vec<_> =
+            ops.iter().map(|op| op.opv.to_owned()).filter(|opv| !opvs.contains(opv)).collect();
+        bool_test(missing.is_empty(), || {
+            ctx.err(span, MissingHandlers { effect: effect.clone(), missing: IndentVec(missing) })
+        })?;
+        ctx_body.effects.insert(effect);
+    }
+    let ty_body = body.syn(ctx_body)?;
+    span.make(ty_body.clone()).ana(KindBase::CType.into(), ctx.clone())?;
+    let ty_val = ty_body.clone().elim_ret(ctx.clone(), span).ok_or_else(|| {
+        ctx.err(
+            span,
+            TypeExpected {
+                context: format!("handle"),
+                expected: format!("Ret _?"),
+                found: ty_body.clone(),
+            },
+        )
+    })?;
+    Ok((ty_body, ty_val))
+}
+
+/// Checks the operation arms of a handler whose result has type `typ`; each continuation
+/// resumes with the result of its operation and runs to the end of the handler.
+fn ana_handlers(
+    arms: &[Handler<OpV, TermV, RcComp>], typ: &Type, ctx: &Ctx,
+) -> Result<(), TyckError> {
+    let mut opvs = HashSet::new();
+    for Handler { opv, vars, kont, body } in arms {
+        let span = opv.span();
+        bool_test(opvs.insert(opv.to_owned()), || {
+            ctx.err(span, DuplicateHandler { opv: opv.to_owned() })
+        })?;
+        let (_, EffectBr { opv: _, tys, ty }) = ctx.resolve_op(opv, span)?;
+        bool_test(vars.len() == tys.len(), || {
+            ctx.err(
+                span,
+                ArityMismatch {
+                    context: format!("handler of operation `{}`", opv),
+                    expected: tys.len(),
+                    found: vars.len(),
+                },
+            )
+        })?;
+        let mut ctx = ctx.clone();
+        for (var, ty) in vars.iter().zip(tys) {
+            ctx.term_ctx.insert(var.to_owned(), ty.inner_clone());
+        }
+        // Note: the rest of the handled computation may perform the effects handled outside
+        let span = body.span();
+        let ty_kont = ctx.with_effects(Arrow(ty, span.make_rc(typ.clone())).into(), span);
+        ctx.term_ctx.insert(kont.to_owned(), Type::make_thunk(span.make_rc(ty_kont)));
+        body.ana(typ.clone(), ctx)?;
+    }
+    Ok(())
+}
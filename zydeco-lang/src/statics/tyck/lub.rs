@@ -64,7 +64,7 @@ impl Lub for Kind {
                     )
                 })?;
                 let mut params = Vec::new();
-                for (l, r) in lhs.params.into_iter().zip(rhs.params.into_iter()) {
+                for (l, r) in lhs.params.into_iter().zip(rhs.params) {
                     let span = l.span().clone();
                     let kd = l.inner().lub(r.inner(), ctx.clone(), &span)?;
                     params.push(span.make(kd))
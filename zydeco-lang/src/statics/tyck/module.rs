@@ -30,6 +30,18 @@ impl prelude::Alias {
     }
 }
 
+impl prelude::Effect {
+    /// An effect `E` makes a computation type `E B` out of the `B` that may perform it.
+    fn type_arity(&self) -> Kind {
+        let span = self.name.span();
+        TypeArity {
+            params: vec![span.make(KindBase::CType.into())],
+            kd: Box::new(span.make(KindBase::CType.into())),
+        }
+        .into()
+    }
+}
+
 impl TypeCheck for Sp<&prelude::Data> {
     type Ctx = Ctx;
     type Out = ();
@@ -102,14 +114,29 @@ impl TypeCheck for Sp<&prelude::Alias> {
     }
 }
 
+impl TypeCheck for Sp<&prelude::Effect> {
+    type Ctx = Ctx;
+    type Out = ();
+
+    fn syn_step(&self, ctx: Self::Ctx) -> Result<Step<(Self::Ctx, &Self), Self::Out>, TyckError> {
+        for EffectBr { opv: _, tys, ty } in self.inner_ref().ops.iter() {
+            for ty in tys {
+                ty.ana(KindBase::VType.into(), ctx.clone())?;
+            }
+            ty.ana(KindBase::VType.into(), ctx.clone())?;
+        }
+        Ok(Step::Done(()))
+    }
+}
+
 impl TypeCheck for Sp<Module> {
     type Ctx = Ctx;
     type Out = Seal<Ctx>;
     fn syn_step(
         &self, mut ctx: Self::Ctx,
     ) -> Result<Step<(Self::Ctx, &Self), Self::Out>, TyckError> {
-        let Module { name: _, data, codata, alias, define, define_ext } = self.inner_ref();
-        // register data type, codata type and type alias declarations in the type context
+        let Module { name: _, data, codata, alias, effect, define, define_ext } = self.inner_ref();
+        // register data type, codata type, effect and type alias declarations in the type context
         for DeclSymbol { inner: data, .. } in data {
             let res = ctx.type_ctx.insert(data.name.clone(), data.type_arity());
             if let Some(_) = res {
@@ -128,6 +155,15 @@ impl TypeCheck for Sp<Module> {
                 ))?;
             }
         }
+        for DeclSymbol { inner: eff, .. } in effect {
+            let res = ctx.type_ctx.insert(eff.name.clone(), eff.type_arity());
+            if let Some(_) = res {
+                Err(ctx.err(
+                    eff.name.span(),
+                    NameResolveError::DuplicateTypeDeclaration { name: eff.name.clone() }.into(),
+                ))?;
+            }
+        }
         for DeclSymbol { inner: alias, .. } in alias {
             // type check alias declarations right away
             let kd = alias.name.span().make(alias).syn(ctx.clone())?;
@@ -150,6 +186,20 @@ impl TypeCheck for Sp<Module> {
             coda.name.span().make(coda).syn(ctx.clone())?;
             ctx.codata_env.insert(coda.name.clone(), coda.clone());
         }
+        // type check effect declarations and register their operations
+        for DeclSymbol { inner: eff, .. } in effect {
+            eff.name.span().make(eff).syn(ctx.clone())?;
+            ctx.effect_env.insert(eff.name.clone(), eff.clone());
+            for op in eff.ops.iter() {
+                let res = ctx.op_env.insert(op.opv.clone(), (eff.name.clone(), op.clone()));
+                if let Some(_) = res {
+                    Err(ctx.err(
+                        op.opv.span(),
+                        NameResolveError::DuplicateOpDeclaration { name: op.opv.clone() }.into(),
+                    ))?;
+                }
+            }
+        }
         for DeclSymbol { inner: Define { name: (var, ty), def: () }, .. } in define_ext {
             ctx.term_ctx.insert(var.clone(), ty.inner_clone());
         }
@@ -98,6 +98,46 @@ impl Ctx {
             })?;
         Ok((codata, args))
     }
+    pub(super) fn resolve_op(
+        &self, opv: &OpV, span: &Span,
+    ) -> Result<(TypeV, EffectBr<OpV, RcType>), TyckError> {
+        self.op_env.get(opv).cloned().ok_or_else(|| {
+            self.err(span, NameResolveError::UnknownOperation { opv: opv.clone() }.into())
+        })
+    }
+    /// Splits `E1 (E2 (... B))` into the effects `E1, E2, ...` and the `B` that performs them.
+    pub(super) fn peel_effects(
+        &self, typ: Type, span: &Span,
+    ) -> Result<(Vec<TypeV>, Type), TyckError> {
+        let mut effects = Vec::new();
+        let mut typ = self.resolve_alias(typ, span)?;
+        while let SynType::TypeApp(TypeApp { tvar: NeutralVar::Var(tvar), args }) = typ.resolve()? {
+            let ([arg], true) = (&args[..], self.effect_env.contains_key(&tvar)) else { break };
+            typ = self.resolve_alias(arg.inner_clone(), span)?;
+            effects.push(tvar);
+        }
+        Ok((effects, typ))
+    }
+    /// Strips the effects off the type of a computation that runs here, as long as an enclosing
+    /// handler handles all of them.
+    pub(super) fn handled(&self, typ: Type, span: &Span) -> Result<Type, TyckError> {
+        let (effects, typ) = self.peel_effects(typ, span)?;
+        for effect in effects {
+            bool_test(self.effects.contains(&effect), || {
+                self.err(span, UnhandledEffect { effect })
+            })?;
+        }
+        Ok(typ)
+    }
+    /// Wraps `typ` in the effects handled here, so that a thunk of it can only be forced where
+    /// they are handled too.
+    pub(super) fn with_effects(&self, typ: Type, span: &Span) -> Type {
+        let mut effects: Vec<_> = self.effects.iter().cloned().collect();
+        effects.sort_by(|a, b| b.name().cmp(a.name()));
+        effects.into_iter().fold(typ, |typ, effect| {
+            TypeApp { tvar: effect.into(), args: vec![span.make_rc(typ)] }.into()
+        })
+    }
     pub(super) fn resolve_alias(&self, mut typ: Type, span: &Span) -> Result<Type, TyckError> {
         while let SynType::TypeApp(TypeApp { tvar: NeutralVar::Var(ref tvar), ref args }) =
             typ.resolve()?
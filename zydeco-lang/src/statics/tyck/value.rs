@@ -6,6 +6,8 @@ impl TypeCheck for Sp<&Literal> {
     fn syn_step(&self, _ctx: Self::Ctx) -> Result<Step<(Self::Ctx, &Self), Self::Out>, TyckError> {
         Ok(Step::Done(match self.inner_ref() {
             Literal::Int(_) => Type::internal("Int", vec![]),
+            Literal::BigInt(_) => Type::internal("BigInt", vec![]),
+            Literal::Float(_) => Type::internal("Float", vec![]),
             Literal::String(_) => Type::internal("String", vec![]),
             Literal::Char(_) => Type::internal("Char", vec![]),
         }))
@@ -73,7 +75,9 @@ impl TypeCheck for Sp<TermValue> {
                         },
                     )
                 })?;
-                let ty = Type::make_thunk(span.make_rc(c.ana(typ_comp, ctx.clone())?));
+                // the thunk may be forced anywhere, so it performs only what its type names
+                let ctx_comp = Ctx { effects: im::HashSet::new(), ..ctx.clone() };
+                let ty = Type::make_thunk(span.make_rc(c.ana(typ_comp, ctx_comp)?));
                 let typ_lub = Type::lub(typ, ty, ctx.clone(), span)?;
                 Step::Done(typ_lub)
             }
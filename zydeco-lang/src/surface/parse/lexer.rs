@@ -31,6 +31,8 @@ pub enum Tok<'input> {
     Codata,
     #[token("alias")]
     Alias,
+    #[token("effect")]
+    Effect,
     #[token("def")]
     #[token("define")]
     Define,
@@ -62,9 +64,18 @@ pub enum Tok<'input> {
     At,
     #[token("pack")]
     Pack,
+    #[token("perform")]
+    Perform,
+    #[token("handle")]
+    Handle,
+    #[token("with")]
+    With,
 
     #[regex(r"[\+-]?[0-9]+")]
     NumLit(&'input str),
+    #[regex(r"[\+-]?[0-9]+\.[0-9]+([eE][\+-]?[0-9]+)?")]
+    #[regex(r"[\+-]?[0-9]+[eE][\+-]?[0-9]+")]
+    FloatLit(&'input str),
     #[regex(r#""[^"\\]*(?:\\.[^"\\]*)*""#)]
     StrLit(&'input str),
     #[regex(r#"'([ -~]|\\[nrt'|(\\)])'"#)]
@@ -128,6 +139,7 @@ impl<'input> Display for Tok<'input> {
             Tok::Data => write!(f, "data"),
             Tok::Codata => write!(f, "codata"),
             Tok::Alias => write!(f, "alias"),
+            Tok::Effect => write!(f, "effect"),
             Tok::Define => write!(f, "define"),
             Tok::Main => write!(f, "main"),
             Tok::Let => write!(f, "let"),
@@ -143,7 +155,11 @@ impl<'input> Display for Tok<'input> {
             Tok::Exists => write!(f, "Exists"),
             Tok::At => write!(f, "@"),
             Tok::Pack => write!(f, "pack"),
+            Tok::Perform => write!(f, "perform"),
+            Tok::Handle => write!(f, "handle"),
+            Tok::With => write!(f, "with"),
             Tok::NumLit(s) => write!(f, "NumLiteral({})", s),
+            Tok::FloatLit(s) => write!(f, "FloatLiteral({})", s),
             Tok::StrLit(s) => write!(f, "StrLiteral({})", s),
             Tok::CharLit(s) => write!(f, "CharLiteral({})", s),
             Tok::VType => write!(f, "VType"),
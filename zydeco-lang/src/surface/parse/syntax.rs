@@ -97,6 +97,8 @@ pub enum TermComputation {
     Dtor(Dtor<BoxComp, DtorV>),
     TyAppTerm(App<BoxComp, BoxType>),
     MatchPack(MatchPack<BoxValue, TypeDef, TermDef, BoxComp>),
+    Perform(Perform<OpV, Sp<TermValue>>),
+    Handle(Handle<OpV, TermDef, BoxComp>),
 }
 pub type BoxComp = Box<Sp<TermComputation>>;
 impl ComputationT for TermComputation {}
@@ -146,6 +148,7 @@ pub enum Declaration {
     Data(Data<TypeDef, Option<Sp<Kind>>, CtorV, Sp<Type>>),
     Codata(Codata<TypeDef, Option<Sp<Kind>>, DtorV, Sp<Type>>),
     Alias(Alias<TypeDef, Option<Sp<Kind>>, BoxType>),
+    Effect(Effect<TypeDef, OpV, Sp<Type>>),
     Define(Define),
     Main(Main),
 }
@@ -9,6 +9,8 @@ pub enum NameResolveError {
     DuplicateCtorDeclaration { name: CtorV },
     #[error("{name} declared multiple times")]
     DuplicateDtorDeclaration { name: DtorV },
+    #[error("{name} declared multiple times")]
+    DuplicateOpDeclaration { name: OpV },
     #[error("{name} declared with neither type signature nor binding")]
     EmptyDeclaration { name: String },
     #[error("{name} declared as external but has implementation")]
@@ -23,4 +25,6 @@ pub enum NameResolveError {
     UnknownConstructor { context: String, ctor: CtorV },
     #[error("Unknown destructor. In {context}, no destructor named {dtor} is found.")]
     UnknownDestructor { context: String, dtor: DtorV },
+    #[error("Unknown operation. No effect declares an operation named {opv}.")]
+    UnknownOperation { opv: OpV },
 }
@@ -31,6 +31,7 @@ pub mod sort {
     sort!(TyVarT);
     sort!(CtorT);
     sort!(DtorT);
+    sort!(OpT);
     sort!(KindT);
     sort!(TypeT);
     sort!(ValueT);
@@ -106,6 +107,20 @@ pub struct CodataBr<D: DtorT, Ty: TypeT> {
     pub ty: Ty,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Effect<TyV: TyVarT, O: OpT, Ty: TypeT> {
+    pub name: TyV,
+    pub ops: Vec<EffectBr<O, Ty>>,
+}
+
+/// An operation `op(A, ...) : R`, which takes the `A`s and resumes with an `R`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EffectBr<O: OpT, Ty: TypeT> {
+    pub opv: O,
+    pub tys: Vec<Ty>,
+    pub ty: Ty,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Alias<TyV: TyVarT, Kd: KindT, Ty: TypeT> {
     pub name: TyV,
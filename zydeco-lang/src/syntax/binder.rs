@@ -42,15 +42,15 @@ pub struct NameView<'a> {
 }
 
 pub trait NameT {
-    fn name(&self) -> NameView;
+    fn name(&self) -> NameView<'_>;
 }
 impl NameT for NameDef {
-    fn name(&self) -> NameView {
+    fn name(&self) -> NameView<'_> {
         NameView { path: &[], ident: &self.ident }
     }
 }
 impl NameT for NameRef {
-    fn name(&self) -> NameView {
+    fn name(&self) -> NameView<'_> {
         NameView { path: &self.path, ident: &self.ident }
     }
 }
@@ -200,6 +200,9 @@ impl CtorT for CtorV {}
 var!(DtorV);
 impl DtorT for DtorV {}
 
+var!(OpV);
+impl OpT for OpV {}
+
 var!(TypeV);
 impl TyVarT for TypeV {}
 impl<Kd: KindT> TyVarT for (TypeV, Kd) {}
@@ -173,6 +173,8 @@ impl FmtArgs for Literal {
     fn fmt_args(&self, _fargs: Args) -> String {
         match self {
             Literal::Int(i) => format!("{}", i),
+            Literal::BigInt(i) => format!("{}", i),
+            Literal::Float(Float(f)) => format!("{:?}", f),
             Literal::String(s) => {
                 format!("\"{}\"", s.into_iter().collect::<String>().escape_debug())
             }
@@ -400,6 +402,67 @@ where
     }
 }
 
+impl<O, A> FmtArgs for Perform<O, A>
+where
+    O: OpT + FmtArgs,
+    A: ValueT + FmtArgs,
+{
+    fn fmt_args(&self, fargs: Args) -> String {
+        let Perform { opv, args } = self;
+        let mut s = String::new();
+        s += "perform ";
+        s += &opv.fmt_args(fargs);
+        s += "(";
+        s += &args.into_iter().map(|arg| arg.fmt_args(fargs)).collect::<Vec<_>>().join(", ");
+        s += ")";
+        s
+    }
+}
+
+impl<O, TeV, B> FmtArgs for Handle<O, TeV, B>
+where
+    O: OpT + FmtArgs,
+    TeV: VarT + FmtArgs,
+    B: ComputationT + FmtArgs,
+{
+    fn fmt_args(&self, fargs: Args) -> String {
+        let Handle { body, ret, arms } = self;
+        let mut s = String::new();
+        s += "handle ";
+        s += &body.fmt_args(fargs);
+        s += " with";
+        if let Some(Abs { param, body }) = ret {
+            s += &fargs.br_indent();
+            s += "| ret ";
+            s += &param.fmt_args(fargs);
+            s += " -> ";
+            {
+                let fargs = fargs.indent();
+                s += &fargs.br_indent();
+                s += &body.fmt_args(fargs);
+            }
+        }
+        for Handler { opv, vars, kont, body } in arms {
+            s += &fargs.br_indent();
+            s += "| ";
+            s += &opv.fmt_args(fargs);
+            s += "(";
+            s += &vars.into_iter().map(|var| var.fmt_args(fargs)).collect::<Vec<_>>().join(", ");
+            s += ") ";
+            s += &kont.fmt_args(fargs);
+            s += " -> ";
+            {
+                let fargs = fargs.indent();
+                s += &fargs.br_indent();
+                s += &body.fmt_args(fargs);
+            }
+        }
+        s += &fargs.br_indent();
+        s += "end";
+        s
+    }
+}
+
 impl<T> FmtArgs for DeclSymbol<T>
 where
     T: FmtArgs,
@@ -509,6 +572,49 @@ where
     }
 }
 
+impl<TyV, O, Ty> FmtArgs for Effect<TyV, O, Ty>
+where
+    TyV: TyVarT + FmtArgs,
+    O: OpT + FmtArgs,
+    Ty: TypeT + FmtArgs,
+{
+    fn fmt_args(&self, fargs: Args) -> String {
+        let Effect { name, ops } = self;
+        let mut s = String::new();
+        s += "effect ";
+        s += &name.fmt_args(fargs);
+        s += " where ";
+        {
+            let fargs = fargs.indent();
+            for effectbr in ops {
+                s += &fargs.br_indent();
+                s += &effectbr.fmt_args(fargs);
+            }
+        }
+        s += &fargs.br_indent();
+        s += "end";
+        s
+    }
+}
+
+impl<O, Ty> FmtArgs for EffectBr<O, Ty>
+where
+    O: OpT + FmtArgs,
+    Ty: TypeT + FmtArgs,
+{
+    fn fmt_args(&self, fargs: Args) -> String {
+        let EffectBr { opv, tys, ty } = self;
+        let mut s = String::new();
+        s += "| ";
+        s += &opv.fmt_args(fargs);
+        s += "(";
+        s += &tys.into_iter().map(|ty| ty.fmt_args(fargs)).collect::<Vec<_>>().join(", ");
+        s += ") : ";
+        s += &ty.fmt_args(fargs);
+        s
+    }
+}
+
 impl<TyV, Kd, Ty> FmtArgs for Alias<TyV, Kd, Ty>
 where
     TyV: TyVarT + FmtArgs,
@@ -289,6 +289,43 @@ where
     }
 }
 
+impl<O, A> SpanHolder for Perform<O, A>
+where
+    O: OpT + SpanHolder,
+    A: ValueT + SpanHolder,
+{
+    fn span_map_mut<F>(&mut self, f: F)
+    where
+        F: Fn(&mut Span) + Clone,
+    {
+        let Perform { opv, args } = self;
+        opv.span_map_mut(f.clone());
+        args.span_map_mut(f);
+    }
+}
+
+impl<O, TeV, B> SpanHolder for Handle<O, TeV, B>
+where
+    O: OpT + SpanHolder,
+    TeV: VarT + SpanHolder,
+    B: ComputationT + SpanHolder,
+{
+    fn span_map_mut<F>(&mut self, f: F)
+    where
+        F: Fn(&mut Span) + Clone,
+    {
+        let Handle { body, ret, arms } = self;
+        body.span_map_mut(f.clone());
+        ret.span_map_mut(f.clone());
+        for Handler { opv, vars, kont, body } in arms {
+            opv.span_map_mut(f.clone());
+            vars.span_map_mut(f.clone());
+            kont.span_map_mut(f.clone());
+            body.span_map_mut(f.clone());
+        }
+    }
+}
+
 impl<TyV, Kd, C, T> SpanHolder for Data<TyV, Kd, C, T>
 where
     TyV: TyVarT + SpanHolder,
@@ -331,6 +368,26 @@ where
     }
 }
 
+impl<TyV, O, T> SpanHolder for Effect<TyV, O, T>
+where
+    TyV: TyVarT + SpanHolder,
+    O: OpT + SpanHolder,
+    T: TypeT + SpanHolder,
+{
+    fn span_map_mut<F>(&mut self, f: F)
+    where
+        F: Fn(&mut Span) + Clone,
+    {
+        let Effect { name, ops } = self;
+        name.span_map_mut(f.clone());
+        for EffectBr { opv, tys, ty } in ops {
+            opv.span_map_mut(f.clone());
+            tys.span_map_mut(f.clone());
+            ty.span_map_mut(f.clone());
+        }
+    }
+}
+
 impl<TyV, Kd, Ty> SpanHolder for Alias<TyV, Kd, Ty>
 where
     TyV: TyVarT + SpanHolder,
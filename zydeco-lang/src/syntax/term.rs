@@ -1,5 +1,6 @@
 use super::*;
 use derive_more::From;
+use num_bigint::BigInt;
 
 /* ---------------------------------- Kind ---------------------------------- */
 
@@ -50,10 +51,24 @@ pub struct Thunk<B: ComputationT>(pub B);
 #[derive(From, Clone, Debug, PartialEq, Eq)]
 pub enum Literal {
     Int(i64),
+    /// only produced by builtins; there is no literal syntax for it
+    BigInt(BigInt),
+    Float(Float),
     String(Vec<char>),
     Char(char),
 }
 
+/// A 64-bit float, equal to another only if their bits are, so that `NaN`s don't break `Eq`.
+#[derive(Clone, Copy, Debug)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+impl Eq for Float {}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Ctor<C: CtorT, A: ValueT> {
     pub ctorv: C,
@@ -130,3 +145,25 @@ pub struct MatchPack<A: ValueT, TyV: TyVarT, TeV: VarT, B: ComputationT> {
     pub var: TeV,
     pub body: B,
 }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Perform<O: OpT, A: ValueT> {
+    pub opv: O,
+    pub args: Vec<A>,
+}
+
+/// Runs `body` with its operations handled by `arms`; its result goes through `ret` if given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Handle<O: OpT, TeV: VarT, B: ComputationT> {
+    pub body: B,
+    pub ret: Option<Abs<TeV, B>>,
+    pub arms: Vec<Handler<O, TeV, B>>,
+}
+/// The arm for `opv`, which binds its arguments to `vars` and its continuation to `kont`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Handler<O: OpT, TeV: VarT, B: ComputationT> {
+    pub opv: O,
+    pub vars: Vec<TeV>,
+    pub kont: TeV,
+    pub body: B,
+}
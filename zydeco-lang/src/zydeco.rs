@@ -1,5 +1,20 @@
 use crate::{
-    dynamics::{eval::Eval, syntax as ds},
+    backend::{
+        c::CGen,
+        cps::CpsTransform,
+        js::JsGen,
+        opt::{self, Pass},
+        vm::Vm,
+        wasm::WasmGen,
+    },
+    dynamics::{
+        err::RuntimeError,
+        eval::Eval,
+        os::{RealOs, VirtualOs},
+        permission::Permissions,
+        profile::Profile,
+        syntax as ds,
+    },
     library::syntax as ls,
     parse::{
         err::ParseError,
@@ -65,17 +80,108 @@ impl ZydecoFile {
         let m: ls::Program = m.into();
         Ok(m)
     }
-    pub fn eval_os(p: ls::Program, args: &[String]) -> ds::Program {
+    pub fn cps(p: ls::Program) -> ls::Program {
+        p.cps_transform()
+    }
+    /// Runs the optimization passes, handing the program after each one to `after`.
+    pub fn opt(p: ls::Program, after: impl FnMut(Pass, &ls::Program)) -> ls::Program {
+        opt::optimize(p, after)
+    }
+    /// Compiles a program into a standalone JavaScript file.
+    pub fn emit_js(p: &ls::Program) -> Result<String, String> {
+        JsGen::program(p).map_err(|e| e.to_string())
+    }
+    /// Compiles a program into a single C file.
+    pub fn emit_c(p: &ls::Program) -> Result<String, String> {
+        CGen::program(p).map_err(|e| e.to_string())
+    }
+    /// Compiles a program into a WebAssembly module that imports WASI.
+    pub fn emit_wasm(p: &ls::Program) -> Result<Vec<u8>, String> {
+        WasmGen::program(p).map_err(|e| e.to_string())
+    }
+    /// Runs a program on stdin and stdout; a `seed` fixes the random numbers it gets, and
+    /// `permissions` says what else it may touch.
+    pub fn eval_os(
+        p: ls::Program, args: &[String], max_steps: Option<u64>, seed: Option<u64>,
+        permissions: Permissions,
+    ) -> ds::Program {
         let mut input = std::io::stdin().lock();
         let mut output = std::io::stdout();
-        Self::eval_virtual_os(p, &mut input, &mut output, args)
+        let os = Self::real_os(&mut input, &mut output, args, seed, permissions);
+        Self::eval_os_with(p, os, max_steps).0
     }
     pub fn eval_virtual_os(
         p: ls::Program, r: &mut dyn std::io::BufRead, w: &mut dyn std::io::Write, args: &[String],
+        max_steps: Option<u64>,
     ) -> ds::Program {
         let mut runtime = ds::Runtime::new(r, w, args);
-        let m = ls::Program::eval(p, &mut runtime);
-        m
+        runtime.fuel = max_steps;
+        Self::eval_runtime(p, &mut runtime)
+    }
+    /// Runs a program on an operating system of the caller's choice, like the in-memory
+    /// [`MemOs`](crate::dynamics::os::MemOs), which is handed back afterwards.
+    pub fn eval_os_with<O: VirtualOs>(
+        p: ls::Program, os: O, max_steps: Option<u64>,
+    ) -> (ds::Program, O) {
+        let mut runtime = ds::Runtime::with_os(os);
+        runtime.fuel = max_steps;
+        let m = Self::eval_runtime(p, &mut runtime);
+        (m, runtime.os)
+    }
+    /// Like `eval_os`, but runs the program on the bytecode VM.
+    pub fn eval_vm(
+        p: ls::Program, args: &[String], max_steps: Option<u64>, seed: Option<u64>,
+        permissions: Permissions,
+    ) -> ds::Program {
+        let mut input = std::io::stdin().lock();
+        let mut output = std::io::stdout();
+        let os = Self::real_os(&mut input, &mut output, args, seed, permissions);
+        Self::eval_vm_with(p, os, max_steps).0
+    }
+    pub fn eval_virtual_vm(
+        p: ls::Program, r: &mut dyn std::io::BufRead, w: &mut dyn std::io::Write, args: &[String],
+        max_steps: Option<u64>,
+    ) -> ds::Program {
+        let mut vm = Vm::new(r, w, args);
+        vm.fuel = max_steps;
+        vm.run(p)
+    }
+    pub fn eval_vm_with<O: VirtualOs>(
+        p: ls::Program, os: O, max_steps: Option<u64>,
+    ) -> (ds::Program, O) {
+        let mut vm = Vm::with_os(os);
+        vm.fuel = max_steps;
+        let m = vm.run(p);
+        (m, vm.os)
+    }
+    /// Like `eval_os`, but also counts the steps spent in each definition and builtin.
+    pub fn eval_os_profiled(
+        p: ls::Program, args: &[String], max_steps: Option<u64>, seed: Option<u64>,
+        permissions: Permissions,
+    ) -> (ds::Program, Profile) {
+        let mut input = std::io::stdin().lock();
+        let mut output = std::io::stdout();
+        let os = Self::real_os(&mut input, &mut output, args, seed, permissions);
+        let mut runtime = ds::Runtime::with_os(os);
+        runtime.fuel = max_steps;
+        runtime.profile = Some(Profile::new(&p.module));
+        let m = Self::eval_runtime(p, &mut runtime);
+        (m, runtime.profile.expect("profile is set before evaluation"))
+    }
+    fn real_os<'a>(
+        input: &'a mut dyn std::io::BufRead, output: &'a mut dyn std::io::Write,
+        args: &'a [String], seed: Option<u64>, permissions: Permissions,
+    ) -> RealOs<'a> {
+        let os = RealOs::new(input, output, args).with_permissions(permissions);
+        match seed {
+            Some(seed) => os.with_seed(seed),
+            None => os,
+        }
+    }
+    fn eval_runtime<O: VirtualOs>(p: ls::Program, runtime: &mut ds::Runtime<O>) -> ds::Program {
+        let name = p.module.name.clone();
+        let m = ls::Program::eval(p, runtime);
+        m.unwrap_or_else(|err| ds::Program { module: ds::Module { name }, entry: err.into() })
     }
 }
 
@@ -83,6 +189,7 @@ impl ZydecoFile {
 pub struct ZydecoExpr {
     pub ctx: Ctx,
     pub env: Env<ls::TermV, ds::SemVal>,
+    pub max_steps: Option<u64>,
 }
 
 impl ZydecoExpr {
@@ -94,8 +201,8 @@ impl ZydecoExpr {
         let mut input = std::io::empty();
         let mut output = std::io::sink();
         let mut runtime = ds::Runtime::new(&mut input, &mut output, &[]);
-        std.eval(&mut runtime);
-        Self { ctx, env: runtime.env }
+        std.eval(&mut runtime).expect("std evaluation failed");
+        Self { ctx, env: runtime.env, max_steps: None }
     }
     pub fn parse(source: &str) -> Result<Sp<ps::Term>, String> {
         TermSpanParser::new().parse(source, Lexer::new(source)).map_err(|e| e.to_string())
@@ -113,33 +220,35 @@ impl ZydecoExpr {
     pub fn link_computation(comp: &ss::TermComputation) -> ls::SynComp {
         comp.into()
     }
-    pub fn eval_value(&mut self, val: ls::SynVal) -> ds::SemVal {
+    pub fn eval_value(&mut self, val: ls::SynVal) -> Result<ds::SemVal, RuntimeError> {
         let mut input = std::io::empty();
         let mut output = std::io::sink();
         let mut runtime = ds::Runtime::new(&mut input, &mut output, &[]);
         runtime.env = self.env.clone();
         val.eval(&mut runtime)
     }
-    pub fn eval_ret_computation(&mut self, comp: ls::SynComp) -> ds::ProgKont {
+    pub fn eval_ret_computation(&mut self, comp: Sp<ls::SynComp>) -> ds::ProgKont {
         let mut input = std::io::empty();
         let mut output = std::io::sink();
         let mut runtime = ds::Runtime::new(&mut input, &mut output, &[]);
         runtime.env = self.env.clone();
+        runtime.fuel = self.max_steps;
         let m = ls::Program::eval(
             ls::Program { module: ls::Module::pure(None), entry: comp },
             &mut runtime,
         );
         self.env = runtime.env;
-        m.entry
+        m.map(|m| m.entry).unwrap_or_else(ProgKont::Error)
     }
-    pub fn eval_os(&mut self, comp: ls::SynComp, args: &[String]) -> ds::Program {
+    pub fn eval_os(&mut self, comp: Sp<ls::SynComp>, args: &[String]) -> ds::Program {
         let mut input = std::io::stdin().lock();
         let mut output = std::io::stdout();
         let p = ls::Program { module: ls::Module::pure(None), entry: comp };
         let mut runtime = ds::Runtime::new(&mut input, &mut output, args);
         runtime.env = self.env.clone();
+        runtime.fuel = self.max_steps;
         let m = ls::Program::eval(p, &mut runtime);
         self.env = runtime.env;
-        m
+        m.unwrap_or_else(|err| ds::Program { module: ds::Module { name: None }, entry: err.into() })
     }
 }
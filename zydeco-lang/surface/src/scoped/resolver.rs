@@ -258,7 +258,7 @@ impl Resolve for NameRef<VarName> {
         for head in state.heads.clone() {
             if let Some(his_scope) = state.ctx.lookup.get(&head) {
                 if let Some(def_id) = his_scope.get(name) {
-                    found_in_his = Some(def_id.clone());
+                    found_in_his = Some(*def_id);
                     break;
                 }
             }
@@ -286,7 +286,7 @@ impl Resolve for PatternId {
         state.span_enter(info.clone());
         let pattern = (&state.textual_ctx.patterns[*self]).resolve(state)?;
         state.span_exit();
-        Ok(state.ctx.pattern(self.clone(), pattern))
+        Ok(state.ctx.pattern(*self, pattern))
     }
 }
 impl Resolve for Pattern {
@@ -417,7 +417,7 @@ impl Resolve for TermId {
         state.span_enter(info.clone());
         let term = term.resolve(state)?;
         state.span_exit();
-        Ok(state.ctx.term(self.clone(), term))
+        Ok(state.ctx.term(*self, term))
     }
 }
 impl Resolve for Term<NameRef<VarName>> {
@@ -727,7 +727,7 @@ impl Resolve for Modifiers<ts::Declaration> {
                 for pub_decl in state.ctx.current_pub.clone() {
                     match pub_decl {
                         PublicDec::Def(name) => {
-                            var_map.insert(name.clone(), in_modules.get(&name).unwrap().clone());
+                            var_map.insert(name.clone(), *in_modules.get(&name).unwrap());
                         }
                         PublicDec::Module(name) => {
                             state.retain_scope(&module_path, &name, &top_lookup)
@@ -740,7 +740,7 @@ impl Resolve for Modifiers<ts::Declaration> {
                                 UseEnum::Name(name) => {
                                     if let Some(from_scope) = state.ctx.lookup.get(&path) {
                                         if let Some(id) = from_scope.get(&name) {
-                                            var_map.insert(name.clone(), id.clone());
+                                            var_map.insert(name.clone(), *id);
                                             // only copy the used variable to the target scope
                                         }
                                     }
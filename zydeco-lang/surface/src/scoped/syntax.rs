@@ -62,14 +62,14 @@ pub struct Ctx {
 
 impl Ctx {
     pub fn pattern(&mut self, id: PatternId, pattern: Pattern) -> PatternId {
-        let res = self.patterns.insert(id.clone(), pattern);
+        let res = self.patterns.insert(id, pattern);
         if res.is_some() {
             panic!("duplicate pattern inserted")
         }
         id
     }
     pub fn term(&mut self, id: TermId, term: Term<DefId>) -> TermId {
-        let res = self.terms.insert(id.clone(), term);
+        let res = self.terms.insert(id, term);
         if res.is_some() {
             panic!("duplicate term inserted")
         }
@@ -67,6 +67,9 @@ pub enum Tok<'input> {
 
     #[regex(r"[\+-]?[0-9]+")]
     NumLit(&'input str),
+    #[regex(r"[\+-]?[0-9]+\.[0-9]+([eE][\+-]?[0-9]+)?")]
+    #[regex(r"[\+-]?[0-9]+[eE][\+-]?[0-9]+")]
+    FloatLit(&'input str),
     #[regex(r#""[^"\\]*(?:\\.[^"\\]*)*""#)]
     StrLit(&'input str),
     #[regex(r#"'([ -~]|\\[nrt'|(\\)])'"#)]
@@ -144,6 +147,7 @@ impl<'input> Display for Tok<'input> {
             Tok::At => write!(f, "@"),
             Tok::Pack => write!(f, "pack"),
             Tok::NumLit(s) => write!(f, "NumLiteral({})", s),
+            Tok::FloatLit(s) => write!(f, "FloatLiteral({})", s),
             Tok::StrLit(s) => write!(f, "StrLiteral({})", s),
             Tok::CharLit(s) => write!(f, "CharLiteral({})", s),
             Tok::ParenOpen => write!(f, "("),
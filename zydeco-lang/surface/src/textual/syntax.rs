@@ -164,6 +164,7 @@ pub struct Destructor(pub TermId, pub DtorName);
 #[derive(From, Clone, Debug)]
 pub enum Literal {
     Int(i64),
+    Float(f64),
     String(String),
     Char(char),
 }
@@ -438,18 +439,12 @@ impl Ctx {
 }
 
 type FileId = usize;
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Default)]
 pub struct ModuleTree {
     pub root: (String, Option<FileId>),
     pub children: Vec<ModuleTree>,
 }
 
-impl Default for ModuleTree {
-    fn default() -> Self {
-        Self { root: (String::new(), None), children: vec![] }
-    }
-}
-
 impl ModuleTree {
     pub fn new(root: String) -> Self {
         Self { root: (root, None), children: vec![] }
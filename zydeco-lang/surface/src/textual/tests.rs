@@ -15,3 +15,10 @@ fn parsing_2() {
     let _t =
         parser::TopLevelParser::new().parse(&source, &mut ctx, lexer::Lexer::new(&source)).unwrap();
 }
+#[test]
+fn parsing_3() {
+    let source = "main { let x = -1.5e3 in let y = 0.25 in ! exit 0 } end";
+    let mut ctx = syntax::Ctx::default();
+    let _t =
+        parser::TopLevelParser::new().parse(&source, &mut ctx, lexer::Lexer::new(&source)).unwrap();
+}
@@ -8,10 +8,42 @@
  *   are not executed
  *
  * - io/ holds tests that need custom I/O mocking to execute.
+ *
+ * - runtime-error/ holds tests that typecheck but must stop with a
+ *   runtime error instead of an exit code
+ *
+ * - tail-call/ holds loops that must run in constant stack space, and
+ *   one that must stop with a stack overflow
+ *
+ * - effect/ holds tests that use effect handlers or delimited
+ *   continuations, which only the evaluator runs; the bytecode VM must
+ *   refuse them
+ *
+ * Every program that runs is executed by both the evaluator and the
+ * bytecode VM, which must agree on the outcome and the output. It is
+ * also run after the optimization passes and after the CPS transform,
+ * which must not change how it exits, and compiled to JavaScript, C and WebAssembly and run when `node` and
+ * `cc` are installed.
  */
 
-use std::path::PathBuf;
-use zydeco_lang::{dynamics::syntax as ds, prelude::*, statics::syntax as ss, zydeco::ZydecoFile};
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use zydeco_lang::{
+    dynamics::{
+        err::RuntimeErrorItem,
+        os::{MemOs, RealOs, VirtualOs},
+        permission::Permissions,
+        syntax as ds,
+    },
+    library::syntax as ls,
+    prelude::*,
+    statics::syntax as ss,
+    zydeco::ZydecoFile,
+};
 
 fn wrapper<T>(r: Result<T, String>) {
     match r {
@@ -40,18 +72,162 @@ fn till_check(base: &str, f: &[&str]) -> Result<Sp<ss::Program>, String> {
     Ok(m)
 }
 
+/// Runs a linked program on the host on the evaluator or the bytecode VM, with `input` as stdin.
+fn run_real(
+    m: ls::Program, input: &str, args: &[String], permissions: &Permissions, vm: bool,
+) -> (ds::ProgKont, String) {
+    let (mut input, mut output) = (std::io::Cursor::new(input), Vec::new());
+    let os = RealOs::new(&mut input, &mut output, args).with_permissions(permissions.clone());
+    let entry = match vm {
+        true => ZydecoFile::eval_vm_with(m, os, None).0.entry,
+        false => ZydecoFile::eval_os_with(m, os, None).0.entry,
+    };
+    (entry, String::from_utf8(output).unwrap())
+}
+
+/// Runs a program from `tests/io` on the evaluator and on the bytecode VM, each on a fresh `os()`,
+/// and hands back what each left behind.
+fn run_in_memory(file: &str, os: impl Fn() -> MemOs) -> [(ds::ProgKont, MemOs); 2] {
+    let m = till_check("tests/io", &[file]).unwrap();
+    let m = ZydecoFile::link(m.inner).unwrap();
+    let (eval, eval_os) = ZydecoFile::eval_os_with(m.clone(), os(), None);
+    let (vm, vm_os) = ZydecoFile::eval_vm_with(m, os(), None);
+    [(eval.entry, eval_os), (vm.entry, vm_os)]
+}
+
+/// Runs a linked program on the evaluator, on the bytecode VM and after the CPS transform,
+/// and checks that they agree.
+fn eval_virtual(
+    m: ls::Program, input: &str, args: &[String], permissions: &Permissions,
+) -> (ds::ProgKont, String) {
+    fn outcome(kont: &ds::ProgKont) -> String {
+        match kont {
+            ds::ProgKont::Ret(_) => "ret".to_string(),
+            ds::ProgKont::ExitCode(x) => format!("exit {}", x),
+            ds::ProgKont::Error(e) => format!("error {}", e),
+            ds::ProgKont::OutOfFuel => "out of fuel".to_string(),
+        }
+    }
+    let (eval, eval_output) = run_real(m.clone(), input, args, permissions, false);
+    let (vm, vm_output) = run_real(m.clone(), input, args, permissions, true);
+    assert_eq!(outcome(&eval), outcome(&vm), "The VM disagrees with the evaluator.");
+    assert_eq!(eval_output, vm_output, "The VM output differs from the evaluator's.");
+    let expected = match &eval {
+        ds::ProgKont::ExitCode(x) => x & 0xff,
+        ds::ProgKont::Error(_) => 1,
+        ds::ProgKont::Ret(_) | ds::ProgKont::OutOfFuel => 0,
+    };
+    let compiled = [
+        ("JavaScript", run_js(&m, input, args)),
+        ("C", run_c(&m, input, args)),
+        ("WebAssembly", run_wasm(&m, input, args)),
+    ];
+    for (target, res) in compiled {
+        if let Some((code, compiled_output)) = res {
+            assert_eq!(expected, code, "The {} program exits differently.", target);
+            assert_eq!(eval_output, compiled_output, "The {} program prints differently.", target);
+        }
+    }
+    let opt = ZydecoFile::opt(m.clone(), |_, _| {});
+    let (opt, opt_output) = run_real(opt, input, args, permissions, false);
+    assert_eq!(outcome(&eval), outcome(&opt), "The optimized program exits differently.");
+    assert_eq!(eval_output, opt_output, "The optimized program prints differently.");
+    // Note: the CPS transform turns `do` continuations into thunks, so an error keeps its
+    // message and location but has no `do` frames left for the backtrace
+    let located = |kont: &ds::ProgKont| match kont {
+        ds::ProgKont::Error(e) => format!("error {} ({})", e.item, e.info),
+        kont => outcome(kont),
+    };
+    let (cps, cps_output) = run_real(ZydecoFile::cps(m), input, args, permissions, false);
+    assert_eq!(located(&eval), located(&cps), "The CPS-transformed program exits differently.");
+    assert_eq!(eval_output, cps_output, "The CPS-transformed program prints differently.");
+    (eval, eval_output)
+}
+
+fn temp_path(ext: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("zydeco-test-{}-{}{}", std::process::id(), n, ext))
+}
+
+/// Runs a command with the given input, returning the exit code and the output.
+fn run_process(mut command: Command, input: &str) -> (i32, String) {
+    let mut child = (command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()))
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let res = child.wait_with_output().unwrap();
+    (res.status.code().unwrap(), String::from_utf8(res.stdout).unwrap())
+}
+
+/// Compiles a program to JavaScript and runs it with `node`, returning the exit code and
+/// the output, or `None` if `node` is not installed.
+fn run_js(m: &ls::Program, input: &str, args: &[String]) -> Option<(i32, String)> {
+    Command::new("node").arg("--version").output().ok()?;
+    let path = temp_path(".js");
+    std::fs::write(&path, ZydecoFile::emit_js(m).unwrap()).unwrap();
+    let mut command = Command::new("node");
+    command.arg(&path).args(args);
+    let res = run_process(command, input);
+    std::fs::remove_file(&path).unwrap();
+    Some(res)
+}
+
+/// Compiles a program to C and builds it with `cc`, returning the exit code and the output
+/// of running it, or `None` if `cc` is not installed.
+fn run_c(m: &ls::Program, input: &str, args: &[String]) -> Option<(i32, String)> {
+    Command::new("cc").arg("--version").output().ok()?;
+    let (source, exe) = (temp_path(".c"), temp_path(""));
+    std::fs::write(&source, ZydecoFile::emit_c(m).unwrap()).unwrap();
+    let status = Command::new("cc").arg("-o").arg(&exe).arg(&source).status().unwrap();
+    assert!(status.success(), "The C program does not compile.");
+    let mut command = Command::new(&exe);
+    command.args(args);
+    let res = run_process(command, input);
+    std::fs::remove_file(&source).unwrap();
+    std::fs::remove_file(&exe).unwrap();
+    Some(res)
+}
+
+/// Compiles a program to WebAssembly and runs it with the WASI support of `node`, returning
+/// the exit code and the output, or `None` if `node` is not installed.
+fn run_wasm(m: &ls::Program, input: &str, args: &[String]) -> Option<(i32, String)> {
+    const HOST: &str = r#"
+        const { WASI } = require('node:wasi');
+        const fs = require('node:fs');
+        const wasi = new WASI({
+            version: 'preview1',
+            args: process.argv.slice(2),
+            env: process.env,
+            preopens: { '/': '/', '.': '.' },
+            returnOnExit: true,
+        });
+        const wasm = new WebAssembly.Module(fs.readFileSync(process.argv[2]));
+        const instance = new WebAssembly.Instance(wasm, wasi.getImportObject());
+        process.exitCode = wasi.start(instance);
+    "#;
+    Command::new("node").arg("--version").output().ok()?;
+    let (host, module) = (temp_path(".cjs"), temp_path(".wasm"));
+    std::fs::write(&host, HOST).unwrap();
+    std::fs::write(&module, ZydecoFile::emit_wasm(m).unwrap()).unwrap();
+    let mut command = Command::new("node");
+    command.arg("--no-warnings").arg(&host).arg(&module).args(args);
+    let res = run_process(command, input);
+    std::fs::remove_file(&host).unwrap();
+    std::fs::remove_file(&module).unwrap();
+    Some(res)
+}
+
 fn test_template(parent: &'static str, run: bool, f: &[&str]) -> Result<(), String> {
     let m = till_check(parent, f)?;
 
     if run {
         let m = ZydecoFile::link(m.inner)?;
 
-        let mut input = std::io::empty();
-        let mut output = std::io::sink();
-        let ds::ProgKont::ExitCode(exit_code) =
-            ZydecoFile::eval_virtual_os(m, &mut input, &mut output, &[]).entry
-        else {
-            Err("Expected ExitCode".to_string())?
+        let exit_code = match eval_virtual(m, "", &[], &Permissions::none()).0 {
+            ds::ProgKont::ExitCode(exit_code) => exit_code,
+            ds::ProgKont::Error(e) => Err(format!("Runtime Error: {}", e))?,
+            ds::ProgKont::Ret(_) | ds::ProgKont::OutOfFuel => Err("Expected ExitCode".to_string())?,
         };
         if exit_code != 0 {
             Err(format!("Non-zero exit code: {}", exit_code))?
@@ -84,21 +260,62 @@ fn io_test(f: &[&str], iomatch: &IOMatch) -> Result<(), String> {
     let m = till_check("tests/io", f)?;
     let m = ZydecoFile::link(m.inner)?;
 
-    let mut input = std::io::Cursor::new(iomatch.input.as_str());
-    let mut output: Vec<u8> = Vec::new();
-    let args = iomatch.args.as_slice();
-
-    let ds::ProgKont::ExitCode(exit_code) =
-        ZydecoFile::eval_virtual_os(m, &mut input, &mut output, args).entry
-    else {
-        Err("Expected ExitCode".to_string())?
+    // Note: the programs here are trusted to touch the files, the environment and other programs
+    let (entry, output) = eval_virtual(m, &iomatch.input, &iomatch.args, &Permissions::all());
+    let exit_code = match entry {
+        ds::ProgKont::ExitCode(exit_code) => exit_code,
+        ds::ProgKont::Error(e) => Err(format!("Runtime Error: {}", e))?,
+        ds::ProgKont::Ret(_) | ds::ProgKont::OutOfFuel => Err("Expected ExitCode".to_string())?,
     };
     if exit_code != 0 {
         Err(format!("Non-zero exit code: {}", exit_code))?
     }
 
-    let s = std::str::from_utf8(&output).unwrap();
-    assert_eq!(iomatch.correct_answer, s, "Output is not correct.");
+    assert_eq!(iomatch.correct_answer, output, "Output is not correct.");
+
+    Ok(())
+}
+
+fn runtime_error_test(f: &[&str]) -> Result<(), String> {
+    let m = till_check("tests/runtime-error", f)?;
+    let m = ZydecoFile::link(m.inner)?;
+
+    match eval_virtual(m, "", &[], &Permissions::none()).0 {
+        ds::ProgKont::Error(_) => Ok(()),
+        _ => Err("Expected a runtime error".to_string()),
+    }
+}
+
+fn effect_test(f: &[&str], iomatch: &IOMatch) -> Result<(), String> {
+    evaluator_test("tests/effect", f, iomatch)
+}
+
+fn delimcc_doc_test(f: &[&str], iomatch: &IOMatch) -> Result<(), String> {
+    evaluator_test("../docs/monadic_interpreter", f, iomatch)
+}
+
+fn evaluator_test(parent: &'static str, f: &[&str], iomatch: &IOMatch) -> Result<(), String> {
+    let m = till_check(parent, f)?;
+    let m = ZydecoFile::link(m.inner)?;
+
+    let (input, args) = (&iomatch.input, &iomatch.args);
+    match run_real(m.clone(), input, args, &Permissions::none(), true).0 {
+        ds::ProgKont::Error(e) if matches!(e.item, RuntimeErrorItem::NoHandlers { .. }) => {}
+        _ => Err("Expected the VM to refuse the handlers".to_string())?,
+    }
+    match run_real(ZydecoFile::cps(m.clone()), input, args, &Permissions::none(), false).0 {
+        ds::ProgKont::Error(e) if matches!(e.item, RuntimeErrorItem::NoHandlers { .. }) => {}
+        _ => Err("Expected the CPS-transformed program to refuse the handlers".to_string())?,
+    }
+    let (entry, output) = run_real(m, input, args, &Permissions::none(), false);
+    match entry {
+        ds::ProgKont::ExitCode(0) => {}
+        ds::ProgKont::ExitCode(exit_code) => Err(format!("Non-zero exit code: {}", exit_code))?,
+        ds::ProgKont::Error(e) => Err(format!("Runtime Error: {}", e))?,
+        ds::ProgKont::Ret(_) | ds::ProgKont::OutOfFuel => Err("Expected ExitCode".to_string())?,
+    }
+
+    assert_eq!(iomatch.correct_answer, output, "Output is not correct.");
 
     Ok(())
 }
@@ -162,6 +379,12 @@ mod batch_tests {
     mk_test!(batch_test, varidic, &["varidic.zy"]);
     mk_test!(batch_test, trans, &["trans.zy"]);
     mk_test!(batch_test, cbpv_monadic, &["cbpv-monadic.zy"]);
+    mk_test!(batch_test, bigint, &["bigint.zy"]);
+    mk_test!(batch_test, checked, &["checked.zy"]);
+    mk_test!(batch_test, float, &["float.zy"]);
+    mk_test!(batch_test, r#ref, &["ref.zy"]);
+    mk_test!(batch_test, array, &["array.zy"]);
+    mk_test!(batch_test, map, &["map.zy"]);
 }
 mod io_tests {
     use super::*;
@@ -197,6 +420,276 @@ mod io_tests {
             correct_answer: "hello world\n5 4 3 2 1".to_string(),
         }
     );
+
+    mk_test!(
+        io_test,
+        random,
+        &["random.zy"],
+        &IOMatch {
+            args: vec![],
+            input: String::new(),
+            correct_answer: [
+                "22115123236532336441",
+                "6 7 3 1 0 9 8 5 4 2 ",
+                "-5641428018500444605",
+                "5347863621891512693",
+                "22115123236532336441",
+                "",
+            ]
+            .join("\n"),
+        }
+    );
+
+    mk_test!(
+        io_test,
+        clock,
+        &["clock.zy"],
+        &IOMatch { args: vec![], input: String::new(), correct_answer: "True\nTrue\n".to_string() }
+    );
+
+    #[test]
+    fn clock_in_memory() {
+        let os = || MemOs::new().with_clock(1_700_000_000_000);
+        for (entry, os) in run_in_memory("clock.zy", os) {
+            assert!(matches!(entry, ds::ProgKont::ExitCode(0)));
+            assert_eq!(os.output_str(), "True\nTrue\n");
+            // Note: the fake clock moves exactly as long as the program sleeps
+            assert_eq!(os.error, b"25\n");
+            assert_eq!(os.clock, 1_700_000_000_025);
+        }
+    }
+
+    #[test]
+    fn files() {
+        // Note: every run of the program works in the same fresh directory
+        let dir = temp_path("");
+        std::fs::create_dir(&dir).unwrap();
+        let dir = dir.to_str().unwrap().to_string();
+        let missing = format!("{}/missing: no such file or directory\n", dir);
+        let iomatch = IOMatch {
+            args: vec![dir.clone()],
+            input: String::new(),
+            correct_answer: format!(
+                "hello\nworld\nTrue\nFalse\nempty.txt\nnotes.txt\n{}{}{}",
+                missing, missing, "environment variable ZYDECO_TEST_UNSET is not set\n"
+            ),
+        };
+        let res = io_test(&["files.zy"], &iomatch);
+        // Note: what the program writes to stderr goes wherever the embedder asks
+        let m = ZydecoFile::link(till_check("tests/io", &["files.zy"]).unwrap().inner).unwrap();
+        let (mut input, mut output, mut error) = (std::io::empty(), Vec::new(), Vec::new());
+        let os = RealOs::new(&mut input, &mut output, &iomatch.args).with_error(&mut error);
+        let entry = ZydecoFile::eval_os_with(m, os.with_permissions(Permissions::all()), None).0;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(entry.entry, ds::ProgKont::ExitCode(0)));
+        assert_eq!(error, b"done\n");
+        wrapper(res)
+    }
+
+    #[test]
+    fn files_in_memory() {
+        let os = || {
+            (MemOs::new().with_args(&["/work"]).with_dir("/work"))
+                .with_env("ZYDECO_TEST_UNSET", "set after all\n")
+        };
+        let missing = "/work/missing: no such file or directory\n";
+        let expected = format!(
+            "hello\nworld\nTrue\nFalse\nempty.txt\nnotes.txt\n{}{}set after all\n",
+            missing, missing
+        );
+        for (entry, os) in run_in_memory("files.zy", os) {
+            assert!(matches!(entry, ds::ProgKont::ExitCode(0)));
+            assert_eq!(os.exit_code, Some(0));
+            assert_eq!(os.output_str(), expected);
+            assert_eq!(os.error, b"done\n");
+            assert_eq!(os.files["/work/notes.txt"], b"hello\nworld\n");
+            assert_eq!(os.files.len(), 2);
+        }
+    }
+
+    const SPAWN: &str = "3\nout\nerr\n127\nzydeco-no-such-program: no such file or directory\n";
+
+    #[test]
+    #[cfg(unix)]
+    fn spawn() {
+        let m = till_check("tests/io", &["spawn.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        for vm in [false, true] {
+            let (entry, output) = run_real(m.clone(), "", &[], &Permissions::all(), vm);
+            assert!(matches!(entry, ds::ProgKont::ExitCode(0)));
+            assert_eq!(output, SPAWN);
+        }
+    }
+
+    #[test]
+    fn spawn_in_memory() {
+        let os = || MemOs::new().with_program("sh", 3, "out\n", "err\n");
+        for (entry, os) in run_in_memory("spawn.zy", os) {
+            assert!(matches!(entry, ds::ProgKont::ExitCode(0)));
+            assert_eq!(os.output_str(), SPAWN);
+        }
+    }
+
+    #[test]
+    fn permissions() {
+        use zydeco_lang::dynamics::permission::{Access, Denied};
+        let work = Access::Under(vec!["/work".into()]);
+        let allows = |path: &str| work.allows(path, |path| MemOs::new().resolve_path(path));
+        assert!(allows("/work/notes.txt") && allows("/work/./a/../b"));
+        assert!(!allows("/workshop") && !allows("/work/../etc/passwd") && !allows("work/notes"));
+        // Note: the files are there, but the environment is not
+        let permissions = Permissions { read: work.clone(), write: work, ..Permissions::none() };
+        let os = || {
+            let os = MemOs::new().with_args(&["/work"]).with_dir("/work");
+            os.with_permissions(permissions.clone())
+        };
+        let missing = "/work/missing: no such file or directory\n";
+        let expected =
+            format!("hello\nworld\nTrue\nFalse\nempty.txt\nnotes.txt\n{}{}", missing, missing);
+        for (entry, os) in run_in_memory("files.zy", os) {
+            let ds::ProgKont::Error(e) = entry else { panic!("expected a runtime error") };
+            let RuntimeErrorItem::PermissionDenied { name, denied } = &e.item else {
+                panic!("expected a denial, got: {}", e)
+            };
+            assert_eq!(*name, "get_env");
+            assert_eq!(*denied, Denied::Env("ZYDECO_TEST_UNSET".to_string()));
+            assert_eq!(os.output_str(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn permissions_follow_links() {
+        use std::os::unix::fs::symlink;
+        use zydeco_lang::dynamics::permission::Access;
+        let dir = temp_path("");
+        let (work, outside) = (dir.join("work"), dir.join("outside"));
+        std::fs::create_dir_all(&work).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        std::fs::write(work.join("notes"), "").unwrap();
+        symlink(&outside, work.join("out")).unwrap();
+        symlink(outside.join("missing"), work.join("dangling")).unwrap();
+        symlink(work.join("notes"), outside.join("in")).unwrap();
+        let access = Access::Under(vec![work.clone()]);
+        let (mut input, mut output) = (std::io::empty(), std::io::sink());
+        let os = RealOs::new(&mut input, &mut output, &[]);
+        let allows =
+            |path: &str| access.allows(dir.join(path).to_str().unwrap(), |p| os.resolve_path(p));
+        // Note: `out/..` is the parent of the real `outside`, which is where `work` is
+        let allowed = ["work/notes", "work/new", "outside/in", "work/out/../work/notes"];
+        let allowed = allowed.map(allows);
+        let denied = ["work/out/secret", "work/out/new", "work/dangling"].map(allows);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(allowed, [true; 4]);
+        assert_eq!(denied, [false; 3]);
+    }
+}
+mod runtime_error_tests {
+    use super::*;
+    mk_test!(runtime_error_test, div_by_zero, &["div-by-zero.zy"]);
+    mk_test!(runtime_error_test, bigint_div_by_zero, &["bigint-div-by-zero.zy"]);
+    mk_test!(runtime_error_test, floor, &["floor.zy"]);
+    mk_test!(runtime_error_test, array_index, &["array-index.zy"]);
+    mk_test!(runtime_error_test, array_too_long, &["array-too-long.zy"]);
+    mk_test!(runtime_error_test, str_to_int, &["str-to-int.zy"]);
+    mk_test!(runtime_error_test, fatal, &["fatal.zy"]);
+    mk_test!(runtime_error_test, random_range, &["random-range.zy"]);
+    mk_test!(runtime_error_test, spawn, &["spawn.zy"]);
+
+    #[test]
+    fn panic() {
+        let m = till_check("tests/runtime-error", &["panic.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let (entry, output) = eval_virtual(m, "", &[], &Permissions::none());
+        assert!(matches!(entry, ds::ProgKont::ExitCode(1)));
+        assert_eq!(output, "Program panicked with message: 42\n");
+    }
+}
+mod effect_tests {
+    use super::*;
+    mk_test!(
+        effect_test,
+        console,
+        &["console.zy"],
+        &IOMatch {
+            args: vec![],
+            input: "world\n".to_string(),
+            correct_answer: "Who is there?\nHello, world\n".to_string(),
+        }
+    );
+    mk_test!(
+        effect_test,
+        state,
+        &["state.zy"],
+        &IOMatch { args: vec![], input: String::new(), correct_answer: String::new() }
+    );
+    mk_test!(
+        effect_test,
+        choice,
+        &["choice.zy"],
+        &IOMatch { args: vec![], input: String::new(), correct_answer: "2\n".to_string() }
+    );
+    mk_test!(
+        effect_test,
+        delimcc,
+        &["delimcc.zy"],
+        &IOMatch {
+            args: vec![],
+            input: String::new(),
+            correct_answer: "42\n23\n200\n".to_string(),
+        }
+    );
+    mk_test!(
+        delimcc_doc_test,
+        cbv_delimcc,
+        &["cbv-delimcc.zy"],
+        &IOMatch {
+            args: vec![],
+            input: String::new(),
+            correct_answer: "true\ntype error\nfree variable y\n".to_string(),
+        }
+    );
+
+    #[test]
+    fn unhandled() {
+        let Err(e) = till_check("tests/effect", &["unhandled.zy"]) else {
+            panic!("Expected a type error")
+        };
+        assert!(e.contains("Effect Choice is performed where no handler handles it"), "{}", e);
+    }
+
+    #[test]
+    fn missing_prompt() {
+        let m = till_check("tests/effect", &["missing-prompt.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let ds::ProgKont::Error(e) = run_real(m, "", &[], &Permissions::none(), false).0 else {
+            panic!("Expected a runtime error")
+        };
+        assert!(matches!(e.item, RuntimeErrorItem::MissingPrompt { .. }), "{}", e);
+    }
+}
+mod fuel_tests {
+    use super::*;
+
+    #[test]
+    fn divergent_loop() {
+        let m = till_check("tests/check-only", &["loop.zydeco"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let mut input = std::io::empty();
+        let mut output = std::io::sink();
+        let res = ZydecoFile::eval_virtual_os(m, &mut input, &mut output, &[], Some(100_000));
+        assert!(matches!(res.entry, ds::ProgKont::OutOfFuel));
+    }
+
+    #[test]
+    fn divergent_loop_vm() {
+        let m = till_check("tests/check-only", &["loop.zydeco"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let mut input = std::io::empty();
+        let mut output = std::io::sink();
+        let res = ZydecoFile::eval_virtual_vm(m, &mut input, &mut output, &[], Some(100_000));
+        assert!(matches!(res.entry, ds::ProgKont::OutOfFuel));
+    }
 }
 mod doc_tests {
     use super::*;
@@ -211,4 +704,207 @@ mod doc_tests {
     mk_test!(doc_test, lense_prism_optics, &["8-lense-prism-optics.zy"], false);
 }
 
-mod custom_tests {}
+mod custom_tests {
+    use super::*;
+    use zydeco_lang::dynamics::err::Backtrace;
+
+    #[test]
+    fn backtrace() {
+        let m = till_check("tests/runtime-error", &["backtrace.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let mut input = std::io::empty();
+        let mut output = std::io::sink();
+        let ds::ProgKont::Error(e) =
+            ZydecoFile::eval_virtual_os(m, &mut input, &mut output, &[], None).entry
+        else {
+            panic!("Expected a runtime error")
+        };
+        let backtrace = format!("{}", Backtrace(&e.stack));
+        let frames: Vec<_> = backtrace.lines().collect();
+        assert_eq!(frames.len(), 2, "{}", backtrace);
+        assert!(frames[0].contains("do x <- ...") && frames[0].contains("backtrace.zy:2:"));
+        assert!(frames[1].contains("do y <- ...") && frames[1].contains("backtrace.zy:7:"));
+    }
+
+    #[test]
+    fn debugger() {
+        use zydeco_lang::dynamics::{
+            debug::{Breakpoint, Debugger, Pause},
+            os::MemOs,
+        };
+        let has_local = |debugger: &Debugger<MemOs>, name: &str| {
+            debugger.locals().iter().any(|(x, _)| x.name() == name)
+        };
+        let line = |debugger: &Debugger<MemOs>| {
+            let info = &debugger.current.as_ref().unwrap().info;
+            info.get_span2().unwrap().0.line
+        };
+
+        let m = till_check("tests/nonzero-exit-code", &["even-odd-data.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let runtime = ds::Runtime::with_os(MemOs::new());
+        let mut debugger = Debugger::new(m, runtime).unwrap();
+        let bp = Breakpoint::Line { file: Some("even-odd-data.zy".to_string()), line: 27 };
+        debugger.breakpoints.push(bp);
+        assert!(matches!(debugger.resume(), Pause::Breakpoint(0)));
+        assert!(has_local(&debugger, "odd") && !has_local(&debugger, "b"));
+        // stepping over `do b <- ...` runs `!odd` to completion
+        assert!(matches!(debugger.step_over(), Pause::Stepped));
+        assert!(has_local(&debugger, "b"));
+        assert_eq!(line(&debugger), 28);
+        assert!(matches!(debugger.resume(), Pause::Finished));
+        assert!(matches!(debugger.outcome, Some(ds::ProgKont::ExitCode(0))));
+
+        let m = till_check("tests/runtime-error", &["backtrace.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let runtime = ds::Runtime::with_os(MemOs::new());
+        let mut debugger = Debugger::new(m, runtime).unwrap();
+        assert!(debugger.has_def("inv"));
+        debugger.breakpoints.push(Breakpoint::Def("inv".to_string()));
+        assert!(matches!(debugger.resume(), Pause::Breakpoint(0)));
+        assert_eq!(line(&debugger), 2);
+        assert!(matches!(debugger.runtime.stack.back(), Some(ds::SemComp::App(_))));
+        assert!(matches!(debugger.resume(), Pause::Finished));
+        assert!(matches!(debugger.outcome, Some(ds::ProgKont::Error(_))));
+    }
+
+    #[test]
+    fn profile() {
+        use zydeco_lang::{
+            dynamics::{profile::Profile, Eval},
+            library::syntax as ls,
+        };
+        let m = till_check("tests/nonzero-exit-code", &["interpreter.zydeco"]).unwrap();
+        let m: ls::Program = ZydecoFile::link(m.inner).unwrap();
+        let mut input = std::io::empty();
+        let mut output = std::io::sink();
+        let mut runtime = ds::Runtime::new(&mut input, &mut output, &[]);
+        runtime.profile = Some(Profile::new(&m.module));
+        let m = ls::Program::eval(m, &mut runtime).unwrap();
+        assert!(matches!(m.entry, ds::ProgKont::ExitCode(0)));
+        let profile = runtime.profile.unwrap();
+        let flat = profile.flat();
+        let entry = |name: &str| flat.iter().find(|e| e.name.as_ref() == name).unwrap();
+        assert_eq!(flat.iter().map(|e| e.steps).sum::<u64>(), profile.total());
+        assert_eq!(entry("interp").calls, 5);
+        assert!(!entry("interp").builtin && entry("exit").builtin);
+        let collapsed = profile.collapsed();
+        assert!(collapsed.lines().any(|line| line.starts_with("main;interp;lookup ")));
+    }
+
+    #[test]
+    fn optimize() {
+        use zydeco_lang::backend::opt::Pass;
+        let m = till_check("tests/nonzero-exit-code", &["add.zy"]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let defs = m.module.define.len();
+        let mut passes = Vec::new();
+        let m = ZydecoFile::opt(m, |pass, _| passes.push(pass));
+        assert_eq!(passes, Pass::ALL);
+        // Note: `exit` is inlined into the entry, which then uses no std definition
+        assert!(
+            m.module.define.is_empty(),
+            "{} of {} definitions left",
+            m.module.define.len(),
+            defs
+        );
+        assert!(m.fmt().contains("prim(exit/1) 0"));
+    }
+}
+mod stack_tests {
+    // Note: the looping programs read how many times to iterate from stdin; the full-size
+    // runs are slow without optimizations, so they only run with `--ignored`
+    use super::*;
+    use zydeco_lang::{backend::vm::Vm, dynamics::err::RuntimeErrorItem, dynamics::Eval};
+
+    const SMALL: &str = "10000\n";
+    const LARGE: &str = "10000000\n";
+
+    /// Runs a program on both the evaluator and the bytecode VM with at most `max_stack`
+    /// frames on the stack.
+    fn eval_bounded(f: &str, input: &str, max_stack: usize) -> Vec<ds::ProgKont> {
+        let m = till_check("tests/tail-call", &[f]).unwrap();
+        let m = ZydecoFile::link(m.inner).unwrap();
+        let mut output = std::io::sink();
+        let mut input_eval = std::io::Cursor::new(input);
+        let mut runtime = ds::Runtime::new(&mut input_eval, &mut output, &[]);
+        runtime.max_stack = Some(max_stack);
+        let eval = m.clone().eval(&mut runtime).map_or_else(ds::ProgKont::Error, |p| p.entry);
+        vec![eval, vm_bounded(m, input, max_stack)]
+    }
+
+    fn vm_bounded(m: ls::Program, input: &str, max_stack: usize) -> ds::ProgKont {
+        let (mut input, mut output) = (std::io::Cursor::new(input), std::io::sink());
+        let mut vm = Vm::new(&mut input, &mut output, &[]);
+        vm.max_stack = Some(max_stack);
+        vm.run(m).entry
+    }
+
+    /// Checks that a program exits successfully without ever holding more than a few frames.
+    fn constant_stack(f: &str, input: &str) {
+        for kont in eval_bounded(f, input, 64) {
+            expect_success(kont);
+        }
+    }
+
+    fn expect_success(kont: ds::ProgKont) {
+        match kont {
+            ds::ProgKont::ExitCode(0) => {}
+            ds::ProgKont::Error(e) => panic!("Runtime Error: {}", e),
+            _ => panic!("Expected ExitCode 0"),
+        }
+    }
+
+    #[test]
+    fn countdown() {
+        constant_stack("countdown.zy", SMALL);
+    }
+
+    #[test]
+    fn sum() {
+        constant_stack("sum.zy", SMALL);
+    }
+
+    #[test]
+    fn print_list() {
+        constant_stack("print_list.zy", SMALL);
+    }
+
+    #[test]
+    #[ignore]
+    fn countdown_large() {
+        constant_stack("countdown.zy", LARGE);
+    }
+
+    #[test]
+    #[ignore]
+    fn sum_large() {
+        constant_stack("sum.zy", LARGE);
+    }
+
+    #[test]
+    #[ignore]
+    fn print_list_large() {
+        constant_stack("print_list.zy", LARGE);
+    }
+
+    #[test]
+    #[ignore]
+    fn sum_large_vm() {
+        let m = till_check("tests/tail-call", &["sum.zy"]).unwrap();
+        expect_success(vm_bounded(ZydecoFile::link(m.inner).unwrap(), LARGE, 64));
+    }
+
+    #[test]
+    fn overflow() {
+        for kont in eval_bounded("deep.zy", SMALL, 1000) {
+            match kont {
+                ds::ProgKont::Error(e) => {
+                    assert!(matches!(e.item, RuntimeErrorItem::StackOverflow { .. }), "{}", e);
+                    assert!(e.stack.len() <= 1000);
+                }
+                _ => panic!("Expected a stack overflow"),
+            }
+        }
+    }
+}
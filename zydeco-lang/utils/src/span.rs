@@ -77,6 +77,12 @@ impl Span {
     pub fn make_rc<T>(&self, inner: T) -> Rc<Sp<T>> {
         Rc::new(Sp { inner, info: self.clone() })
     }
+    pub fn get_path(&self) -> Option<&Rc<PathBuf>> {
+        self.path.get()
+    }
+    pub fn get_span2(&self) -> Option<&(Cursor2, Cursor2)> {
+        self.span2.get()
+    }
     pub fn set_info(&self, gen: &FileInfo) {
         let (start, end) = self.span1;
         self.span2
@@ -86,6 +92,14 @@ impl Span {
     }
 }
 
+impl Hash for Span {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Note: span2 is determined by span1 and the file, so it is left out
+        self.span1.hash(state);
+        self.path.get().hash(state);
+    }
+}
+
 impl Display for Span {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let (l, r) = self.span1;